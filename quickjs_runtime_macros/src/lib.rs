@@ -0,0 +1,549 @@
+//! # quickjs_runtime_macros
+//! Attribute macros to expose rust structs and their impls as classes in [quickjs_runtime](https://crates.io/crates/quickjs_runtime)
+//!
+//! these macros are re-exported by quickjs_runtime, please see the docs of the quickjs_runtime::jsclass module for an example
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, FnArg, ImplItem, ImplItemFn, ItemImpl, ItemStruct, LitStr, Pat, ReturnType,
+    Type,
+};
+
+/// implement quickjs_runtime::jsclass::JsClass and ToJsValue for a struct
+///
+/// the name of the class defaults to the name of the struct, it may be set with `name = "..."`
+/// and a namespace may be set with `namespace = "com.hirofa"`
+#[proc_macro_attribute]
+pub fn js_class(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item_struct = parse_macro_input!(item as ItemStruct);
+
+    let mut opt_name: Option<String> = None;
+    let mut namespace: Vec<String> = vec![];
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            opt_name = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else if meta.path.is_ident("namespace") {
+            let ns = meta.value()?.parse::<LitStr>()?.value();
+            namespace = ns.split('.').map(|s| s.to_string()).collect();
+            Ok(())
+        } else {
+            Err(meta.error("unsupported js_class property, expected name or namespace"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+
+    let ident = &item_struct.ident;
+    if !item_struct.generics.params.is_empty() {
+        return syn::Error::new(
+            item_struct.generics.span(),
+            "js_class does not support generic structs",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let name = opt_name.unwrap_or_else(|| ident.to_string());
+
+    let expanded = quote! {
+        #item_struct
+
+        impl ::quickjs_runtime::jsclass::JsClass for #ident {
+            const JS_CLASS_NAME: &'static str = #name;
+            const JS_NAMESPACE: &'static [&'static str] = &[#(#namespace),*];
+        }
+
+        impl ::quickjs_runtime::jsclass::ToJsValue for #ident {
+            fn to_js_value(
+                self,
+                q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+            ) -> Result<::quickjs_runtime::valueref::JSValueRef, ::quickjs_runtime::eserror::EsError> {
+                ::quickjs_runtime::jsclass::new_js_instance(q_ctx, self)
+            }
+        }
+    };
+    expanded.into()
+}
+
+/// implement quickjs_runtime::jsclass::JsMethods for an impl of a [js_class] struct
+///
+/// all pub functions are exposed, functions may be configured with a `#[js(...)]` attribute
+/// * `#[js(constructor)]` the function which creates a new instance from script, it should return Self or Result<Self, EsError>
+/// * `#[js(getter)]` or `#[js(getter = "name")]` a getter, the name defaults to the name of the function
+/// * `#[js(setter)]` or `#[js(setter = "name")]` a setter, the name defaults to the name of the function without the set_ prefix
+/// * `#[js(name = "jsName")]` the name of a method in script
+/// * `#[js(skip)]` don't expose a pub function
+///
+/// functions with a &self or &mut self receiver become methods, getters or setters of instances, functions without a receiver become static
+#[proc_macro_attribute]
+pub fn js_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            TokenStream2::from(attr).span(),
+            "js_methods does not accept properties",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let mut item_impl = parse_macro_input!(item as ItemImpl);
+    match expand_js_methods(&mut item_impl) {
+        Ok(config) => {
+            let self_ty = &item_impl.self_ty;
+            let expanded = quote! {
+                #item_impl
+
+                impl ::quickjs_runtime::jsclass::JsMethods for #self_ty {
+                    fn configure_js_proxy(
+                        proxy: ::quickjs_runtime::reflection::DataProxy<Self>,
+                    ) -> ::quickjs_runtime::reflection::DataProxy<Self> {
+                        #config
+                    }
+                }
+            };
+            expanded.into()
+        }
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct JsAttr {
+    constructor: bool,
+    getter: Option<Option<String>>,
+    setter: Option<Option<String>>,
+    name: Option<String>,
+    skip: bool,
+}
+
+enum Receiver {
+    None,
+    Ref,
+    RefMut,
+}
+
+#[derive(Default)]
+struct Accessor {
+    getter: Option<TokenStream2>,
+    setter: Option<TokenStream2>,
+}
+
+/// remove the #[js] attributes from a function and parse them
+fn take_js_attr(func: &mut ImplItemFn) -> syn::Result<Option<JsAttr>> {
+    let mut ret: Option<JsAttr> = None;
+    let mut remaining = vec![];
+    for attr in func.attrs.drain(..) {
+        if !attr.path().is_ident("js") {
+            remaining.push(attr);
+            continue;
+        }
+        let js_attr = ret.get_or_insert_with(JsAttr::default);
+        attr.parse_nested_meta(|meta| {
+            let opt_value = |meta: &syn::meta::ParseNestedMeta| -> syn::Result<Option<String>> {
+                if meta.input.peek(syn::Token![=]) {
+                    Ok(Some(meta.value()?.parse::<LitStr>()?.value()))
+                } else {
+                    Ok(None)
+                }
+            };
+            if meta.path.is_ident("constructor") {
+                js_attr.constructor = true;
+            } else if meta.path.is_ident("getter") {
+                js_attr.getter = Some(opt_value(&meta)?);
+            } else if meta.path.is_ident("setter") {
+                js_attr.setter = Some(opt_value(&meta)?);
+            } else if meta.path.is_ident("name") {
+                js_attr.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                js_attr.skip = true;
+            } else {
+                return Err(meta.error(
+                    "unsupported js property, expected constructor, getter, setter, name or skip",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    func.attrs = remaining;
+    Ok(ret)
+}
+
+fn get_receiver(func: &ImplItemFn) -> syn::Result<Receiver> {
+    match func.sig.inputs.first() {
+        Some(FnArg::Receiver(receiver)) => {
+            if receiver.reference.is_none() {
+                Err(syn::Error::new(
+                    receiver.span(),
+                    "js methods should take &self or &mut self",
+                ))
+            } else if receiver.mutability.is_some() {
+                Ok(Receiver::RefMut)
+            } else {
+                Ok(Receiver::Ref)
+            }
+        }
+        _ => Ok(Receiver::None),
+    }
+}
+
+/// check if the last segment of a type path is a certain ident, e.g. Result or QuickJsContext
+fn is_type_named(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident == name)
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+fn returns_result(func: &ImplItemFn) -> bool {
+    match &func.sig.output {
+        ReturnType::Default => false,
+        ReturnType::Type(_, ty) => is_type_named(ty, "Result"),
+    }
+}
+
+/// generate the statements which convert the arguments and the expressions to pass them to the rust function
+fn convert_args(
+    func: &ImplItemFn,
+    js_func_name: &str,
+) -> syn::Result<(Vec<TokenStream2>, Vec<TokenStream2>)> {
+    let mut stmts = vec![];
+    let mut call_args = vec![];
+    let mut index = 0_usize;
+    for input in func.sig.inputs.iter() {
+        let pat_type = match input {
+            FnArg::Receiver(_) => continue,
+            FnArg::Typed(pat_type) => pat_type,
+        };
+        let arg_name = match &*pat_type.pat {
+            Pat::Ident(pat_ident) => pat_ident.ident.to_string(),
+            _ => format!("arg{}", index),
+        };
+        let (ty, by_ref) = match &*pat_type.ty {
+            Type::Reference(type_ref) => {
+                if is_type_named(&type_ref.elem, "QuickJsContext") {
+                    call_args.push(quote! { _q_ctx });
+                    continue;
+                }
+                if type_ref.mutability.is_some() {
+                    return Err(syn::Error::new(
+                        type_ref.span(),
+                        "js functions can not take &mut arguments",
+                    ));
+                }
+                let elem = &*type_ref.elem;
+                if let Type::Path(p) = elem {
+                    if p.path.is_ident("str") {
+                        (quote! { String }, true)
+                    } else {
+                        (quote! { #elem }, true)
+                    }
+                } else {
+                    (quote! { #elem }, true)
+                }
+            }
+            ty => (quote! { #ty }, false),
+        };
+        let var = format_ident!("_arg{}", index);
+        stmts.push(quote! {
+            let #var: #ty = ::quickjs_runtime::jsclass::get_arg(_q_ctx, &_args, #index, #js_func_name, #arg_name)?;
+        });
+        if by_ref {
+            call_args.push(quote! { &#var });
+        } else {
+            call_args.push(quote! { #var });
+        }
+        index += 1;
+    }
+    Ok((stmts, call_args))
+}
+
+/// convert the result of a rust function to a JSValueRef
+fn convert_result(func: &ImplItemFn) -> TokenStream2 {
+    if returns_result(func) {
+        quote! { ::quickjs_runtime::jsclass::ToJsValue::to_js_value(_ret?, _q_ctx) }
+    } else {
+        quote! { ::quickjs_runtime::jsclass::ToJsValue::to_js_value(_ret, _q_ctx) }
+    }
+}
+
+/// generate the statement which makes a setter result in Ok(())
+fn convert_setter_result(func: &ImplItemFn) -> TokenStream2 {
+    if returns_result(func) {
+        quote! { _ret?; Ok(()) }
+    } else {
+        quote! { let _ = _ret; Ok(()) }
+    }
+}
+
+fn expand_js_methods(item_impl: &mut ItemImpl) -> syn::Result<TokenStream2> {
+    if !item_impl.generics.params.is_empty() || item_impl.trait_.is_some() {
+        return Err(syn::Error::new(
+            item_impl.span(),
+            "js_methods should be used on a non generic impl of a struct",
+        ));
+    }
+
+    let mut config: Vec<TokenStream2> = vec![];
+    let mut has_constructor = false;
+    // accessors are kept in declaration order so the generated code is stable
+    let mut accessors: Vec<(String, Accessor)> = vec![];
+    let mut static_accessors: Vec<(String, Accessor)> = vec![];
+
+    for item in item_impl.items.iter_mut() {
+        let func = match item {
+            ImplItem::Fn(func) => func,
+            _ => continue,
+        };
+        let is_pub = matches!(func.vis, syn::Visibility::Public(_));
+        let js_attr = take_js_attr(func)?;
+        let js_attr = match js_attr {
+            Some(js_attr) => js_attr,
+            None if is_pub => JsAttr::default(),
+            None => continue,
+        };
+        if js_attr.skip {
+            continue;
+        }
+        if func.sig.asyncness.is_some() || !func.sig.generics.params.is_empty() {
+            return Err(syn::Error::new(
+                func.sig.span(),
+                "js functions can not be async or generic",
+            ));
+        }
+
+        let rust_name = func.sig.ident.clone();
+        let receiver = get_receiver(func)?;
+
+        if js_attr.constructor {
+            if !matches!(receiver, Receiver::None) {
+                return Err(syn::Error::new(
+                    func.sig.span(),
+                    "a js constructor can not have a receiver",
+                ));
+            }
+            if has_constructor {
+                return Err(syn::Error::new(
+                    func.sig.span(),
+                    "a js class can only have one constructor",
+                ));
+            }
+            has_constructor = true;
+            let (stmts, call_args) = convert_args(func, "constructor")?;
+            let ret = if returns_result(func) {
+                quote! { Self::#rust_name(#(#call_args),*) }
+            } else {
+                quote! { Ok(Self::#rust_name(#(#call_args),*)) }
+            };
+            config.push(quote! {
+                let proxy = proxy.constructor(
+                    |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                     _args: Vec<::quickjs_runtime::valueref::JSValueRef>| {
+                        #(#stmts)*
+                        #ret
+                    },
+                );
+            });
+            continue;
+        }
+
+        if let Some(opt_getter_name) = js_attr.getter {
+            let name = opt_getter_name.unwrap_or_else(|| rust_name.to_string());
+            let (stmts, call_args) = convert_args(func, name.as_str())?;
+            if !stmts.is_empty() {
+                return Err(syn::Error::new(
+                    func.sig.span(),
+                    "a js getter can not have arguments",
+                ));
+            }
+            let conv = convert_result(func);
+            let (target, getter) = match receiver {
+                Receiver::None => (
+                    &mut static_accessors,
+                    quote! {
+                        |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext| {
+                            let _ret = Self::#rust_name(#(#call_args),*);
+                            #conv
+                        }
+                    },
+                ),
+                Receiver::Ref => (
+                    &mut accessors,
+                    quote! {
+                        |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext, _this: &Self| {
+                            let _ret = Self::#rust_name(_this, #(#call_args),*);
+                            #conv
+                        }
+                    },
+                ),
+                Receiver::RefMut => {
+                    return Err(syn::Error::new(
+                        func.sig.span(),
+                        "a js getter should take &self",
+                    ))
+                }
+            };
+            get_accessor(target, name).getter = Some(getter);
+            continue;
+        }
+
+        if let Some(opt_setter_name) = js_attr.setter {
+            let name = opt_setter_name.unwrap_or_else(|| {
+                let rust_name = rust_name.to_string();
+                rust_name
+                    .strip_prefix("set_")
+                    .unwrap_or(rust_name.as_str())
+                    .to_string()
+            });
+            let (stmts, call_args) = convert_args(func, name.as_str())?;
+            if stmts.len() != 1 {
+                return Err(syn::Error::new(
+                    func.sig.span(),
+                    "a js setter should have exactly one argument",
+                ));
+            }
+            let conv = convert_setter_result(func);
+            let (target, setter) = match receiver {
+                Receiver::None => (
+                    &mut static_accessors,
+                    quote! {
+                        |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                         _value: ::quickjs_runtime::valueref::JSValueRef| {
+                            let _args = vec![_value];
+                            #(#stmts)*
+                            let _ret = Self::#rust_name(#(#call_args),*);
+                            #conv
+                        }
+                    },
+                ),
+                Receiver::RefMut => (
+                    &mut accessors,
+                    quote! {
+                        |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                         _this: &mut Self,
+                         _value: ::quickjs_runtime::valueref::JSValueRef| {
+                            let _args = vec![_value];
+                            #(#stmts)*
+                            let _ret = Self::#rust_name(_this, #(#call_args),*);
+                            #conv
+                        }
+                    },
+                ),
+                Receiver::Ref => {
+                    return Err(syn::Error::new(
+                        func.sig.span(),
+                        "a js setter should take &mut self",
+                    ))
+                }
+            };
+            get_accessor(target, name).setter = Some(setter);
+            continue;
+        }
+
+        let name = js_attr.name.unwrap_or_else(|| rust_name.to_string());
+        let (stmts, call_args) = convert_args(func, name.as_str())?;
+        let conv = convert_result(func);
+        let method = match receiver {
+            Receiver::None => quote! {
+                let proxy = proxy.static_method(
+                    #name,
+                    |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                     _args: Vec<::quickjs_runtime::valueref::JSValueRef>| {
+                        #(#stmts)*
+                        let _ret = Self::#rust_name(#(#call_args),*);
+                        #conv
+                    },
+                );
+            },
+            Receiver::Ref => quote! {
+                let proxy = proxy.method(
+                    #name,
+                    |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                     _this: &Self,
+                     _args: Vec<::quickjs_runtime::valueref::JSValueRef>| {
+                        #(#stmts)*
+                        let _ret = Self::#rust_name(_this, #(#call_args),*);
+                        #conv
+                    },
+                );
+            },
+            Receiver::RefMut => quote! {
+                let proxy = proxy.method_mut(
+                    #name,
+                    |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                     _this: &mut Self,
+                     _args: Vec<::quickjs_runtime::valueref::JSValueRef>| {
+                        #(#stmts)*
+                        let _ret = Self::#rust_name(_this, #(#call_args),*);
+                        #conv
+                    },
+                );
+            },
+        };
+        config.push(method);
+    }
+
+    for (name, accessor) in accessors {
+        let getter = accessor.getter.unwrap_or_else(|| {
+            quote! {
+                |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext, _this: &Self| {
+                    Ok(::quickjs_runtime::quickjs_utils::new_undefined_ref())
+                }
+            }
+        });
+        let setter = accessor.setter.unwrap_or_else(|| {
+            quote! {
+                |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                 _this: &mut Self,
+                 _value: ::quickjs_runtime::valueref::JSValueRef| {
+                    Err(::quickjs_runtime::jsclass::read_only_error(#name))
+                }
+            }
+        });
+        config.push(quote! {
+            let proxy = proxy.getter_setter(#name, #getter, #setter);
+        });
+    }
+
+    for (name, accessor) in static_accessors {
+        let getter = accessor.getter.unwrap_or_else(|| {
+            quote! {
+                |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext| {
+                    Ok(::quickjs_runtime::quickjs_utils::new_undefined_ref())
+                }
+            }
+        });
+        let setter = accessor.setter.unwrap_or_else(|| {
+            quote! {
+                |_q_ctx: &::quickjs_runtime::quickjscontext::QuickJsContext,
+                 _value: ::quickjs_runtime::valueref::JSValueRef| {
+                    Err(::quickjs_runtime::jsclass::read_only_error(#name))
+                }
+            }
+        });
+        config.push(quote! {
+            let proxy = proxy.static_getter_setter(#name, #getter, #setter);
+        });
+    }
+
+    Ok(quote! {
+        #(#config)*
+        proxy
+    })
+}
+
+fn get_accessor(accessors: &mut Vec<(String, Accessor)>, name: String) -> &mut Accessor {
+    let idx = match accessors.iter().position(|(n, _)| n == &name) {
+        Some(idx) => idx,
+        None => {
+            accessors.push((name, Accessor::default()));
+            accessors.len() - 1
+        }
+    };
+    &mut accessors[idx].1
+}
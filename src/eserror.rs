@@ -1,31 +1,214 @@
 use std::fmt::{Error, Formatter};
 
-/// The EsError struct is used throughout this crate to represent errors
+/// The kind of an EsError, this can be used to distinguish script errors from infrastructure failures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EsErrorKind {
+    /// an Error thrown by script which does not have a more specific kind
+    JsException,
+    /// a SyntaxError, e.g. when compiling a script or module
+    Syntax,
+    /// a TypeError
+    Type,
+    /// a RangeError
+    Range,
+    /// a ReferenceError
+    Reference,
+    /// the runtime ran out of memory
+    OutOfMemory,
+    /// the script was interrupted
+    Interrupted,
+    /// a module could not be resolved or loaded
+    ModuleNotFound,
+    /// a value could not be converted from or to a JSValue
+    Conversion,
+    /// the EsRuntime was dropped before the action could be completed
+    RuntimeDropped,
+    /// the QuickJsContext of a value was dropped before the action could be completed
+    ContextDropped,
+    /// an action did not complete within the given time
+    Timeout,
+    /// the EsRuntime was shut down and does not accept new work
+    ShutDown,
+    /// a script tried to use a capability which is not allowed by the [Permissions](../permissions/struct.Permissions.html) of its context
+    PermissionDenied,
+    /// any other failure in this crate or in quickjs
+    Internal,
+}
+
+impl EsErrorKind {
+    /// determine the kind of an error based on the name and message of a JavaScript error object
+    pub fn from_js_error(name: &str, message: &str) -> Self {
+        match name {
+            "SyntaxError" => EsErrorKind::Syntax,
+            "TypeError" => EsErrorKind::Type,
+            "RangeError" => EsErrorKind::Range,
+            "InternalError" if message == "out of memory" => EsErrorKind::OutOfMemory,
+            "InternalError" if message == "interrupted" => EsErrorKind::Interrupted,
+            "ReferenceError" | "InternalError"
+                if message.starts_with("could not load module")
+                    || (message.starts_with("Module ") && message.ends_with(" was not found")) =>
+            {
+                EsErrorKind::ModuleNotFound
+            }
+            "ReferenceError" => EsErrorKind::Reference,
+            "" => EsErrorKind::Internal,
+            _ => EsErrorKind::JsException,
+        }
+    }
+}
+
+/// the prefix of the message of errors of kind PermissionDenied
+///
+/// this is not used to determine the kind of an error thrown in script because script could fake it, the Error objects of denied actions are marked internally instead
+const PERMISSION_DENIED_PREFIX: &str = "permission denied: ";
+
+/// the location of an error as parsed from the first frame of its stack
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EsErrorLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+impl EsErrorLocation {
+    /// parse a single stack frame line, e.g. `    at foo (test.es:3)` or `    at test.es:3:12`
+    pub fn parse_frame(frame: &str) -> Option<Self> {
+        let frame = frame.trim();
+        let frame = frame.strip_prefix("at ")?;
+        let loc = if frame.ends_with(')') {
+            let start = frame.rfind('(')?;
+            &frame[start + 1..frame.len() - 1]
+        } else {
+            frame
+        };
+
+        let (rest, last) = split_num(loc)?;
+        match split_num(rest) {
+            Some((file, line)) if !file.is_empty() => Some(Self {
+                file: file.to_string(),
+                line,
+                column: Some(last),
+            }),
+            _ if !rest.is_empty() => Some(Self {
+                file: rest.to_string(),
+                line: last,
+                column: None,
+            }),
+            _ => None,
+        }
+    }
 
+    /// parse the first frame of a stack which has a file and line number
+    pub fn parse_stack(stack: &str) -> Option<Self> {
+        stack.lines().find_map(Self::parse_frame)
+    }
+}
+
+/// split "a:b:12" into ("a:b", 12)
+fn split_num(s: &str) -> Option<(&str, u32)> {
+    let idx = s.rfind(':')?;
+    let num = s[idx + 1..].parse::<u32>().ok()?;
+    Some((&s[..idx], num))
+}
+
+/// The EsError struct is used throughout this crate to represent errors
+#[derive(Clone)]
 pub struct EsError {
+    kind: EsErrorKind,
     name: String,
     message: String,
     stack: String,
+    cause: Option<Box<EsError>>,
+    properties: Vec<(String, String)>,
 }
 
 impl EsError {
+    /// create a new EsError, the kind is determined by the name and message (see [EsErrorKind::from_js_error])
     pub fn new(name: String, message: String, stack: String) -> Self {
         Self {
+            kind: EsErrorKind::from_js_error(name.as_str(), message.as_str()),
             name,
             message,
             stack,
+            cause: None,
+            properties: vec![],
         }
     }
     pub fn new_str(err: &str) -> Self {
         Self::new_string(err.to_string())
     }
     pub fn new_string(err: String) -> Self {
+        Self::new_kind(EsErrorKind::Internal, err)
+    }
+    /// create a new EsError of a specific kind
+    pub fn new_kind(kind: EsErrorKind, message: String) -> Self {
         EsError {
+            kind,
             name: "".to_string(),
-            message: err,
+            message,
             stack: "".to_string(),
+            cause: None,
+            properties: vec![],
         }
     }
+    /// create an error of kind RuntimeDropped
+    pub fn runtime_dropped() -> Self {
+        Self::new_kind(
+            EsErrorKind::RuntimeDropped,
+            "runtime was dropped".to_string(),
+        )
+    }
+    /// create an error of kind ContextDropped
+    pub fn context_dropped(context_id: &str) -> Self {
+        Self::new_kind(
+            EsErrorKind::ContextDropped,
+            format!("context {} was dropped", context_id),
+        )
+    }
+    /// create a new EsError of kind PermissionDenied
+    pub fn permission_denied(message: String) -> Self {
+        Self::new_kind(
+            EsErrorKind::PermissionDenied,
+            format!("{}{}", PERMISSION_DENIED_PREFIX, message),
+        )
+    }
+    /// set the kind of this error
+    pub fn with_kind(mut self, kind: EsErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    /// set the message of this error
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = message;
+        self
+    }
+    /// set the name of this error, when this error is thrown in script the name is used to look up a custom Error class
+    /// (see [QuickJsContext::register_error_class](../quickjscontext/struct.QuickJsContext.html#method.register_error_class))
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+    /// add an extra property which will be set on the Error object when this error is thrown in script
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::eserror::{EsError, EsErrorKind};
+    /// let err = EsError::new_kind(EsErrorKind::Range, "too big".to_string()).with_property("code", "ERR_TOO_BIG");
+    /// assert_eq!(err.get_property("code").unwrap(), "ERR_TOO_BIG");
+    /// ```
+    pub fn with_property(mut self, name: &str, value: &str) -> Self {
+        self.properties.retain(|(n, _)| n != name);
+        self.properties.push((name.to_string(), value.to_string()));
+        self
+    }
+    /// set the error which caused this error
+    pub fn with_cause(mut self, cause: EsError) -> Self {
+        self.cause = Some(Box::new(cause));
+        self
+    }
+    pub fn get_kind(&self) -> EsErrorKind {
+        self.kind
+    }
     pub fn get_message(&self) -> &str {
         self.message.as_str()
     }
@@ -35,11 +218,130 @@ impl EsError {
     pub fn get_name(&self) -> &str {
         self.name.as_str()
     }
+    pub fn get_properties(&self) -> &[(String, String)] {
+        self.properties.as_slice()
+    }
+    pub fn get_property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn get_cause(&self) -> Option<&EsError> {
+        self.cause.as_deref()
+    }
+    /// get the file/line/column of the first frame in the stack
+    pub fn get_location(&self) -> Option<EsErrorLocation> {
+        EsErrorLocation::parse_stack(self.stack.as_str())
+    }
+    /// true if this error was thrown by script (as opposed to a failure in the runtime itself)
+    pub fn is_script_error(&self) -> bool {
+        matches!(
+            self.kind,
+            EsErrorKind::JsException
+                | EsErrorKind::Syntax
+                | EsErrorKind::Type
+                | EsErrorKind::Range
+                | EsErrorKind::Reference
+        )
+    }
 }
 
 impl std::fmt::Display for EsError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let e = format!("{}: {} at{}", self.name, self.message, self.stack);
-        f.write_str(e.as_str())
+        f.write_str(e.as_str())?;
+        if let Some(cause) = &self.cause {
+            f.write_str(format!("\ncaused by: {}", cause).as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for EsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.debug_struct("EsError")
+            .field("kind", &self.kind)
+            .field("name", &self.name)
+            .field("message", &self.message)
+            .field("stack", &self.stack)
+            .field("cause", &self.cause)
+            .field("properties", &self.properties)
+            .finish()
+    }
+}
+
+impl std::error::Error for EsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.cause
+            .as_deref()
+            .map(|c| c as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eserror::{EsError, EsErrorKind, EsErrorLocation};
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esscript::EsScript;
+    use std::error::Error;
+
+    #[test]
+    fn test_location() {
+        assert_eq!(
+            EsErrorLocation::parse_frame("    at foo (test.es:3)"),
+            Some(EsErrorLocation {
+                file: "test.es".to_string(),
+                line: 3,
+                column: None
+            })
+        );
+        assert_eq!(
+            EsErrorLocation::parse_frame("    at file:///a/b.js:12:7"),
+            Some(EsErrorLocation {
+                file: "file:///a/b.js".to_string(),
+                line: 12,
+                column: Some(7)
+            })
+        );
+        assert_eq!(
+            EsErrorLocation::parse_frame("    at <anonymous> (native)"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_kinds() {
+        let rt = init_test_rt();
+        let err = rt
+            .eval_sync(EsScript::new("test_kinds.es", "\n\nlet a = {;"))
+            .expect_err("script should fail");
+        assert_eq!(err.get_kind(), EsErrorKind::Syntax);
+        assert!(err.is_script_error());
+
+        let err = rt
+            .eval_sync(EsScript::new(
+                "test_kinds2.es",
+                "function f() {\nnull.foo();\n}\nf();",
+            ))
+            .expect_err("script should fail");
+        assert_eq!(err.get_kind(), EsErrorKind::Type);
+        let loc = err.get_location().expect("no location");
+        assert_eq!(loc.file, "test_kinds2.es");
+        assert_eq!(loc.line, 2);
+
+        let err = rt
+            .eval_module_sync(EsScript::new(
+                "test_kinds3.mes",
+                "import {a} from 'notfound.mes';",
+            ))
+            .expect_err("module should fail");
+        assert_eq!(err.get_kind(), EsErrorKind::ModuleNotFound);
+
+        let wrapped = EsError::runtime_dropped().with_cause(err);
+        assert_eq!(wrapped.get_kind(), EsErrorKind::RuntimeDropped);
+        assert!(!wrapped.is_script_error());
+        let source = wrapped.source().expect("no source");
+        assert!(source.to_string().contains("notfound.mes"));
     }
 }
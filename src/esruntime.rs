@@ -1,23 +1,80 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esruntime_utils::context_templates::ContextTemplate;
+use crate::esruntime_utils::helper_tasks::{
+    add_global_helper_task, HelperTaskExecutor, HelperTaskMetrics,
+};
+use crate::esruntime_utils::promises;
 use crate::esruntimebuilder::EsRuntimeBuilder;
 use crate::esscript::EsScript;
 use crate::esvalue::EsValueFacade;
 use crate::features;
+use crate::features::console::ConsoleSink;
 use crate::features::fetch::request::FetchRequest;
 use crate::features::fetch::response::FetchResponse;
+use crate::features::lockdown;
+use crate::permissions::Permissions;
 use crate::quickjs_utils::{functions, objects};
 use crate::quickjscontext::QuickJsContext;
-use crate::quickjsruntime::{NativeModuleLoaderAdapter, QuickJsRuntime, ScriptModuleLoaderAdapter};
-use crate::utils::single_threaded_event_queue::SingleThreadedEventQueue;
-use crate::utils::task_manager::TaskManager;
+use crate::quickjsruntime::{
+    NativeModuleLoaderAdapter, QuickJsRuntime, ScriptModuleLoaderAdapter, MAIN_CONTEXT_ID,
+};
+use crate::utils::clock::Clock;
+use crate::utils::single_threaded_event_queue::{
+    SingleThreadedEventQueue, TaskFuture, TaskFutureResolver,
+};
 use libquickjs_sys as q;
+use std::cell::RefCell;
 use std::future::Future;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
-lazy_static! {
-    /// a static Multithreaded task manager used to run rust ops async and multithreaded ( in at least 2 threads)
-    static ref HELPER_TASKS: TaskManager = TaskManager::new(std::cmp::max(2, num_cpus::get()));
+/// the max number of times the virtual clock is moved forward while waiting for the runtime to become idle
+/// this ends the wait when a setInterval is still active
+const MAX_VIRTUAL_CLOCK_ADVANCES: usize = 10_000;
+
+/// the max number of rounds of due tasks and promise jobs which are run before the worker thread does something else
+const MAX_DUE_TASK_ROUNDS: usize = 1_000;
+
+thread_local! {
+    // the callers of EsRuntime::idle / run_until_idle_sync which wait for the runtime to become idle
+    static IDLE_WAITERS: RefCell<Vec<IdleWaiter>> = const { RefCell::new(Vec::new()) };
+}
+
+/// a caller which waits for the runtime to become idle, when the waiter is dropped before it was resolved (e.g. on shutdown) it fails with an EsError of kind ShutDown
+struct IdleWaiter {
+    resolver: Option<Arc<TaskFutureResolver<Result<(), EsError>>>>,
+    deadline: Option<Instant>,
+    clock_advances: usize,
+}
+
+impl IdleWaiter {
+    fn resolve(mut self, res: Result<(), EsError>) {
+        if let Some(resolver) = self.resolver.take() {
+            let _ = resolver.resolve(res);
+        }
+    }
+}
+
+impl Drop for IdleWaiter {
+    fn drop(&mut self) {
+        if let Some(resolver) = self.resolver.take() {
+            let _ = resolver.resolve(Err(EsError::new_kind(
+                EsErrorKind::ShutDown,
+                "runtime was shut down before it became idle".to_string(),
+            )));
+        }
+    }
+}
+
+/// the result of a single check if the runtime is idle
+enum IdleStep {
+    Idle,
+    /// there is work left, check again after the duration
+    Busy(Duration),
+    /// the virtual clock was moved forward to the next timer
+    AdvancedClock,
 }
 
 pub type FetchResponseProvider =
@@ -26,13 +83,36 @@ pub type FetchResponseProvider =
 pub struct EsRuntimeInner {
     pub(crate) event_queue: Arc<SingleThreadedEventQueue>,
     pub(crate) fetch_response_provider: Option<Box<FetchResponseProvider>>,
+    pub(crate) helper_tasks: HelperTaskExecutor,
+    pub(crate) max_timers: Option<usize>,
+    pub(crate) max_queued_tasks: Option<usize>,
+    pub(crate) max_resolving_promises: Option<usize>,
+    pub(crate) is_shutting_down: AtomicBool,
+}
+
+/// a snapshot of the work which is waiting in the event loop of an EsRuntime
+#[derive(Clone, Debug)]
+pub struct EventLoopStats {
+    /// tasks which were added from other threads (e.g. eval or add_task) and did not run yet
+    pub queued_tasks: usize,
+    /// tasks which were added from the worker thread (e.g. setImmediate) and did not run yet
+    pub local_tasks: usize,
+    /// number of active setTimeout timers
+    pub timeouts: usize,
+    /// number of active setInterval timers
+    pub intervals: usize,
+    /// the time at which the next timer is due
+    pub next_deadline: Option<Instant>,
+    /// promises created by new_resolving_promise (e.g. by fetch) which wait for their helper task
+    pub resolving_promises: usize,
+    /// true if there are pending promise jobs
+    pub has_pending_jobs: bool,
 }
 
 impl Drop for EsRuntimeInner {
     fn drop(&mut self) {
         // shutdown the queue and wait for thread to end
-        self.clear_contexts();
-        self.event_queue.shutdown();
+        self.teardown();
     }
 }
 
@@ -49,15 +129,6 @@ pub struct EsRuntime {
 }
 
 impl EsRuntimeInner {
-    pub(crate) fn clear_contexts(&self) {
-        self.exe_task(|| {
-            let context_ids = QuickJsRuntime::get_context_ids();
-            for id in context_ids {
-                QuickJsRuntime::drop_context(id.as_str());
-            }
-        });
-    }
-
     pub fn add_task<C>(&self, task: C)
     where
         C: FnOnce() + Send + 'static,
@@ -74,11 +145,21 @@ impl EsRuntimeInner {
     }
 
     pub fn exe_task<C, R: Send + 'static>(&self, task: C) -> R
+    where
+        C: FnOnce() -> R + Send + 'static,
+    {
+        match self.try_exe_task(task) {
+            Ok(res) => res,
+            Err(e) => panic!("task failed: {}", e),
+        }
+    }
+
+    pub fn try_exe_task<C, R: Send + 'static>(&self, task: C) -> Result<R, EsError>
     where
         C: FnOnce() -> R + Send + 'static,
     {
         let eq_arc = self.event_queue.clone();
-        self.event_queue.exe_task(move || {
+        self.event_queue.try_exe_task(move || {
             let res = task();
             eq_arc.add_task_from_worker(|| {
                 QuickJsRuntime::do_with(|q_js_rt| {
@@ -137,17 +218,281 @@ impl EsRuntimeInner {
         self.exe_task(|| QuickJsRuntime::do_with(consumer))
     }
 
+    pub fn try_add_to_event_queue_sync<C, R>(&self, consumer: C) -> Result<R, EsError>
+    where
+        C: FnOnce(&QuickJsRuntime) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.try_exe_task(|| QuickJsRuntime::do_with(consumer))
+    }
+
+    /// add a task to the helper tasks executor of this runtime
+    pub(crate) fn add_helper_task<T>(&self, task: T) -> Result<(), EsError>
+    where
+        T: FnOnce() + Send + 'static,
+    {
+        self.helper_tasks.add_task(task)
+    }
+
+    /// check if a script may create a new timer, returns an EsError of kind Range if max_timers was reached
+    /// this should only be called from the worker thread
+    pub(crate) fn check_timer_limit(&self) -> Result<(), EsError> {
+        if let Some(max) = self.max_timers {
+            let (timeouts, intervals) = self.event_queue.scheduled_task_counts();
+            if timeouts + intervals >= max {
+                return Err(EsError::new_kind(
+                    EsErrorKind::Range,
+                    format!("max number of timers ({}) exceeded", max),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// check if a new task may be queued from the worker thread, returns an EsError of kind Range if max_queued_tasks was reached
+    pub(crate) fn check_local_task_limit(&self) -> Result<(), EsError> {
+        if let Some(max) = self.max_queued_tasks {
+            if self.event_queue.local_todo_count() >= max {
+                return Err(EsError::new_kind(
+                    EsErrorKind::Range,
+                    format!("max number of queued tasks ({}) exceeded", max),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// returns an EsError of kind ShutDown if shutdown was called
+    pub(crate) fn check_accepts_work(&self) -> Result<(), EsError> {
+        if self.is_shutting_down.load(Ordering::SeqCst) {
+            Err(EsError::new_kind(
+                EsErrorKind::ShutDown,
+                "runtime was shut down".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// see [EsRuntime::shutdown_sync](struct.EsRuntime.html#method.shutdown_sync)
+    pub(crate) fn shutdown_sync(&self, grace: Duration) {
+        if self.is_shutting_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.event_queue
+            .exe_task(|| dispatch_event_in_all_contexts("beforeunload"));
+        if let Err(e) = self.run_until_idle_sync(Some(grace)) {
+            log::info!("runtime did not finish its work before shutdown: {}", e);
+        }
+        let eq = self.event_queue.clone();
+        self.event_queue.exe_task(move || {
+            dispatch_event_in_all_contexts("unload");
+            run_until_idle_in_worker(&eq);
+        });
+        self.teardown();
+    }
+
+    /// drop all timers, tasks and contexts and stop the worker thread
+    fn teardown(&self) {
+        if self.event_queue.is_shut_down() {
+            return;
+        }
+        let eq = self.event_queue.clone();
+        self.event_queue.exe_task(move || {
+            // timers and tasks may hold references to values so they are dropped before the contexts
+            eq.clear_local_tasks();
+            promises::clear_resolving_promises();
+            IDLE_WAITERS.with(|rc| rc.borrow_mut().clear());
+            let context_ids = QuickJsRuntime::get_context_ids();
+            for id in context_ids {
+                QuickJsRuntime::drop_context(id.as_str());
+            }
+        });
+        self.event_queue.shutdown();
+    }
+
+    /// drop all contexts, timers and tasks and create a new main context, used by [EsRuntimePool](../esruntimepool/struct.EsRuntimePool.html) between leases
+    /// this must be called from the worker thread
+    pub(crate) fn reset_contexts_in_worker(&self, template: Option<ContextTemplate>) {
+        self.event_queue.assert_is_worker_thread();
+        QuickJsRuntime::do_with(|q_js_rt| q_js_rt.run_pending_jobs_if_any());
+        self.event_queue.clear_local_tasks();
+        promises::clear_resolving_promises();
+        for id in QuickJsRuntime::get_context_ids() {
+            QuickJsRuntime::drop_context(id.as_str());
+        }
+        if let Err(e) = QuickJsRuntime::create_context(MAIN_CONTEXT_ID) {
+            log::error!("could not recreate main context: {}", e);
+        } else if let Some(template) = template {
+            QuickJsRuntime::do_with(|q_js_rt| {
+                if let Err(e) = template.instantiate_q(q_js_rt.get_main_context()) {
+                    log::error!("could not init main context from template: {}", e);
+                }
+            });
+        }
+    }
+
+    /// wait until the runtime has no more work, see [EsRuntime::run_until_idle_sync](struct.EsRuntime.html#method.run_until_idle_sync)
+    pub(crate) fn run_until_idle_sync(&self, timeout: Option<Duration>) -> Result<(), EsError> {
+        if self.event_queue.is_worker_thread() {
+            // the worker would wait for itself
+            return Err(EsError::new_str(
+                "run_until_idle_sync can not be called from the worker thread of the runtime",
+            ));
+        }
+        futures::executor::block_on(self.idle(timeout))
+    }
+
+    /// wait until the runtime has no more work, see [EsRuntime::idle](struct.EsRuntime.html#method.idle)
+    ///
+    /// the waiter is checked by the worker loop (see [check_idle_waiters]) so no thread is needed to wait
+    pub(crate) fn idle(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Future<Output = Result<(), EsError>> {
+        let fut = TaskFuture::new();
+        let waiter = IdleWaiter {
+            resolver: Some(fut.get_resolver()),
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+            clock_advances: 0,
+        };
+        // if the task is dropped because the runtime was shut down the waiter fails
+        self.event_queue.add_task(move || {
+            IDLE_WAITERS.with(|rc| rc.borrow_mut().push(waiter));
+        });
+        fut
+    }
+
     pub(crate) fn create_context(&self, id: &str) -> Result<(), EsError> {
         let id = id.to_string();
         self.event_queue
-            .exe_task(move || QuickJsRuntime::create_context(id.as_str()))
+            .try_exe_task(move || QuickJsRuntime::create_context(id.as_str()))?
     }
 
     pub(crate) fn drop_context(&self, id: &str) {
         let id = id.to_string();
-        self.event_queue
-            .exe_task(move || QuickJsRuntime::drop_context(id.as_str()))
+        // the contexts were already dropped if the event queue was shut down
+        let _ = self
+            .event_queue
+            .try_exe_task(move || QuickJsRuntime::drop_context(id.as_str()));
+    }
+}
+
+/// run tasks and pending promise jobs in the worker thread until there is nothing left to run at the current time
+///
+/// this stops after MAX_DUE_TASK_ROUNDS rounds because a setInterval with a delay of 0 is always due on a real clock
+fn run_until_idle_in_worker(event_queue: &SingleThreadedEventQueue) {
+    for _round in 0..MAX_DUE_TASK_ROUNDS {
+        let ran_tasks = event_queue.run_ready_tasks();
+        let ran_jobs = QuickJsRuntime::do_with(|q_js_rt| {
+            let has_jobs = q_js_rt.has_pending_jobs();
+            q_js_rt.run_pending_jobs_if_any();
+            has_jobs
+        });
+        if !ran_tasks && !ran_jobs {
+            break;
+        }
+    }
+}
+
+/// dispatch an event on the global object of every context
+fn dispatch_event_in_all_contexts(event_type: &str) {
+    for id in QuickJsRuntime::get_context_ids() {
+        QuickJsRuntime::do_with(|q_js_rt| {
+            if let Some(q_ctx) = q_js_rt.opt_context(id.as_str()) {
+                if let Err(e) = features::events::dispatch_global_event(q_ctx, event_type) {
+                    log::error!("{} listener failed in context {}: {}", event_type, id, e);
+                }
+            }
+            q_js_rt.run_pending_jobs_if_any();
+        });
+    }
+}
+
+/// run everything which is due and check if there is any work left, with a virtual clock the clock is moved to the next timer when nothing else can happen before it
+fn idle_step_in_worker(event_queue: &SingleThreadedEventQueue) -> IdleStep {
+    // the max time to wait before checking again if there are promises waiting for a helper task
+    const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+    run_until_idle_in_worker(event_queue);
+
+    let resolving_promises = promises::resolving_promise_count();
+    let next_run_opt = event_queue.get_next_scheduled_run();
+    let clock = event_queue.get_clock();
+
+    match next_run_opt {
+        None if resolving_promises == 0 => IdleStep::Idle,
+        None => IdleStep::Busy(POLL_INTERVAL),
+        Some(next_run) => {
+            if clock.is_virtual() {
+                if resolving_promises == 0 {
+                    // nothing else can happen before the next timer so we may move the clock forward
+                    clock.advance_to(next_run);
+                    run_until_idle_in_worker(event_queue);
+                    IdleStep::AdvancedClock
+                } else {
+                    IdleStep::Busy(POLL_INTERVAL)
+                }
+            } else {
+                let wait = next_run.saturating_duration_since(clock.now());
+                if resolving_promises == 0 {
+                    IdleStep::Busy(wait)
+                } else {
+                    IdleStep::Busy(wait.min(POLL_INTERVAL))
+                }
+            }
+        }
+    }
+}
+
+/// the loop listener of the worker thread, this resolves the waiters of [EsRuntime::idle](struct.EsRuntime.html#method.idle) when the runtime became idle or when their timeout passed
+/// returns the max time until the waiters should be checked again
+fn check_idle_waiters(event_queue: &SingleThreadedEventQueue) -> Option<Duration> {
+    let waiters = IDLE_WAITERS.with(|rc| std::mem::take(&mut *rc.borrow_mut()));
+    if waiters.is_empty() {
+        return None;
+    }
+    let step = idle_step_in_worker(event_queue);
+    let now = Instant::now();
+    let mut wait = match step {
+        IdleStep::Busy(wait) => wait,
+        _ => Duration::from_secs(0),
+    };
+    let mut remaining = vec![];
+    for mut waiter in waiters {
+        match step {
+            IdleStep::Idle => {
+                waiter.resolve(Ok(()));
+                continue;
+            }
+            IdleStep::AdvancedClock => waiter.clock_advances += 1,
+            IdleStep::Busy(_) => {}
+        }
+        if waiter.clock_advances > MAX_VIRTUAL_CLOCK_ADVANCES {
+            waiter.resolve(Err(EsError::new_kind(
+                EsErrorKind::Timeout,
+                format!(
+                    "runtime did not become idle after the virtual clock was moved forward {} times, is a setInterval still active?",
+                    MAX_VIRTUAL_CLOCK_ADVANCES
+                ),
+            )));
+        } else if waiter.deadline.map(|d| d <= now).unwrap_or(false) {
+            waiter.resolve(Err(EsError::new_kind(
+                EsErrorKind::Timeout,
+                "runtime did not become idle before the timeout".to_string(),
+            )));
+        } else {
+            if let Some(deadline) = waiter.deadline {
+                wait = wait.min(deadline - now);
+            }
+            remaining.push(waiter);
+        }
+    }
+    if remaining.is_empty() {
+        return None;
     }
+    IDLE_WAITERS.with(|rc| rc.borrow_mut().extend(remaining));
+    Some(wait)
 }
 
 impl EsRuntime {
@@ -155,17 +500,47 @@ impl EsRuntime {
         let fetch_response_provider =
             std::mem::replace(&mut builder.opt_fetch_response_provider, None);
 
+        let helper_tasks = if let Some(spawner) = builder.opt_helper_task_spawner.take() {
+            HelperTaskExecutor::new(spawner, builder.opt_max_helper_tasks)
+        } else if let Some(thread_count) = builder.opt_helper_thread_count {
+            HelperTaskExecutor::new_dedicated(thread_count, builder.opt_max_helper_tasks)
+        } else {
+            HelperTaskExecutor::new_global(builder.opt_max_helper_tasks)
+        };
+
+        let clock = match builder.opt_virtual_clock {
+            Some(start_time) => Clock::new_virtual(start_time),
+            None => Clock::new_real(),
+        };
+
         let ret = Arc::new(Self {
             inner: Arc::new(EsRuntimeInner {
-                event_queue: SingleThreadedEventQueue::new(),
+                event_queue: SingleThreadedEventQueue::new_with_clock(clock),
                 fetch_response_provider,
+                helper_tasks,
+                max_timers: builder.opt_max_timers,
+                max_queued_tasks: builder.opt_max_queued_tasks,
+                max_resolving_promises: builder.opt_max_resolving_promises,
+                is_shutting_down: AtomicBool::new(false),
             }),
         });
 
-        ret.inner.event_queue.exe_task(|| {
+        let opt_lockdown = builder.opt_lockdown;
+        let opt_permissions = builder.opt_permissions.take();
+        let proxy_registry = std::mem::take(&mut builder.proxy_registry);
+        let eq = ret.inner.event_queue.clone();
+        ret.inner.event_queue.exe_task(move || {
             let rt_ptr = unsafe { q::JS_NewRuntime() };
-            let rt = QuickJsRuntime::new(rt_ptr);
+            let mut rt = QuickJsRuntime::new(rt_ptr);
+            // this needs to be set before the features are initialized
+            rt.opt_lockdown = opt_lockdown;
+            rt.proxy_registry = proxy_registry;
+            if let Some(permissions) = opt_permissions {
+                rt.get_main_context().set_permissions(permissions.clone());
+                rt.default_permissions = permissions;
+            }
             QuickJsRuntime::init_rt_for_current_thread(rt);
+            eq.set_loop_listener_from_worker(check_idle_waiters);
         });
 
         // init ref in q_js_rt
@@ -205,6 +580,13 @@ impl EsRuntime {
                             native_module_loader,
                         )));
                 }
+                if let Some(console_sink) = builder.opt_console_sink {
+                    let console_sink: Rc<dyn ConsoleSink + Send> = Rc::from(console_sink);
+                    q_js_rt.console_sink = Some(console_sink);
+                }
+                for script_pre_processor in builder.script_pre_processors {
+                    q_js_rt.script_pre_processors.push(script_pre_processor);
+                }
                 for script_module_loader in builder.script_module_loaders {
                     q_js_rt
                         .module_loaders
@@ -239,21 +621,188 @@ impl EsRuntime {
         self.inner.event_queue.todo_count()
     }
 
+    /// get the number of queued tasks, timers and resolving promises of this runtime
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync(EsScript::new("test_stats.es", "setTimeout(() => {}, 10000); setInterval(() => {}, 10000);")).expect("script failed");
+    /// let stats = rt.get_event_loop_stats().expect("runtime was shut down");
+    /// assert_eq!(stats.timeouts, 1);
+    /// assert_eq!(stats.intervals, 1);
+    /// assert!(stats.next_deadline.is_some());
+    /// ```
+    ///
+    /// returns an EsError of kind ShutDown if the runtime was shut down
+    pub fn get_event_loop_stats(&self) -> Result<EventLoopStats, EsError> {
+        let eq = self.inner.event_queue.clone();
+        self.inner.event_queue.try_exe_task(move || {
+            let (timeouts, intervals) = eq.scheduled_task_counts();
+            EventLoopStats {
+                queued_tasks: eq.todo_count(),
+                local_tasks: eq.local_todo_count(),
+                timeouts,
+                intervals,
+                next_deadline: eq.get_next_scheduled_run(),
+                resolving_promises: promises::resolving_promise_count(),
+                has_pending_jobs: QuickJsRuntime::do_with(|q_js_rt| q_js_rt.has_pending_jobs()),
+            }
+        })
+    }
+
     pub fn builder() -> EsRuntimeBuilder {
         EsRuntimeBuilder::new()
     }
 
+    /// run all tasks, timers and pending promise jobs which are due at the current time of the runtime's clock until there is nothing left to run
+    /// this does not move the clock and does not wait, timers which are due in the future are not run (see [run_until_idle_sync](#method.run_until_idle_sync) to wait for those)
+    /// this stops after 1000 rounds of tasks so an interval with a delay of 0 does not keep it running forever
+    ///
+    /// this does nothing if the runtime was shut down
+    pub fn run_due_tasks(&self) {
+        let eq = self.inner.event_queue.clone();
+        let _ = self
+            .inner
+            .event_queue
+            .try_exe_task(move || run_until_idle_in_worker(&eq));
+    }
+
+    /// wait until the runtime has no more work to do
+    ///
+    /// the runtime is idle when there are no queued tasks, no timers (setTimeout / setInterval), no setImmediate callbacks, no pending promise jobs
+    /// and no promises created with [new_resolving_promise](../esruntime_utils/promises/fn.new_resolving_promise.html) (e.g. by fetch) which still wait for their helper task
+    ///
+    /// please note that a running setInterval keeps the runtime busy until it is cleared
+    ///
+    /// when the runtime uses a virtual clock the clock is moved forward to the next timer as soon as nothing else can happen before that timer,
+    /// the wait fails when the clock was moved forward 10000 times (e.g. because of an active setInterval)
+    ///
+    /// returns an EsError of kind Timeout when the runtime did not become idle within the timeout, this must not be called from the worker thread of the runtime
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// use std::time::Duration;
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync(EsScript::new("test_idle.es", "this.done = false; setTimeout(() => {Promise.resolve().then(() => {this.done = true;});}, 50);")).expect("script failed");
+    /// rt.run_until_idle_sync(Some(Duration::from_secs(5))).expect("runtime did not become idle");
+    /// assert!(rt.eval_sync(EsScript::new("test_idle2.es", "this.done;")).ok().unwrap().get_boolean());
+    /// ```
+    pub fn run_until_idle_sync(&self, timeout: Option<Duration>) -> Result<(), EsError> {
+        self.inner.run_until_idle_sync(timeout)
+    }
+
+    /// shut down the runtime gracefully
+    ///
+    /// * new work (e.g. eval, call_function, create_context) is refused with an EsError of kind ShutDown
+    /// * a beforeunload event is dispatched on the global object of every context
+    /// * timers, promises and resolving promises may finish their work during the grace period (see [run_until_idle_sync](#method.run_until_idle_sync))
+    /// * an unload event is dispatched on the global object of every context
+    /// * remaining timers and tasks are dropped, the contexts are dropped and the worker thread is stopped
+    ///
+    /// calling shutdown more than once has no effect
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// use std::time::Duration;
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.eval_sync(EsScript::new("test_shutdown.es", "addEventListener('unload', () => {console.log('bye');});")).expect("script failed");
+    /// rt.shutdown_sync(Duration::from_secs(5));
+    /// assert!(rt.eval_sync(EsScript::new("test_shutdown2.es", "1;")).is_err());
+    /// ```
+    pub fn shutdown_sync(&self, grace: Duration) {
+        self.inner.shutdown_sync(grace)
+    }
+
+    /// shut down the runtime gracefully, the returned future resolves when the runtime was shut down, see [shutdown_sync](#method.shutdown_sync)
+    pub async fn shutdown(&self, grace: Duration) {
+        let (tx, rx) = futures::channel::oneshot::channel();
+        let inner = self.inner.clone();
+        std::thread::spawn(move || {
+            inner.shutdown_sync(grace);
+            let _ = tx.send(());
+        });
+        let _ = rx.await;
+    }
+
+    /// true if shutdown was called for this runtime
+    pub fn is_shutting_down(&self) -> bool {
+        self.inner.is_shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// wait asynchronously until the runtime has no more work to do, see [run_until_idle_sync](#method.run_until_idle_sync)
+    pub async fn idle(&self, timeout: Option<Duration>) -> Result<(), EsError> {
+        self.inner.idle(timeout).await
+    }
+
+    /// move the virtual clock of the runtime forward and run all timers which become due in order
+    /// the clock is stopped at the due time of every timer so that timers see the time at which they were scheduled to run
+    ///
+    /// this does nothing if the runtime was shut down
+    /// # Panics
+    /// panics if the runtime does not use a virtual clock, see [EsRuntimeBuilder::virtual_clock](../esruntimebuilder/struct.EsRuntimeBuilder.html#method.virtual_clock)
+    pub fn advance_time(&self, duration: Duration) {
+        assert!(
+            self.inner.event_queue.get_clock().is_virtual(),
+            "advance_time requires a virtual clock, see EsRuntimeBuilder::virtual_clock"
+        );
+        let eq = self.inner.event_queue.clone();
+        let _ = self.inner.event_queue.try_exe_task(move || {
+            let clock = eq.get_clock();
+            let target = clock.now() + duration;
+            run_until_idle_in_worker(&eq);
+            while let Some(next_run) = eq.get_next_scheduled_run() {
+                if next_run > target {
+                    break;
+                }
+                clock.advance_to(next_run);
+                run_until_idle_in_worker(&eq);
+            }
+            clock.advance_to(target);
+            run_until_idle_in_worker(&eq);
+        });
+    }
+
     /// this can be used to run a function in the event_queue thread for the QuickJSRuntime
     /// without borrowing the q_js_rt
+    ///
+    /// the task is dropped (and an error is logged) if the runtime was shut down or max_queued_tasks tasks are already waiting,
+    /// use [try_add_task](#method.try_add_task) to get an error instead
     pub fn add_task<C>(&self, task: C)
     where
         C: FnOnce() + Send + 'static,
     {
+        if let Err(e) = self.try_add_task(task) {
+            log::error!("task was dropped: {}", e);
+        }
+    }
+
+    /// like [add_task](#method.add_task) but this returns an EsError of kind ShutDown if the runtime was shut down
+    /// or an EsError of kind Range when max_queued_tasks tasks are already waiting,
+    /// see [EsRuntimeBuilder::max_queued_tasks](../esruntimebuilder/struct.EsRuntimeBuilder.html#method.max_queued_tasks)
+    pub fn try_add_task<C>(&self, task: C) -> Result<(), EsError>
+    where
+        C: FnOnce() + Send + 'static,
+    {
+        self.inner.check_accepts_work()?;
+        if let Some(max) = self.inner.max_queued_tasks {
+            if self.inner.event_queue.todo_count() >= max {
+                return Err(EsError::new_kind(
+                    EsErrorKind::Range,
+                    format!("max number of queued tasks ({}) exceeded", max),
+                ));
+            }
+        }
         self.inner.add_task(task);
+        Ok(())
     }
 
     /// this can be used to run a function in the event_queue thread for the QuickJSRuntime
     /// without borrowing the q_js_rt
+    /// # Panics
+    /// panics if the runtime was shut down, see [try_exe_task](#method.try_exe_task)
     pub fn exe_task<C, R: Send + 'static>(&self, task: C) -> R
     where
         C: FnOnce() -> R + Send + 'static,
@@ -261,8 +810,17 @@ impl EsRuntime {
         self.inner.exe_task(task)
     }
 
+    /// like [exe_task](#method.exe_task) but this returns an EsError of kind ShutDown instead of panicking if the runtime was shut down
+    pub fn try_exe_task<C, R: Send + 'static>(&self, task: C) -> Result<R, EsError>
+    where
+        C: FnOnce() -> R + Send + 'static,
+    {
+        self.inner.try_exe_task(task)
+    }
+
     /// Evaluate a script asynchronously
     pub async fn eval(&self, script: EsScript) -> Result<EsValueFacade, EsError> {
+        self.inner.check_accepts_work()?;
         self.add_to_event_queue(|q_js_rt| {
             let q_ctx = q_js_rt.get_main_context();
             let res = q_ctx.eval(script);
@@ -285,6 +843,7 @@ impl EsRuntime {
     /// assert_eq!(res.get_i32(), 27);
     /// ```
     pub fn eval_sync(&self, script: EsScript) -> Result<EsValueFacade, EsError> {
+        self.inner.check_accepts_work()?;
         self.add_to_event_queue_sync(move |q_js_rt| {
             let q_ctx = q_js_rt.get_main_context();
             let res = q_ctx.eval(script);
@@ -300,9 +859,15 @@ impl EsRuntime {
         self.add_to_event_queue(|q_js_rt| q_js_rt.gc()).await
     }
 
-    /// run the garbage collector and wait for it to be done
+    /// run the garbage collector and wait for it to be done, this does nothing if the runtime was shut down
     pub fn gc_sync(&self) {
-        self.add_to_event_queue_sync(|q_js_rt| q_js_rt.gc())
+        let _ = self.try_add_to_event_queue_sync(|q_js_rt| q_js_rt.gc());
+    }
+
+    /// get the memory usage statistics of the quickjs runtime (e.g. memory_used_size, obj_count)
+    /// returns an EsError of kind ShutDown if the runtime was shut down
+    pub fn memory_usage(&self) -> Result<q::JSMemoryUsage, EsError> {
+        self.try_add_to_event_queue_sync(|q_js_rt| q_js_rt.memory_usage())
     }
 
     /// call a function in the engine and await the result
@@ -325,6 +890,7 @@ impl EsRuntime {
         func_name: &str,
         mut arguments: Vec<EsValueFacade>,
     ) -> Result<EsValueFacade, EsError> {
+        self.inner.check_accepts_work()?;
         let func_name_string = func_name.to_string();
 
         self.add_to_event_queue_sync(move |q_js_rt| {
@@ -365,6 +931,7 @@ impl EsRuntime {
         func_name: String,
         mut arguments: Vec<EsValueFacade>,
     ) -> Result<EsValueFacade, EsError> {
+        self.inner.check_accepts_work()?;
         let func_name_string = func_name.to_string();
 
         self.add_to_event_queue(move |q_js_rt| {
@@ -433,6 +1000,7 @@ impl EsRuntime {
 
     /// evaluate a module and return result synchronously
     pub fn eval_module_sync(&self, script: EsScript) -> Result<EsValueFacade, EsError> {
+        self.inner.check_accepts_work()?;
         self.add_to_event_queue_sync(move |q_js_rt| {
             let q_ctx = q_js_rt.get_main_context();
             let res = q_ctx.eval_module(script);
@@ -477,6 +1045,8 @@ impl EsRuntime {
     /// });
     /// assert_eq!(res, 66);
     /// ```
+    /// # Panics
+    /// panics if the runtime was shut down, see [try_add_to_event_queue_sync](#method.try_add_to_event_queue_sync)
     pub fn add_to_event_queue_sync<C, R>(&self, consumer: C) -> R
     where
         C: FnOnce(&QuickJsRuntime) -> R + Send + 'static,
@@ -485,6 +1055,26 @@ impl EsRuntime {
         self.inner.add_to_event_queue_sync(consumer)
     }
 
+    /// like [add_to_event_queue_sync](#method.add_to_event_queue_sync) but this returns an EsError of kind ShutDown instead of panicking if the runtime was shut down
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::eserror::EsErrorKind;
+    /// use std::time::Duration;
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// assert_eq!(rt.try_add_to_event_queue_sync(|_q_js_rt| 1).ok().unwrap(), 1);
+    /// rt.shutdown_sync(Duration::from_secs(1));
+    /// let err = rt.try_add_to_event_queue_sync(|_q_js_rt| 1).err().unwrap();
+    /// assert_eq!(err.get_kind(), EsErrorKind::ShutDown);
+    /// ```
+    pub fn try_add_to_event_queue_sync<C, R>(&self, consumer: C) -> Result<R, EsError>
+    where
+        C: FnOnce(&QuickJsRuntime) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.inner.try_add_to_event_queue_sync(consumer)
+    }
+
     /// this adds a rust function to JavaScript, it is added for all current and future contexts
     /// # Example
     /// ```rust
@@ -512,6 +1102,7 @@ impl EsRuntime {
             + Send
             + 'static,
     {
+        self.inner.check_accepts_work()?;
         let name = name.to_string();
         self.add_to_event_queue_sync(move |q_js_rt| {
             let func_rc = Rc::new(function);
@@ -543,19 +1134,45 @@ impl EsRuntime {
                 )?;
 
                 objects::set_property2_q(q_ctx, &ns, name.as_str(), &func, 0)?;
+                lockdown::harden_if_locked_down_q(q_ctx, &func)?;
 
                 Ok(())
             })
         })
     }
 
-    /// add a task the the "helper" thread pool
+    /// add a task the the global "helper" thread pool which is shared by all runtimes
     pub fn add_helper_task<T>(task: T)
     where
         T: FnOnce() + Send + 'static,
     {
         log::trace!("adding a helper task");
-        HELPER_TASKS.add_task(task);
+        add_global_helper_task(task);
+    }
+
+    /// add a task to the helper tasks executor of this runtime
+    /// this is the global thread pool unless a dedicated pool or spawner was configured in the [EsRuntimeBuilder]
+    /// this fails if the max number of queued helper tasks for this runtime was reached
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// let rt = EsRuntimeBuilder::new().helper_thread_count(2).max_helper_tasks(16).build();
+    /// rt.add_rt_helper_task(|| {
+    ///     println!("running in a helper thread");
+    /// }).expect("task was refused");
+    /// ```
+    pub fn add_rt_helper_task<T>(&self, task: T) -> Result<(), EsError>
+    where
+        T: FnOnce() + Send + 'static,
+    {
+        log::trace!("adding a rt helper task");
+        self.inner.check_accepts_work()?;
+        self.inner.add_helper_task(task)
+    }
+
+    /// get the queued/running/completed/rejected counters for the helper tasks of this runtime
+    pub fn get_helper_task_metrics(&self) -> HelperTaskMetrics {
+        self.inner.helper_tasks.metrics()
     }
 
     /// create a new context besides the always existing main_context
@@ -573,9 +1190,64 @@ impl EsRuntime {
     /// });
     /// ```
     pub fn create_context(&self, id: &str) -> Result<(), EsError> {
+        self.inner.check_accepts_work()?;
         self.inner.create_context(id)
     }
 
+    /// compile scripts into a [ContextTemplate](../esruntime_utils/context_templates/struct.ContextTemplate.html) which can be used to create pre-initialized contexts
+    /// the scripts are compiled but not evaluated
+    pub fn create_context_template(
+        &self,
+        scripts: Vec<EsScript>,
+    ) -> Result<ContextTemplate, EsError> {
+        self.inner.check_accepts_work()?;
+        self.add_to_event_queue_sync(move |q_js_rt| {
+            ContextTemplate::compile_q(q_js_rt.get_main_context(), scripts)
+        })
+    }
+
+    /// create a new context and evaluate the scripts of a [ContextTemplate](../esruntime_utils/context_templates/struct.ContextTemplate.html) in it
+    /// if a script of the template fails the new context is dropped again
+    pub fn create_context_from_template(
+        &self,
+        id: &str,
+        template: &ContextTemplate,
+    ) -> Result<(), EsError> {
+        self.create_context(id)?;
+        let id = id.to_string();
+        let template = template.clone();
+        self.exe_task(move || {
+            let res = QuickJsRuntime::do_with(|q_js_rt| {
+                template.instantiate_q(q_js_rt.get_context(id.as_str()))
+            });
+            if res.is_err() {
+                QuickJsRuntime::drop_context(id.as_str());
+            }
+            res
+        })
+    }
+
+    /// set the [Permissions](../permissions/struct.Permissions.html) of a context, this fails if the context does not exist
+    pub fn set_permissions(
+        &self,
+        context_id: &str,
+        permissions: Permissions,
+    ) -> Result<(), EsError> {
+        let context_id = context_id.to_string();
+        self.add_to_event_queue_sync(move |q_js_rt| {
+            match q_js_rt.opt_context(context_id.as_str()) {
+                Some(q_ctx) => {
+                    q_ctx.set_permissions(permissions);
+                    Ok(())
+                }
+                None => Err(EsError::new_string(format!(
+                    "no such context: {}",
+                    context_id
+                ))),
+            }
+        })
+    }
+
     /// drop a context which was created earlier with a call to [create_context()](struct.EsRuntime.html#method.create_context)
     pub fn drop_context(&self, id: &str) {
         self.inner.drop_context(id)
@@ -863,4 +1535,284 @@ pub mod tests {
     fn test_macro() {
         let _args = es_args!(1, 2i32, true, "sdf".to_string());
     }
+
+    #[test]
+    fn test_helper_task_spawner() {
+        use crate::esvalue::{EsPromise, EsValueConvertible};
+        let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+        let handle_tx = std::sync::Mutex::new(handle_tx);
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new()
+            .helper_task_spawner(move |task| {
+                let _ = handle_tx.lock().unwrap().send(std::thread::spawn(task));
+            })
+            .build();
+        rt.add_rt_helper_task(|| {}).expect("task was refused");
+        handle_rx.recv().unwrap().join().expect("task panicked");
+        let metrics = rt.get_helper_task_metrics();
+        assert_eq!(metrics.completed, 1);
+        assert_eq!(metrics.queued, 0);
+        assert_eq!(metrics.running, 0);
+
+        // the resolver of an EsPromise runs in the spawner of the runtime
+        rt.set_function(vec!["test_spawner"], "prom", |_q_ctx, _args| {
+            Ok(EsPromise::new(|| Ok(7.to_es_value_facade())).to_es_value_facade())
+        })
+        .expect("could not set function");
+        let prom = rt
+            .eval_sync(EsScript::new(
+                "test_helper_task_spawner.es",
+                "test_spawner.prom();",
+            ))
+            .expect("script failed");
+        handle_rx.recv().unwrap().join().expect("task panicked");
+        let res = prom
+            .get_promise_result_sync()
+            .expect("promise was rejected");
+        assert_eq!(res.get_i32(), 7);
+        assert_eq!(rt.get_helper_task_metrics().completed, 2);
+    }
+
+    #[test]
+    fn test_run_until_idle_sync() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new().build();
+        let rt_ref = rt.clone();
+        rt.add_to_event_queue_sync(move |q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let func_ref = crate::quickjs_utils::functions::new_function_q(
+                q_ctx,
+                "slowValue",
+                move |q_ctx, _this_ref, _args| {
+                    crate::esruntime_utils::promises::new_resolving_promise(
+                        q_ctx,
+                        || {
+                            std::thread::sleep(Duration::from_millis(100));
+                            Ok(12)
+                        },
+                        |_q_ctx, res| Ok(primitives::from_i32(res)),
+                        &rt_ref,
+                    )
+                },
+                0,
+            )
+            .expect("could not create func");
+            let global_ref = crate::quickjs_utils::get_global_q(q_ctx);
+            crate::quickjs_utils::objects::set_property_q(
+                q_ctx,
+                &global_ref,
+                "slowValue",
+                &func_ref,
+            )
+            .expect("could not set prop");
+        });
+
+        rt.eval_sync(EsScript::new(
+            "test_idle.es",
+            "this.res = [];\
+             setTimeout(() => {slowValue().then((v) => {res.push(v); setImmediate(() => {res.push('imm');});});}, 50);",
+        ))
+        .expect("script failed");
+
+        rt.run_until_idle_sync(Some(Duration::from_secs(5)))
+            .expect("runtime did not become idle");
+        let res = rt
+            .eval_sync(EsScript::new("test_idle2.es", "res.join(',');"))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "12,imm");
+
+        // an interval keeps the runtime busy
+        rt.eval_sync(EsScript::new(
+            "test_idle3.es",
+            "this.i_id = setInterval(() => {}, 10);",
+        ))
+        .expect("script failed");
+        let err = rt
+            .run_until_idle_sync(Some(Duration::from_millis(100)))
+            .expect_err("runtime should not become idle");
+        assert_eq!(err.get_kind(), crate::eserror::EsErrorKind::Timeout);
+
+        rt.eval_sync(EsScript::new("test_idle4.es", "clearInterval(i_id);"))
+            .expect("script failed");
+        block_on(rt.idle(Some(Duration::from_secs(1)))).expect("runtime did not become idle");
+    }
+
+    #[test]
+    fn test_run_until_idle_sync_virtual_clock() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new()
+            .virtual_clock(std::time::UNIX_EPOCH)
+            .build();
+        rt.eval_sync(EsScript::new(
+            "test_idle_vc.es",
+            "this.done_at = 0; setTimeout(() => {setTimeout(() => {this.done_at = Date.now();}, 3600000);}, 3600000);",
+        ))
+        .expect("script failed");
+        // the virtual clock is moved forward so this does not take 2 hours
+        rt.run_until_idle_sync(Some(Duration::from_secs(1)))
+            .expect("runtime did not become idle");
+        let res = rt
+            .eval_sync(EsScript::new("test_idle_vc2.es", "this.done_at;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 7200000);
+
+        // an interval never lets the runtime become idle, the number of clock advances is bounded
+        rt.eval_sync(EsScript::new(
+            "test_idle_vc3.es",
+            "this.i_id = setInterval(() => {}, 10);",
+        ))
+        .expect("script failed");
+        let err = rt
+            .run_until_idle_sync(None)
+            .expect_err("runtime should not become idle");
+        assert_eq!(err.get_kind(), crate::eserror::EsErrorKind::Timeout);
+
+        rt.eval_sync(EsScript::new("test_idle_vc4.es", "clearInterval(i_id);"))
+            .expect("script failed");
+        // multiple waiters are resolved by the same worker loop
+        let idle1 = rt.idle(None);
+        let idle2 = rt.idle(Some(Duration::from_secs(1)));
+        block_on(idle1).expect("runtime did not become idle");
+        block_on(idle2).expect("runtime did not become idle");
+    }
+
+    #[test]
+    fn test_event_loop_stats_and_limits() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new()
+            .max_timers(3)
+            .max_queued_tasks(2)
+            .max_resolving_promises(1)
+            .build();
+
+        rt.eval_sync(EsScript::new(
+            "test_limits.es",
+            "this.t1 = setTimeout(() => {}, 10000); setInterval(() => {}, 20000); setInterval(() => {}, 5000);",
+        ))
+        .expect("script failed");
+        let stats = rt.get_event_loop_stats().expect("runtime was shut down");
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.intervals, 2);
+        assert_eq!(stats.resolving_promises, 0);
+        assert!(stats.next_deadline.expect("no deadline") > std::time::Instant::now());
+
+        let err = rt
+            .eval_sync(EsScript::new("test_limits2.es", "setTimeout(() => {}, 1);"))
+            .expect_err("timer limit was not enforced");
+        assert_eq!(err.get_name(), "RangeError");
+        rt.eval_sync(EsScript::new(
+            "test_limits3.es",
+            "clearTimeout(t1); setTimeout(() => {}, 1);",
+        ))
+        .expect("timer could not be created after clearTimeout");
+
+        let err = rt
+            .eval_sync(EsScript::new(
+                "test_limits4.es",
+                "setImmediate(() => {}); setImmediate(() => {}); setImmediate(() => {});",
+            ))
+            .expect_err("task limit was not enforced");
+        assert_eq!(err.get_name(), "RangeError");
+
+        // block the worker thread so added tasks stay queued
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        rt.try_add_task(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        })
+        .expect("could not add task");
+        started_rx.recv().unwrap();
+        assert!(rt.try_add_task(|| {}).is_ok());
+        assert!(rt.try_add_task(|| {}).is_ok());
+        let err = rt
+            .try_add_task(|| {})
+            .expect_err("task limit was not enforced");
+        // add_task drops the task instead of failing
+        rt.add_task(|| panic!("task should have been dropped"));
+        assert_eq!(err.get_kind(), crate::eserror::EsErrorKind::Range);
+        release_tx.send(()).unwrap();
+
+        let rt_ref = rt.clone();
+        let res = rt.add_to_event_queue_sync(move |q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let producer = || {
+                std::thread::sleep(Duration::from_millis(50));
+                Ok(1)
+            };
+            let mapper = |_q_ctx: &QuickJsContext, res: i32| Ok(primitives::from_i32(res));
+            let first = crate::esruntime_utils::promises::new_resolving_promise(
+                q_ctx, producer, mapper, &rt_ref,
+            );
+            let second = crate::esruntime_utils::promises::new_resolving_promise(
+                q_ctx, producer, mapper, &rt_ref,
+            );
+            (first.is_ok(), second.err().map(|e| e.get_kind()))
+        });
+        assert_eq!(res, (true, Some(crate::eserror::EsErrorKind::Range)));
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new().build();
+        let log = Arc::new(std::sync::Mutex::new(vec![]));
+        let log2 = log.clone();
+        rt.set_function(vec!["test"], "log", move |_q_ctx, args| {
+            log2.lock().unwrap().push(args[0].get_str().to_string());
+            Ok(true.to_es_value_facade())
+        })
+        .expect("could not set function");
+
+        rt.eval_sync(EsScript::new(
+            "test_shutdown.es",
+            "this.i_id = setInterval(() => {}, 10);\
+             try { dispatchEvent('unload'); } catch (ex) { test.log(ex.name); }\
+             addEventListener('beforeunload', (evt) => {test.log(evt.type); clearInterval(i_id);});\
+             addEventListener('unload', (evt) => {test.log(evt.type);});\
+             setTimeout(() => {Promise.resolve().then(() => {test.log('written');});}, 100);",
+        ))
+        .expect("script failed");
+
+        block_on(rt.shutdown(Duration::from_secs(5)));
+        assert!(rt.is_shutting_down());
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "TypeError".to_string(),
+                "beforeunload".to_string(),
+                "written".to_string(),
+                "unload".to_string()
+            ]
+        );
+
+        let err = rt
+            .eval_sync(EsScript::new("test_shutdown2.es", "1;"))
+            .expect_err("eval should fail after shutdown");
+        assert_eq!(err.get_kind(), crate::eserror::EsErrorKind::ShutDown);
+        // tasks are refused and shutting down again does nothing
+        assert!(rt.try_add_task(|| {}).is_err());
+        rt.shutdown_sync(Duration::from_secs(1));
+
+        // synchronous apis fail or do nothing instead of panicking
+        let err = rt
+            .try_exe_task(|| 1)
+            .expect_err("exe_task should fail after shutdown");
+        assert_eq!(err.get_kind(), crate::eserror::EsErrorKind::ShutDown);
+        assert!(rt.get_event_loop_stats().is_err());
+        assert!(rt.memory_usage().is_err());
+        assert!(rt.run_until_idle_sync(None).is_err());
+        rt.gc_sync();
+        rt.run_due_tasks();
+        rt.drop_context("__main__");
+    }
+
+    #[test]
+    fn test_shutdown_grace() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new().build();
+        rt.eval_sync(EsScript::new(
+            "test_shutdown_grace.es",
+            "setInterval(() => {}, 10);",
+        ))
+        .expect("script failed");
+        let start = std::time::Instant::now();
+        rt.shutdown_sync(Duration::from_millis(100));
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
 }
@@ -0,0 +1,147 @@
+//! ContextTemplates are used to create pre-initialized contexts quickly
+//!
+//! a template contains the bytecode of one or more bootstrap scripts, these are compiled once and then evaluated from bytecode in every new context,
+//! this saves parsing and compiling (large) bootstrap scripts for every context
+//!
+//! please note that the context init hooks (e.g. the console and setTimeout features) still run for every new context,
+//! a template only replaces the evaluation of your own bootstrap scripts
+//!
+//! a ContextTemplate may be shared between runtimes (e.g. the runtimes of an [EsRuntimePool](../../esruntimepool/struct.EsRuntimePool.html))
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! let rt = EsRuntimeBuilder::new().build();
+//! let template = rt.create_context_template(vec![EsScript::new("bootstrap.es", "this.greet = function(name){return 'hello ' + name;};")]).expect("compile failed");
+//! rt.create_context_from_template("tenant_1", &template).expect("could not create context");
+//! let res = rt.add_to_event_queue_sync(|q_js_rt| {
+//!     let q_ctx = q_js_rt.get_context("tenant_1");
+//!     let res = q_ctx.eval(EsScript::new("tenant.es", "greet('tenant 1');")).expect("script failed");
+//!     quickjs_runtime::quickjs_utils::primitives::to_string_q(q_ctx, &res).ok().unwrap()
+//! });
+//! assert_eq!(res, "hello tenant 1");
+//! ```
+
+use crate::eserror::EsError;
+use crate::esscript::EsScript;
+use crate::quickjs_utils::compile;
+use crate::quickjscontext::QuickJsContext;
+use crate::quickjsruntime::QuickJsRuntime;
+use std::sync::Arc;
+
+struct CompiledScript {
+    path: String,
+    bytecode: Vec<u8>,
+}
+
+/// the compiled bootstrap scripts for new contexts, cloning a ContextTemplate is cheap
+#[derive(Clone)]
+pub struct ContextTemplate {
+    scripts: Arc<Vec<CompiledScript>>,
+}
+
+impl ContextTemplate {
+    /// compile scripts into a template, the scripts are pre-processed (see [ScriptPreProcessor](../../preprocessors/trait.ScriptPreProcessor.html)) but not evaluated
+    pub fn compile_q(q_ctx: &QuickJsContext, scripts: Vec<EsScript>) -> Result<Self, EsError> {
+        let mut compiled = vec![];
+        for script in scripts {
+            let script = QuickJsRuntime::pre_process(script)?;
+            let path = script.get_path().to_string();
+            let func = unsafe { compile::compile(q_ctx.context, script) }?;
+            let bytecode = unsafe { compile::to_bytecode(q_ctx.context, &func) };
+            compiled.push(CompiledScript { path, bytecode });
+        }
+        Ok(Self {
+            scripts: Arc::new(compiled),
+        })
+    }
+
+    /// evaluate the scripts of this template in a context, in the order in which they were compiled
+    pub fn instantiate_q(&self, q_ctx: &QuickJsContext) -> Result<(), EsError> {
+        for script in self.scripts.iter() {
+            log::trace!("ContextTemplate: running {} in {}", script.path, q_ctx.id);
+            let func = unsafe { compile::from_bytecode(q_ctx.context, script.bytecode.clone()) }?;
+            unsafe { compile::run_compiled_function(q_ctx.context, &func) }?;
+        }
+        Ok(())
+    }
+
+    /// the paths of the scripts in this template
+    pub fn get_paths(&self) -> Vec<&str> {
+        self.scripts.iter().map(|s| s.path.as_str()).collect()
+    }
+
+    /// the total size of the bytecode in this template
+    pub fn get_bytecode_size(&self) -> usize {
+        self.scripts.iter().map(|s| s.bytecode.len()).sum()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eserror::EsErrorKind;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esscript::EsScript;
+    use crate::quickjs_utils::primitives;
+
+    #[test]
+    fn test_context_template() {
+        let rt = EsRuntimeBuilder::new().build();
+        let template = rt
+            .create_context_template(vec![
+                EsScript::new(
+                    "bootstrap1.es",
+                    "this.counter = 0; this.inc = () => ++counter;",
+                ),
+                EsScript::new("bootstrap2.es", "inc();"),
+            ])
+            .expect("compile failed");
+        assert_eq!(template.get_paths(), vec!["bootstrap1.es", "bootstrap2.es"]);
+        // compiling does not run the scripts
+        let res = rt
+            .eval_sync(EsScript::new("test_tpl.es", "typeof inc;"))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "undefined");
+
+        // every context has its own state, the template may be used by another runtime
+        let rt2 = EsRuntimeBuilder::new().build();
+        rt.create_context_from_template("ctx_a", &template)
+            .expect("could not create ctx_a");
+        rt2.create_context_from_template("ctx_b", &template)
+            .expect("could not create ctx_b");
+        let a = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_context("ctx_a");
+            let res = q_ctx
+                .eval(EsScript::new("a.es", "inc(); inc();"))
+                .ok()
+                .unwrap();
+            primitives::to_i32(&res).ok().unwrap()
+        });
+        let b = rt2.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_context("ctx_b");
+            let res = q_ctx.eval(EsScript::new("b.es", "inc();")).ok().unwrap();
+            primitives::to_i32(&res).ok().unwrap()
+        });
+        assert_eq!((a, b), (3, 2));
+
+        // a failing template does not leave a half initialized context behind
+        let failing = rt
+            .create_context_template(vec![EsScript::new(
+                "fail.es",
+                "throw new RangeError('nope');",
+            )])
+            .expect("compile failed");
+        let err = rt
+            .create_context_from_template("ctx_c", &failing)
+            .expect_err("template should fail");
+        assert_eq!(err.get_kind(), EsErrorKind::Range);
+        assert!(!rt.add_to_event_queue_sync(|q_js_rt| q_js_rt.has_context("ctx_c")));
+
+        let err = rt
+            .create_context_template(vec![EsScript::new("syntax.es", "let a = ;")])
+            .err()
+            .expect("compile should fail");
+        assert_eq!(err.get_kind(), EsErrorKind::Syntax);
+    }
+}
@@ -0,0 +1,215 @@
+//! Helper tasks are blocking rust ops (e.g. the producer of a resolving promise or a fetch) which run outside of the EventQueue
+//!
+//! By default all runtimes share a single global rayon based [TaskManager](../../utils/task_manager/struct.TaskManager.html).
+//! The [EsRuntimeBuilder](../../esruntimebuilder/struct.EsRuntimeBuilder.html) can be used to give a runtime its own thread pool
+//! or to inject a custom spawner (e.g. a tokio handle), and to limit the number of helper tasks a runtime may have queued.
+
+use crate::eserror::EsError;
+use crate::utils::task_manager::TaskManager;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+lazy_static! {
+    /// a static Multithreaded task manager used to run rust ops async and multithreaded ( in at least 2 threads)
+    static ref HELPER_TASKS: TaskManager = TaskManager::new(std::cmp::max(2, num_cpus::get()));
+}
+
+/// a boxed task as passed to a HelperTaskSpawner
+pub type HelperTask = Box<dyn FnOnce() + Send + 'static>;
+
+/// a spawner is responsible for running a HelperTask in some thread other than the EventQueue's worker thread
+pub type HelperTaskSpawner = dyn Fn(HelperTask) + Send + Sync + 'static;
+
+/// add a task to the global (shared) helper thread pool
+pub fn add_global_helper_task<T>(task: T)
+where
+    T: FnOnce() + Send + 'static,
+{
+    HELPER_TASKS.add_task(task);
+}
+
+/// a snapshot of the helper task counters of a runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct HelperTaskMetrics {
+    /// tasks which were handed to the spawner but did not start yet
+    pub queued: usize,
+    /// tasks which are currently running
+    pub running: usize,
+    /// tasks which have completed (including tasks which panicked)
+    pub completed: usize,
+    /// tasks which were refused because the max queue size was reached
+    pub rejected: usize,
+}
+
+#[derive(Default)]
+struct Counters {
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+/// marks a running task as completed when it is dropped, this way a task which panics is not counted as running forever
+struct RunningGuard {
+    counters: Arc<Counters>,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.counters.running.fetch_sub(1, Ordering::SeqCst);
+        self.counters.completed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// the per runtime executor for helper tasks, this wraps a spawner and keeps track of metrics and the queue limit
+pub(crate) struct HelperTaskExecutor {
+    spawner: Box<HelperTaskSpawner>,
+    max_queue_size: Option<usize>,
+    counters: Arc<Counters>,
+}
+
+impl HelperTaskExecutor {
+    pub(crate) fn new(spawner: Box<HelperTaskSpawner>, max_queue_size: Option<usize>) -> Self {
+        Self {
+            spawner,
+            max_queue_size,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    /// an executor which uses the global shared thread pool
+    pub(crate) fn new_global(max_queue_size: Option<usize>) -> Self {
+        Self::new(
+            Box::new(|task: HelperTask| add_global_helper_task(task)),
+            max_queue_size,
+        )
+    }
+
+    /// an executor with its own dedicated thread pool
+    pub(crate) fn new_dedicated(thread_count: usize, max_queue_size: Option<usize>) -> Self {
+        let task_manager = TaskManager::new(thread_count);
+        Self::new(
+            Box::new(move |task: HelperTask| task_manager.add_task(task)),
+            max_queue_size,
+        )
+    }
+
+    pub(crate) fn add_task<T>(&self, task: T) -> Result<(), EsError>
+    where
+        T: FnOnce() + Send + 'static,
+    {
+        let counters = self.counters.clone();
+
+        let queued = counters.queued.fetch_add(1, Ordering::SeqCst);
+        if let Some(max) = self.max_queue_size {
+            if queued >= max {
+                counters.queued.fetch_sub(1, Ordering::SeqCst);
+                counters.rejected.fetch_add(1, Ordering::SeqCst);
+                return Err(EsError::new_string(format!(
+                    "max number of queued helper tasks ({}) reached",
+                    max
+                )));
+            }
+        }
+
+        (self.spawner)(Box::new(move || {
+            counters.queued.fetch_sub(1, Ordering::SeqCst);
+            counters.running.fetch_add(1, Ordering::SeqCst);
+            let _running = RunningGuard { counters };
+            task();
+        }));
+        Ok(())
+    }
+
+    pub(crate) fn metrics(&self) -> HelperTaskMetrics {
+        HelperTaskMetrics {
+            queued: self.counters.queued.load(Ordering::SeqCst),
+            running: self.counters.running.load(Ordering::SeqCst),
+            completed: self.counters.completed.load(Ordering::SeqCst),
+            rejected: self.counters.rejected.load(Ordering::SeqCst),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntime_utils::helper_tasks::{HelperTask, HelperTaskExecutor, HelperTaskMetrics};
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_queue_limit() {
+        let executor = HelperTaskExecutor::new_dedicated(1, Some(2));
+        let (started_tx, started_rx) = channel();
+        let (release_tx, release_rx) = channel::<()>();
+        let (done_tx, done_rx) = channel();
+
+        // first task will start running and block, second and third will be queued
+        let started = started_tx.clone();
+        executor
+            .add_task(move || {
+                started.send(()).unwrap();
+                let _ = release_rx.recv();
+            })
+            .expect("add task failed");
+        started_rx.recv().unwrap();
+        for _x in 0..2 {
+            let done = done_tx.clone();
+            executor
+                .add_task(move || done.send(()).unwrap())
+                .expect("add task failed");
+        }
+        assert!(executor.add_task(|| {}).is_err());
+
+        release_tx.send(()).unwrap();
+        done_rx.recv().unwrap();
+        done_rx.recv().unwrap();
+
+        // the pool has a single thread so the previous tasks are done when this one starts
+        let (release2_tx, release2_rx) = channel::<()>();
+        executor
+            .add_task(move || {
+                started_tx.send(()).unwrap();
+                let _ = release2_rx.recv();
+            })
+            .expect("add task failed");
+        started_rx.recv().unwrap();
+
+        assert_eq!(
+            executor.metrics(),
+            HelperTaskMetrics {
+                queued: 0,
+                running: 1,
+                completed: 3,
+                rejected: 1
+            }
+        );
+        release2_tx.send(()).unwrap();
+    }
+
+    #[test]
+    fn test_panicking_task() {
+        let (handle_tx, handle_rx) = channel();
+        let handle_tx = Mutex::new(handle_tx);
+        let executor = HelperTaskExecutor::new(
+            Box::new(move |task: HelperTask| {
+                let _ = handle_tx.lock().unwrap().send(std::thread::spawn(task));
+            }),
+            None,
+        );
+        executor
+            .add_task(|| panic!("task failed"))
+            .expect("add task failed");
+        assert!(handle_rx.recv().unwrap().join().is_err());
+
+        assert_eq!(
+            executor.metrics(),
+            HelperTaskMetrics {
+                queued: 0,
+                running: 0,
+                completed: 1,
+                rejected: 0
+            }
+        );
+    }
+}
@@ -1 +1,4 @@
+pub mod context_templates;
+pub mod helper_tasks;
 pub mod promises;
+pub mod source_maps;
@@ -1,6 +1,6 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::esruntime::EsRuntime;
-use crate::quickjs_utils::primitives;
+use crate::quickjs_utils::errors;
 use crate::quickjs_utils::promises::new_promise_q;
 use crate::quickjs_utils::promises::PromiseRef;
 use crate::quickjscontext::QuickJsContext;
@@ -8,7 +8,19 @@ use crate::utils::auto_id_map::AutoIdMap;
 use crate::valueref::JSValueRef;
 use std::cell::RefCell;
 thread_local! {
-    static RESOLVING_PROMISES: RefCell<AutoIdMap<PromiseRef>> = RefCell::new(AutoIdMap::new());
+    // the promises and the values which are kept alive until the promise is resolved
+    static RESOLVING_PROMISES: RefCell<AutoIdMap<(PromiseRef, Vec<JSValueRef>)>> = RefCell::new(AutoIdMap::new());
+}
+
+/// get the number of promises created by new_resolving_promise which have not been resolved yet
+/// this should only be called from the worker thread of the EsRuntime
+pub(crate) fn resolving_promise_count() -> usize {
+    RESOLVING_PROMISES.with(|map_rc| map_rc.borrow().len())
+}
+
+/// drop all promises created by new_resolving_promise which have not been resolved yet, used when a runtime shuts down
+pub(crate) fn clear_resolving_promises() {
+    RESOLVING_PROMISES.with(|map_rc| map_rc.borrow_mut().clear());
 }
 
 /// create a new promise with a resolver/mapper
@@ -69,6 +81,31 @@ where
     P: FnOnce() -> Result<R, String> + Send + 'static,
     M: FnOnce(&QuickJsContext, R) -> Result<JSValueRef, EsError> + Send + 'static,
 {
+    new_resolving_promise2(q_ctx, producer, mapper, es_rt, vec![])
+}
+
+/// create a new promise with a resolver/mapper (see [new_resolving_promise]) which keeps a number of values (e.g. the instance whose method created the promise) alive until the promise is resolved or rejected
+pub(crate) fn new_resolving_promise2<P, R, M>(
+    q_ctx: &QuickJsContext,
+    producer: P,
+    mapper: M,
+    es_rt: &EsRuntime,
+    keep_alive: Vec<JSValueRef>,
+) -> Result<JSValueRef, EsError>
+where
+    R: Send + 'static,
+    P: FnOnce() -> Result<R, String> + Send + 'static,
+    M: FnOnce(&QuickJsContext, R) -> Result<JSValueRef, EsError> + Send + 'static,
+{
+    if let Some(max) = es_rt.inner.max_resolving_promises {
+        if resolving_promise_count() >= max {
+            return Err(EsError::new_kind(
+                EsErrorKind::Range,
+                format!("max number of resolving promises ({}) exceeded", max),
+            ));
+        }
+    }
+
     // create promise
     let promise_ref = new_promise_q(q_ctx)?;
     let return_ref = promise_ref.get_promise_obj_ref();
@@ -76,24 +113,36 @@ where
     // add to map and keep id
     let id = RESOLVING_PROMISES.with(|map_rc| {
         let map = &mut *map_rc.borrow_mut();
-        map.insert(promise_ref)
+        map.insert((promise_ref, keep_alive))
     });
 
     let rti_ref = es_rt.inner.clone();
 
     let ctx_id = q_ctx.id.clone();
     // go async
-    EsRuntime::add_helper_task(move || {
+    let res = es_rt.inner.add_helper_task(move || {
         // in helper thread, produce result
         let produced_result = producer();
         let _ = rti_ref.add_to_event_queue(move |q_js_rt| {
-            let q_ctx = q_js_rt.get_context(ctx_id.as_str());
             // in q_js_rt worker thread, resolve promise
             // retrieve promise
-            let prom_ref = RESOLVING_PROMISES.with(|map_rc| {
+            let prom_ref_opt = RESOLVING_PROMISES.with(|map_rc| {
                 let map = &mut *map_rc.borrow_mut();
-                map.remove(&id)
+                if map.contains_key(&id) {
+                    Some(map.remove(&id))
+                } else {
+                    None
+                }
             });
+            // the promise or its context may have been dropped in the meantime (e.g. when the runtime was reset or shut down)
+            let (q_ctx, prom_ref, _keep_alive) =
+                match (q_js_rt.opt_context(ctx_id.as_str()), prom_ref_opt) {
+                    (Some(q_ctx), Some((prom_ref, keep_alive))) => (q_ctx, prom_ref, keep_alive),
+                    _ => {
+                        log::debug!("resolving promise was dropped before it was resolved");
+                        return;
+                    }
+                };
 
             match produced_result {
                 Ok(ok_res) => {
@@ -109,10 +158,8 @@ where
                                 .expect("prom resolution failed");
                         }
                         Err(err) => {
-                            // todo use error:new_error(err.get_message)
-                            let err_ref = primitives::from_string_q(q_ctx, err.get_message())
-                                .ok()
-                                .expect("could not create str");
+                            let err_ref = errors::new_error_from_q(q_ctx, &err)
+                                .expect("could not create error");
                             prom_ref
                                 .reject_q(q_ctx, err_ref)
                                 .ok()
@@ -121,10 +168,11 @@ where
                     }
                 }
                 Err(err) => {
-                    // todo use error:new_error(err)
-                    let err_ref = primitives::from_string_q(q_ctx, err.as_str())
-                        .ok()
-                        .expect("could not create str");
+                    let err_ref = errors::new_error_from_q(
+                        q_ctx,
+                        &EsError::new_kind(EsErrorKind::JsException, err),
+                    )
+                    .expect("could not create error");
                     prom_ref
                         .reject_q(q_ctx, err_ref)
                         .ok()
@@ -134,6 +182,15 @@ where
         });
     });
 
+    if let Err(e) = res {
+        // the task was refused, so it will never resolve the promise
+        RESOLVING_PROMISES.with(|map_rc| {
+            let map = &mut *map_rc.borrow_mut();
+            map.remove(&id);
+        });
+        return Err(e);
+    }
+
     Ok(return_ref)
 }
 
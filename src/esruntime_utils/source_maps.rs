@@ -0,0 +1,623 @@
+//! Source map support for stack traces
+//!
+//! When a script is evaluated or a module is loaded with a [source map](https://sourcemaps.info/spec.html) the map is registered for the
+//! path of the script. The map is either passed explicitly with [EsScript::with_source_map](../../esscript/struct.EsScript.html#method.with_source_map)
+//! or found as an inline `//# sourceMappingURL=data:application/json;base64,...` comment in the script.
+//! Module loaders may provide maps for other urls via [ScriptModuleLoader::load_source_map](../../quickjsruntime/trait.ScriptModuleLoader.html#method.load_source_map).
+//!
+//! The stacks of errors (see [EsError::get_stack](../../eserror/struct.EsError.html#method.get_stack)) and console.trace are then rewritten to the original locations.
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! let rt = EsRuntimeBuilder::new().build();
+//! // a map which maps line 2 of bundle.js to line 10 of src/app.ts
+//! let map = r#"{"version":3,"sources":["src/app.ts"],"names":[],"mappings":";AASA"}"#;
+//! let err = rt.eval_sync(EsScript::new("bundle.js", "let a = 1;\nthrow Error('oops');").with_source_map(map)).err().unwrap();
+//! assert!(err.get_stack().contains("src/app.ts:10:1"));
+//! ```
+
+use crate::eserror::{EsError, EsErrorKind, EsErrorLocation};
+use crate::esscript::EsScript;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// the max number of source maps which are kept per runtime, the map which was registered first is dropped when this is exceeded
+const MAX_SOURCE_MAPS: usize = 1024;
+
+thread_local! {
+    static SOURCE_MAPS: RefCell<SourceMapRegistry> = RefCell::new(SourceMapRegistry::default());
+}
+
+/// the source maps per script path in the order in which they were registered
+#[derive(Default)]
+struct SourceMapRegistry {
+    maps: HashMap<String, Rc<SourceMap>>,
+    order: VecDeque<String>,
+}
+
+impl SourceMapRegistry {
+    fn insert(&mut self, path: &str, map: SourceMap) {
+        self.remove(path);
+        if self.order.len() >= MAX_SOURCE_MAPS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.maps.remove(oldest.as_str());
+            }
+        }
+        self.order.push_back(path.to_string());
+        self.maps.insert(path.to_string(), Rc::new(map));
+    }
+    fn remove(&mut self, path: &str) {
+        if self.maps.remove(path).is_some() {
+            self.order.retain(|p| p != path);
+        }
+    }
+}
+
+/// a position in an original source file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginalPosition {
+    pub source: String,
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based column number
+    pub column: u32,
+    pub name: Option<String>,
+}
+
+struct Segment {
+    generated_column: u32,
+    source: usize,
+    line: u32,
+    column: u32,
+    name: Option<usize>,
+}
+
+/// a parsed (version 3) source map
+pub struct SourceMap {
+    sources: Vec<String>,
+    names: Vec<String>,
+    lines: Vec<Vec<Segment>>,
+}
+
+impl SourceMap {
+    /// parse the json of a source map
+    pub fn parse(json: &str) -> Result<Self, EsError> {
+        let value = JsonParser::new(json).parse_document()?;
+        let root = match value {
+            Json::Object(map) => map,
+            _ => return Err(conversion_err("source map is not an object")),
+        };
+
+        let source_root = match root.get("sourceRoot") {
+            Some(Json::String(s)) if !s.is_empty() => {
+                if s.ends_with('/') {
+                    s.clone()
+                } else {
+                    format!("{}/", s)
+                }
+            }
+            _ => "".to_string(),
+        };
+
+        let sources = string_array(&root, "sources")?
+            .into_iter()
+            .map(|s| format!("{}{}", source_root, s))
+            .collect();
+        let names = string_array(&root, "names").unwrap_or_default();
+        let mappings = match root.get("mappings") {
+            Some(Json::String(s)) => s.as_str(),
+            _ => return Err(conversion_err("source map has no mappings")),
+        };
+
+        Ok(Self {
+            sources,
+            names,
+            lines: parse_mappings(mappings)?,
+        })
+    }
+
+    /// get the original position for a position in the generated code
+    /// line is 1-based, column is 1-based, if no column is passed the first mapping of the line is used
+    pub fn original_position_for(
+        &self,
+        line: u32,
+        column: Option<u32>,
+    ) -> Option<OriginalPosition> {
+        if line == 0 {
+            return None;
+        }
+        let segments = self.lines.get(line as usize - 1)?;
+        let segment = match column {
+            Some(col) => segments
+                .iter()
+                .rev()
+                .find(|s| s.generated_column < col)
+                .or_else(|| segments.first()),
+            None => segments.first(),
+        }?;
+        Some(OriginalPosition {
+            source: self.sources.get(segment.source)?.clone(),
+            line: segment.line.saturating_add(1),
+            column: segment.column.saturating_add(1),
+            name: segment.name.and_then(|n| self.names.get(n).cloned()),
+        })
+    }
+}
+
+/// register the source map of a script (if it has one) so its stacks can be rewritten
+pub fn register_script(script: &EsScript) {
+    let map_json = match script.get_source_map() {
+        Some(map) => Some(map.to_string()),
+        None => find_source_mapping_url(script.get_code()).and_then(decode_data_url),
+    };
+
+    SOURCE_MAPS.with(|rc| {
+        let maps = &mut *rc.borrow_mut();
+        match map_json.map(|json| SourceMap::parse(json.as_str())) {
+            Some(Ok(map)) => {
+                maps.insert(script.get_path(), map);
+            }
+            Some(Err(e)) => {
+                log::error!(
+                    "could not parse source map for {}: {}",
+                    script.get_path(),
+                    e
+                );
+                maps.remove(script.get_path());
+            }
+            None => {
+                maps.remove(script.get_path());
+            }
+        }
+    });
+}
+
+/// get the registered source map for a script path
+pub fn get_source_map(path: &str) -> Option<Rc<SourceMap>> {
+    SOURCE_MAPS.with(|rc| {
+        let registry = &*rc.borrow();
+        registry.maps.get(path).cloned()
+    })
+}
+
+/// rewrite all frames in a stack which refer to a script with a registered source map
+pub fn rewrite_stack(stack: &str) -> String {
+    let has_maps = SOURCE_MAPS.with(|rc| !rc.borrow().maps.is_empty());
+    if !has_maps {
+        return stack.to_string();
+    }
+    let mut ret = String::new();
+    for frame in stack.split_inclusive('\n') {
+        ret.push_str(rewrite_frame(frame).as_str());
+    }
+    ret
+}
+
+fn rewrite_frame(frame: &str) -> String {
+    if let Some(loc) = EsErrorLocation::parse_frame(frame) {
+        if let Some(map) = get_source_map(loc.file.as_str()) {
+            if let Some(orig) = map.original_position_for(loc.line, loc.column) {
+                let generated = match loc.column {
+                    Some(col) => format!("{}:{}:{}", loc.file, loc.line, col),
+                    None => format!("{}:{}", loc.file, loc.line),
+                };
+                if let Some(idx) = frame.rfind(generated.as_str()) {
+                    return format!(
+                        "{}{}:{}:{}{}",
+                        &frame[..idx],
+                        orig.source,
+                        orig.line,
+                        orig.column,
+                        &frame[idx + generated.len()..]
+                    );
+                }
+            }
+        }
+    }
+    frame.to_string()
+}
+
+/// find the url in a `//# sourceMappingURL=` comment, only a comment on the last non-empty line of the code is used
+pub fn find_source_mapping_url(code: &str) -> Option<&str> {
+    let line = code
+        .lines()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())?;
+    line.strip_prefix("//# sourceMappingURL=")
+        .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+        .map(|url| url.trim())
+}
+
+/// decode a `data:application/json;base64,` url to the json it contains
+pub fn decode_data_url(url: &str) -> Option<String> {
+    let rest = url.strip_prefix("data:")?;
+    let comma = rest.find(',')?;
+    let (meta, data) = (&rest[..comma], &rest[comma + 1..]);
+    if !meta.starts_with("application/json") {
+        return None;
+    }
+    if meta.ends_with(";base64") {
+        String::from_utf8(decode_base64(data)?).ok()
+    } else {
+        Some(data.to_string())
+    }
+}
+
+fn conversion_err(msg: &str) -> EsError {
+    EsError::new_kind(EsErrorKind::Conversion, msg.to_string())
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut ret = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buf: u32 = 0;
+    let mut bits = 0;
+    for c in data.bytes() {
+        let val = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b'\n' | b'\r' => continue,
+            _ => return None,
+        } as u32;
+        buf = (buf << 6) | val;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Some(ret)
+}
+
+fn base64_vlq_digit(c: u8) -> Option<i64> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as i64),
+        b'a'..=b'z' => Some((c - b'a' + 26) as i64),
+        b'0'..=b'9' => Some((c - b'0' + 52) as i64),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn parse_mappings(mappings: &str) -> Result<Vec<Vec<Segment>>, EsError> {
+    let mut lines = vec![];
+    // these are relative to the previous segment (source, line, column and name are relative across lines)
+    let mut source: i64 = 0;
+    let mut line: i64 = 0;
+    let mut column: i64 = 0;
+    let mut name: i64 = 0;
+
+    for line_mappings in mappings.split(';') {
+        let mut segments = vec![];
+        let mut generated_column: i64 = 0;
+        for segment in line_mappings.split(',').filter(|s| !s.is_empty()) {
+            let fields = decode_vlq(segment)
+                .ok_or_else(|| conversion_err("invalid vlq in source map mappings"))?;
+            if fields.is_empty() {
+                continue;
+            }
+            generated_column += fields[0];
+            if fields.len() >= 4 {
+                source += fields[1];
+                line += fields[2];
+                column += fields[3];
+                let name_idx = if fields.len() >= 5 {
+                    name += fields[4];
+                    Some(to_u32(name)? as usize)
+                } else {
+                    None
+                };
+                segments.push(Segment {
+                    generated_column: to_u32(generated_column)?,
+                    source: to_u32(source)? as usize,
+                    line: to_u32(line)?,
+                    column: to_u32(column)?,
+                    name: name_idx,
+                });
+            }
+        }
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+/// a relative field of a segment must not make an absolute value negative or larger than u32
+fn to_u32(value: i64) -> Result<u32, EsError> {
+    u32::try_from(value).map_err(|_| conversion_err("value out of range in source map mappings"))
+}
+
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut ret = vec![];
+    let mut value: i64 = 0;
+    let mut shift = 0;
+    for c in segment.bytes() {
+        let digit = base64_vlq_digit(c)?;
+        value += (digit & 31) << shift;
+        if digit & 32 == 32 {
+            shift += 5;
+            // a value has at most 32 bits (7 digits), longer continuations would overflow
+            if shift > 30 {
+                return None;
+            }
+        } else {
+            let negative = value & 1 == 1;
+            value >>= 1;
+            ret.push(if negative { -value } else { value });
+            value = 0;
+            shift = 0;
+        }
+    }
+    Some(ret)
+}
+
+fn string_array(root: &HashMap<String, Json>, name: &str) -> Result<Vec<String>, EsError> {
+    match root.get(name) {
+        Some(Json::Array(items)) => Ok(items
+            .iter()
+            .map(|i| match i {
+                Json::String(s) => s.clone(),
+                _ => "".to_string(),
+            })
+            .collect()),
+        _ => Err(conversion_err(
+            format!("source map has no {} array", name).as_str(),
+        )),
+    }
+}
+
+/// just enough json to read a source map
+enum Json {
+    Null,
+    Other,
+    String(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+struct JsonParser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(json: &'a str) -> Self {
+        Self {
+            chars: json.chars().peekable(),
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Json, EsError> {
+        let val = self.parse_value()?;
+        self.skip_ws();
+        if self.chars.peek().is_some() {
+            return Err(conversion_err("trailing characters in source map json"));
+        }
+        Ok(val)
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), EsError> {
+        self.skip_ws();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(conversion_err(
+                format!("invalid source map json, expected {}", expected).as_str(),
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, EsError> {
+        self.skip_ws();
+        match self.chars.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some(_) => {
+                // number, boolean or null
+                let mut word = String::new();
+                while let Some(c) = self.chars.peek() {
+                    if c.is_alphanumeric() || *c == '-' || *c == '+' || *c == '.' {
+                        word.push(*c);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match word.as_str() {
+                    "" => Err(conversion_err("invalid source map json")),
+                    "null" => Ok(Json::Null),
+                    _ => Ok(Json::Other),
+                }
+            }
+            None => Err(conversion_err("unexpected end of source map json")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, EsError> {
+        self.expect('{')?;
+        let mut map = HashMap::new();
+        self.skip_ws();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(map));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            let val = self.parse_value()?;
+            map.insert(key, val);
+            self.skip_ws();
+            match self.chars.next() {
+                Some(',') => {}
+                Some('}') => return Ok(Json::Object(map)),
+                _ => return Err(conversion_err("invalid object in source map json")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, EsError> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.skip_ws();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_ws();
+            match self.chars.next() {
+                Some(',') => {}
+                Some(']') => return Ok(Json::Array(items)),
+                _ => return Err(conversion_err("invalid array in source map json")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, EsError> {
+        self.expect('"')?;
+        let mut ret = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(ret),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => ret.push('\n'),
+                    Some('t') => ret.push('\t'),
+                    Some('r') => ret.push('\r'),
+                    Some('b') => ret.push('\u{8}'),
+                    Some('f') => ret.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let code = u32::from_str_radix(hex.as_str(), 16)
+                            .map_err(|_| conversion_err("invalid unicode escape in source map"))?;
+                        ret.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => ret.push(c),
+                    None => return Err(conversion_err("unterminated string in source map")),
+                },
+                Some(c) => ret.push(c),
+                None => return Err(conversion_err("unterminated string in source map")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esruntime_utils::source_maps::{
+        decode_data_url, find_source_mapping_url, get_source_map, register_script,
+        OriginalPosition, SourceMap, MAX_SOURCE_MAPS, SOURCE_MAPS,
+    };
+    use crate::esscript::EsScript;
+
+    #[test]
+    fn test_parse() {
+        // generated line 1 maps to a.ts:1:1, line 3 col 5 maps to b.ts:8:3 with name "foo"
+        let map = SourceMap::parse(
+            r#"{"version":3,"sourceRoot":"src","sources":["a.ts","b.ts"],"names":["foo"],"mappings":"AAAA;;AAAA,ICOEA"}"#,
+        )
+        .expect("parse failed");
+        assert_eq!(
+            map.original_position_for(1, None),
+            Some(OriginalPosition {
+                source: "src/a.ts".to_string(),
+                line: 1,
+                column: 1,
+                name: None
+            })
+        );
+        assert_eq!(map.original_position_for(2, None), None);
+        assert_eq!(
+            map.original_position_for(3, Some(6)),
+            Some(OriginalPosition {
+                source: "src/b.ts".to_string(),
+                line: 8,
+                column: 3,
+                name: Some("foo".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_mappings() {
+        // a continuation which does not end would overflow the value
+        assert!(SourceMap::parse(
+            r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"gggggggggggggggggA"}"#
+        )
+        .is_err());
+        // the line of the second segment would be negative
+        assert!(SourceMap::parse(
+            r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AAAA,AADA"}"#
+        )
+        .is_err());
+        // the largest valid line does not overflow
+        let map = SourceMap::parse(
+            r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AA+/////HA"}"#,
+        )
+        .expect("parse failed");
+        assert_eq!(
+            map.original_position_for(1, None).map(|p| p.line),
+            Some(u32::MAX)
+        );
+    }
+
+    #[test]
+    fn test_max_maps() {
+        let map = r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AAAA"}"#;
+        for i in 0..MAX_SOURCE_MAPS + 10 {
+            register_script(
+                &EsScript::new(format!("max_maps_{}.js", i).as_str(), "").with_source_map(map),
+            );
+        }
+        assert!(get_source_map("max_maps_0.js").is_none());
+        assert!(get_source_map(format!("max_maps_{}.js", MAX_SOURCE_MAPS + 9).as_str()).is_some());
+        SOURCE_MAPS.with(|rc| assert_eq!(rc.borrow().maps.len(), MAX_SOURCE_MAPS));
+    }
+
+    #[test]
+    fn test_inline_url() {
+        let code =
+            "let a = 1;\n//# sourceMappingURL=data:application/json;base64,eyJ2ZXJzaW9uIjozfQ==\n";
+        let url = find_source_mapping_url(code).expect("no url");
+        assert_eq!(decode_data_url(url), Some("{\"version\":3}".to_string()));
+        // a comment which is not on the last line is ignored
+        let code = "let a = `\n//# sourceMappingURL=wrong.js.map\n`;\nlet b = 2;\n";
+        assert_eq!(find_source_mapping_url(code), None);
+        let code = "let a = `\n//# sourceMappingURL=wrong.js.map\n`;\n//# sourceMappingURL=right.js.map\n\n";
+        assert_eq!(find_source_mapping_url(code), Some("right.js.map"));
+    }
+
+    #[test]
+    fn test_rewrite_stack() {
+        let rt = init_test_rt();
+        // {"version":3,"sources":["src/app.ts"],"names":[],"mappings":";;AAIA"}
+        let code = "let a = 1;\nlet b = 2;\nthrow Error('oops');\n//# sourceMappingURL=data:application/json;base64,eyJ2ZXJzaW9uIjozLCJzb3VyY2VzIjpbInNyYy9hcHAudHMiXSwibmFtZXMiOltdLCJtYXBwaW5ncyI6Ijs7QUFJQSJ9";
+        let err = rt
+            .eval_sync(EsScript::new("bundle_inline.js", code))
+            .expect_err("script should fail");
+        assert!(err.get_stack().contains("src/app.ts:5:1"));
+        assert_eq!(
+            err.get_location().map(|l| (l.file, l.line)),
+            Some(("src/app.ts".to_string(), 5))
+        );
+    }
+}
@@ -1,9 +1,15 @@
 use crate::esruntime::{EsRuntime, FetchResponseProvider};
+use crate::esruntime_utils::helper_tasks::{HelperTask, HelperTaskSpawner};
+use crate::features::console::ConsoleSink;
 use crate::features::fetch::request::FetchRequest;
 use crate::features::fetch::response::FetchResponse;
+use crate::features::lockdown::LockdownOptions;
+use crate::permissions::Permissions;
+use crate::preprocessors::ScriptPreProcessor;
 use crate::quickjsruntime::{NativeModuleLoader, ScriptModuleLoader};
+use crate::reflection::ProxyRegistry;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 /// the EsRuntimeBuilder is used to init an EsRuntime
 /// # Example
@@ -17,11 +23,23 @@ use std::time::Duration;
 pub struct EsRuntimeBuilder {
     pub(crate) script_module_loaders: Vec<Box<dyn ScriptModuleLoader + Send>>,
     pub(crate) native_module_loaders: Vec<Box<dyn NativeModuleLoader + Send>>,
+    pub(crate) script_pre_processors: Vec<Box<dyn ScriptPreProcessor + Send>>,
     pub(crate) opt_fetch_response_provider: Option<Box<FetchResponseProvider>>,
     pub(crate) opt_memory_limit_bytes: Option<u64>,
     pub(crate) opt_gc_threshold: Option<u64>,
     pub(crate) opt_max_stack_size: Option<u64>,
     pub(crate) opt_gc_interval: Option<Duration>,
+    pub(crate) opt_helper_task_spawner: Option<Box<HelperTaskSpawner>>,
+    pub(crate) opt_helper_thread_count: Option<usize>,
+    pub(crate) opt_max_helper_tasks: Option<usize>,
+    pub(crate) opt_console_sink: Option<Box<dyn ConsoleSink + Send>>,
+    pub(crate) opt_virtual_clock: Option<SystemTime>,
+    pub(crate) opt_max_timers: Option<usize>,
+    pub(crate) opt_max_queued_tasks: Option<usize>,
+    pub(crate) opt_max_resolving_promises: Option<usize>,
+    pub(crate) opt_lockdown: Option<LockdownOptions>,
+    pub(crate) opt_permissions: Option<Permissions>,
+    pub(crate) proxy_registry: ProxyRegistry,
 }
 
 impl EsRuntimeBuilder {
@@ -35,11 +53,23 @@ impl EsRuntimeBuilder {
         Self {
             script_module_loaders: vec![],
             native_module_loaders: vec![],
+            script_pre_processors: vec![],
             opt_fetch_response_provider: None,
             opt_memory_limit_bytes: None,
             opt_gc_threshold: None,
             opt_max_stack_size: None,
             opt_gc_interval: None,
+            opt_helper_task_spawner: None,
+            opt_helper_thread_count: None,
+            opt_max_helper_tasks: None,
+            opt_console_sink: None,
+            opt_virtual_clock: None,
+            opt_max_timers: None,
+            opt_max_queued_tasks: None,
+            opt_max_resolving_promises: None,
+            opt_lockdown: None,
+            opt_permissions: None,
+            proxy_registry: ProxyRegistry::new(),
         }
     }
 
@@ -131,6 +161,27 @@ impl EsRuntimeBuilder {
         self
     }
 
+    /// add a preprocessor which may transform the source of scripts before they are evaluated or compiled (e.g. to strip TypeScript types)
+    /// preprocessors are run in the order in which they were added
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// use quickjs_runtime::preprocessors::typescript::TypeScriptPreProcessor;
+    /// let rt = EsRuntimeBuilder::new()
+    ///     .script_pre_processor(TypeScriptPreProcessor::new())
+    ///     .build();
+    /// let res = rt.eval_sync(EsScript::new("test_pp.ts", "let a: number = 12; a;")).expect("script failed");
+    /// assert_eq!(res.get_i32(), 12);
+    /// ```
+    pub fn script_pre_processor<P: ScriptPreProcessor + Send + 'static>(
+        mut self,
+        pre_processor: P,
+    ) -> Self {
+        self.script_pre_processors.push(Box::new(pre_processor));
+        self
+    }
+
     /// Provide a fetch response provider in order to make the fetch api work in the EsRuntime
     /// # Example
     /// ```rust
@@ -188,6 +239,14 @@ impl EsRuntimeBuilder {
         self
     }
 
+    /// set a sink which receives the output of all console methods (e.g. console.log()) instead of the log crate
+    /// see [ConsoleSink](../features/console/trait.ConsoleSink.html) for an example
+    pub fn console_sink<S: ConsoleSink + Send + 'static>(mut self, sink: S) -> Self {
+        assert!(self.opt_console_sink.is_none());
+        self.opt_console_sink = Some(Box::new(sink));
+        self
+    }
+
     /// set max memory the runtime may use
     pub fn memory_limit(mut self, bytes: u64) -> Self {
         self.opt_memory_limit_bytes = Some(bytes);
@@ -210,6 +269,115 @@ impl EsRuntimeBuilder {
         self.opt_gc_interval = Some(interval);
         self
     }
+
+    /// provide a custom spawner which is used to run helper tasks (e.g. fetch providers or the producers of resolving promises)
+    /// this may be used to run helper tasks in e.g. a tokio runtime
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// let rt = EsRuntimeBuilder::new()
+    /// .helper_task_spawner(|task| {
+    ///     std::thread::spawn(task);
+    /// })
+    /// .build();
+    /// ```
+    pub fn helper_task_spawner<S>(mut self, spawner: S) -> Self
+    where
+        S: Fn(HelperTask) + Send + Sync + 'static,
+    {
+        assert!(self.opt_helper_task_spawner.is_none());
+        self.opt_helper_task_spawner = Some(Box::new(spawner));
+        self
+    }
+
+    /// run helper tasks for this runtime in a dedicated thread pool instead of the global pool which is shared by all runtimes
+    /// this is ignored when a helper_task_spawner is set
+    pub fn helper_thread_count(mut self, thread_count: usize) -> Self {
+        self.opt_helper_thread_count = Some(thread_count);
+        self
+    }
+
+    /// set the max number of helper tasks which may be queued (but not yet running) for this runtime
+    /// when the limit is reached new tasks are refused, e.g. fetch() will throw an error
+    pub fn max_helper_tasks(mut self, max: usize) -> Self {
+        self.opt_max_helper_tasks = Some(max);
+        self
+    }
+
+    /// set the max number of active timers (setTimeout and setInterval), when the limit is reached setTimeout() and setInterval() throw a RangeError
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// let rt = EsRuntimeBuilder::new()
+    ///     .max_timers(2)
+    ///     .build();
+    /// let res = rt.eval_sync(EsScript::new("test_max_timers.es", "setInterval(() => {}, 1000); setInterval(() => {}, 1000); setInterval(() => {}, 1000);"));
+    /// assert_eq!(res.expect_err("should fail").get_name(), "RangeError");
+    /// ```
+    pub fn max_timers(mut self, max: usize) -> Self {
+        self.opt_max_timers = Some(max);
+        self
+    }
+
+    /// set the max number of tasks which may be waiting in the event queue
+    /// when the limit is reached setImmediate() throws a RangeError, [EsRuntime::try_add_task](../esruntime/struct.EsRuntime.html#method.try_add_task) returns an error
+    /// and [EsRuntime::add_task](../esruntime/struct.EsRuntime.html#method.add_task) drops the task
+    pub fn max_queued_tasks(mut self, max: usize) -> Self {
+        self.opt_max_queued_tasks = Some(max);
+        self
+    }
+
+    /// set the max number of promises created by [new_resolving_promise](../esruntime_utils/promises/fn.new_resolving_promise.html) (e.g. by fetch) which may be waiting for their helper task at the same time
+    /// when the limit is reached new_resolving_promise returns an error, which is thrown as RangeError in script
+    pub fn max_resolving_promises(mut self, max: usize) -> Self {
+        self.opt_max_resolving_promises = Some(max);
+        self
+    }
+
+    /// use a virtual clock which starts at start_time instead of the system clock
+    /// a virtual clock does not move by itself, timers (setTimeout / setInterval), Date.now() and performance.now() only move forward when
+    /// [EsRuntime::advance_time](../esruntime/struct.EsRuntime.html#method.advance_time) is called, this makes timing dependent code testable
+    /// N.B. new Date() still uses the system clock
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// let rt = EsRuntimeBuilder::new()
+    ///     .virtual_clock(UNIX_EPOCH + Duration::from_secs(3600))
+    ///     .build();
+    /// rt.eval_sync(EsScript::new("test_vc.es", "this.done = false; setTimeout(() => {this.done = Date.now();}, 5000);")).expect("script failed");
+    /// rt.advance_time(Duration::from_secs(4));
+    /// assert!(!rt.eval_sync(EsScript::new("test_vc2.es", "this.done;")).ok().unwrap().get_boolean());
+    /// rt.advance_time(Duration::from_secs(1));
+    /// assert_eq!(rt.eval_sync(EsScript::new("test_vc3.es", "this.done;")).ok().unwrap().get_i32(), 3605000);
+    /// ```
+    pub fn virtual_clock(mut self, start_time: SystemTime) -> Self {
+        self.opt_virtual_clock = Some(start_time);
+        self
+    }
+
+    /// lock down every context of the runtime, this freezes the intrinsics and all globals installed by features, set_function and Proxy classes
+    /// and disables eval, use this when untrusted code (e.g. third-party plugins) runs in shared contexts
+    /// see the [lockdown module](../features/lockdown/index.html) for details
+    pub fn lockdown(mut self, options: LockdownOptions) -> Self {
+        self.opt_lockdown = Some(options);
+        self
+    }
+
+    /// set the default [Permissions](../permissions/struct.Permissions.html) for all contexts of the runtime, by default everything is allowed
+    /// the permissions of a single context may be changed with [EsRuntime::set_permissions](../esruntime/struct.EsRuntime.html#method.set_permissions)
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.opt_permissions = Some(permissions);
+        self
+    }
+
+    /// declare Proxy classes once for all contexts of the runtime, see [ProxyRegistry](../reflection/struct.ProxyRegistry.html)
+    pub fn proxy_registry(mut self, registry: ProxyRegistry) -> Self {
+        self.proxy_registry = registry;
+        self
+    }
 }
 
 impl Default for EsRuntimeBuilder {
@@ -0,0 +1,406 @@
+//! a pool of pre-warmed EsRuntimes
+//!
+//! creating an EsRuntime starts a worker thread, a quickjs runtime and initializes all features, which is too slow to do for every short-lived evaluation (e.g. per http request)
+//! an EsRuntimePool keeps a number of runtimes ready and hands them out as [EsRuntimeLease]s
+//!
+//! when a lease is dropped its runtime is returned to the pool, the contexts of the runtime are reset (if enabled) in the worker thread of the runtime
+//! and the runtime is replaced by a new one (in a new thread) when it was used max_uses times, when it uses more than max_memory bytes or when it was shut down
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esruntimepool::EsRuntimePoolBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! let pool = EsRuntimePoolBuilder::new(|| EsRuntimeBuilder::new().memory_limit(1024 * 1024 * 32))
+//!     .size(2)
+//!     .max_uses(100)
+//!     .build();
+//! let rt = pool.lease();
+//! let res = rt.eval_sync(EsScript::new("test_pool.es", "6 * 7;")).expect("script failed");
+//! assert_eq!(res.get_i32(), 42);
+//! ```
+
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esruntime::EsRuntime;
+use crate::esruntime_utils::context_templates::ContextTemplate;
+use crate::esruntimebuilder::EsRuntimeBuilder;
+use crate::quickjsruntime::QuickJsRuntime;
+use std::ops::Deref;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+pub type EsRuntimeBuilderFactory = dyn Fn() -> EsRuntimeBuilder + Send + Sync + 'static;
+
+/// the EsRuntimePoolBuilder is used to init an EsRuntimePool
+pub struct EsRuntimePoolBuilder {
+    factory: Box<EsRuntimeBuilderFactory>,
+    size: usize,
+    reset_contexts: bool,
+    opt_max_uses: Option<usize>,
+    opt_max_memory_bytes: Option<u64>,
+    opt_context_template: Option<ContextTemplate>,
+}
+
+impl EsRuntimePoolBuilder {
+    /// init a new EsRuntimePoolBuilder, the factory is called to create the EsRuntimeBuilder for every runtime in the pool
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn() -> EsRuntimeBuilder + Send + Sync + 'static,
+    {
+        Self {
+            factory: Box::new(factory),
+            size: num_cpus::get(),
+            reset_contexts: true,
+            opt_max_uses: None,
+            opt_max_memory_bytes: None,
+            opt_context_template: None,
+        }
+    }
+
+    /// the number of runtimes in the pool, defaults to the number of cpus
+    pub fn size(mut self, size: usize) -> Self {
+        assert!(size > 0);
+        self.size = size;
+        self
+    }
+
+    /// drop and recreate the contexts of a runtime when it is returned to the pool, defaults to true
+    /// when this is disabled the globals of a previous lease remain visible to the next lease
+    pub fn reset_contexts(mut self, reset: bool) -> Self {
+        self.reset_contexts = reset;
+        self
+    }
+
+    /// replace a runtime by a new one after it was leased max_uses times
+    pub fn max_uses(mut self, max_uses: usize) -> Self {
+        self.opt_max_uses = Some(max_uses);
+        self
+    }
+
+    /// replace a runtime by a new one when it uses more than bytes of memory after it was returned to the pool
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.opt_max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// initialize the main context of every runtime with a [ContextTemplate](../esruntime_utils/context_templates/struct.ContextTemplate.html), also after the contexts were reset
+    pub fn context_template(mut self, template: ContextTemplate) -> Self {
+        self.opt_context_template = Some(template);
+        self
+    }
+
+    /// build the pool, this creates all runtimes before returning
+    /// # Panics
+    /// panics if the main context of a runtime could not be initialized from the context template
+    pub fn build(self) -> EsRuntimePool {
+        let inner = Arc::new(EsRuntimePoolInner {
+            factory: self.factory,
+            size: self.size,
+            reset_contexts: self.reset_contexts,
+            opt_max_uses: self.opt_max_uses,
+            opt_max_memory_bytes: self.opt_max_memory_bytes,
+            opt_context_template: self.opt_context_template,
+            idle: Mutex::new(vec![]),
+            idle_cond: Condvar::new(),
+            returning: Mutex::new(vec![]),
+        });
+        for _ in 0..inner.size {
+            let pooled = inner.new_runtime().expect("could not init runtime");
+            inner.add_idle(pooled);
+        }
+        EsRuntimePool { inner }
+    }
+}
+
+struct PooledRuntime {
+    rt: Arc<EsRuntime>,
+    uses: usize,
+}
+
+struct EsRuntimePoolInner {
+    factory: Box<EsRuntimeBuilderFactory>,
+    size: usize,
+    reset_contexts: bool,
+    opt_max_uses: Option<usize>,
+    opt_max_memory_bytes: Option<u64>,
+    opt_context_template: Option<ContextTemplate>,
+    idle: Mutex<Vec<PooledRuntime>>,
+    idle_cond: Condvar,
+    // runtimes which were returned by a lease and are being reset in their worker thread
+    returning: Mutex<Vec<PooledRuntime>>,
+}
+
+impl EsRuntimePoolInner {
+    fn new_runtime(&self) -> Result<PooledRuntime, EsError> {
+        let rt = (self.factory)().build();
+        if let Some(template) = &self.opt_context_template {
+            let template = template.clone();
+            rt.add_to_event_queue_sync(move |q_js_rt| {
+                template.instantiate_q(q_js_rt.get_main_context())
+            })?;
+        }
+        Ok(PooledRuntime { rt, uses: 0 })
+    }
+
+    fn add_idle(&self, pooled: PooledRuntime) {
+        self.idle.lock().unwrap().push(pooled);
+        self.idle_cond.notify_one();
+    }
+
+    /// reset or replace a runtime which was returned by a lease and make it available again
+    ///
+    /// the reset runs in the worker thread of the runtime so the thread which drops the lease does not wait for it,
+    /// only replacing a runtime is done in a new thread
+    fn recycle(pool: Arc<Self>, mut pooled: PooledRuntime) {
+        pooled.uses += 1;
+
+        let mut replace = pooled.rt.is_shutting_down();
+        if let Some(max_uses) = pool.opt_max_uses {
+            replace = replace || pooled.uses >= max_uses;
+        }
+        if replace {
+            Self::replace(pool, pooled);
+            return;
+        }
+
+        // the task does not own the runtime, a runtime must not be dropped in its own worker thread
+        let weak_rt = Arc::downgrade(&pooled.rt);
+        let inner = pooled.rt.inner.clone();
+        pool.returning.lock().unwrap().push(pooled);
+
+        let task_pool = pool.clone();
+        inner.add_task(move || {
+            let pooled = {
+                let returning = &mut *task_pool.returning.lock().unwrap();
+                let index = returning
+                    .iter()
+                    .position(|p| std::ptr::eq(Arc::as_ptr(&p.rt), weak_rt.as_ptr()));
+                match index {
+                    Some(index) => returning.remove(index),
+                    None => return,
+                }
+            };
+            if task_pool.reset_contexts {
+                pooled
+                    .rt
+                    .inner
+                    .reset_contexts_in_worker(task_pool.opt_context_template.clone());
+            }
+            let too_big = match task_pool.opt_max_memory_bytes {
+                Some(max_memory) => QuickJsRuntime::do_with(|q_js_rt| {
+                    q_js_rt.gc();
+                    q_js_rt.memory_usage().memory_used_size as u64 > max_memory
+                }),
+                None => false,
+            };
+            if too_big {
+                Self::replace(task_pool, pooled);
+            } else {
+                task_pool.add_idle(pooled);
+            }
+        });
+    }
+
+    /// replace a runtime by a new one in a new thread
+    fn replace(pool: Arc<Self>, pooled: PooledRuntime) {
+        std::thread::spawn(move || {
+            log::debug!("replacing a runtime in the EsRuntimePool");
+            // drop the old runtime before creating a new one so we do not use more than size runtimes
+            drop(pooled);
+            match pool.new_runtime() {
+                Ok(new_pooled) => pool.add_idle(new_pooled),
+                Err(e) => {
+                    // the pool shrinks, this can only happen when the context template fails
+                    log::error!("could not replace a runtime in the EsRuntimePool: {}", e);
+                }
+            }
+        });
+    }
+}
+
+impl Drop for EsRuntimePoolInner {
+    fn drop(&mut self) {
+        // a runtime can not be dropped in its own worker thread, that happens when a recycle task held the last reference to the pool
+        let mut runtimes = std::mem::take(&mut *self.idle.lock().unwrap());
+        runtimes.append(&mut *self.returning.lock().unwrap());
+        let (in_worker, others): (Vec<PooledRuntime>, Vec<PooledRuntime>) = runtimes
+            .into_iter()
+            .partition(|pooled| pooled.rt.inner.event_queue.is_worker_thread());
+        drop(others);
+        if !in_worker.is_empty() {
+            std::thread::spawn(move || drop(in_worker));
+        }
+    }
+}
+
+/// a pool of pre-warmed EsRuntimes, see the [module docs](index.html) for an example
+pub struct EsRuntimePool {
+    inner: Arc<EsRuntimePoolInner>,
+}
+
+impl EsRuntimePool {
+    /// lease a runtime, this blocks until a runtime is available
+    pub fn lease(&self) -> EsRuntimeLease {
+        let mut idle = self.inner.idle.lock().unwrap();
+        loop {
+            if let Some(pooled) = idle.pop() {
+                return self.new_lease(pooled);
+            }
+            idle = self.inner.idle_cond.wait(idle).unwrap();
+        }
+    }
+
+    /// lease a runtime if one is available
+    pub fn try_lease(&self) -> Option<EsRuntimeLease> {
+        let pooled_opt = self.inner.idle.lock().unwrap().pop();
+        pooled_opt.map(|pooled| self.new_lease(pooled))
+    }
+
+    /// lease a runtime, this blocks until a runtime is available or fails with an EsError of kind Timeout
+    pub fn lease_timeout(&self, timeout: Duration) -> Result<EsRuntimeLease, EsError> {
+        let deadline = Instant::now() + timeout;
+        let mut idle = self.inner.idle.lock().unwrap();
+        loop {
+            if let Some(pooled) = idle.pop() {
+                return Ok(self.new_lease(pooled));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(EsError::new_kind(
+                    EsErrorKind::Timeout,
+                    "no runtime became available before the timeout".to_string(),
+                ));
+            }
+            idle = self
+                .inner
+                .idle_cond
+                .wait_timeout(idle, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// the number of runtimes in the pool
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// the number of runtimes which are ready to be leased
+    pub fn idle_count(&self) -> usize {
+        self.inner.idle.lock().unwrap().len()
+    }
+
+    fn new_lease(&self, pooled: PooledRuntime) -> EsRuntimeLease {
+        EsRuntimeLease {
+            pooled: Some(pooled),
+            pool: self.inner.clone(),
+        }
+    }
+}
+
+/// a runtime leased from an [EsRuntimePool], the runtime is returned to the pool when the lease is dropped
+/// the lease derefs to the [EsRuntime](../esruntime/struct.EsRuntime.html)
+pub struct EsRuntimeLease {
+    pooled: Option<PooledRuntime>,
+    pool: Arc<EsRuntimePoolInner>,
+}
+
+impl EsRuntimeLease {
+    /// get the leased runtime, e.g. to pass it to new_resolving_promise
+    /// please note that the runtime is reset or replaced when the lease is dropped so you should not keep it after that
+    pub fn get_runtime(&self) -> Arc<EsRuntime> {
+        self.pooled.as_ref().unwrap().rt.clone()
+    }
+}
+
+impl Deref for EsRuntimeLease {
+    type Target = EsRuntime;
+
+    fn deref(&self) -> &EsRuntime {
+        &self.pooled.as_ref().unwrap().rt
+    }
+}
+
+impl Drop for EsRuntimeLease {
+    fn drop(&mut self) {
+        if let Some(pooled) = self.pooled.take() {
+            EsRuntimePoolInner::recycle(self.pool.clone(), pooled);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esruntimepool::EsRuntimePoolBuilder;
+    use crate::esscript::EsScript;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pool() {
+        let pool = EsRuntimePoolBuilder::new(EsRuntimeBuilder::new)
+            .size(1)
+            .max_uses(2)
+            .build();
+        assert_eq!(pool.idle_count(), 1);
+
+        let lease = pool.lease();
+        assert!(pool.try_lease().is_none());
+        assert!(pool.lease_timeout(Duration::from_millis(10)).is_err());
+        lease
+            .eval_sync(EsScript::new("test_pool.es", "this.a = 1; this.a;"))
+            .expect("script failed");
+        let rt1 = lease.get_runtime();
+        drop(lease);
+
+        // the contexts are reset between leases
+        let lease = pool
+            .lease_timeout(Duration::from_secs(5))
+            .expect("no runtime");
+        assert!(std::sync::Arc::ptr_eq(&rt1, &lease.get_runtime()));
+        let res = lease
+            .eval_sync(EsScript::new("test_pool2.es", "typeof this.a;"))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "undefined");
+        drop(lease);
+
+        // the runtime was used twice so it is replaced
+        let lease = pool
+            .lease_timeout(Duration::from_secs(5))
+            .expect("no runtime");
+        assert!(!std::sync::Arc::ptr_eq(&rt1, &lease.get_runtime()));
+        let res = lease
+            .eval_sync(EsScript::new("test_pool3.es", "1 + 2;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 3);
+    }
+
+    #[test]
+    fn test_pool_reuse() {
+        let pool = EsRuntimePoolBuilder::new(EsRuntimeBuilder::new)
+            .size(1)
+            .max_memory(1024 * 1024 * 64)
+            .build();
+        let rt1 = pool.lease().get_runtime();
+        for x in 0..20 {
+            let lease = pool
+                .lease_timeout(Duration::from_secs(5))
+                .expect("no runtime");
+            // the runtime is reset in its worker thread and reused
+            assert!(std::sync::Arc::ptr_eq(&rt1, &lease.get_runtime()));
+            let res = lease
+                .eval_sync(EsScript::new(
+                    "test_pool_reuse.es",
+                    &format!("let t = typeof this.b; this.b = {}; t;", x),
+                ))
+                .expect("script failed");
+            assert_eq!(res.get_str(), "undefined");
+        }
+        drop(rt1);
+
+        // the pool may be dropped while a runtime is being reset
+        let lease = pool.lease();
+        drop(lease);
+        drop(pool);
+    }
+}
@@ -1,6 +1,7 @@
 pub struct EsScript {
     absolute_path: String,
     script_code: String,
+    source_map: Option<String>,
 }
 
 impl EsScript {
@@ -8,14 +9,32 @@ impl EsScript {
         Self {
             absolute_path: absolute_path.to_string(),
             script_code: script_code.to_string(),
+            source_map: None,
         }
     }
+    /// add a source map (json) for this script, this is used to rewrite stack traces to the original source
+    /// if no source map is set an inline `//# sourceMappingURL=data:...` comment in the code is used
+    pub fn with_source_map(mut self, source_map: &str) -> Self {
+        self.source_map = Some(source_map.to_string());
+        self
+    }
     pub fn get_path(&self) -> &str {
         self.absolute_path.as_str()
     }
     pub fn get_code(&self) -> &str {
         self.script_code.as_str()
     }
+    pub fn get_source_map(&self) -> Option<&str> {
+        self.source_map.as_deref()
+    }
+    /// replace the code of this script, used by [ScriptPreProcessor](../preprocessors/trait.ScriptPreProcessor.html)s
+    pub fn set_code(&mut self, script_code: String) {
+        self.script_code = script_code;
+    }
+    /// replace the source map of this script, used by [ScriptPreProcessor](../preprocessors/trait.ScriptPreProcessor.html)s
+    pub fn set_source_map(&mut self, source_map: Option<String>) {
+        self.source_map = source_map;
+    }
 }
 
 impl Clone for EsScript {
@@ -23,6 +42,7 @@ impl Clone for EsScript {
         Self {
             absolute_path: self.get_path().to_string(),
             script_code: self.get_code().to_string(),
+            source_map: self.source_map.clone(),
         }
     }
 }
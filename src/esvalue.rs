@@ -1,7 +1,7 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::esruntime::EsRuntime;
 use crate::quickjs_utils::promises::PromiseRef;
-use crate::quickjs_utils::{arrays, dates, functions, new_null_ref, promises};
+use crate::quickjs_utils::{arrays, dates, functions, new_null_ref, objects, promises};
 use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::QuickJsRuntime;
 use crate::reflection;
@@ -121,6 +121,12 @@ pub trait EsValueConvertible {
     fn get_array_mut(&mut self) -> &mut Vec<EsValueFacade> {
         panic!("i am not an array");
     }
+    fn is_proxy_instance(&self) -> bool {
+        false
+    }
+    fn get_proxy_instance(&self) -> &EsProxyInstance {
+        panic!("i am not a proxy instance");
+    }
 }
 
 pub struct EsUndefinedValue {}
@@ -129,25 +135,137 @@ pub struct EsNullValue {}
 pub const ES_NULL: EsNullValue = EsNullValue {};
 pub const ES_UNDEFINED: EsUndefinedValue = EsUndefinedValue {};
 
+/// an instance of a Proxy class
+///
+/// an EsProxyInstance which is created with new() results in a new instance when it is converted to a JS value,
+/// an instance which was passed from script to rust (see [EsValueFacade::from_jsval]) is the same object when it is passed back to script
+/// and its methods and getters may be used from any thread
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::reflection::Proxy;
+/// use quickjs_runtime::quickjs_utils::primitives;
+/// use quickjs_runtime::esvalue::EsValueConvertible;
+/// use quickjs_runtime::esscript::EsScript;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     Proxy::new()
+///         .name("Greeter")
+///         .constructor(|_q_ctx, _id, _args| Ok(()))
+///         .method("greet", |q_ctx, _id, args| {
+///             let name = primitives::to_string_q(q_ctx, &args[0])?;
+///             primitives::from_string_q(q_ctx, format!("hello {}", name).as_str())
+///         })
+///         .install(q_ctx, true)
+///         .expect("install failed");
+/// });
+/// let res = rt.eval_sync(EsScript::new("test_proxy_instance.es", "new Greeter();")).expect("script failed");
+/// assert!(res.is_proxy_instance());
+/// let greeter = res.get_proxy_instance();
+/// assert_eq!(greeter.class_name(), "Greeter");
+/// let greeting = greeter.invoke_method_sync("greet", vec!["world".to_string().to_es_value_facade()]).expect("greet failed");
+/// assert_eq!(greeting.get_str(), "hello world");
+/// ```
 pub struct EsProxyInstance {
-    class_name: &'static str,
+    class_name: String,
     instance_id: usize,
+    cached_instance: Option<CachedJSObject>,
 }
 
 impl EsProxyInstance {
-    pub fn new(class_name: &'static str, instance_id: usize) -> Self {
+    pub fn new(class_name: &str, instance_id: usize) -> Self {
         Self {
-            class_name,
+            class_name: class_name.to_string(),
             instance_id,
+            cached_instance: None,
+        }
+    }
+    /// the name of the Proxy class of the instance (namespace.ClassName)
+    pub fn class_name(&self) -> &str {
+        self.class_name.as_str()
+    }
+    /// the id of the instance as passed to the methods, getters/setters and finalizer of the Proxy class
+    pub fn instance_id(&self) -> usize {
+        self.instance_id
+    }
+    /// invoke a method of the instance, this may be called from any thread
+    ///
+    /// this fails if the instance was not passed from script to rust
+    pub fn invoke_method_sync(
+        &self,
+        method_name: &str,
+        mut arguments: Vec<EsValueFacade>,
+    ) -> Result<EsValueFacade, EsError> {
+        let method_name = method_name.to_string();
+        self.with_instance_sync(move |q_ctx, instance_ref| {
+            let mut ref_args = vec![];
+            for arg in arguments.iter_mut() {
+                ref_args.push(arg.as_js_value(q_ctx)?);
+            }
+            let res_ref = functions::invoke_member_function_q(
+                q_ctx,
+                instance_ref,
+                method_name.as_str(),
+                ref_args,
+            )?;
+            EsValueFacade::from_jsval(q_ctx, &res_ref)
+        })
+    }
+    /// get a property (e.g. a getter) of the instance, this may be called from any thread
+    ///
+    /// this fails if the instance was not passed from script to rust
+    pub fn get_property_sync(&self, property_name: &str) -> Result<EsValueFacade, EsError> {
+        let property_name = property_name.to_string();
+        self.with_instance_sync(move |q_ctx, instance_ref| {
+            let res_ref = objects::get_property_q(q_ctx, instance_ref, property_name.as_str())?;
+            EsValueFacade::from_jsval(q_ctx, &res_ref)
+        })
+    }
+    fn with_instance_sync<C>(&self, consumer: C) -> Result<EsValueFacade, EsError>
+    where
+        C: FnOnce(&QuickJsContext, &JSValueRef) -> Result<EsValueFacade, EsError> + Send + 'static,
+    {
+        let cached = self.cached_instance.as_ref().ok_or_else(|| {
+            EsError::new_string(format!(
+                "instance {} of {} was not passed from script",
+                self.instance_id, self.class_name
+            ))
+        })?;
+        let cached_obj_id = cached.cached_obj_id;
+        let context_id = cached.context_id.clone();
+        if let Some(rt_arc) = cached.es_rt.upgrade() {
+            rt_arc.try_add_to_event_queue_sync(move |q_js_rt| {
+                let q_ctx = q_js_rt
+                    .opt_context(context_id.as_str())
+                    .ok_or_else(|| EsError::context_dropped(context_id.as_str()))?;
+                // the instance is cloned so the cache is not borrowed while the method runs
+                let instance_ref = q_ctx.with_cached_obj(cached_obj_id, |obj_ref| obj_ref.clone());
+                consumer(q_ctx, &instance_ref)
+            })?
+        } else {
+            Err(EsError::runtime_dropped())
         }
     }
 }
 
 impl EsValueConvertible for EsProxyInstance {
     fn as_js_value(&mut self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
-        let proxy_opt = reflection::get_proxy(q_ctx, self.class_name);
+        if let Some(cached) = &self.cached_instance {
+            if cached.context_id.eq(&q_ctx.id) {
+                let cloned_ref =
+                    q_ctx.with_cached_obj(cached.cached_obj_id, |obj_ref| obj_ref.clone());
+                return Ok(cloned_ref);
+            }
+            return Err(EsError::new_string(format!(
+                "instance {} of {} can not be passed to another context",
+                self.instance_id, self.class_name
+            )));
+        }
+        let proxy_opt = reflection::get_proxy(q_ctx, self.class_name.as_str());
         if let Some(proxy) = proxy_opt {
-            reflection::new_instance3(&proxy, self.instance_id, q_ctx)
+            reflection::new_instance3(&proxy, self.instance_id, q_ctx, None)
         } else {
             Err(EsError::new_string(format!(
                 "no such proxy: {}",
@@ -155,6 +273,14 @@ impl EsValueConvertible for EsProxyInstance {
             )))
         }
     }
+
+    fn is_proxy_instance(&self) -> bool {
+        true
+    }
+
+    fn get_proxy_instance(&self) -> &EsProxyInstance {
+        self
+    }
 }
 
 impl EsValueConvertible for EsNullValue {
@@ -177,6 +303,31 @@ impl EsValueConvertible for EsUndefinedValue {
     }
 }
 
+// placeholder for objects (e.g. proxy instances) that were passed from the script engine to rust
+struct CachedJSObject {
+    cached_obj_id: i32,
+    context_id: String,
+    es_rt: Weak<EsRuntime>,
+}
+
+impl Drop for CachedJSObject {
+    fn drop(&mut self) {
+        if let Some(rt_arc) = self.es_rt.upgrade() {
+            let cached_obj_id = self.cached_obj_id;
+            let context_id = self.context_id.clone();
+            // this uses the inner runtime so the cleanup is not refused by the max_queued_tasks limit
+            rt_arc.inner.add_task(move || {
+                QuickJsRuntime::do_with(|q_js_rt| {
+                    // the context may have been dropped in the meantime, its cache is gone in that case
+                    if let Some(q_ctx) = q_js_rt.opt_context(context_id.as_str()) {
+                        q_ctx.consume_cached_obj(cached_obj_id);
+                    }
+                })
+            });
+        }
+    }
+}
+
 // placeholder for promises that were passed from the script engine to rust
 struct CachedJSPromise {
     cached_obj_id: i32,
@@ -427,7 +578,7 @@ impl EsValueConvertible for CachedJSPromise {
             });
             Ok(())
         } else {
-            Err(EsError::new_str("runtime was dropped"))
+            Err(EsError::runtime_dropped())
         }
     }
 }
@@ -459,7 +610,7 @@ impl EsValueConvertible for CachedJSFunction {
                 })
             })
         } else {
-            Err(EsError::new_str("rt was dropped"))
+            Err(EsError::runtime_dropped())
         }
     }
 
@@ -497,7 +648,7 @@ impl EsValueConvertible for CachedJSFunction {
                 });
             });
         } else {
-            let _ = tx.resolve(Err(EsError::new_str("rt was dropped")));
+            let _ = tx.resolve(Err(EsError::runtime_dropped()));
         }
         ret
     }
@@ -536,7 +687,7 @@ impl EsValueConvertible for CachedJSFunction {
                 })
             })
         } else {
-            vec![Err(EsError::new_str("rt was dropped"))]
+            vec![Err(EsError::runtime_dropped())]
         }
     }
 
@@ -571,7 +722,7 @@ impl EsValueConvertible for CachedJSFunction {
             });
             Ok(())
         } else {
-            Err(EsError::new_str("rt was dropped"))
+            Err(EsError::runtime_dropped())
         }
     }
 }
@@ -704,6 +855,8 @@ pub type EsPromiseResolver = Box<dyn FnOnce() -> Result<EsValueFacade, String> +
 
 thread_local! {
     static ESPROMISE_REFS: RefCell<AutoIdMap<PromiseRef>> = RefCell::new(AutoIdMap::new());
+    /// the objects and arrays which are currently being converted by from_jsval, used to detect circular structures
+    static CONVERTING_OBJECTS: RefCell<Vec<*mut std::os::raw::c_void>> = const { RefCell::new(Vec::new()) };
 }
 
 struct EsPromiseResolvableHandleInfo {
@@ -801,7 +954,7 @@ impl EsPromiseResolvableHandle {
         })
     }
     fn set_info(&self, es_rt: &Arc<EsRuntime>, id: usize, context_id: &str) -> Result<(), EsError> {
-        self.with_inner(|inner| {
+        let resolution = self.with_inner(|inner| {
             if inner.js_info.is_some() {
                 Err(EsError::new_str("info was already set"))
             } else {
@@ -812,20 +965,18 @@ impl EsPromiseResolvableHandle {
                     context_id: context_id.to_string(),
                 });
 
-                if let Some(resolution) = inner.resolution.take() {
-                    match resolution {
-                        Ok(val) => {
-                            self.resolve(val);
-                        }
-                        Err(val) => {
-                            self.reject(val);
-                        }
-                    }
-                }
-
-                Ok(())
+                Ok(inner.resolution.take())
             }
-        })
+        })?;
+
+        // a handle which was resolved before it was converted to a JSValue is resolved now, this is done after the lock was released
+        match resolution {
+            Some(Ok(val)) => self.resolve(val),
+            Some(Err(val)) => self.reject(val),
+            None => {}
+        }
+
+        Ok(())
     }
 }
 
@@ -866,29 +1017,22 @@ impl Drop for EsPromiseResolvableHandleInner {
 /// ```
 pub struct EsPromise {
     handle: Arc<EsPromiseResolvableHandle>,
+    resolver: Option<EsPromiseResolver>,
 }
 
 impl EsPromise {
+    /// create a new Promise which is resolved with the result of the resolver
+    ///
+    /// the resolver runs in the helper task executor of the runtime (see [EsRuntime::add_rt_helper_task](../esruntime/struct.EsRuntime.html#method.add_rt_helper_task)) once the EsPromise is converted to a JS value,
+    /// converting it fails when the max number of queued helper tasks of the runtime was reached
     pub fn new<R>(resolver: R) -> Self
     where
         R: FnOnce() -> Result<EsValueFacade, String> + Send + 'static,
     {
-        let ret = Self::new_unresolving();
-
-        let handle = ret.get_handle();
-        EsRuntime::add_helper_task(move || {
-            let val = resolver();
-            match val {
-                Ok(v) => {
-                    handle.resolve(v);
-                }
-                Err(e) => {
-                    handle.reject(e.to_es_value_facade());
-                }
-            }
-        });
-
-        ret
+        Self {
+            handle: Arc::new(EsPromiseResolvableHandle::new()),
+            resolver: Some(Box::new(resolver)),
+        }
     }
     /// create a new Promise which will be resolved later
     /// this achieved by creating a Handle which is wrapped in an Arc and thus may be passed to another thread
@@ -919,6 +1063,7 @@ impl EsPromise {
     pub fn new_unresolving() -> Self {
         Self {
             handle: Arc::new(EsPromiseResolvableHandle::new()),
+            resolver: None,
         }
     }
     /// get the handle which can be used to resolve a promise
@@ -931,6 +1076,18 @@ impl EsValueConvertible for EsPromise {
     fn as_js_value(&mut self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
         log::trace!("EsPromise::to_js_value");
 
+        let es_rt = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.get_rt_ref())
+            .ok_or_else(EsError::runtime_dropped)?;
+
+        if let Some(resolver) = self.resolver.take() {
+            // the handle keeps the resolution until the promise was created
+            let handle = self.get_handle();
+            es_rt.inner.add_helper_task(move || match resolver() {
+                Ok(v) => handle.resolve(v),
+                Err(e) => handle.reject(e.to_es_value_facade()),
+            })?;
+        }
+
         let prom_ref = promises::new_promise_q(q_ctx)?;
 
         let ret = prom_ref.get_promise_obj_ref();
@@ -938,7 +1095,6 @@ impl EsValueConvertible for EsPromise {
             let map = &mut *rc.borrow_mut();
             map.insert(prom_ref)
         });
-        let es_rt = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.get_rt_ref().unwrap());
 
         self.handle.set_info(&es_rt, id, q_ctx.id.as_str())?;
 
@@ -996,7 +1152,22 @@ impl EsValueFacade {
 
             // Object.
             TAG_OBJECT => {
-                if promises::is_promise_q(q_ctx, value_ref) {
+                if let Some((class_name, instance_id)) =
+                    reflection::get_proxy_instance_class_and_id(value_ref)
+                {
+                    let es_rt = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.get_rt_ref().unwrap());
+                    let cached_obj_id = q_ctx.cache_object(value_ref.clone());
+                    Ok(EsProxyInstance {
+                        class_name,
+                        instance_id,
+                        cached_instance: Some(CachedJSObject {
+                            cached_obj_id,
+                            context_id: q_ctx.id.clone(),
+                            es_rt: Arc::downgrade(&es_rt),
+                        }),
+                    }
+                    .to_es_value_facade())
+                } else if promises::is_promise_q(q_ctx, value_ref) {
                     let es_rt = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.get_rt_ref().unwrap());
                     let cached_obj_id = q_ctx.cache_object(value_ref.clone());
                     Ok(CachedJSPromise {
@@ -1017,17 +1188,23 @@ impl EsValueFacade {
                     };
                     Ok(cached_func.to_es_value_facade())
                 } else if dates::is_date_q(q_ctx, value_ref)? {
-                    Err(EsError::new_str("dates are currently not supported"))
+                    Err(EsError::new_kind(
+                        EsErrorKind::Conversion,
+                        "dates are currently not supported".to_string(),
+                    ))
                 } else {
                     Self::from_jsval_object(q_ctx, value_ref)
                 }
             }
             // BigInt
-            TAG_BIG_INT => Err(EsError::new_str("BigInts are currently not supported")),
-            x => Err(EsError::new_string(format!(
-                "Unhandled JS_TAG value: {}",
-                x
-            ))),
+            TAG_BIG_INT => Err(EsError::new_kind(
+                EsErrorKind::Conversion,
+                "BigInts are currently not supported".to_string(),
+            )),
+            x => Err(EsError::new_kind(
+                EsErrorKind::Conversion,
+                format!("Unhandled JS_TAG value: {}", x),
+            )),
         }
     }
 
@@ -1037,18 +1214,21 @@ impl EsValueFacade {
     ) -> Result<EsValueFacade, EsError> {
         assert!(value_ref.is_object());
 
-        let len = crate::quickjs_utils::arrays::get_length_q(q_ctx, value_ref)?;
+        Self::convert_once(value_ref, || {
+            let len = crate::quickjs_utils::arrays::get_length_q(q_ctx, value_ref)?;
 
-        let mut values = Vec::new();
-        for index in 0..len {
-            let element_ref = crate::quickjs_utils::arrays::get_element_q(q_ctx, value_ref, index)?;
+            let mut values = Vec::new();
+            for index in 0..len {
+                let element_ref =
+                    crate::quickjs_utils::arrays::get_element_q(q_ctx, value_ref, index)?;
 
-            let element_value = EsValueFacade::from_jsval(q_ctx, &element_ref)?;
+                let element_value = EsValueFacade::from_jsval(q_ctx, &element_ref)?;
 
-            values.push(element_value);
-        }
+                values.push(element_value);
+            }
 
-        Ok(values.to_es_value_facade())
+            Ok(values.to_es_value_facade())
+        })
     }
 
     fn from_jsval_object(
@@ -1057,11 +1237,40 @@ impl EsValueFacade {
     ) -> Result<EsValueFacade, EsError> {
         assert!(obj_ref.is_object());
 
-        let map =
-            crate::quickjs_utils::objects::traverse_properties_q(q_ctx, obj_ref, |_key, val| {
-                EsValueFacade::from_jsval(q_ctx, &val)
-            })?;
-        Ok(map.to_es_value_facade())
+        Self::convert_once(obj_ref, || {
+            let map = crate::quickjs_utils::objects::traverse_properties_q(
+                q_ctx,
+                obj_ref,
+                |_key, val| EsValueFacade::from_jsval(q_ctx, &val),
+            )?;
+            Ok(map.to_es_value_facade())
+        })
+    }
+
+    /// run the conversion of an object or array, fails if that object is already being converted (it contains a circular reference)
+    fn convert_once<C>(obj_ref: &JSValueRef, conversion: C) -> Result<EsValueFacade, EsError>
+    where
+        C: FnOnce() -> Result<EsValueFacade, EsError>,
+    {
+        let ptr = unsafe { obj_ref.borrow_value().u.ptr };
+        let circular = CONVERTING_OBJECTS.with(|rc| {
+            let converting = &mut *rc.borrow_mut();
+            if converting.contains(&ptr) {
+                true
+            } else {
+                converting.push(ptr);
+                false
+            }
+        });
+        if circular {
+            return Err(EsError::new_kind(
+                EsErrorKind::Conversion,
+                "could not convert a circular structure".to_string(),
+            ));
+        }
+        let res = conversion();
+        CONVERTING_OBJECTS.with(|rc| rc.borrow_mut().pop());
+        res
     }
     /// get the String value
     pub fn get_str(&self) -> &str {
@@ -1172,6 +1381,14 @@ impl EsValueFacade {
     pub fn get_promise_result_sync(&self) -> Result<EsValueFacade, EsValueFacade> {
         self.convertible.get_promise_result_sync()
     }
+    /// check if the value is an instance of a Proxy class
+    pub fn is_proxy_instance(&self) -> bool {
+        self.convertible.is_proxy_instance()
+    }
+    /// get the instance of a Proxy class, see [EsProxyInstance]
+    pub fn get_proxy_instance(&self) -> &EsProxyInstance {
+        self.convertible.get_proxy_instance()
+    }
 
     /// wait for the result of a Promise async
     /// # Example
@@ -1207,6 +1424,8 @@ impl Debug for EsValueFacade {
             f.write_str(format!("{}", self.get_f64()).as_str())
         } else if self.is_boolean() {
             f.write_str(format!("{}", self.get_boolean()).as_str())
+        } else if self.is_proxy_instance() {
+            f.write_str(format!("[{}]", self.get_proxy_instance().class_name()).as_str())
         } else if self.is_promise() {
             f.write_str("[Promise]")
         } else if self.is_function() {
@@ -1227,13 +1446,17 @@ impl Debug for EsValueFacade {
 
 #[cfg(test)]
 pub mod tests {
+    use crate::eserror::EsErrorKind;
     use crate::esruntime::tests::init_test_rt;
     use crate::esruntime::EsRuntime;
     use crate::esruntimebuilder::EsRuntimeBuilder;
     use crate::esscript::EsScript;
-    use crate::esvalue::EsValueFacade;
+    use crate::esvalue::{EsPromise, EsValueConvertible, EsValueFacade};
+    use crate::quickjs_utils::primitives;
+    use crate::reflection::Proxy;
     use futures::executor::block_on;
     use std::sync::Arc;
+    use std::time::Duration;
 
     async fn test_async_func1(esvf: EsValueFacade) -> i32 {
         let res = esvf.invoke_function(vec![]).await;
@@ -1255,6 +1478,19 @@ pub mod tests {
         assert_eq!(fut, 147);
     }
 
+    #[test]
+    fn test_circular() {
+        let rt: Arc<EsRuntime> = init_test_rt();
+        let res = rt.eval_sync(EsScript::new(
+            "test_circular.es",
+            "let o = {a: [1]}; o.a.push(o); o;",
+        ));
+        match res {
+            Ok(_) => panic!("circular structure should not convert"),
+            Err(e) => assert!(e.get_message().contains("circular structure")),
+        }
+    }
+
     #[test]
     fn test_promise() {
         let rt: Arc<EsRuntime> = init_test_rt();
@@ -1281,6 +1517,35 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_promise_resolved_before_conversion() {
+        let rt: Arc<EsRuntime> = init_test_rt();
+        rt.eval_sync(EsScript::new(
+            "test_promise_resolved_before_conversion.es",
+            "this.double_prom = function(prom){return prom.then((v) => v * 2);};",
+        ))
+        .expect("script failed");
+
+        let prom = EsPromise::new_unresolving();
+        prom.get_handle().resolve(21_i32.to_es_value_facade());
+        let res = rt
+            .call_function_sync(vec![], "double_prom", vec![prom.to_es_value_facade()])
+            .expect("call failed");
+        let v = res.get_promise_result_sync().expect("promise was rejected");
+        assert_eq!(v.get_i32(), 42);
+
+        let prom = EsPromise::new_unresolving();
+        prom.get_handle()
+            .reject("nope".to_string().to_es_value_facade());
+        let res = rt
+            .call_function_sync(vec![], "double_prom", vec![prom.to_es_value_facade()])
+            .expect("call failed");
+        let e = res
+            .get_promise_result_sync()
+            .expect_err("promise was resolved");
+        assert_eq!(e.get_str(), "nope");
+    }
+
     #[test]
     fn test_promise_async() {
         //simple_logging::log_to_stderr(LevelFilter::max());
@@ -1301,4 +1566,108 @@ pub mod tests {
         let i = block_on(test_async(esvf));
         assert_eq!(i, 1360);
     }
+
+    #[test]
+    fn test_proxy_instance() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<i32>()
+                .name("TestCounter")
+                .constructor(|_q_ctx, _args| Ok(0))
+                .method_mut("inc", |_q_ctx, count, _args| {
+                    *count += 1;
+                    Ok(primitives::from_i32(*count))
+                })
+                .getter_setter(
+                    "count",
+                    |_q_ctx, count| Ok(primitives::from_i32(*count)),
+                    |_q_ctx, _count, _value| Ok(()),
+                )
+                .install(q_ctx, true)
+                .expect("install failed");
+        });
+        let counter = rt
+            .eval_sync(EsScript::new(
+                "test_proxy_instance.es",
+                "this.counter = new TestCounter(); this.isCounter = function(c) {return c === counter;}; counter;",
+            ))
+            .expect("script failed");
+        assert!(counter.is_proxy_instance());
+        assert_eq!(counter.get_proxy_instance().class_name(), "TestCounter");
+
+        let counter = std::thread::spawn(move || {
+            let res = counter
+                .get_proxy_instance()
+                .invoke_method_sync("inc", vec![])
+                .expect("inc failed");
+            assert_eq!(res.get_i32(), 1);
+            counter
+        })
+        .join()
+        .expect("thread failed");
+
+        let count = counter
+            .get_proxy_instance()
+            .get_property_sync("count")
+            .expect("count failed");
+        assert_eq!(count.get_i32(), 1);
+
+        // the instance is the same object when it is passed back to script
+        let same = rt
+            .call_function_sync(vec![], "isCounter", vec![counter])
+            .expect("isCounter failed");
+        assert!(same.get_boolean());
+    }
+
+    #[test]
+    fn test_proxy_instance_dropped() {
+        let rt = EsRuntimeBuilder::new().build();
+        rt.create_context("ctx_proxy_instance")
+            .expect("could not create context");
+        let install_and_create = |context_id: &'static str| {
+            rt.add_to_event_queue_sync(move |q_js_rt| {
+                let q_ctx = q_js_rt.get_context(context_id);
+                Proxy::new()
+                    .name("TestDropped")
+                    .constructor(|_q_ctx, _id, _args| Ok(()))
+                    .method("ping", |_q_ctx, _id, _args| Ok(primitives::from_i32(1)))
+                    .install(q_ctx, true)
+                    .expect("install failed");
+                let instance_ref = q_ctx
+                    .eval(EsScript::new(
+                        "test_proxy_instance_dropped.es",
+                        "new TestDropped();",
+                    ))
+                    .expect("script failed");
+                EsValueFacade::from_jsval(q_ctx, &instance_ref).expect("conversion failed")
+            })
+        };
+        let in_ctx = install_and_create("ctx_proxy_instance");
+        let in_main = install_and_create("__main__");
+        assert_eq!(
+            in_ctx
+                .get_proxy_instance()
+                .invoke_method_sync("ping", vec![])
+                .expect("ping failed")
+                .get_i32(),
+            1
+        );
+
+        rt.drop_context("ctx_proxy_instance");
+        let err = in_ctx
+            .get_proxy_instance()
+            .invoke_method_sync("ping", vec![])
+            .expect_err("ping should fail after the context was dropped");
+        assert_eq!(err.get_kind(), EsErrorKind::ContextDropped);
+        // releasing the cached instance of a dropped context does nothing
+        drop(in_ctx);
+
+        rt.shutdown_sync(Duration::from_secs(1));
+        let err = in_main
+            .get_proxy_instance()
+            .invoke_method_sync("ping", vec![])
+            .expect_err("ping should fail after shutdown");
+        assert_eq!(err.get_kind(), EsErrorKind::ShutDown);
+    }
 }
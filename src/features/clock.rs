@@ -0,0 +1,137 @@
+use crate::eserror::EsError;
+use crate::quickjs_utils;
+use crate::quickjs_utils::{functions, get_constructor, get_global_q, objects, primitives};
+use crate::quickjsruntime::QuickJsRuntime;
+use libquickjs_sys as q;
+use std::time::UNIX_EPOCH;
+
+/// provides performance.now() for the runtime, when the runtime uses a virtual clock Date.now() is also replaced so it returns the time of the virtual clock
+/// N.B. new Date() always uses the system clock
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// let rt = EsRuntimeBuilder::new().build();
+/// let res = rt.eval_sync(EsScript::new("test_perf.es", "performance.now() >= 0;")).expect("script failed");
+/// assert!(res.get_boolean());
+/// ```
+pub fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
+    log::trace!("clock::init");
+
+    q_js_rt.add_context_init_hook(|q_js_rt, q_ctx| {
+        let performance = objects::create_object_q(q_ctx)?;
+        let now_func =
+            functions::new_native_function_q(q_ctx, "now", Some(performance_now), 0, false)?;
+        objects::set_property2_q(q_ctx, &performance, "now", &now_func, 0)?;
+
+        let global = get_global_q(q_ctx);
+        objects::set_property2_q(q_ctx, &global, "performance", &performance, 0)?;
+
+        let is_virtual = q_js_rt
+            .get_rt_ref()
+            .map(|rt| rt.inner.event_queue.get_clock().is_virtual())
+            .unwrap_or(false);
+        if is_virtual {
+            let date_constructor = unsafe { get_constructor(q_ctx.context, "Date") }?;
+            let date_now_func =
+                functions::new_native_function_q(q_ctx, "now", Some(date_now), 0, false)?;
+            objects::set_property2_q(q_ctx, &date_constructor, "now", &date_now_func, 0)?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+unsafe extern "C" fn performance_now(
+    _context: *mut q::JSContext,
+    _this_val: q::JSValue,
+    _argc: ::std::os::raw::c_int,
+    _argv: *mut q::JSValue,
+) -> q::JSValue {
+    QuickJsRuntime::do_with(|q_js_rt| {
+        if let Some(rt) = q_js_rt.get_rt_ref() {
+            let elapsed = rt.inner.event_queue.get_clock().elapsed();
+            primitives::from_f64(elapsed.as_secs_f64() * 1000.0).clone_value_incr_rc()
+        } else {
+            quickjs_utils::new_null()
+        }
+    })
+}
+
+unsafe extern "C" fn date_now(
+    _context: *mut q::JSContext,
+    _this_val: q::JSValue,
+    _argc: ::std::os::raw::c_int,
+    _argv: *mut q::JSValue,
+) -> q::JSValue {
+    QuickJsRuntime::do_with(|q_js_rt| {
+        if let Some(rt) = q_js_rt.get_rt_ref() {
+            let system_time = rt.inner.event_queue.get_clock().system_time();
+            let millis = match system_time.duration_since(UNIX_EPOCH) {
+                Ok(since_epoch) => since_epoch.as_millis() as f64,
+                Err(e) => -(e.duration().as_millis() as f64),
+            };
+            primitives::from_f64(millis).clone_value_incr_rc()
+        } else {
+            quickjs_utils::new_null()
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esscript::EsScript;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_virtual_clock() {
+        let rt = EsRuntimeBuilder::new()
+            .virtual_clock(UNIX_EPOCH + Duration::from_secs(1000))
+            .build();
+
+        rt.eval_sync(EsScript::new(
+            "test_virtual_clock.es",
+            "this.log = [];\
+             this.p_start = performance.now();\
+             setTimeout(() => {log.push('t200@' + Date.now());}, 200);\
+             setTimeout(() => {log.push('t100@' + Date.now()); Promise.resolve().then(() => {log.push('p100');});}, 100);\
+             this.i_id = setInterval(() => {log.push('i150@' + Date.now()); setTimeout(() => {log.push('ti@' + Date.now());}, 10);}, 150);",
+        ))
+        .expect("script failed");
+
+        // the virtual clock does not move by itself
+        std::thread::sleep(Duration::from_millis(250));
+        rt.run_due_tasks();
+        let res = rt
+            .eval_sync(EsScript::new("test_virtual_clock2.es", "log.join(',');"))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "");
+
+        rt.advance_time(Duration::from_millis(320));
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_virtual_clock3.es",
+                "clearInterval(i_id); log.join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "t100@1000100,p100,i150@1000150,ti@1000160,t200@1000200,i150@1000300,ti@1000310"
+        );
+
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_virtual_clock4.es",
+                "performance.now() - p_start;",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 320);
+
+        rt.advance_time(Duration::from_millis(1000));
+        let res = rt
+            .eval_sync(EsScript::new("test_virtual_clock5.es", "log.length;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 7);
+    }
+}
@@ -6,11 +6,25 @@
 //! * console.error()
 //! * console.warning()
 //! * console.trace()
+//! * console.debug()
+//! * console.assert()
+//! * console.count() / console.countReset()
+//! * console.time() / console.timeLog() / console.timeEnd()
+//! * console.group() / console.groupCollapsed() / console.groupEnd(), messages within a group are indented
+//! * console.table()
+//! * console.dir()
+//!
+//! console.trace() also outputs the current stack, if the script has a source map the stack refers to the original source
 //!
 //! The methods use rust's log crate to output messages. e.g. console.info() uses the log::info!() macro
 //! so the console messages should appear in the log you initialized from rust
 //!
-//! All methods accept a single message string and optional substitution values
+//! Alternatively a [ConsoleSink] may be set with [EsRuntimeBuilder::console_sink](../../esruntimebuilder/struct.EsRuntimeBuilder.html#method.console_sink),
+//! the sink then receives all console messages as [ConsoleEntry]s instead of the log crate
+//!
+//! All methods accept a single message string and optional substitution values, arguments which are not used by a substitution are appended.
+//! Arguments which are not strings are rendered with [inspect](../../quickjs_utils/inspect/index.html), e.g. console.log({a: [1, 2]}) outputs { a: [ 1, 2 ] }
+//! and console.dir(obj, {depth: null, colors: true}) accepts the depth and colors options
 //!
 //! e.g.
 //! ```javascript
@@ -19,9 +33,9 @@
 //! will output 'Oh dear some guy totaly failed 12 times because of a 2.4600 variance in the space time continuum'
 //!
 //! The string substitution you can use are
-//! * %o or %O Outputs a JavaScript object (serialized)
+//! * %o or %O Outputs a JavaScript object (rendered like Node.js' util.inspect, %o renders 4 levels deep and %O 2 levels)
 //! * %d or %i Outputs an integer. Number formatting is supported, for example  console.log("Foo %.2d", 1.1) will output the number as two significant figures with a leading 0: Foo 01
-//! * %s Outputs a string (will attempt to call .toString() on objects, use %o to output a serialized JSON string)
+//! * %s Outputs a string (objects with a custom .toString() method are converted with that method, other objects are inspected 0 levels deep)
 //! * %f Outputs a floating-point value. Formatting is supported, for example  console.log("Foo %.2f", 1.1) will output the number to 2 decimal places: Foo 1.10
 //! # Example
 //! ```rust
@@ -41,17 +55,24 @@
 //! which will result in a log entry like
 //! ```[00:00:00.012] (7f44e7d24700) INFO   the quick brown fox jumped over 32 fences with a accuracy of 0.51```
 
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorLocation};
+use crate::esruntime_utils::source_maps;
+use crate::esvalue::{EsValueConvertible, EsValueFacade};
 use crate::quickjs_utils;
 use crate::quickjs_utils::functions::call_to_string;
-use crate::quickjs_utils::{functions, json, parse_args, primitives};
+use crate::quickjs_utils::inspect::InspectOptions;
+use crate::quickjs_utils::{arrays, functions, inspect, objects, parse_args, primitives};
 use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::QuickJsRuntime;
 use crate::reflection::Proxy;
 use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
-use log::LevelFilter;
+use log::Level;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::str::FromStr;
+use std::time::Instant;
 
 pub fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
     log::trace!("console::init");
@@ -67,8 +88,18 @@ pub(crate) fn init_ctx(q_ctx: &QuickJsContext) -> Result<(), EsError> {
         .static_native_method("info", Some(console_info))
         .static_native_method("warn", Some(console_warn))
         .static_native_method("error", Some(console_error))
-        //.static_native_method("assert", Some(console_assert)) // todo
+        .static_native_method("assert", Some(console_assert))
         .static_native_method("debug", Some(console_debug))
+        .static_native_method("count", Some(console_count))
+        .static_native_method("countReset", Some(console_count_reset))
+        .static_native_method("time", Some(console_time))
+        .static_native_method("timeLog", Some(console_time_log))
+        .static_native_method("timeEnd", Some(console_time_end))
+        .static_native_method("group", Some(console_group))
+        .static_native_method("groupCollapsed", Some(console_group))
+        .static_native_method("groupEnd", Some(console_group_end))
+        .static_native_method("table", Some(console_table))
+        .static_native_method("dir", Some(console_dir))
         .install(q_ctx, true)
         .map(|_| {})
 }
@@ -148,29 +179,70 @@ unsafe fn parse_field_value(ctx: *mut q::JSContext, field: &str, value: &JSValue
                     }
                 }
             }
-            return f_val;
-        } else if field.ends_with('o') || field.ends_with('O') {
-            let json_str_res = json::stringify(ctx, value, None);
-            let json = match json_str_res {
-                Ok(json_str) => primitives::to_string(ctx, &json_str)
-                    .or::<String>(Ok("".to_string()))
-                    .unwrap(),
-                Err(_e) => "".to_string(),
-            };
-            return json;
         }
+        return f_val;
+    } else if field.ends_with('o') {
+        return inspect_or_empty(ctx, value, &InspectOptions::new().depth(Some(4)));
+    } else if field.ends_with('O') {
+        return inspect_or_empty(ctx, value, &InspectOptions::new());
+    }
+    if value.is_object() {
+        if !has_user_to_string(ctx, value) {
+            return inspect_or_empty(ctx, value, &InspectOptions::new().depth(Some(0)));
+        }
+    } else if !value.is_string() {
+        // e.g. -0, bigints and symbols
+        return inspect_or_empty(ctx, value, &InspectOptions::new());
     }
     call_to_string(ctx, value)
         .or::<String>(Ok("".to_string()))
         .unwrap()
 }
 
+unsafe fn inspect_or_empty(
+    ctx: *mut q::JSContext,
+    value: &JSValueRef,
+    options: &InspectOptions,
+) -> String {
+    inspect::inspect(ctx, value, options).unwrap_or_default()
+}
+
+/// check if an object has a toString method which is not built-in (built-in functions are rendered as [native code])
+unsafe fn has_user_to_string(ctx: *mut q::JSContext, value: &JSValueRef) -> bool {
+    match objects::get_property(ctx, value, "toString") {
+        Ok(to_string_ref) if functions::is_function(ctx, &to_string_ref) => {
+            functions::call_to_string(ctx, &to_string_ref)
+                .map(|source| !source.contains("[native code]"))
+                .unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+/// format an argument which is not used by a substitution, strings are output as is and other values are inspected
+unsafe fn format_arg(ctx: *mut q::JSContext, value: &JSValueRef) -> String {
+    if value.is_string() {
+        primitives::to_string(ctx, value).unwrap_or_default()
+    } else {
+        inspect_or_empty(ctx, value, &InspectOptions::new())
+    }
+}
+
+/// format the args of a console call, substitutions in the first arg are replaced by the next args and remaining args are appended
 #[allow(clippy::or_fun_call)]
-unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<JSValueRef>) -> String {
+unsafe fn parse_line(ctx: *mut q::JSContext, args: &[JSValueRef]) -> String {
     if args.is_empty() {
         return "".to_string();
     }
 
+    if !args[0].is_string() {
+        return args
+            .iter()
+            .map(|arg| format_arg(ctx, arg))
+            .collect::<Vec<String>>()
+            .join(" ");
+    }
+
     let message = functions::call_to_string(ctx, &args[0])
         .or::<String>(Ok(String::new()))
         .unwrap();
@@ -184,7 +256,13 @@ unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<JSValueRef>) -> String {
     for chr in message.chars() {
         if in_field {
             field_code.push(chr);
-            if chr.eq(&'s') || chr.eq(&'d') || chr.eq(&'f') || chr.eq(&'o') || chr.eq(&'i') {
+            if chr.eq(&'s')
+                || chr.eq(&'d')
+                || chr.eq(&'f')
+                || chr.eq(&'o')
+                || chr.eq(&'O')
+                || chr.eq(&'i')
+            {
                 // end field
 
                 if x < args.len() {
@@ -202,9 +280,190 @@ unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<JSValueRef>) -> String {
         }
     }
 
+    for arg in &args[x..] {
+        output.push(' ');
+        output.push_str(format_arg(ctx, arg).as_str());
+    }
+
     output
 }
 
+/// a single console message as passed to a [ConsoleSink]
+pub struct ConsoleEntry {
+    /// the level of the message, e.g. console.warn() results in Level::Warn
+    pub level: Level,
+    /// the name of the console method which was called, e.g. "log" or "timeEnd"
+    pub method: &'static str,
+    /// the id of the QuickJsContext in which the method was called
+    pub context_id: String,
+    /// the script location of the call (if the script has a source map this refers to the original source),
+    /// this is only set when [ConsoleSink::wants_location] returns true
+    pub location: Option<EsErrorLocation>,
+    /// the formatted message, indented for the current group
+    pub message: String,
+    /// the number of open console.group() calls
+    pub group_depth: usize,
+    /// the arguments as passed to the console method
+    pub args: Vec<EsValueFacade>,
+}
+
+/// a ConsoleSink receives all console output of a runtime, this replaces the default output to the log crate
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// use quickjs_runtime::features::console::ConsoleEntry;
+/// use std::sync::{Arc, Mutex};
+///
+/// let lines = Arc::new(Mutex::new(vec![]));
+/// let lines2 = lines.clone();
+/// let rt = EsRuntimeBuilder::new()
+///     .console_sink(move |entry: ConsoleEntry| {
+///         lines2.lock().unwrap().push(entry.message);
+///     })
+///     .build();
+/// rt.eval_sync(EsScript::new("console_sink.es", "console.log('hello %s', 'world');")).expect("script failed");
+/// assert_eq!(lines.lock().unwrap()[0], "hello world");
+/// ```
+pub trait ConsoleSink {
+    fn log(&self, entry: ConsoleEntry);
+    /// return true to get the script location of every console call in [ConsoleEntry::location],
+    /// this is off by default because getting the location creates an Error object for every call
+    fn wants_location(&self) -> bool {
+        false
+    }
+}
+
+impl<F> ConsoleSink for F
+where
+    F: Fn(ConsoleEntry),
+{
+    fn log(&self, entry: ConsoleEntry) {
+        self(entry)
+    }
+}
+
+#[derive(Default)]
+struct ConsoleState {
+    counts: HashMap<String, u64>,
+    timers: HashMap<String, Instant>,
+    group_depth: usize,
+}
+
+thread_local! {
+    /// the state of count(), time() and group() per context
+    static CONSOLE_STATES: RefCell<HashMap<String, ConsoleState>> = RefCell::new(HashMap::new());
+}
+
+fn with_state<C, R>(context_id: &str, consumer: C) -> R
+where
+    C: FnOnce(&mut ConsoleState) -> R,
+{
+    CONSOLE_STATES.with(|rc| {
+        let states = &mut *rc.borrow_mut();
+        if !states.contains_key(context_id) {
+            states.insert(context_id.to_string(), ConsoleState::default());
+        }
+        consumer(states.get_mut(context_id).expect("no console state"))
+    })
+}
+
+/// remove the console state of a context when it is dropped
+pub(crate) fn remove_state(context_id: &str) {
+    let _ = CONSOLE_STATES.try_with(|rc| {
+        if let Ok(mut states) = rc.try_borrow_mut() {
+            states.remove(context_id);
+        }
+    });
+}
+
+fn has_sink() -> bool {
+    QuickJsRuntime::do_with(|q_js_rt| q_js_rt.console_sink.is_some())
+}
+
+fn get_sink() -> Option<Rc<dyn ConsoleSink>> {
+    QuickJsRuntime::do_with(|q_js_rt| q_js_rt.console_sink.clone())
+}
+
+/// check if output at a level is needed, either there is a sink or the log crate accepts the level
+fn enabled(level: Level) -> bool {
+    log::max_level() >= level || has_sink()
+}
+
+/// output a formatted message to the ConsoleSink of the runtime or to the log crate
+unsafe fn output(
+    ctx: *mut q::JSContext,
+    level: Level,
+    method: &'static str,
+    message: String,
+    args: &[JSValueRef],
+) {
+    let context_id = QuickJsContext::get_id(ctx);
+    let group_depth = with_state(context_id, |state| state.group_depth);
+    let message = if group_depth > 0 {
+        let indent = "  ".repeat(group_depth);
+        message
+            .lines()
+            .map(|line| format!("{}{}", indent, line))
+            .collect::<Vec<String>>()
+            .join("\n")
+    } else {
+        message
+    };
+
+    if let Some(sink) = get_sink() {
+        let location = if sink.wants_location() {
+            EsErrorLocation::parse_stack(get_trace_stack(ctx).as_str())
+        } else {
+            None
+        };
+        let args = QuickJsContext::with_context(ctx, |q_ctx| {
+            args.iter()
+                .map(|arg| {
+                    EsValueFacade::from_jsval(q_ctx, arg)
+                        .unwrap_or_else(|_| parse_field_value(ctx, "%s", arg).to_es_value_facade())
+                })
+                .collect()
+        });
+        let entry = ConsoleEntry {
+            level,
+            method,
+            context_id: context_id.to_string(),
+            location,
+            message,
+            group_depth,
+            args,
+        };
+        // the sink is called outside of QuickJsRuntime::do_with
+        sink.log(entry);
+    } else {
+        log::log!(level, "{}", message);
+    }
+}
+
+/// the implementation of log(), info(), debug(), warn() and error()
+unsafe fn log_args(
+    ctx: *mut q::JSContext,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    level: Level,
+    method: &'static str,
+) -> q::JSValue {
+    if enabled(level) {
+        let args = parse_args(ctx, argc, argv);
+        output(ctx, level, method, parse_line(ctx, &args), &args);
+    }
+    quickjs_utils::new_null()
+}
+
+/// get the label argument of count(), time() etc
+unsafe fn get_label(ctx: *mut q::JSContext, args: &[JSValueRef]) -> String {
+    match args.first() {
+        Some(label) if !label.is_undefined() => parse_field_value(ctx, "%s", label),
+        _ => "default".to_string(),
+    }
+}
+
 unsafe extern "C" fn console_log(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
@@ -212,12 +471,29 @@ unsafe extern "C" fn console_log(
     argv: *mut q::JSValue,
 ) -> q::JSValue {
     log::trace!("> console.log");
+    log_args(ctx, argc, argv, Level::Info, "log")
+}
 
-    if log::max_level() >= LevelFilter::Info {
-        let args = parse_args(ctx, argc, argv);
-        log::info!("{}", parse_line(ctx, args));
+/// get the current stack (rewritten by source maps, if any) without the frame of the native console method
+unsafe fn get_trace_stack(ctx: *mut q::JSContext) -> String {
+    let stack = quickjs_utils::get_constructor(ctx, "Error")
+        .and_then(|constructor| objects::construct_object(ctx, &constructor, vec![]))
+        .and_then(|err| objects::get_property(ctx, &err, "stack"))
+        .and_then(|stack_ref| primitives::to_string(ctx, &stack_ref));
+    match stack {
+        Ok(stack) => {
+            let stack = source_maps::rewrite_stack(stack.as_str());
+            stack
+                .lines()
+                .filter(|frame| !frame.ends_with("(native)"))
+                .collect::<Vec<&str>>()
+                .join("\n")
+        }
+        Err(e) => {
+            log::error!("could not get stack for console.trace: {}", e);
+            "".to_string()
+        }
     }
-    quickjs_utils::new_null()
 }
 
 unsafe extern "C" fn console_trace(
@@ -227,9 +503,10 @@ unsafe extern "C" fn console_trace(
     argv: *mut q::JSValue,
 ) -> q::JSValue {
     log::trace!("> console.trace");
-    if log::max_level() >= LevelFilter::Trace {
+    if enabled(Level::Trace) {
         let args = parse_args(ctx, argc, argv);
-        log::trace!("{}", parse_line(ctx, args));
+        let message = format!("{}\n{}", parse_line(ctx, &args), get_trace_stack(ctx));
+        output(ctx, Level::Trace, "trace", message, &args);
     }
     quickjs_utils::new_null()
 }
@@ -241,55 +518,414 @@ unsafe extern "C" fn console_debug(
     argv: *mut q::JSValue,
 ) -> q::JSValue {
     log::trace!("> console.debug");
+    log_args(ctx, argc, argv, Level::Debug, "debug")
+}
 
-    if log::max_level() >= LevelFilter::Debug {
-        let args = parse_args(ctx, argc, argv);
-        log::debug!("{}", parse_line(ctx, args));
+unsafe extern "C" fn console_info(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.info");
+    log_args(ctx, argc, argv, Level::Info, "info")
+}
+
+unsafe extern "C" fn console_warn(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.warn");
+    log_args(ctx, argc, argv, Level::Warn, "warn")
+}
+
+unsafe extern "C" fn console_error(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.error");
+    log_args(ctx, argc, argv, Level::Error, "error")
+}
+
+unsafe extern "C" fn console_assert(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.assert");
+    let args = parse_args(ctx, argc, argv);
+    let ok = match args.first() {
+        Some(condition) => q::JS_ToBool(ctx, *condition.borrow_value()) > 0,
+        None => false,
+    };
+    if !ok && enabled(Level::Error) {
+        let message = if args.len() > 1 {
+            format!("Assertion failed: {}", parse_line(ctx, &args[1..]))
+        } else {
+            "Assertion failed".to_string()
+        };
+        output(ctx, Level::Error, "assert", message, &args);
     }
     quickjs_utils::new_null()
 }
 
-unsafe extern "C" fn console_info(
+unsafe extern "C" fn console_count(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    log::trace!("> console.info");
+    log::trace!("> console.count");
+    let args = parse_args(ctx, argc, argv);
+    let label = get_label(ctx, &args);
+    let count = with_state(QuickJsContext::get_id(ctx), |state| {
+        let count = state.counts.entry(label.clone()).or_insert(0);
+        *count += 1;
+        *count
+    });
+    if enabled(Level::Info) {
+        output(
+            ctx,
+            Level::Info,
+            "count",
+            format!("{}: {}", label, count),
+            &args,
+        );
+    }
+    quickjs_utils::new_null()
+}
 
-    if log::max_level() >= LevelFilter::Info {
-        let args = parse_args(ctx, argc, argv);
-        log::info!("{}", parse_line(ctx, args));
+unsafe extern "C" fn console_count_reset(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.countReset");
+    let args = parse_args(ctx, argc, argv);
+    let label = get_label(ctx, &args);
+    let existed = with_state(QuickJsContext::get_id(ctx), |state| {
+        state.counts.insert(label.clone(), 0).is_some()
+    });
+    if !existed && enabled(Level::Warn) {
+        let message = format!("Count for '{}' does not exist", label);
+        output(ctx, Level::Warn, "countReset", message, &args);
     }
     quickjs_utils::new_null()
 }
 
-unsafe extern "C" fn console_warn(
+unsafe extern "C" fn console_time(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    log::trace!("> console.warn");
+    log::trace!("> console.time");
+    let args = parse_args(ctx, argc, argv);
+    let label = get_label(ctx, &args);
+    let existed = with_state(QuickJsContext::get_id(ctx), |state| {
+        if state.timers.contains_key(&label) {
+            true
+        } else {
+            state.timers.insert(label.clone(), clock_now());
+            false
+        }
+    });
+    if existed && enabled(Level::Warn) {
+        let message = format!("Timer '{}' already exists", label);
+        output(ctx, Level::Warn, "time", message, &args);
+    }
+    quickjs_utils::new_null()
+}
 
-    if log::max_level() >= LevelFilter::Warn {
-        let args = parse_args(ctx, argc, argv);
-        log::warn!("{}", parse_line(ctx, args));
+/// get the current Instant of the runtime's clock, this is a virtual time when the runtime uses a virtual clock
+fn clock_now() -> Instant {
+    QuickJsRuntime::do_with(|q_js_rt| match q_js_rt.get_rt_ref() {
+        Some(rt) => rt.inner.event_queue.get_clock().now(),
+        None => Instant::now(),
+    })
+}
+
+/// the implementation of timeLog() and timeEnd()
+unsafe fn time_log(
+    ctx: *mut q::JSContext,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    method: &'static str,
+    end: bool,
+) -> q::JSValue {
+    let args = parse_args(ctx, argc, argv);
+    let label = get_label(ctx, &args);
+    let started = with_state(QuickJsContext::get_id(ctx), |state| {
+        if end {
+            state.timers.remove(&label)
+        } else {
+            state.timers.get(&label).cloned()
+        }
+    });
+    match started {
+        Some(started) => {
+            if enabled(Level::Info) {
+                let elapsed = clock_now().duration_since(started).as_secs_f64() * 1000.0;
+                let mut message = format!("{}: {:.3}ms", label, elapsed);
+                if !end && args.len() > 1 {
+                    for arg in &args[1..] {
+                        message.push(' ');
+                        message.push_str(format_arg(ctx, arg).as_str());
+                    }
+                }
+                output(ctx, Level::Info, method, message, &args);
+            }
+        }
+        None => {
+            if enabled(Level::Warn) {
+                let message = format!("Timer '{}' does not exist", label);
+                output(ctx, Level::Warn, method, message, &args);
+            }
+        }
     }
     quickjs_utils::new_null()
 }
 
-unsafe extern "C" fn console_error(
+unsafe extern "C" fn console_time_log(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    log::trace!("> console.error");
+    log::trace!("> console.timeLog");
+    time_log(ctx, argc, argv, "timeLog", false)
+}
+
+unsafe extern "C" fn console_time_end(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.timeEnd");
+    time_log(ctx, argc, argv, "timeEnd", true)
+}
+
+unsafe extern "C" fn console_group(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.group");
+    let args = parse_args(ctx, argc, argv);
+    if !args.is_empty() && enabled(Level::Info) {
+        output(ctx, Level::Info, "group", parse_line(ctx, &args), &args);
+    }
+    with_state(QuickJsContext::get_id(ctx), |state| state.group_depth += 1);
+    quickjs_utils::new_null()
+}
+
+unsafe extern "C" fn console_group_end(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    _argc: ::std::os::raw::c_int,
+    _argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.groupEnd");
+    with_state(QuickJsContext::get_id(ctx), |state| {
+        state.group_depth = state.group_depth.saturating_sub(1)
+    });
+    quickjs_utils::new_null()
+}
+
+/// format a value for a cell of console.table()
+unsafe fn format_cell(ctx: *mut q::JSContext, value: &JSValueRef) -> String {
+    let options = InspectOptions::new()
+        .depth(Some(0))
+        .max_array_length(3)
+        .break_length(usize::MAX);
+    inspect_or_empty(ctx, value, &options)
+}
+
+/// render the rows of an object or array as a table
+unsafe fn format_table(
+    ctx: *mut q::JSContext,
+    data: &JSValueRef,
+    columns: Option<Vec<String>>,
+) -> Result<String, EsError> {
+    let mut headers: Vec<String> = vec![];
+    let mut has_values = false;
+    let mut rows = vec![];
+
+    for index in objects::get_property_names(ctx, data)? {
+        let row = objects::get_property(ctx, data, index.as_str())?;
+        let mut cells = HashMap::new();
+        let mut value = None;
+        if row.is_object() && !functions::is_function(ctx, &row) {
+            for name in objects::get_property_names(ctx, &row)? {
+                let cell = objects::get_property(ctx, &row, name.as_str())?;
+                cells.insert(name.clone(), format_cell(ctx, &cell));
+                if !headers.contains(&name) {
+                    headers.push(name);
+                }
+            }
+        } else {
+            has_values = true;
+            value = Some(format_cell(ctx, &row));
+        }
+        rows.push((index, cells, value));
+    }
+    if let Some(columns) = columns {
+        headers = columns;
+    }
+
+    let mut table: Vec<Vec<String>> = vec![];
+    let mut header_row = vec!["(index)".to_string()];
+    header_row.extend(headers.iter().cloned());
+    if has_values {
+        header_row.push("Values".to_string());
+    }
+    table.push(header_row);
+    for (index, mut cells, value) in rows {
+        let mut row = vec![index];
+        for header in &headers {
+            row.push(cells.remove(header).unwrap_or_default());
+        }
+        if has_values {
+            row.push(value.unwrap_or_default());
+        }
+        table.push(row);
+    }
 
-    if log::max_level() >= LevelFilter::Error {
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|col| {
+            table
+                .iter()
+                .map(|row| row[col].chars().count() + 2)
+                .max()
+                .unwrap_or(2)
+        })
+        .collect();
+    let line = |left: &str, mid: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+        format!("{}{}{}", left, parts.join(mid), right)
+    };
+    let render_row = |row: &Vec<String>| {
+        let parts: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| {
+                let pad = width - cell.chars().count();
+                format!(
+                    "{}{}{}",
+                    " ".repeat(pad / 2),
+                    cell,
+                    " ".repeat(pad - pad / 2)
+                )
+            })
+            .collect();
+        format!("│{}│", parts.join("│"))
+    };
+
+    let mut lines = vec![
+        line("┌", "┬", "┐"),
+        render_row(&table[0]),
+        line("├", "┼", "┤"),
+    ];
+    for row in &table[1..] {
+        lines.push(render_row(row));
+    }
+    lines.push(line("└", "┴", "┘"));
+    Ok(lines.join("\n"))
+}
+
+unsafe extern "C" fn console_table(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.table");
+    if enabled(Level::Info) {
         let args = parse_args(ctx, argc, argv);
-        log::error!("{}", parse_line(ctx, args));
+        let message = match args.first() {
+            Some(data) if data.is_object() => {
+                let columns = match args.get(1) {
+                    Some(columns) if arrays::is_array(ctx, columns) => {
+                        let names: Result<Vec<String>, EsError> =
+                            (0..arrays::get_length(ctx, columns).unwrap_or(0))
+                                .map(|i| {
+                                    arrays::get_element(ctx, columns, i)
+                                        .map(|name| parse_field_value(ctx, "%s", &name))
+                                })
+                                .collect();
+                        names.ok()
+                    }
+                    _ => None,
+                };
+                format_table(ctx, data, columns).unwrap_or_else(|e| {
+                    log::error!("console.table failed: {}", e);
+                    parse_line(ctx, &args)
+                })
+            }
+            _ => parse_line(ctx, &args),
+        };
+        output(ctx, Level::Info, "table", message, &args);
+    }
+    quickjs_utils::new_null()
+}
+
+/// get the InspectOptions from the options argument of console.dir(obj, {depth: 4, colors: true}), a depth of null renders all levels
+unsafe fn get_dir_options(ctx: *mut q::JSContext, options_ref: &JSValueRef) -> InspectOptions {
+    let mut options = InspectOptions::new();
+    if let Ok(depth_ref) = objects::get_property(ctx, options_ref, "depth") {
+        if depth_ref.is_null() {
+            options = options.depth(None);
+        } else if depth_ref.is_i32() {
+            let depth = primitives::to_i32(&depth_ref).unwrap_or(2);
+            options = options.depth(Some(depth.max(0) as usize));
+        } else if depth_ref.is_f64() {
+            // e.g. Infinity
+            let depth = primitives::to_f64(&depth_ref).unwrap_or(2.0);
+            options = if depth.is_finite() {
+                options.depth(Some(depth.max(0.0) as usize))
+            } else {
+                options.depth(None)
+            };
+        }
+    }
+    if let Ok(colors_ref) = objects::get_property(ctx, options_ref, "colors") {
+        if colors_ref.is_bool() {
+            options = options.colors(primitives::to_bool(&colors_ref).unwrap_or(false));
+        }
+    }
+    options
+}
+
+unsafe extern "C" fn console_dir(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> console.dir");
+    if enabled(Level::Info) {
+        let args = parse_args(ctx, argc, argv);
+        let message = match args.first() {
+            Some(obj) => {
+                let options = match args.get(1) {
+                    Some(options_ref) if options_ref.is_object() => {
+                        get_dir_options(ctx, options_ref)
+                    }
+                    _ => InspectOptions::new(),
+                };
+                inspect_or_empty(ctx, obj, &options)
+            }
+            None => "undefined".to_string(),
+        };
+        output(ctx, Level::Info, "dir", message, &args);
     }
     quickjs_utils::new_null()
 }
@@ -298,8 +934,13 @@ unsafe extern "C" fn console_error(
 pub mod tests {
     use crate::esruntime::tests::init_test_rt;
     use crate::esruntime::EsRuntime;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
     use crate::esscript::EsScript;
-    use std::sync::Arc;
+    use crate::features::console::{parse_line, ConsoleEntry, ConsoleSink};
+    use crate::quickjs_utils::primitives;
+    use crate::valueref::JSValueRef;
+    use log::Level;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     pub fn test_console() {
@@ -311,6 +952,174 @@ pub mod tests {
         ))
         .ok()
         .expect("test_console.es failed");
+        rt.eval_sync(EsScript::new(
+            "test_console_trace.es",
+            "function t() {console.trace('tracing %s', 'this');}; t();",
+        ))
+        .expect("test_console_trace.es failed");
         log::info!("< test_console");
     }
+
+    #[test]
+    pub fn test_console_sink() {
+        type Entries = Arc<Mutex<Vec<(Level, &'static str, String, u32)>>>;
+        struct LocatingSink {
+            entries: Entries,
+        }
+        impl ConsoleSink for LocatingSink {
+            fn log(&self, entry: ConsoleEntry) {
+                let line = entry.location.map(|l| l.line).unwrap_or(0);
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .push((entry.level, entry.method, entry.message, line));
+            }
+            fn wants_location(&self) -> bool {
+                true
+            }
+        }
+
+        let entries: Entries = Arc::new(Mutex::new(vec![]));
+        let rt = EsRuntimeBuilder::new()
+            .console_sink(LocatingSink {
+                entries: entries.clone(),
+            })
+            .build();
+        rt.eval_sync(EsScript::new(
+            "test_console_sink.es",
+            "console.log('a %s', 'b', 'c');\n\
+             console.assert(1 === 1, 'not logged');\n\
+             console.assert(1 === 2, 'one is %s', 'two');\n\
+             console.count(); console.count('x'); console.count();\n\
+             console.countReset('x'); console.count('x');\n\
+             console.group('outer');\n\
+             console.warn('inner');\n\
+             console.groupEnd();\n\
+             console.time('t'); console.timeEnd('t'); console.timeEnd('t');\n\
+             console.table([{a: 1, b: 'y'}, {a: 2}]);\n\
+             console.dir({a: [1, 2]});",
+        ))
+        .expect("test_console_sink.es failed");
+
+        let entries = &*entries.lock().unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.2.as_str()).collect();
+        assert_eq!(messages[0], "a b c");
+        assert_eq!(entries[0].0, Level::Info);
+        assert_eq!(entries[0].3, 1);
+        assert_eq!(messages[1], "Assertion failed: one is two");
+        assert_eq!(entries[1].0, Level::Error);
+        assert_eq!(entries[1].3, 3);
+        assert_eq!(
+            &messages[2..6],
+            &["default: 1", "x: 1", "default: 2", "x: 1"]
+        );
+        assert_eq!(messages[6], "outer");
+        assert_eq!(messages[7], "  inner");
+        assert_eq!(entries[7].0, Level::Warn);
+        assert!(messages[8].starts_with("t: "));
+        assert!(messages[8].ends_with("ms"));
+        assert_eq!(messages[9], "Timer 't' does not exist");
+        assert_eq!(
+            messages[10],
+            "┌─────────┬───┬─────┐\n\
+             │ (index) │ a │  b  │\n\
+             ├─────────┼───┼─────┤\n\
+             │    0    │ 1 │ 'y' │\n\
+             │    1    │ 2 │     │\n\
+             └─────────┴───┴─────┘"
+        );
+        assert_eq!(entries[10].1, "table");
+        assert_eq!(messages[11], "{ a: [ 1, 2 ] }");
+        assert_eq!(entries.len(), 12);
+    }
+
+    #[test]
+    pub fn test_parse_line() {
+        let rt: Arc<EsRuntime> = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let line = |args: Vec<JSValueRef>| unsafe { parse_line(q_ctx.context, &args) };
+            // args without a substitution are appended
+            assert_eq!(
+                line(vec![
+                    primitives::from_string_q(q_ctx, "%s and %d").expect("a"),
+                    primitives::from_string_q(q_ctx, "one").expect("b"),
+                    primitives::from_i32(2),
+                    primitives::from_bool(true),
+                    primitives::from_string_q(q_ctx, "four").expect("c"),
+                ]),
+                "one and 2 true four"
+            );
+            assert_eq!(
+                line(vec![
+                    primitives::from_string_q(q_ctx, "no fields").expect("d"),
+                    primitives::from_i32(1),
+                ]),
+                "no fields 1"
+            );
+            // missing args leave the field empty
+            assert_eq!(
+                line(vec![primitives::from_string_q(q_ctx, "a %s b").expect("e")]),
+                "a  b"
+            );
+            // a first arg which is not a string is formatted like the other args
+            assert_eq!(
+                line(vec![primitives::from_i32(1), primitives::from_i32(2)]),
+                "1 2"
+            );
+        });
+    }
+
+    #[test]
+    pub fn test_console_sink_without_location() {
+        let locations = Arc::new(Mutex::new(vec![]));
+        let locations2 = locations.clone();
+        let rt = EsRuntimeBuilder::new()
+            .console_sink(move |entry: ConsoleEntry| {
+                locations2.lock().unwrap().push(entry.location.is_some());
+            })
+            .build();
+        rt.eval_sync(EsScript::new(
+            "test_console_sink_without_location.es",
+            "console.log('a'); console.warn('b');",
+        ))
+        .expect("script failed");
+        assert_eq!(*locations.lock().unwrap(), vec![false, false]);
+    }
+
+    #[test]
+    pub fn test_console_inspect() {
+        let messages = Arc::new(Mutex::new(vec![]));
+        let messages2 = messages.clone();
+        let rt = EsRuntimeBuilder::new()
+            .console_sink(move |entry: ConsoleEntry| {
+                messages2.lock().unwrap().push(entry.message);
+            })
+            .build();
+        rt.eval_sync(EsScript::new(
+            "test_console_inspect.es",
+            "let o = {a: {b: {c: {d: 1}}}}; o.me = o;\n\
+             console.log(o);\n\
+             console.log('o: %o', {f: function f() {}, m: new Map([[1, 'one']])});\n\
+             console.log('s: %s %s %s', {a: [1]}, {toString: () => 'custom'}, -0);\n\
+             console.log('rest', 1, 'two', [3], null);\n\
+             console.dir({a: {b: {c: {d: 1}}}}, {depth: null});\n\
+             console.dir({a: {b: 1}}, {depth: 0});",
+        ))
+        .expect("test_console_inspect.es failed");
+
+        let messages = &*messages.lock().unwrap();
+        assert_eq!(
+            messages[0],
+            "<ref *1> { a: { b: { c: [Object] } }, me: [Circular *1] }"
+        );
+        assert_eq!(
+            messages[1],
+            "o: { f: [Function: f], m: Map(1) { 1 => 'one' } }"
+        );
+        assert_eq!(messages[2], "s: { a: [Array] } custom -0");
+        assert_eq!(messages[3], "rest 1 two [ 3 ] null");
+        assert_eq!(messages[4], "{ a: { b: { c: { d: 1 } } } }");
+        assert_eq!(messages[5], "{ a: [Object] }");
+    }
 }
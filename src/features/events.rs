@@ -0,0 +1,137 @@
+use crate::eserror::{EsError, EsErrorKind};
+use crate::quickjs_utils;
+use crate::quickjs_utils::{functions, get_global_q, objects, parse_args, primitives};
+use crate::quickjscontext::QuickJsContext;
+use crate::quickjsruntime::QuickJsRuntime;
+use crate::reflection::eventtarget;
+use crate::valueref::JSValueRef;
+use libquickjs_sys as q;
+
+/// provides addEventListener(), removeEventListener() and dispatchEvent() on the global object
+/// the runtime dispatches a beforeunload and an unload event when it is shut down, see [EsRuntime::shutdown](../../esruntime/struct.EsRuntime.html#method.shutdown)
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.eval_sync(EsScript::new("test_events.es", "addEventListener('unload', (evt) => {console.log('runtime is shutting down');});")).expect("script failed");
+/// ```
+pub fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
+    log::trace!("events::init");
+
+    q_js_rt.add_context_init_hook(|_q_js_rt, q_ctx| {
+        let add_func = functions::new_native_function_q(
+            q_ctx,
+            "addEventListener",
+            Some(add_event_listener),
+            2,
+            false,
+        )?;
+        let remove_func = functions::new_native_function_q(
+            q_ctx,
+            "removeEventListener",
+            Some(remove_event_listener),
+            2,
+            false,
+        )?;
+        let dispatch_func = functions::new_native_function_q(
+            q_ctx,
+            "dispatchEvent",
+            Some(dispatch_event),
+            2,
+            false,
+        )?;
+
+        let global = get_global_q(q_ctx);
+        objects::set_property2_q(q_ctx, &global, "addEventListener", &add_func, 0)?;
+        objects::set_property2_q(q_ctx, &global, "removeEventListener", &remove_func, 0)?;
+        objects::set_property2_q(q_ctx, &global, "dispatchEvent", &dispatch_func, 0)?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// the global functions may be called without a this (e.g. addEventListener('unload', ..)), in that case the global object is the target
+fn get_target(q_ctx: &QuickJsContext, this_val: q::JSValue) -> JSValueRef {
+    let this_ref = JSValueRef::new(q_ctx.context, this_val, true, true, "events_this");
+    if this_ref.is_null_or_undefined() {
+        get_global_q(q_ctx)
+    } else {
+        this_ref
+    }
+}
+
+unsafe extern "C" fn add_event_listener(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> addEventListener");
+    let args = parse_args(context, argc, argv);
+    QuickJsContext::with_context(context, |q_ctx| {
+        match eventtarget::add_event_listener(q_ctx, &get_target(q_ctx, this_val), &args) {
+            Ok(_) => quickjs_utils::new_null(),
+            Err(e) => q_ctx.report_es_error(&e),
+        }
+    })
+}
+
+unsafe extern "C" fn remove_event_listener(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> removeEventListener");
+    let args = parse_args(context, argc, argv);
+    QuickJsContext::with_context(context, |q_ctx| {
+        match eventtarget::remove_event_listener(q_ctx, &get_target(q_ctx, this_val), &args) {
+            Ok(_) => quickjs_utils::new_null(),
+            Err(e) => q_ctx.report_es_error(&e),
+        }
+    })
+}
+
+unsafe extern "C" fn dispatch_event(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    log::trace!("> dispatchEvent");
+    let args = parse_args(context, argc, argv);
+    QuickJsContext::with_context(context, |q_ctx| {
+        if args.len() != 2 || !args[0].is_string() {
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "dispatchEvent requires 2 arguments (eventId: String and eventObj: Object)"
+                    .to_string(),
+            ));
+        }
+        let res = primitives::to_string_q(q_ctx, &args[0]).and_then(|event_id| {
+            eventtarget::dispatch_event(
+                q_ctx,
+                &get_target(q_ctx, this_val),
+                event_id.as_str(),
+                args[1].clone(),
+            )
+        });
+        match res {
+            Ok(_) => quickjs_utils::new_null(),
+            Err(e) => q_ctx.report_es_error(&e),
+        }
+    })
+}
+
+/// dispatch an event (e.g. unload) on the global object of a context, the listeners receive an object with a type property
+pub(crate) fn dispatch_global_event(
+    q_ctx: &QuickJsContext,
+    event_type: &str,
+) -> Result<(), EsError> {
+    let evt_obj = objects::create_object_q(q_ctx)?;
+    let type_ref = primitives::from_string_q(q_ctx, event_type)?;
+    objects::set_property_q(q_ctx, &evt_obj, "type", &type_ref)?;
+    let global = get_global_q(q_ctx);
+    eventtarget::dispatch_event(q_ctx, &global, event_type, evt_obj)
+}
@@ -1,4 +1,4 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::esruntime::EsRuntime;
 use crate::esruntime_utils::promises;
 use crate::features::fetch::request::FetchRequest;
@@ -7,6 +7,7 @@ use crate::quickjs_utils;
 use crate::quickjs_utils::{functions, objects, parse_args, primitives};
 use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::QuickJsRuntime;
+use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -37,6 +38,17 @@ pub(crate) fn init(es_rt: Arc<EsRuntime>) -> Result<(), EsError> {
     })
 }
 
+/// get the method from the (optional) init object, e.g. fetch(url, {method: 'POST'}), defaults to GET
+fn get_method(q_ctx: &QuickJsContext, args: &[JSValueRef]) -> Result<String, EsError> {
+    if args.len() > 1 && args[1].is_object() {
+        let method_ref = objects::get_property_q(q_ctx, &args[1], "method")?;
+        if method_ref.is_string() {
+            return primitives::to_string_q(q_ctx, &method_ref);
+        }
+    }
+    Ok("GET".to_string())
+}
+
 unsafe extern "C" fn fetch_func(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
@@ -48,16 +60,33 @@ unsafe extern "C" fn fetch_func(
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(ctx);
         if args_vec.is_empty() {
-            return q_ctx.report_ex("need at least a url arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "need at least a url arg".to_string(),
+            ));
         }
 
         let url_arg = &args_vec[0];
         if !url_arg.is_string() {
-            return q_ctx.report_ex("url argument needs to be a string");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "url argument needs to be a string".to_string(),
+            ));
         }
 
         let url = primitives::to_string(ctx, &url_arg).ok().unwrap();
 
+        let method = match get_method(q_ctx, &args_vec) {
+            Ok(method) => method,
+            Err(e) => return q_ctx.report_es_error(&e),
+        };
+        if let Err(e) = q_ctx
+            .get_permissions()
+            .check_fetch(url.as_str(), method.as_str())
+        {
+            return q_ctx.report_es_error(&e);
+        }
+
         if let Some(rt_ref) = q_js_rt.get_rt_ref() {
             if rt_ref.inner.fetch_response_provider.is_some() {
                 let producer = move || {
@@ -86,13 +115,16 @@ unsafe extern "C" fn fetch_func(
                 let prom_res = promises::new_resolving_promise(q_ctx, producer, mapper, es_rt);
                 match prom_res {
                     Ok(prom_ref) => prom_ref.clone_value_incr_rc(),
-                    Err(e) => q_ctx.report_ex(e.get_message()),
+                    Err(e) => q_ctx.report_es_error(&e),
                 }
             } else {
-                q_ctx.report_ex("No fetch response provider present in this runtime")
+                q_ctx.report_es_error(&EsError::new_kind(
+                    EsErrorKind::Internal,
+                    "No fetch response provider present in this runtime".to_string(),
+                ))
             }
         } else {
-            q_ctx.report_ex("Runtime was dropped")
+            q_ctx.report_es_error(&EsError::runtime_dropped())
         }
     })
 }
@@ -130,6 +162,19 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_fetch_errors() {
+        let rt = EsRuntimeBuilder::new().build();
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_fetch_errors.es",
+                "[() => fetch(), () => fetch(1), () => fetch('https://example.com')]\
+                 .map((f) => { try { f(); return 'ok'; } catch (ex) { return ex.name; } }).join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "TypeError,TypeError,InternalError");
+    }
+
     #[test]
     fn test_fetch() {
         let main_rt: Arc<EsRuntime> = init_test_rt();
@@ -0,0 +1,397 @@
+//! lockdown hardens contexts which run untrusted code (e.g. third-party plugins sharing a context), see [EsRuntimeBuilder::lockdown](../../esruntimebuilder/struct.EsRuntimeBuilder.html#method.lockdown)
+//!
+//! when a runtime is locked down every context is hardened after the features were initialized
+//! * all intrinsics (Object, Promise, Object.prototype etc) and all globals installed by features (e.g. console, setTimeout) are frozen deeply
+//! * the existing properties of the global object become non-writable and non-configurable, scripts may still add new globals
+//! * functions installed by [EsRuntime::set_function](../../esruntime/struct.EsRuntime.html#method.set_function) and installed Proxy classes are frozen
+//! * eval() and the Function constructors (also the async and generator variants) throw a TypeError unless eval is allowed
+//! * a harden(obj) function is added to the global object which may be used to freeze your own objects deeply
+//! * optionally a Compartment class is added which evaluates code with its own isolated global object
+//!
+//! assigning a property of a frozen prototype normally fails (e.g. MyClass.prototype.toString = ..), for Object.prototype, Function.prototype, Array.prototype,
+//! Promise.prototype and the Error prototypes these properties are converted to accessors which define the property on the object which is assigned instead
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::features::lockdown::LockdownOptions;
+//! let rt = EsRuntimeBuilder::new().lockdown(LockdownOptions::new()).build();
+//! let res = rt.eval_sync(EsScript::new("plugin.es", "Object.prototype.polluted = true; ({}).polluted;")).expect("script failed");
+//! assert!(res.is_undefined());
+//! let res = rt.eval_sync(EsScript::new("plugin2.es", "try {eval('1');} catch(ex) {ex.name;}")).expect("script failed");
+//! assert_eq!(res.get_str(), "TypeError");
+//! ```
+
+use crate::eserror::EsError;
+use crate::quickjs_utils::{compile, functions, get_global_q, objects, primitives};
+use crate::quickjscontext::QuickJsContext;
+use crate::quickjsruntime::QuickJsRuntime;
+use crate::valueref::JSValueRef;
+
+/// the options for locking down the contexts of a runtime
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LockdownOptions {
+    allow_eval: bool,
+    compartments: bool,
+}
+
+impl LockdownOptions {
+    /// init new LockdownOptions, by default eval is not allowed and no Compartment class is installed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// do not replace eval() and the Function constructors
+    pub fn allow_eval(mut self, allow: bool) -> Self {
+        self.allow_eval = allow;
+        self
+    }
+
+    /// install a Compartment class, new Compartment(endowments).evaluate(code) evaluates code with its own global object
+    /// which only contains the (frozen) intrinsics, harden and the endowments
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// use quickjs_runtime::features::lockdown::LockdownOptions;
+    /// let rt = EsRuntimeBuilder::new().lockdown(LockdownOptions::new().compartments(true)).build();
+    /// let res = rt.eval_sync(EsScript::new("compartment.es", "new Compartment({a: 2}).evaluate('a * 21;');")).expect("script failed");
+    /// assert_eq!(res.get_i32(), 42);
+    /// ```
+    /// N.B. a compartment only isolates code if eval is not allowed, otherwise code can escape through the Function constructor
+    /// the variables and functions declared by the code are not kept between calls to evaluate, use globalThis to share state
+    /// code which contains an import expression (import(...)) is rejected with a SyntaxError because it could load modules outside of the compartment,
+    /// this check is done on the source text so strings and comments which contain "import(" are rejected as well
+    pub fn compartments(mut self, compartments: bool) -> Self {
+        self.compartments = compartments;
+        self
+    }
+}
+
+const LOCKDOWN_SCRIPT: &str = r#"(function lockdown(allowEval, compartments) {
+    'use strict';
+    const global = globalThis;
+    const {defineProperty, getOwnPropertyDescriptor, getPrototypeOf, setPrototypeOf, freeze} = Object;
+    const ownKeys = Reflect.ownKeys;
+    const intrinsicEval = global.eval;
+
+    const hardened = new WeakSet();
+    function harden(root) {
+        const todo = [root];
+        const seen = new Set();
+        while (todo.length > 0) {
+            const val = todo.pop();
+            if (val === null || (typeof val !== 'object' && typeof val !== 'function') || seen.has(val) || hardened.has(val)) {
+                continue;
+            }
+            seen.add(val);
+            freeze(val);
+            todo.push(getPrototypeOf(val));
+            for (const key of ownKeys(val)) {
+                const desc = getOwnPropertyDescriptor(val, key);
+                if ('value' in desc) {
+                    todo.push(desc.value);
+                } else {
+                    todo.push(desc.get, desc.set);
+                }
+            }
+        }
+        seen.forEach((val) => hardened.add(val));
+        return root;
+    }
+
+    if (!allowEval) {
+        const tamedEval = function() {
+            throw new TypeError('eval is not allowed in a locked down context');
+        };
+        defineProperty(tamedEval, 'name', {value: 'eval'});
+        defineProperty(global, 'eval', {value: tamedEval, writable: true, configurable: true});
+        const tamedConstructors = new Map();
+        const protos = [Function.prototype, getPrototypeOf(async function() {}), getPrototypeOf(function*() {}), getPrototypeOf(async function*() {})];
+        for (const proto of protos) {
+            const original = proto.constructor;
+            const tamed = function() {
+                throw new TypeError(original.name + ' constructor is not allowed in a locked down context');
+            };
+            defineProperty(tamed, 'name', {value: original.name});
+            defineProperty(tamed, 'prototype', {value: proto});
+            const originalProto = getPrototypeOf(original);
+            setPrototypeOf(tamed, tamedConstructors.has(originalProto) ? tamedConstructors.get(originalProto) : originalProto);
+            defineProperty(proto, 'constructor', {value: tamed, writable: true, configurable: true});
+            tamedConstructors.set(original, tamed);
+        }
+        defineProperty(global, 'Function', {value: Function.prototype.constructor, writable: true, configurable: true});
+    }
+
+    const overridable = [Object.prototype, Function.prototype, Array.prototype, Promise.prototype, Error.prototype,
+        EvalError.prototype, RangeError.prototype, ReferenceError.prototype, SyntaxError.prototype, TypeError.prototype, URIError.prototype];
+    for (const proto of overridable) {
+        for (const key of ownKeys(proto)) {
+            const desc = getOwnPropertyDescriptor(proto, key);
+            if (!('value' in desc) || !desc.writable || !desc.configurable) {
+                continue;
+            }
+            const value = desc.value;
+            defineProperty(proto, key, {
+                get() {
+                    return value;
+                },
+                set(newValue) {
+                    if (this === proto) {
+                        throw new TypeError('Cannot assign to read only property \'' + String(key) + '\'');
+                    }
+                    defineProperty(this, key, {value: newValue, writable: true, enumerable: true, configurable: true});
+                },
+                enumerable: desc.enumerable,
+                configurable: false
+            });
+        }
+    }
+
+    if (compartments) {
+        const stdNames = ['Object', 'Function', 'Array', 'Number', 'Boolean', 'String', 'Symbol', 'Date', 'Promise', 'RegExp',
+            'Error', 'EvalError', 'RangeError', 'ReferenceError', 'SyntaxError', 'TypeError', 'URIError', 'AggregateError',
+            'JSON', 'Math', 'Reflect', 'Proxy', 'Map', 'Set', 'WeakMap', 'WeakSet', 'WeakRef', 'FinalizationRegistry',
+            'ArrayBuffer', 'SharedArrayBuffer', 'DataView', 'Atomics', 'Int8Array', 'Uint8Array', 'Uint8ClampedArray',
+            'Int16Array', 'Uint16Array', 'Int32Array', 'Uint32Array', 'Float32Array', 'Float64Array', 'BigInt',
+            'BigInt64Array', 'BigUint64Array', 'parseFloat', 'parseInt', 'isFinite', 'isNaN', 'decodeURI',
+            'decodeURIComponent', 'encodeURI', 'encodeURIComponent', 'escape', 'unescape', 'NaN', 'Infinity', 'undefined']
+            .filter((name) => name in global)
+            .map((name) => [name, global[name]]);
+        const makeEvaluator = intrinsicEval('(function(scope) { with (scope) { return function() { \'use strict\'; return eval(arguments[0]); }; } })');
+        const compartmentData = new WeakMap();
+        const importPattern = /(^|[^.])\bimport(\s*(?:\(|\/[/*]))/;
+        class Compartment {
+            constructor(endowments) {
+                const compartmentGlobal = {};
+                for (const [name, value] of stdNames) {
+                    defineProperty(compartmentGlobal, name, {value, writable: true, configurable: true});
+                }
+                defineProperty(compartmentGlobal, 'harden', {value: harden, writable: true, configurable: true});
+                defineProperty(compartmentGlobal, 'globalThis', {value: compartmentGlobal, writable: true, configurable: true});
+                defineProperty(compartmentGlobal, 'eval', {value: (code) => this.evaluate(code), writable: true, configurable: true});
+                if (endowments) {
+                    for (const key of ownKeys(endowments)) {
+                        defineProperty(compartmentGlobal, key, {value: endowments[key], writable: true, enumerable: true, configurable: true});
+                    }
+                }
+                const data = {global: compartmentGlobal, allowEval: false};
+                // every identifier is resolved through the scope proxy so nothing leaks from the real global object
+                const scope = new Proxy(compartmentGlobal, {
+                    has() {
+                        return true;
+                    },
+                    get(target, prop) {
+                        if (prop === 'eval' && data.allowEval) {
+                            data.allowEval = false;
+                            return intrinsicEval;
+                        }
+                        if (prop === Symbol.unscopables) {
+                            return undefined;
+                        }
+                        return target[prop];
+                    }
+                });
+                data.evaluator = makeEvaluator(scope);
+                compartmentData.set(this, data);
+            }
+            get globalThis() {
+                return compartmentData.get(this).global;
+            }
+            evaluate(code) {
+                const data = compartmentData.get(this);
+                code = String(code);
+                // import() is syntax so the scope proxy can not hide it, code which looks like it contains an import expression is rejected
+                if (importPattern.test(code)) {
+                    throw new SyntaxError('Possible import expression rejected');
+                }
+                data.allowEval = true;
+                try {
+                    return data.evaluator.call(data.global, code);
+                } finally {
+                    data.allowEval = false;
+                }
+            }
+        }
+        defineProperty(global, 'Compartment', {value: Compartment, writable: true, configurable: true});
+    }
+
+    defineProperty(global, 'harden', {value: harden, writable: true, configurable: true});
+
+    for (const key of ownKeys(global)) {
+        const desc = getOwnPropertyDescriptor(global, key);
+        if ('value' in desc) {
+            if (desc.value !== global) {
+                harden(desc.value);
+            }
+            defineProperty(global, key, {writable: false, configurable: false});
+        } else {
+            harden(desc.get);
+            harden(desc.set);
+            defineProperty(global, key, {configurable: false});
+        }
+    }
+})"#;
+
+pub fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
+    log::trace!("lockdown::init");
+
+    q_js_rt.add_context_init_hook(|q_js_rt, q_ctx| {
+        if let Some(options) = q_js_rt.opt_lockdown {
+            lockdown_q(q_ctx, options)
+        } else {
+            Ok(())
+        }
+    })?;
+    Ok(())
+}
+
+fn lockdown_q(q_ctx: &QuickJsContext, options: LockdownOptions) -> Result<(), EsError> {
+    log::trace!("lockdown::lockdown_q {}", q_ctx.id);
+    compile::eval_internal_q(
+        q_ctx,
+        "lockdown.es",
+        LOCKDOWN_SCRIPT,
+        vec![
+            primitives::from_bool(options.allow_eval),
+            primitives::from_bool(options.compartments),
+        ],
+    )?;
+    Ok(())
+}
+
+/// freeze an object, its prototypes and everything reachable through its properties if the context was already locked down
+/// objects which are installed before the lockdown (e.g. by features) are hardened by the lockdown itself
+pub(crate) fn harden_if_locked_down_q(
+    q_ctx: &QuickJsContext,
+    obj: &JSValueRef,
+) -> Result<(), EsError> {
+    let is_locked_down = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.opt_lockdown.is_some());
+    if is_locked_down {
+        let global = get_global_q(q_ctx);
+        let harden_func = objects::get_property_q(q_ctx, &global, "harden")?;
+        if functions::is_function_q(q_ctx, &harden_func) {
+            functions::call_function_q(q_ctx, &harden_func, vec![obj.clone()], None)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esscript::EsScript;
+    use crate::esvalue::{EsValueConvertible, EsValueFacade};
+    use crate::features::lockdown::LockdownOptions;
+    use crate::quickjs_utils::primitives;
+    use crate::quickjscontext::QuickJsContext;
+    use crate::reflection::Proxy;
+
+    #[test]
+    fn test_lockdown() {
+        let rt = EsRuntimeBuilder::new()
+            .lockdown(LockdownOptions::new().compartments(true))
+            .build();
+        rt.set_function(
+            vec!["com", "plugins"],
+            "double",
+            |_q_ctx: &QuickJsContext, args: Vec<EsValueFacade>| {
+                Ok((args[0].get_i32() * 2).to_es_value_facade())
+            },
+        )
+        .expect("set_function failed");
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            Proxy::new()
+                .name("Plugin")
+                .static_method("version", |_q_ctx, _args| Ok(primitives::from_i32(1)))
+                .install(q_js_rt.get_main_context(), true)
+                .expect("install failed");
+        });
+
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_lockdown.es",
+                "'use strict'; const results = [];\
+                 const attempt = (f) => {try {f(); results.push('ok');} catch(ex) {results.push(ex.name);}};\
+                 attempt(() => {Object.prototype.polluted = 1;});\
+                 attempt(() => {Promise.prototype.then = null;});\
+                 attempt(() => {setTimeout = null;});\
+                 attempt(() => {com.plugins.double = null;});\
+                 attempt(() => {com.plugins.double.extra = 1;});\
+                 attempt(() => {eval('1');});\
+                 attempt(() => {new Function('return 1;');});\
+                 attempt(() => {(async function(){}).constructor('return 1;');});\
+                 attempt(() => {this.myGlobal = 1;});\
+                 class MyClass {}; MyClass.prototype.toString = () => 'mine';\
+                 results.push(String(new MyClass()), com.plugins.double(21), Plugin.version(), Object.isFrozen(Array.prototype));\
+                 attempt(() => {console.log = null;});\
+                 attempt(() => {Plugin.extra = 1;});\
+                 results.push(typeof console.log, String(Plugin.extra));\
+                 results.join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "TypeError,TypeError,TypeError,TypeError,TypeError,TypeError,TypeError,TypeError,ok,mine,42,1,true,TypeError,TypeError,function,undefined"
+        );
+
+        // a compartment has its own global object and can not reach the real one
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_compartment.es",
+                "const c = new Compartment({name: 'plugin'});\
+                 const r = [c.evaluate('globalThis.counter = 1; name;')];\
+                 r.push(c.evaluate('counter + 1;'), c.evaluate('typeof console;'), c.evaluate('typeof myGlobal;'));\
+                 r.push(c.evaluate('this === globalThis;'), c.evaluate('(0, eval)(\"counter\");'), typeof counter);\
+                 try { c.evaluate('({}).constructor.constructor(\"return this\")();'); } catch(ex) { r.push(ex.name); }\
+                 r.join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "plugin,2,undefined,undefined,true,1,undefined,TypeError"
+        );
+
+        // import expressions are rejected, member calls named import are not
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_compartment_import.es",
+                "const ci = new Compartment({lib: {import: (x) => x + 1}});\
+                 const ri = [];\
+                 for (const code of ['import(\\'x.mes\\')', 'import /* c */ (\\'x.mes\\')', 'eval(\\'import(1)\\')']) {\
+                     try { ci.evaluate(code); ri.push('ok'); } catch(ex) { ri.push(ex.name); }\
+                 }\
+                 ri.push(ci.evaluate('lib.import(1);'));\
+                 ri.join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "SyntaxError,SyntaxError,SyntaxError,2");
+
+        // contexts created later are locked down as well
+        rt.create_context("lockdown_ctx")
+            .expect("could not create context");
+        let frozen = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_context("lockdown_ctx");
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_lockdown2.es",
+                    "Object.isFrozen(Object.prototype) && Object.isFrozen(com.plugins.double);",
+                ))
+                .expect("script failed");
+            primitives::to_bool(&res).ok().unwrap()
+        });
+        assert!(frozen);
+
+        let rt2 = EsRuntimeBuilder::new()
+            .lockdown(LockdownOptions::new().allow_eval(true))
+            .build();
+        let res = rt2
+            .eval_sync(EsScript::new(
+                "test_lockdown3.es",
+                "eval('1 + 1') + new Function('return 40;')() + (typeof Compartment === 'undefined' ? 0 : 1);",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 42);
+    }
+}
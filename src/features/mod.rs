@@ -1,9 +1,13 @@
 use crate::eserror::EsError;
 use crate::esruntime::EsRuntime;
+use crate::reflection;
 use std::sync::Arc;
 
+pub mod clock;
 pub mod console;
+pub mod events;
 pub mod fetch;
+pub mod lockdown;
 pub mod set_timeout;
 pub mod setimmediate;
 
@@ -12,10 +16,16 @@ pub fn init(es_rt: Arc<EsRuntime>) -> Result<(), EsError> {
 
     let es_rt2 = es_rt.clone();
     es_rt.add_to_event_queue_sync(move |q_js_rt| {
+        clock::init(q_js_rt)?;
         console::init(q_js_rt)?;
+        events::init(q_js_rt)?;
         fetch::init(es_rt2)?;
         setimmediate::init(q_js_rt)?;
         set_timeout::init(q_js_rt)?;
+        // the classes of the ProxyRegistry are declared before the lockdown so it hardens them as well
+        reflection::init(q_js_rt)?;
+        // lockdown must be last so it hardens the globals of all other features
+        lockdown::init(q_js_rt)?;
         Ok(())
     })
 }
@@ -1,8 +1,10 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::quickjs_utils;
 use crate::quickjs_utils::{functions, get_global, objects, parse_args, primitives};
 use crate::quickjsruntime::QuickJsRuntime;
+use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
+use std::cell::Cell;
 use std::time::Duration;
 
 /// provides the setImmediate methods for the runtime
@@ -45,6 +47,56 @@ pub fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
     Ok(())
 }
 
+/// get the delay for a timer
+///
+/// with a virtual clock the delay is at least 1 ms (like in browsers and node.js) so a timer never runs in the same tick as it was created,
+/// this way moving the virtual clock forward always ends even when a setInterval is active
+fn get_delay_ms(delay_ref: Option<&JSValueRef>, virtual_clock: bool) -> u64 {
+    let delay_ms = match delay_ref {
+        Some(delay_ref) if delay_ref.is_i32() => primitives::to_i32(delay_ref).ok().unwrap() as f64,
+        Some(delay_ref) => primitives::to_f64(delay_ref).ok().unwrap(),
+        None => 0.0,
+    };
+    if virtual_clock && (delay_ms.is_nan() || delay_ms < 1.0) {
+        1
+    } else {
+        // NaN and negative delays become 0
+        delay_ms as u64
+    }
+}
+
+thread_local! {
+    /// true when a task which runs the pending jobs was already added after a timer ran
+    static PENDING_JOBS_TASK_ADDED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// run the pending jobs (e.g. promise reactions) of a timer
+///
+/// with a virtual clock the jobs of a timer run before the clock is moved to the next timer,
+/// with a real clock the jobs run once after all due timers ran
+fn run_pending_jobs_after_timer(q_js_rt: &QuickJsRuntime, virtual_clock: bool) {
+    if virtual_clock {
+        q_js_rt.run_pending_jobs_if_any();
+    } else if !PENDING_JOBS_TASK_ADDED.with(|added| added.replace(true)) {
+        if let Some(rt) = q_js_rt.get_rt_ref() {
+            rt.inner.event_queue.add_task_from_worker(|| {
+                PENDING_JOBS_TASK_ADDED.with(|added| added.set(false));
+                QuickJsRuntime::do_with(|q_js_rt| q_js_rt.run_pending_jobs_if_any());
+            });
+        } else {
+            PENDING_JOBS_TASK_ADDED.with(|added| added.set(false));
+        }
+    }
+}
+
+/// check if the runtime of the QuickJsRuntime uses a virtual clock
+fn uses_virtual_clock(q_js_rt: &QuickJsRuntime) -> bool {
+    q_js_rt
+        .get_rt_ref()
+        .map(|rt| rt.inner.event_queue.get_clock().is_virtual())
+        .unwrap_or(false)
+}
+
 unsafe extern "C" fn set_timeout(
     context: *mut q::JSContext,
     _this_val: q::JSValue,
@@ -58,25 +110,39 @@ unsafe extern "C" fn set_timeout(
     QuickJsRuntime::do_with(move |q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
         if args.is_empty() {
-            return q_ctx.report_ex("setTimeout requires at least one argument");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setTimeout requires at least one argument".to_string(),
+            ));
         }
         if !functions::is_function(context, &args[0]) {
-            return q_ctx.report_ex("setTimeout requires a functions as first arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setTimeout requires a functions as first arg".to_string(),
+            ));
         }
 
         if args.len() >= 2 && !args[1].is_i32() && !args[1].is_f64() {
-            return q_ctx.report_ex("setTimeout requires a number as second arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setTimeout requires a number as second arg".to_string(),
+            ));
         }
 
-        let delay_ms = if args.len() >= 2 {
-            let delay_ref = args.remove(1);
-            if delay_ref.is_i32() {
-                primitives::to_i32(&delay_ref).ok().unwrap() as u64
-            } else {
-                primitives::to_f64(&delay_ref).ok().unwrap() as u64
+        if let Err(e) = q_ctx.get_permissions().check_timers() {
+            return q_ctx.report_es_error(&e);
+        }
+        if let Some(rt) = q_js_rt.get_rt_ref() {
+            if let Err(e) = rt.inner.check_timer_limit() {
+                return q_ctx.report_es_error(&e);
             }
+        }
+
+        let virtual_clock = uses_virtual_clock(q_js_rt);
+        let delay_ms = if args.len() >= 2 {
+            get_delay_ms(Some(&args.remove(1)), virtual_clock)
         } else {
-            0
+            get_delay_ms(None, virtual_clock)
         };
 
         let q_ctx_id = q_ctx.id.clone();
@@ -94,6 +160,7 @@ unsafe extern "C" fn set_timeout(
                                 log::error!("setTimeout func failed: {}", e);
                             }
                         };
+                        run_pending_jobs_after_timer(q_js_rt, virtual_clock);
                     })
                 },
                 None,
@@ -120,25 +187,39 @@ unsafe extern "C" fn set_interval(
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
         if args.is_empty() {
-            return q_ctx.report_ex("setInterval requires at least one argument");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setInterval requires at least one argument".to_string(),
+            ));
         }
         if !functions::is_function(context, &args[0]) {
-            return q_ctx.report_ex("setInterval requires a functions as first arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setInterval requires a functions as first arg".to_string(),
+            ));
         }
 
         if args.len() >= 2 && !args[1].is_i32() && !args[1].is_f64() {
-            return q_ctx.report_ex("setInterval requires a number as second arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setInterval requires a number as second arg".to_string(),
+            ));
         }
 
-        let delay_ms = if args.len() >= 2 {
-            let delay_ref = args.remove(1);
-            if delay_ref.is_i32() {
-                primitives::to_i32(&delay_ref).ok().unwrap() as u64
-            } else {
-                primitives::to_f64(&delay_ref).ok().unwrap() as u64
+        if let Err(e) = q_ctx.get_permissions().check_timers() {
+            return q_ctx.report_es_error(&e);
+        }
+        if let Some(rt) = q_js_rt.get_rt_ref() {
+            if let Err(e) = rt.inner.check_timer_limit() {
+                return q_ctx.report_es_error(&e);
             }
+        }
+
+        let virtual_clock = uses_virtual_clock(q_js_rt);
+        let delay_ms = if args.len() >= 2 {
+            get_delay_ms(Some(&args.remove(1)), virtual_clock)
         } else {
-            0
+            get_delay_ms(None, virtual_clock)
         };
 
         let q_ctx_id = q_ctx.id.clone();
@@ -158,6 +239,7 @@ unsafe extern "C" fn set_interval(
                                 log::error!("setInterval func failed: {}", e);
                             }
                         };
+                        run_pending_jobs_after_timer(q_js_rt, virtual_clock);
                     })
                 },
                 Some(Duration::from_millis(delay_ms)),
@@ -183,10 +265,16 @@ unsafe extern "C" fn clear_interval(
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
         if args.is_empty() {
-            return q_ctx.report_ex("clearInterval requires at least one argument");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "clearInterval requires at least one argument".to_string(),
+            ));
         }
         if !&args[0].is_i32() {
-            return q_ctx.report_ex("clearInterval requires a number as first arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "clearInterval requires a number as first arg".to_string(),
+            ));
         }
         let id = primitives::to_i32(&args[0]).ok().unwrap();
         log::trace!("clear_interval: {}", id);
@@ -210,10 +298,16 @@ unsafe extern "C" fn clear_timeout(
     QuickJsRuntime::do_with(move |q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
         if args.is_empty() {
-            return q_ctx.report_ex("clearTimeout requires at least one argument");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "clearTimeout requires at least one argument".to_string(),
+            ));
         }
         if !&args[0].is_i32() {
-            return q_ctx.report_ex("clearTimeout requires a number as first arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "clearTimeout requires a number as first arg".to_string(),
+            ));
         }
         let id = primitives::to_i32(&args[0]).ok().unwrap();
         log::trace!("clear_timeout: {}", id);
@@ -231,8 +325,10 @@ pub mod tests {
     use crate::esruntime::tests::init_test_rt;
     use crate::esruntime::EsRuntime;
     use crate::esscript::EsScript;
+    use crate::features::set_timeout::get_delay_ms;
     use crate::quickjs_utils::get_global_q;
     use crate::quickjs_utils::objects::get_property_q;
+    use crate::quickjs_utils::primitives;
     use crate::quickjs_utils::primitives::to_i32;
     use std::sync::Arc;
     use std::time::Duration;
@@ -337,4 +433,57 @@ pub mod tests {
 
         rt.gc_sync();
     }
+
+    #[test]
+    fn test_delay() {
+        let zero = primitives::from_i32(0);
+        let negative = primitives::from_f64(-5.5);
+        let nan = primitives::from_f64(f64::NAN);
+        let ten = primitives::from_f64(10.7);
+        // the real clock keeps a delay of 0
+        assert_eq!(get_delay_ms(None, false), 0);
+        assert_eq!(get_delay_ms(Some(&zero), false), 0);
+        assert_eq!(get_delay_ms(Some(&negative), false), 0);
+        assert_eq!(get_delay_ms(Some(&nan), false), 0);
+        assert_eq!(get_delay_ms(Some(&ten), false), 10);
+        // the virtual clock has a min delay of 1 ms
+        assert_eq!(get_delay_ms(None, true), 1);
+        assert_eq!(get_delay_ms(Some(&zero), true), 1);
+        assert_eq!(get_delay_ms(Some(&negative), true), 1);
+        assert_eq!(get_delay_ms(Some(&nan), true), 1);
+        assert_eq!(get_delay_ms(Some(&ten), true), 10);
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_timer_argument_errors.es",
+                "[() => setTimeout(), () => setTimeout(1), () => setTimeout(() => {}, 'a'),\
+                  () => setInterval(1), () => clearTimeout(), () => clearInterval('a'), () => setImmediate(1)]\
+                 .map((f) => { try { f(); return 'ok'; } catch (ex) { return ex instanceof TypeError; } }).join(',');",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "true,true,true,true,true,true,true");
+    }
+
+    #[test]
+    fn test_zero_interval() {
+        let rt = crate::esruntimebuilder::EsRuntimeBuilder::new().build();
+        rt.eval_sync(EsScript::new(
+            "test_zero_interval.es",
+            "this.count = 0; this.i_id = setInterval(() => {this.count++;}, 0);",
+        ))
+        .expect("script failed");
+        // an interval of 0 is always due, this must still return
+        rt.run_due_tasks();
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_zero_interval2.es",
+                "clearInterval(i_id); count;",
+            ))
+            .expect("script failed");
+        assert!(res.get_i32() > 0);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::quickjs_utils;
 use crate::quickjs_utils::{functions, get_global_q, objects, parse_args};
 use crate::quickjsruntime::QuickJsRuntime;
@@ -43,13 +43,22 @@ unsafe extern "C" fn set_immediate(
     QuickJsRuntime::do_with(move |q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
         if args.is_empty() {
-            return q_ctx.report_ex("setImmediate requires at least one argument");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setImmediate requires at least one argument".to_string(),
+            ));
         }
         if !functions::is_function(context, &args[0]) {
-            return q_ctx.report_ex("setImmediate requires a functions as first arg");
+            return q_ctx.report_es_error(&EsError::new_kind(
+                EsErrorKind::Type,
+                "setImmediate requires a functions as first arg".to_string(),
+            ));
         }
 
         if let Some(rt) = q_js_rt.get_rt_ref() {
+            if let Err(e) = rt.inner.check_local_task_limit() {
+                return q_ctx.report_es_error(&e);
+            }
             rt.inner.add_to_event_queue_from_worker(move |_q_js_rt| {
                 let func = args.remove(0);
 
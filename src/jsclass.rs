@@ -0,0 +1,421 @@
+//! Expose rust structs as classes in script with the [js_class] and [js_methods] attribute macros
+//!
+//! [js_class] implements [JsClass] for a struct and [js_methods] implements [JsMethods] for an impl of that struct,
+//! the generated code registers a [DataProxy](../reflection/struct.DataProxy.html) whose instances each own a value of the struct
+//!
+//! arguments are converted with [FromJsValue] and return values with [ToJsValue], functions may return a Result<T, EsError> in which case the error is thrown in script
+//! when an argument can not be converted a TypeError is thrown
+//!
+//! a function may take a &QuickJsContext argument which is not passed from script
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::eserror::EsError;
+//! use quickjs_runtime::jsclass::{js_class, js_methods, JsMethods};
+//!
+//! #[js_class(namespace = "com.hirofa")]
+//! struct Counter {
+//!     count: i32,
+//! }
+//!
+//! #[js_methods]
+//! impl Counter {
+//!     #[js(constructor)]
+//!     pub fn new(start: Option<i32>) -> Self {
+//!         Self { count: start.unwrap_or(0) }
+//!     }
+//!     pub fn increment(&mut self, by: i32) -> i32 {
+//!         self.count += by;
+//!         self.count
+//!     }
+//!     #[js(name = "toString")]
+//!     pub fn to_string(&self) -> String {
+//!         format!("Counter({})", self.count)
+//!     }
+//!     #[js(getter)]
+//!     pub fn count(&self) -> i32 {
+//!         self.count
+//!     }
+//!     #[js(setter)]
+//!     pub fn set_count(&mut self, count: i32) -> Result<(), EsError> {
+//!         if count < 0 {
+//!             return Err(EsError::new_str("count should not be negative"));
+//!         }
+//!         self.count = count;
+//!         Ok(())
+//!     }
+//!     pub fn max(a: i32, b: i32) -> i32 {
+//!         a.max(b)
+//!     }
+//! }
+//!
+//! let rt = EsRuntimeBuilder::new().build();
+//! rt.add_to_event_queue_sync(|q_js_rt| {
+//!     let q_ctx = q_js_rt.get_main_context();
+//!     Counter::install_js_class(q_ctx, true).expect("install failed");
+//! });
+//! let res = rt.eval_sync(EsScript::new(
+//!     "counter.es",
+//!     "let c = new com.hirofa.Counter(2); c.increment(3); c.count = c.count * 2; \
+//!      `${c.toString()} ${com.hirofa.Counter.max(1, 4)}`",
+//! )).expect("script failed");
+//! assert_eq!(res.get_str(), "Counter(10) 4");
+//! ```
+
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esvalue::EsValueFacade;
+use crate::quickjs_utils::{arrays, new_null_ref, new_undefined_ref, primitives};
+use crate::quickjscontext::QuickJsContext;
+use crate::reflection::{new_instance_with_data, DataProxy, Proxy};
+use crate::valueref::JSValueRef;
+
+pub use quickjs_runtime_macros::{js_class, js_methods};
+
+/// a rust struct which is exposed as a class in script, this is implemented by [js_class]
+pub trait JsClass: Sized + 'static {
+    /// the name of the class in script
+    const JS_CLASS_NAME: &'static str;
+    /// the namespace of the class in script, e.g. ["com", "hirofa"]
+    const JS_NAMESPACE: &'static [&'static str];
+
+    /// get the canonical class name (namespace.ClassName) of the class
+    fn get_js_class_name() -> String {
+        if Self::JS_NAMESPACE.is_empty() {
+            Self::JS_CLASS_NAME.to_string()
+        } else {
+            format!("{}.{}", Self::JS_NAMESPACE.join("."), Self::JS_CLASS_NAME)
+        }
+    }
+}
+
+/// the methods, getters and setters of a [JsClass], this is implemented by [js_methods]
+pub trait JsMethods: JsClass {
+    /// add the constructor, methods, getters and setters to a DataProxy
+    fn configure_js_proxy(proxy: DataProxy<Self>) -> DataProxy<Self>;
+
+    /// install the class in a QuickJsContext
+    fn install_js_class(
+        q_ctx: &QuickJsContext,
+        add_variable_to_global: bool,
+    ) -> Result<JSValueRef, EsError> {
+        let mut proxy = Proxy::with_data::<Self>().name(Self::JS_CLASS_NAME);
+        if !Self::JS_NAMESPACE.is_empty() {
+            proxy = proxy.namespace(Self::JS_NAMESPACE.to_vec());
+        }
+        Self::configure_js_proxy(proxy).install(q_ctx, add_variable_to_global)
+    }
+}
+
+/// create a new instance of an installed [JsClass] in script
+pub fn new_js_instance<T: JsClass>(q_ctx: &QuickJsContext, data: T) -> Result<JSValueRef, EsError> {
+    new_instance_with_data(T::get_js_class_name().as_str(), q_ctx, data)
+        .map(|(_id, instance_ref)| instance_ref)
+}
+
+/// convert an argument of a function called from script, a missing argument is converted as undefined
+/// conversion errors are mapped to an EsError of kind Type
+pub fn get_arg<T: FromJsValue>(
+    q_ctx: &QuickJsContext,
+    args: &[JSValueRef],
+    index: usize,
+    func_name: &str,
+    arg_name: &str,
+) -> Result<T, EsError> {
+    let value = args.get(index).cloned().unwrap_or_else(new_undefined_ref);
+    T::from_js_value(q_ctx, value).map_err(|e| {
+        EsError::new_kind(
+            EsErrorKind::Type,
+            format!(
+                "{}: invalid argument {} ({}): {}",
+                func_name,
+                index,
+                arg_name,
+                e.get_message()
+            ),
+        )
+    })
+}
+
+/// the error which is thrown when a property without a setter is assigned
+pub fn read_only_error(name: &str) -> EsError {
+    EsError::new_kind(EsErrorKind::Type, format!("property {} is read only", name))
+}
+
+/// a value which can be created from a JSValueRef
+pub trait FromJsValue: Sized {
+    fn from_js_value(q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError>;
+}
+
+/// a value which can be converted to a JSValueRef
+pub trait ToJsValue {
+    fn to_js_value(self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError>;
+}
+
+fn conversion_error(msg: &str) -> EsError {
+    EsError::new_kind(EsErrorKind::Conversion, msg.to_string())
+}
+
+impl FromJsValue for JSValueRef {
+    fn from_js_value(_q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        Ok(value)
+    }
+}
+
+impl FromJsValue for EsValueFacade {
+    fn from_js_value(q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        EsValueFacade::from_jsval(q_ctx, &value)
+    }
+}
+
+impl FromJsValue for String {
+    fn from_js_value(q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        if value.is_string() {
+            primitives::to_string_q(q_ctx, &value)
+        } else {
+            Err(conversion_error("value is not a string"))
+        }
+    }
+}
+
+impl FromJsValue for bool {
+    fn from_js_value(_q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        primitives::to_bool(&value)
+    }
+}
+
+impl FromJsValue for i32 {
+    fn from_js_value(_q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        if value.is_f64() {
+            // quickjs may represent integer results of calculations as a float64
+            let f = primitives::to_f64(&value)?;
+            if f.fract() == 0.0 && f >= i32::MIN as f64 && f <= i32::MAX as f64 {
+                return Ok(f as i32);
+            }
+            return Err(conversion_error("value is not an i32"));
+        }
+        primitives::to_i32(&value)
+    }
+}
+
+impl FromJsValue for f64 {
+    fn from_js_value(_q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        if value.is_i32() {
+            Ok(primitives::to_i32(&value)? as f64)
+        } else {
+            primitives::to_f64(&value)
+        }
+    }
+}
+
+impl<T: FromJsValue> FromJsValue for Option<T> {
+    fn from_js_value(q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        if value.is_null_or_undefined() {
+            Ok(None)
+        } else {
+            T::from_js_value(q_ctx, value).map(Some)
+        }
+    }
+}
+
+impl<T: FromJsValue> FromJsValue for Vec<T> {
+    fn from_js_value(q_ctx: &QuickJsContext, value: JSValueRef) -> Result<Self, EsError> {
+        if !arrays::is_array_q(q_ctx, &value) {
+            return Err(conversion_error("value is not an array"));
+        }
+        let len = arrays::get_length_q(q_ctx, &value)?;
+        let mut ret = Vec::with_capacity(len as usize);
+        for index in 0..len {
+            let element = arrays::get_element_q(q_ctx, &value, index)?;
+            ret.push(T::from_js_value(q_ctx, element)?);
+        }
+        Ok(ret)
+    }
+}
+
+impl ToJsValue for JSValueRef {
+    fn to_js_value(self, _q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        Ok(self)
+    }
+}
+
+impl ToJsValue for EsValueFacade {
+    fn to_js_value(mut self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        self.as_js_value(q_ctx)
+    }
+}
+
+impl ToJsValue for () {
+    fn to_js_value(self, _q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        Ok(new_undefined_ref())
+    }
+}
+
+impl ToJsValue for String {
+    fn to_js_value(self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        primitives::from_string_q(q_ctx, self.as_str())
+    }
+}
+
+impl ToJsValue for &str {
+    fn to_js_value(self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        primitives::from_string_q(q_ctx, self)
+    }
+}
+
+impl ToJsValue for bool {
+    fn to_js_value(self, _q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        Ok(primitives::from_bool(self))
+    }
+}
+
+impl ToJsValue for i32 {
+    fn to_js_value(self, _q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        Ok(primitives::from_i32(self))
+    }
+}
+
+impl ToJsValue for f64 {
+    fn to_js_value(self, _q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        Ok(primitives::from_f64(self))
+    }
+}
+
+impl<T: ToJsValue> ToJsValue for Option<T> {
+    fn to_js_value(self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        match self {
+            Some(value) => value.to_js_value(q_ctx),
+            None => Ok(new_null_ref()),
+        }
+    }
+}
+
+impl<T: ToJsValue> ToJsValue for Vec<T> {
+    fn to_js_value(self, q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+        let arr = arrays::create_array_q(q_ctx)?;
+        for (index, value) in self.into_iter().enumerate() {
+            arrays::set_element_q(q_ctx, &arr, index as u32, value.to_js_value(q_ctx)?)?;
+        }
+        Ok(arr)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eserror::{EsError, EsErrorKind};
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esscript::EsScript;
+    use crate::jsclass::{js_class, js_methods, JsMethods};
+    use crate::quickjscontext::QuickJsContext;
+
+    #[js_class(name = "TestJsClass")]
+    struct TestStruct {
+        name: String,
+        values: Vec<f64>,
+    }
+
+    #[js_methods]
+    impl TestStruct {
+        #[js(constructor)]
+        fn new(name: &str) -> Result<Self, EsError> {
+            if name.is_empty() {
+                return Err(EsError::new_str("name should not be empty"));
+            }
+            Ok(Self {
+                name: name.to_string(),
+                values: vec![],
+            })
+        }
+        pub fn add(&mut self, values: Vec<f64>) -> i32 {
+            self.values.extend(values);
+            self.values.len() as i32
+        }
+        pub fn sum(&self) -> f64 {
+            self.values.iter().sum()
+        }
+        pub fn greet(&self, greeting: Option<String>) -> String {
+            format!(
+                "{} {}",
+                greeting.unwrap_or_else(|| "hi".to_string()),
+                self.name
+            )
+        }
+        pub fn context_id(&self, q_ctx: &QuickJsContext) -> String {
+            q_ctx.id.clone()
+        }
+        #[js(getter)]
+        fn name(&self) -> &str {
+            self.name.as_str()
+        }
+        #[js(getter = "size")]
+        fn get_size(&self) -> i32 {
+            self.values.len() as i32
+        }
+        #[js(setter)]
+        fn set_name(&mut self, name: String) {
+            self.name = name;
+        }
+        pub fn create(name: String) -> Self {
+            Self {
+                name,
+                values: vec![1.0],
+            }
+        }
+        #[js(getter)]
+        fn version() -> &'static str {
+            "1.0"
+        }
+        #[allow(dead_code)]
+        fn helper(&self) {}
+    }
+
+    #[test]
+    fn test_js_class() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            TestStruct::install_js_class(q_ctx, true).expect("install failed");
+        });
+
+        let eval = |code: &str| {
+            rt.eval_sync(EsScript::new("test_js_class.es", code))
+                .expect("script failed")
+                .get_str()
+                .to_string()
+        };
+
+        assert_eq!(
+            eval(
+                "let tjc = new TestJsClass('a'); tjc.add([1, 2.5]); tjc.add([]);\
+                 `${tjc.sum()} ${tjc.size} ${tjc.greet()} ${tjc.greet('hello')}`"
+            ),
+            "3.5 2 hi a hello a"
+        );
+        assert_eq!(
+            eval("tjc.name = 'b'; `${tjc.name} ${tjc.context_id()}`"),
+            "b __main__"
+        );
+        assert_eq!(
+            eval("let c = TestJsClass.create('c'); `${c.name} ${c.sum()} ${TestJsClass.version}`"),
+            "c 1 1.0"
+        );
+        assert_eq!(
+            eval("`${tjc.helper} ${tjc instanceof TestJsClass}`"),
+            "undefined true"
+        );
+
+        let err = |code: &str| {
+            rt.eval_sync(EsScript::new("test_js_class_err.es", code))
+                .expect_err("script should fail")
+        };
+        let e = err("new TestJsClass('');");
+        assert!(e.get_message().contains("name should not be empty"));
+        let e = err("tjc.add(['x']);");
+        assert_eq!(e.get_kind(), EsErrorKind::Type);
+        assert!(e.get_message().contains("add: invalid argument 0 (values)"));
+        let e = err("'use strict'; tjc.size = 3;");
+        assert_eq!(e.get_kind(), EsErrorKind::Type);
+        assert!(e.get_message().contains("property size is read only"));
+    }
+}
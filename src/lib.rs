@@ -49,6 +49,9 @@
 #[macro_use]
 extern crate lazy_static;
 
+// lets the code generated by the jsclass macros refer to ::quickjs_runtime from within this crate
+extern crate self as quickjs_runtime;
+
 #[macro_export]
 macro_rules! es_args {
     ( $( $x:expr ),* ) => {
@@ -67,9 +70,13 @@ pub mod eserror;
 pub mod esruntime;
 pub mod esruntime_utils;
 pub mod esruntimebuilder;
+pub mod esruntimepool;
 pub mod esscript;
 pub mod esvalue;
 pub mod features;
+pub mod jsclass;
+pub mod permissions;
+pub mod preprocessors;
 pub mod quickjs_utils;
 pub mod quickjscontext;
 pub mod quickjsruntime;
@@ -0,0 +1,389 @@
+//! Permissions limit which built-in capabilities the scripts in a context may use
+//!
+//! the permissions are checked when a script
+//! * calls fetch(), the origin of the url and the method are checked
+//! * imports a module, the normalized path of script modules is matched against the allowed module patterns and native modules must be allowed by name
+//! * calls setTimeout() or setInterval()
+//!
+//! when a capability is not allowed an Error is thrown in script, which results in an EsError of kind [PermissionDenied](../eserror/enum.EsErrorKind.html#variant.PermissionDenied)
+//!
+//! the default permissions for all contexts are set with [EsRuntimeBuilder::permissions](../esruntimebuilder/struct.EsRuntimeBuilder.html#method.permissions)
+//! and may be changed per context with [EsRuntime::set_permissions](../esruntime/struct.EsRuntime.html#method.set_permissions)
+//!
+//! patterns may contain * as a wildcard which matches any sequence of characters, e.g. "https://*.example.com" or "file:///plugins/*"
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::eserror::EsErrorKind;
+//! use quickjs_runtime::permissions::Permissions;
+//! let rt = EsRuntimeBuilder::new()
+//!     .permissions(Permissions::deny_all().allow_fetch_origin("https://api.example.com"))
+//!     .build();
+//! let err = rt.eval_sync(EsScript::new("perm.es", "setTimeout(() => {}, 10);")).expect_err("setTimeout should be denied");
+//! assert_eq!(err.get_kind(), EsErrorKind::PermissionDenied);
+//! ```
+
+use crate::eserror::EsError;
+
+/// the capabilities which the scripts in a context may use, see the [module docs](index.html)
+#[derive(Clone, Debug)]
+pub struct Permissions {
+    opt_fetch_origins: Option<Vec<String>>,
+    opt_fetch_methods: Option<Vec<String>>,
+    opt_module_patterns: Option<Vec<String>>,
+    opt_native_modules: Option<Vec<String>>,
+    allow_timers: bool,
+}
+
+impl Permissions {
+    /// permissions which allow everything, this is the default
+    pub fn allow_all() -> Self {
+        Self {
+            opt_fetch_origins: None,
+            opt_fetch_methods: None,
+            opt_module_patterns: None,
+            opt_native_modules: None,
+            allow_timers: true,
+        }
+    }
+
+    /// permissions which allow nothing, use the allow methods to allow specific capabilities
+    pub fn deny_all() -> Self {
+        Self {
+            opt_fetch_origins: Some(vec![]),
+            opt_fetch_methods: Some(vec![]),
+            opt_module_patterns: Some(vec![]),
+            opt_native_modules: Some(vec![]),
+            allow_timers: false,
+        }
+    }
+
+    /// allow fetch() for urls with an origin (e.g. "https://api.example.com:8443") which matches a pattern
+    /// after this is called fetch() is only allowed for the allowed origins
+    pub fn allow_fetch_origin(mut self, origin_pattern: &str) -> Self {
+        let origin_pattern = origin_pattern.trim_end_matches('/').to_ascii_lowercase();
+        self.opt_fetch_origins
+            .get_or_insert_with(Vec::new)
+            .push(origin_pattern);
+        self
+    }
+
+    /// allow fetch() with a method (e.g. "GET"), after this is called fetch() is only allowed with the allowed methods
+    pub fn allow_fetch_method(mut self, method: &str) -> Self {
+        self.opt_fetch_methods
+            .get_or_insert_with(Vec::new)
+            .push(method.to_ascii_uppercase());
+        self
+    }
+
+    /// allow importing script modules whose normalized path matches a pattern
+    /// after this is called only the allowed script modules may be imported
+    pub fn allow_module(mut self, path_pattern: &str) -> Self {
+        self.opt_module_patterns
+            .get_or_insert_with(Vec::new)
+            .push(path_pattern.to_string());
+        self
+    }
+
+    /// allow importing a native module by name, after this is called only the allowed native modules may be imported
+    pub fn allow_native_module(mut self, name: &str) -> Self {
+        self.opt_native_modules
+            .get_or_insert_with(Vec::new)
+            .push(name.to_string());
+        self
+    }
+
+    /// allow or deny setTimeout() and setInterval()
+    pub fn allow_timers(mut self, allow: bool) -> Self {
+        self.allow_timers = allow;
+        self
+    }
+
+    /// check if fetch() is allowed for a url and method
+    pub fn check_fetch(&self, url: &str, method: &str) -> Result<(), EsError> {
+        if let Some(origins) = &self.opt_fetch_origins {
+            let origin = get_origin(url)
+                .ok_or_else(|| {
+                    EsError::permission_denied(format!("fetch is not allowed for url {}", url))
+                })?
+                .to_ascii_lowercase();
+            if !origins.iter().any(|p| matches_pattern(p, origin.as_str())) {
+                return Err(EsError::permission_denied(format!(
+                    "fetch is not allowed for origin {}",
+                    origin
+                )));
+            }
+        }
+        if let Some(methods) = &self.opt_fetch_methods {
+            let method = method.to_ascii_uppercase();
+            if !methods.contains(&method) {
+                return Err(EsError::permission_denied(format!(
+                    "fetch is not allowed with method {}",
+                    method
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// check if a module may be imported, for script modules the path is the normalized path, for native modules it is the name of the module
+    pub fn check_module(&self, path: &str, is_native: bool) -> Result<(), EsError> {
+        let allowed = if is_native {
+            self.opt_native_modules
+                .as_ref()
+                .map(|names| names.iter().any(|n| n == path))
+        } else {
+            self.opt_module_patterns
+                .as_ref()
+                .map(|patterns| patterns.iter().any(|p| matches_pattern(p, path)))
+        };
+        if allowed.unwrap_or(true) {
+            Ok(())
+        } else {
+            Err(EsError::permission_denied(format!(
+                "importing module {} is not allowed",
+                path
+            )))
+        }
+    }
+
+    /// check if setTimeout() and setInterval() may be used
+    pub fn check_timers(&self) -> Result<(), EsError> {
+        if self.allow_timers {
+            Ok(())
+        } else {
+            Err(EsError::permission_denied(
+                "timers are not allowed".to_string(),
+            ))
+        }
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+/// get the scheme, host and port of a url, e.g. "https://example.com:8080" for "https://example.com:8080/path?q=1"
+///
+/// returns None if the url has no authority or if the authority contains anything but a host and a port,
+/// e.g. userinfo ("https://a.com@b.com") or a backslash ("https://b.com\\a.com") which a fetch implementation may interpret differently
+fn get_origin(url: &str) -> Option<&str> {
+    let authority_start = url.find("://")? + 3;
+    let scheme = &url[..authority_start - 3];
+    if scheme.is_empty()
+        || !scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+    {
+        return None;
+    }
+    let authority_end = url[authority_start..]
+        .find(['/', '?', '#'])
+        .map(|idx| authority_start + idx)
+        .unwrap_or(url.len());
+    let authority = &url[authority_start..authority_end];
+    let valid = !authority.is_empty()
+        && authority
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | ':' | '[' | ']'));
+    if valid {
+        Some(&url[..authority_end])
+    } else {
+        None
+    }
+}
+
+/// match a value against a pattern where * matches any sequence of characters
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !value.starts_with(first) {
+        return false;
+    }
+    let mut rest = &value[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    if parts.is_empty() {
+        return rest.is_empty();
+    }
+    let (last, middle) = parts.split_last().unwrap();
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eserror::EsErrorKind;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esscript::EsScript;
+    use crate::features::fetch::request::FetchRequest;
+    use crate::features::fetch::response::FetchResponse;
+    use crate::permissions::{get_origin, matches_pattern, Permissions};
+    use crate::quickjsruntime::ScriptModuleLoader;
+    use std::time::Duration;
+
+    struct TestResponse {}
+
+    impl FetchResponse for TestResponse {
+        fn get_http_status(&self) -> u16 {
+            200
+        }
+        fn get_header(&self, _name: &str) -> Option<&str> {
+            None
+        }
+        fn read(&mut self) -> Option<Vec<u8>> {
+            None
+        }
+    }
+
+    struct TestModuleLoader {}
+
+    impl ScriptModuleLoader for TestModuleLoader {
+        fn normalize_path(&self, _ref_path: &str, path: &str) -> Option<String> {
+            Some(path.to_string())
+        }
+        fn load_module(&self, _absolute_path: &str) -> String {
+            "export const a = 1;".to_string()
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(
+            get_origin("https://a.com:80/p?q#h"),
+            Some("https://a.com:80")
+        );
+        assert_eq!(get_origin("https://a.com?q"), Some("https://a.com"));
+        assert_eq!(get_origin("https://a.com"), Some("https://a.com"));
+        assert_eq!(
+            get_origin("https://[::1]:8080/p"),
+            Some("https://[::1]:8080")
+        );
+        assert_eq!(get_origin("https://evil.com\\.example.com/x"), None);
+        assert_eq!(get_origin("https://evil.com\\@a.example.com"), None);
+        assert_eq!(get_origin("https://user:pw@a.example.com/"), None);
+        assert_eq!(get_origin("https:///path"), None);
+        assert_eq!(get_origin("a.example.com/path"), None);
+        assert!(matches_pattern(
+            "https://*.example.com",
+            "https://api.example.com"
+        ));
+        assert!(!matches_pattern(
+            "https://*.example.com",
+            "https://example.com.evil"
+        ));
+        assert!(matches_pattern("plugins/*", "plugins/a/b.mes"));
+        assert!(matches_pattern("a*b*c", "abc"));
+        assert!(!matches_pattern("a*bc", "abc_bc_"));
+        assert!(matches_pattern("exact", "exact"));
+        assert!(!matches_pattern("exact", "exact2"));
+    }
+
+    #[test]
+    fn test_permissions() {
+        let rt = EsRuntimeBuilder::new()
+            .fetch_response_provider(|_req: &FetchRequest| {
+                Box::new(TestResponse {}) as Box<dyn FetchResponse + Send>
+            })
+            .script_module_loader(TestModuleLoader {})
+            .permissions(
+                Permissions::deny_all()
+                    .allow_fetch_origin("https://*.example.com")
+                    .allow_fetch_method("get")
+                    .allow_module("plugins/*"),
+            )
+            .build();
+
+        let eval_err = |code: &str| {
+            rt.eval_sync(EsScript::new("test_perm.es", code))
+                .err()
+                .map(|e| e.get_kind())
+        };
+        assert_eq!(
+            eval_err("setTimeout(() => {}, 1);"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        assert_eq!(
+            eval_err("setInterval(() => {}, 1);"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        assert_eq!(
+            eval_err("fetch('https://evil.com/steal');"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        assert_eq!(
+            eval_err("fetch('https://api.example.com/data', {method: 'POST'});"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        assert_eq!(eval_err("fetch('https://api.example.com/data');"), None);
+        assert_eq!(
+            eval_err("fetch('https://evil.com\\\\.example.com/x');"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        assert_eq!(
+            eval_err("fetch('https://evil.com\\\\@a.example.com');"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+        // script can not fake a permission error
+        assert_eq!(
+            eval_err("throw new Error('permission denied: fake');"),
+            Some(EsErrorKind::JsException)
+        );
+        // the mark can not be seen or copied by script
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_perm_mark.es",
+                "try { setTimeout(() => {}, 1); } catch (ex) { Reflect.ownKeys(ex).filter((k) => typeof k === 'symbol').length; }",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 0);
+        assert_eq!(
+            eval_err("try { setTimeout(() => {}, 1); } catch (ex) { throw Object.assign(new Error(ex.message), ex); }"),
+            Some(EsErrorKind::JsException)
+        );
+        // a permission error which is caught and rethrown keeps its kind
+        assert_eq!(
+            eval_err("try { setTimeout(() => {}, 1); } catch (ex) { throw ex; }"),
+            Some(EsErrorKind::PermissionDenied)
+        );
+
+        let res = rt.eval_module_sync(EsScript::new(
+            "test_perm_mod.mes",
+            "import {a} from 'plugins/a.mes';",
+        ));
+        assert!(res.is_ok());
+        let err = rt
+            .eval_module_sync(EsScript::new(
+                "test_perm_mod2.mes",
+                "import {a} from 'secrets/a.mes';",
+            ))
+            .expect_err("import should be denied");
+        assert_eq!(err.get_kind(), EsErrorKind::PermissionDenied);
+
+        // permissions can be changed per context
+        rt.create_context("trusted_ctx")
+            .expect("could not create context");
+        rt.set_permissions("trusted_ctx", Permissions::allow_all())
+            .expect("could not set permissions");
+        let res = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_context("trusted_ctx");
+            q_ctx
+                .eval(EsScript::new("test_perm2.es", "setTimeout(() => {}, 1);"))
+                .is_ok()
+        });
+        assert!(res);
+        assert!(rt
+            .set_permissions("no_ctx", Permissions::allow_all())
+            .is_err());
+        rt.run_until_idle_sync(Some(Duration::from_secs(5)))
+            .expect("not idle");
+    }
+}
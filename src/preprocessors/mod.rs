@@ -0,0 +1,37 @@
+//! Script preprocessors transform the source of a script before it is evaluated or compiled
+//!
+//! Preprocessors are added to an EsRuntime with [EsRuntimeBuilder::script_pre_processor](../esruntimebuilder/struct.EsRuntimeBuilder.html#method.script_pre_processor)
+//! and are run (in the order they were added) for every script passed to eval or eval_module and for every module source provided by a [ScriptModuleLoader](../quickjsruntime/trait.ScriptModuleLoader.html).
+//!
+//! A preprocessor decides for itself if it wants to process a script (e.g. by checking the extension of the path) and may replace the code and source map of the script.
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::eserror::EsError;
+//! use quickjs_runtime::preprocessors::ScriptPreProcessor;
+//!
+//! struct DebugRemover {}
+//! impl ScriptPreProcessor for DebugRemover {
+//!     fn process(&self, script: &mut EsScript) -> Result<(), EsError> {
+//!         let code = script.get_code().replace("debugger;", "");
+//!         script.set_code(code);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let rt = EsRuntimeBuilder::new().script_pre_processor(DebugRemover{}).build();
+//! let res = rt.eval_sync(EsScript::new("pp.es", "debugger; 1 + 2;")).ok().unwrap();
+//! assert_eq!(res.get_i32(), 3);
+//! ```
+
+use crate::eserror::EsError;
+use crate::esscript::EsScript;
+
+pub mod typescript;
+
+pub trait ScriptPreProcessor {
+    /// process a script, the code (and source map) of the script may be replaced with [EsScript::set_code] and [EsScript::set_source_map]
+    fn process(&self, script: &mut EsScript) -> Result<(), EsError>;
+}
@@ -0,0 +1,1395 @@
+//! A ScriptPreProcessor which strips TypeScript type syntax from scripts
+//!
+//! This is not a TypeScript compiler, it supports the commonly used subset of TypeScript which only adds types to JavaScript:
+//! type annotations, interfaces, type aliases, generics, instantiation expressions, `as` and `satisfies` casts, `<T>` type assertions,
+//! non-null assertions, access modifiers, `implements` clauses, `declare` statements, abstract members, function and method overload signatures
+//! and type only imports and exports.
+//!
+//! Types are replaced by whitespace so the line and column numbers of the resulting script are the same as those of the TypeScript source and no source map is needed.
+//!
+//! Syntax which would require generating code (enums, namespaces and constructor parameter properties) is not supported and results in an error.
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::preprocessors::typescript::TypeScriptPreProcessor;
+//!
+//! let rt = EsRuntimeBuilder::new()
+//!     .script_pre_processor(TypeScriptPreProcessor::new())
+//!     .build();
+//! let res = rt.eval_sync(EsScript::new("test_ts.ts", "function add(a: number, b: number): number {\n    return a + b;\n}\nadd(1, 2);")).expect("script failed");
+//! assert_eq!(res.get_i32(), 3);
+//! ```
+
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esscript::EsScript;
+use crate::preprocessors::ScriptPreProcessor;
+
+/// strips TypeScript types from scripts whose path ends with .ts, .mts or .cts
+pub struct TypeScriptPreProcessor {}
+
+impl TypeScriptPreProcessor {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Default for TypeScriptPreProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptPreProcessor for TypeScriptPreProcessor {
+    fn process(&self, script: &mut EsScript) -> Result<(), EsError> {
+        let path = script.get_path();
+        if path.ends_with(".ts") || path.ends_with(".mts") || path.ends_with(".cts") {
+            let code = strip_types(script.get_code())?;
+            script.set_code(code);
+        }
+        Ok(())
+    }
+}
+
+/// replace all TypeScript type syntax in a script by whitespace
+/// # Example
+/// ```rust
+/// use quickjs_runtime::preprocessors::typescript::strip_types;
+/// let js = strip_types("let a: number = 1;").ok().unwrap();
+/// assert_eq!(js, "let a         = 1;");
+/// ```
+pub fn strip_types(code: &str) -> Result<String, EsError> {
+    Stripper::new(code).run()
+}
+
+const KEYWORDS: &[&str] = &[
+    "as",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "of",
+    "return",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+const MODIFIERS: &[&str] = &["public", "private", "protected", "readonly", "override"];
+
+/// keywords which may be used in a type but do not complete it
+const TYPE_OPERATORS: &[&str] = &[
+    "keyof", "typeof", "infer", "readonly", "unique", "is", "asserts", "new", "extends",
+];
+
+const PUNCTUATORS: &[&str] = &[
+    "...", "===", "!==", "=>", "==", "!=", "?.", "??", "&&", "||", "<=", ">=",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TokKind {
+    Ident,
+    Punct,
+    Literal,
+}
+
+#[derive(Clone, Debug)]
+struct Tok {
+    kind: TokKind,
+    text: String,
+    start: usize,
+    end: usize,
+    nl_before: bool,
+}
+
+impl Tok {
+    fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+    fn is_ident(&self) -> bool {
+        self.kind == TokKind::Ident
+    }
+    fn is_keyword(&self) -> bool {
+        self.is_ident() && KEYWORDS.contains(&self.text.as_str())
+    }
+    /// true if this token may end an expression, a following `as`, `!` or `<` is then TypeScript syntax
+    fn ends_expression(&self) -> bool {
+        match self.kind {
+            TokKind::Ident => !self.is_keyword(),
+            TokKind::Literal => true,
+            TokKind::Punct => matches!(self.text.as_str(), ")" | "]" | "}"),
+        }
+    }
+    /// true if this token may end a type
+    fn completes_type(&self) -> bool {
+        match self.kind {
+            TokKind::Ident => !TYPE_OPERATORS.contains(&self.text.as_str()),
+            TokKind::Literal => true,
+            TokKind::Punct => matches!(self.text.as_str(), ")" | "]" | "}" | ">"),
+        }
+    }
+    /// true if this token may start the name of a class member (after a modifier)
+    fn starts_member_name(&self) -> bool {
+        match self.kind {
+            TokKind::Ident => true,
+            TokKind::Literal => !self.text.starts_with('`') && !self.text.starts_with('/'),
+            TokKind::Punct => self.is("[") || self.is("*"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum FrameKind {
+    Block,
+    ClassBody,
+    Object,
+    Params,
+    Paren,
+    Bracket,
+    Specifiers,
+}
+
+/// the state of a variable declaration or parameter list
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Decl {
+    None,
+    Binding,
+    AfterBinding,
+    Initializer,
+}
+
+/// the state of a class member declaration
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Member {
+    Start,
+    Name,
+    Initializer,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TypeCtx {
+    Annotation,
+    Return,
+    Cast,
+    Alias,
+}
+
+struct Frame {
+    kind: FrameKind,
+    decl: Decl,
+    member: Member,
+    // the start of the current class member
+    member_start: Option<usize>,
+    ternaries: usize,
+    in_case: bool,
+}
+
+impl Frame {
+    fn new(kind: FrameKind) -> Self {
+        Self {
+            kind,
+            decl: if kind == FrameKind::Params {
+                Decl::Binding
+            } else {
+                Decl::None
+            },
+            member: Member::Start,
+            member_start: None,
+            ternaries: 0,
+            in_case: false,
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphabetic()
+}
+
+fn is_ident_part(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphanumeric()
+}
+
+struct Stripper {
+    src: Vec<char>,
+    out: Vec<char>,
+    pos: usize,
+    frames: Vec<Frame>,
+    prev: Option<Tok>,
+    class_header: bool,
+    module_stmt: bool,
+}
+
+impl Stripper {
+    fn new(code: &str) -> Self {
+        let src: Vec<char> = code.chars().collect();
+        Self {
+            out: src.clone(),
+            src,
+            pos: 0,
+            frames: vec![Frame::new(FrameKind::Block)],
+            prev: None,
+            class_header: false,
+            module_stmt: false,
+        }
+    }
+
+    fn run(mut self) -> Result<String, EsError> {
+        while let Some(tok) = self.next_token() {
+            if !self.handle(&tok)? {
+                self.prev = Some(tok);
+            }
+        }
+        Ok(self.out.into_iter().collect())
+    }
+
+    // lexing
+
+    fn at(&self, i: usize) -> char {
+        self.src.get(i).copied().unwrap_or('\0')
+    }
+
+    fn starts_with(&self, i: usize, s: &str) -> bool {
+        s.chars().enumerate().all(|(k, c)| self.at(i + k) == c)
+    }
+
+    fn skip_trivia(&self, mut i: usize) -> (usize, bool) {
+        let len = self.src.len();
+        let mut nl = false;
+        while i < len {
+            let c = self.src[i];
+            if c == '\n' || c == '\r' || c == '\u{2028}' || c == '\u{2029}' {
+                nl = true;
+                i += 1;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '/' && self.at(i + 1) == '/' {
+                while i < len && self.src[i] != '\n' {
+                    i += 1;
+                }
+            } else if c == '/' && self.at(i + 1) == '*' {
+                i += 2;
+                while i < len && !(self.src[i] == '*' && self.at(i + 1) == '/') {
+                    if self.src[i] == '\n' {
+                        nl = true;
+                    }
+                    i += 1;
+                }
+                i = std::cmp::min(i + 2, len);
+            } else {
+                break;
+            }
+        }
+        (i, nl)
+    }
+
+    fn skip_string(&self, start: usize) -> usize {
+        let quote = self.src[start];
+        let mut i = start + 1;
+        while i < self.src.len() {
+            match self.src[i] {
+                '\\' => i += 2,
+                '\n' => return i,
+                c if c == quote => return i + 1,
+                _ => i += 1,
+            }
+        }
+        self.src.len()
+    }
+
+    fn skip_template(&self, start: usize) -> usize {
+        let mut expressions = vec![];
+        self.scan_template(start, &mut expressions)
+    }
+
+    /// find the end of a template literal and the ranges of its `${}` expressions
+    fn scan_template(&self, start: usize, expressions: &mut Vec<(usize, usize)>) -> usize {
+        let mut i = start + 1;
+        while i < self.src.len() {
+            match self.src[i] {
+                '\\' => i += 2,
+                '`' => return i + 1,
+                '$' if self.at(i + 1) == '{' => {
+                    let end = self.skip_braced(i + 2);
+                    expressions.push((i + 2, std::cmp::max(i + 2, end - 1)));
+                    i = end;
+                }
+                _ => i += 1,
+            }
+        }
+        self.src.len()
+    }
+
+    /// strip the expressions of a template literal
+    fn template(&mut self, tok: &Tok) -> Result<(), EsError> {
+        let mut expressions = vec![];
+        self.scan_template(tok.start, &mut expressions);
+        for (start, end) in expressions {
+            let code: String = self.src[start..end].iter().collect();
+            for (i, c) in Stripper::new(code.as_str()).run()?.chars().enumerate() {
+                self.out[start + i] = c;
+            }
+        }
+        Ok(())
+    }
+
+    /// skip code up to and including the `}` which closes an already opened `{`
+    fn skip_braced(&self, mut pos: usize) -> usize {
+        let mut depth = 1;
+        while let Some(tok) = self.lex(pos, false) {
+            if tok.is("{") {
+                depth += 1;
+            } else if tok.is("}") {
+                depth -= 1;
+                if depth == 0 {
+                    return tok.end;
+                }
+            }
+            pos = tok.end;
+        }
+        self.src.len()
+    }
+
+    fn skip_regex(&self, start: usize) -> usize {
+        let mut i = start + 1;
+        let mut in_class = false;
+        while i < self.src.len() {
+            match self.src[i] {
+                '\\' => {
+                    i += 2;
+                    continue;
+                }
+                '\n' => break,
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '/' if !in_class => {
+                    i += 1;
+                    while i < self.src.len() && is_ident_part(self.src[i]) {
+                        i += 1;
+                    }
+                    return i;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        std::cmp::min(i, self.src.len())
+    }
+
+    fn lex(&self, pos: usize, regex_ok: bool) -> Option<Tok> {
+        let (start, nl_before) = self.skip_trivia(pos);
+        if start >= self.src.len() {
+            return None;
+        }
+        let c = self.src[start];
+        let (kind, end) = if is_ident_start(c) || (c == '#' && is_ident_start(self.at(start + 1))) {
+            let mut i = start + 1;
+            while i < self.src.len() && is_ident_part(self.src[i]) {
+                i += 1;
+            }
+            (TokKind::Ident, i)
+        } else if c.is_ascii_digit() || (c == '.' && self.at(start + 1).is_ascii_digit()) {
+            let mut i = start + 1;
+            while i < self.src.len() && (is_ident_part(self.src[i]) || self.src[i] == '.') {
+                i += 1;
+            }
+            (TokKind::Literal, i)
+        } else if c == '"' || c == '\'' {
+            (TokKind::Literal, self.skip_string(start))
+        } else if c == '`' {
+            (TokKind::Literal, self.skip_template(start))
+        } else if c == '/' && regex_ok {
+            (TokKind::Literal, self.skip_regex(start))
+        } else {
+            let len = PUNCTUATORS
+                .iter()
+                .find(|p| {
+                    self.starts_with(start, p)
+                        && !(**p == "?." && self.at(start + 2).is_ascii_digit())
+                })
+                .map(|p| p.len())
+                .unwrap_or(1);
+            (TokKind::Punct, start + len)
+        };
+        let end = std::cmp::min(end, self.src.len());
+        Some(Tok {
+            kind,
+            text: self.src[start..end].iter().collect(),
+            start,
+            end,
+            nl_before,
+        })
+    }
+
+    fn regex_allowed(&self) -> bool {
+        match &self.prev {
+            None => true,
+            Some(p) => match p.kind {
+                TokKind::Ident => p.is_keyword(),
+                TokKind::Literal => false,
+                TokKind::Punct => !matches!(p.text.as_str(), ")" | "]" | "}"),
+            },
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Tok> {
+        let tok = self.lex(self.pos, self.regex_allowed())?;
+        self.pos = tok.end;
+        Some(tok)
+    }
+
+    fn peek(&self) -> Option<Tok> {
+        self.lex(self.pos, false)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek().map(|t| t.is(text)).unwrap_or(false)
+    }
+
+    // output
+
+    fn blank(&mut self, from: usize, to: usize) {
+        for c in &mut self.out[from..to] {
+            if *c != '\n' && *c != '\r' {
+                *c = ' ';
+            }
+        }
+    }
+
+    /// blank from `from` up to `to` and continue lexing at `to`
+    fn blank_to(&mut self, from: usize, to: usize) -> Result<bool, EsError> {
+        self.blank(from, to);
+        self.pos = to;
+        Ok(true)
+    }
+
+    fn unsupported(&self, tok: &Tok, what: &str) -> EsError {
+        let line = self.src[..tok.start].iter().filter(|c| **c == '\n').count() + 1;
+        EsError::new_kind(
+            EsErrorKind::Syntax,
+            format!("unsupported TypeScript syntax: {} at line {}", what, line),
+        )
+    }
+
+    fn top(&self) -> &Frame {
+        self.frames.last().expect("no frames")
+    }
+
+    fn top_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("no frames")
+    }
+
+    fn prev_ends_expression(&self) -> bool {
+        self.prev
+            .as_ref()
+            .map(|p| p.ends_expression())
+            .unwrap_or(false)
+    }
+
+    // scanning of types
+
+    /// find the end of a type which starts at pos
+    fn scan_type(&self, start: usize, ctx: TypeCtx) -> usize {
+        let mut stack: Vec<&str> = vec![];
+        let mut pos = start;
+        let mut end = start;
+        let mut last: Option<Tok> = None;
+        let mut conditionals = 0;
+        let mut starts_with_paren = false;
+        let mut fn_type = false;
+
+        while let Some(tok) = self.lex(pos, false) {
+            let complete = last.as_ref().map(|l| l.completes_type()).unwrap_or(false);
+            if stack.is_empty() {
+                let continues = matches!(tok.text.as_str(), "|" | "&" | "." | "=>" | "extends")
+                    || (conditionals > 0 && (tok.is("?") || tok.is(":")));
+                if tok.nl_before && complete && !continues {
+                    break;
+                }
+                let stop = match tok.kind {
+                    TokKind::Ident => complete && !tok.is("extends") && !tok.is("is"),
+                    TokKind::Literal => complete,
+                    TokKind::Punct => match tok.text.as_str() {
+                        "[" | "<" | "|" | "&" | "." => false,
+                        "(" | "{" | "-" => complete,
+                        "=>" => ctx == TypeCtx::Return || !fn_type,
+                        "?" | ":" => conditionals == 0,
+                        _ => true,
+                    },
+                };
+                if stop {
+                    break;
+                }
+                if tok.is("extends") {
+                    conditionals += 1;
+                } else if tok.is(":") {
+                    conditionals -= 1;
+                } else if tok.is("(") && last.is_none() {
+                    starts_with_paren = true;
+                }
+                fn_type = false;
+            }
+            match tok.text.as_str() {
+                "(" | "[" | "{" | "<" => stack.push(match tok.text.as_str() {
+                    "(" => ")",
+                    "[" => "]",
+                    "{" => "}",
+                    _ => ">",
+                }),
+                ")" | "]" | "}" | ">" if stack.last() == Some(&tok.text.as_str()) => {
+                    stack.pop();
+                    fn_type = stack.is_empty() && starts_with_paren && tok.is(")");
+                }
+                _ => {}
+            }
+            pos = tok.end;
+            end = tok.end;
+            last = Some(tok);
+        }
+        end
+    }
+
+    /// find the end of a list of generic parameters or arguments starting with `<`, None if it does not look like one
+    fn scan_generic(&self, lt: &Tok) -> Option<usize> {
+        let mut angles = 1;
+        let mut others = 0;
+        let mut pos = lt.end;
+        while let Some(tok) = self.lex(pos, false) {
+            pos = tok.end;
+            if tok.kind != TokKind::Punct {
+                continue;
+            }
+            match tok.text.as_str() {
+                "<" => angles += 1,
+                ">" => {
+                    angles -= 1;
+                    if angles == 0 {
+                        return Some(tok.end);
+                    }
+                }
+                "(" | "[" | "{" => others += 1,
+                ")" | "]" | "}" => {
+                    if others == 0 {
+                        return None;
+                    }
+                    others -= 1;
+                }
+                "," | "." | "|" | "&" | ":" | ";" | "?" | "=" | "=>" | "..." | "-" => {}
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// find the end of a type only statement or class member starting at pos
+    fn statement_end(&self, mut pos: usize, stop_after_brace: bool) -> usize {
+        let mut depth = 0;
+        let mut last: Option<Tok> = None;
+        while let Some(tok) = self.lex(pos, false) {
+            if depth == 0 {
+                if tok.is(";") {
+                    return tok.end;
+                }
+                if tok.nl_before && last.as_ref().map(|l| l.completes_type()).unwrap_or(false) {
+                    break;
+                }
+            }
+            match tok.text.as_str() {
+                "(" | "[" | "{" => depth += 1,
+                ")" | "]" | "}" => {
+                    if depth == 0 {
+                        break;
+                    }
+                    depth -= 1;
+                    if depth == 0 && stop_after_brace && tok.is("}") {
+                        return tok.end;
+                    }
+                }
+                _ => {}
+            }
+            pos = tok.end;
+            last = Some(tok);
+        }
+        last.map(|l| l.end).unwrap_or(pos)
+    }
+
+    fn matching_paren(&self, mut pos: usize) -> Option<usize> {
+        let mut depth = 1;
+        while let Some(tok) = self.lex(pos, false) {
+            if tok.is("(") {
+                depth += 1;
+            } else if tok.is(")") {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(tok.end);
+                }
+            }
+            pos = tok.end;
+        }
+        None
+    }
+
+    /// find the end of a function or method signature without a body, None if the parameter list starting with `open` is followed by a body
+    fn signature_end(&self, open: &Tok) -> Option<usize> {
+        let mut end = self.matching_paren(open.end)?;
+        if let Some(colon) = self.lex(end, false).filter(|t| t.is(":")) {
+            end = self.scan_type(colon.end, TypeCtx::Return);
+        }
+        match self.lex(end, false) {
+            Some(next) if next.is("{") => None,
+            Some(next) if next.is(";") => Some(next.end),
+            _ => Some(end),
+        }
+    }
+
+    /// find the end of a function overload signature (a declaration without a body) starting with `tok`
+    fn overload_end(&self, tok: &Tok) -> Option<usize> {
+        let mut tok = tok.clone();
+        for optional in &["default", "async"] {
+            if tok.is(optional) {
+                tok = self.lex(tok.end, false)?;
+            }
+        }
+        if !tok.is("function") {
+            return None;
+        }
+        let mut open = self.lex(tok.end, false)?;
+        if open.is("*") {
+            open = self.lex(open.end, false)?;
+        }
+        if open.is_ident() {
+            open = self.lex(open.end, false)?;
+        }
+        if open.is("<") {
+            open = self.lex(self.scan_generic(&open)?, false)?;
+        }
+        if !open.is("(") {
+            return None;
+        }
+        self.signature_end(&open)
+    }
+
+    // handling of tokens, these return true if the token was blanked
+
+    fn handle(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        if tok.nl_before
+            && tok.is_ident()
+            && self.top().decl == Decl::Initializer
+            && self.prev_ends_expression()
+        {
+            // automatic semicolon insertion
+            self.top_mut().decl = Decl::None;
+        }
+        if tok.is_ident()
+            && self.top().kind == FrameKind::Block
+            && self.at_statement_start(tok)
+            && self.statement(tok)?
+        {
+            return Ok(true);
+        }
+        if self.top().kind == FrameKind::ClassBody && self.class_member(tok)? {
+            return Ok(true);
+        }
+        match tok.kind {
+            TokKind::Ident => self.ident(tok),
+            TokKind::Punct => self.punct(tok),
+            TokKind::Literal => {
+                if tok.text.starts_with('`') {
+                    self.template(tok)?;
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    fn at_statement_start(&self, tok: &Tok) -> bool {
+        tok.nl_before
+            || match &self.prev {
+                None => true,
+                Some(p) => p.kind == TokKind::Punct && matches!(p.text.as_str(), ";" | "{" | "}"),
+            }
+    }
+
+    fn is_type_alias(&self, name: &Tok) -> bool {
+        name.is_ident()
+            && self
+                .lex(name.end, false)
+                .map(|t| t.is("=") || t.is("<"))
+                .unwrap_or(false)
+    }
+
+    /// blank a type alias, `type_end` is the end of the `type` keyword
+    fn blank_alias(&mut self, from: usize, type_end: usize) -> Result<bool, EsError> {
+        let name = match self.lex(type_end, false) {
+            Some(name) => name,
+            None => return Ok(false),
+        };
+        let mut eq = self.lex(name.end, false);
+        if let Some(lt) = eq.clone().filter(|t| t.is("<")) {
+            eq = self.scan_generic(&lt).and_then(|end| self.lex(end, false));
+        }
+        let eq = match eq.filter(|t| t.is("=")) {
+            Some(eq) => eq,
+            None => return Ok(false),
+        };
+        let mut end = self.scan_type(eq.end, TypeCtx::Alias);
+        if let Some(semi) = self.lex(end, false).filter(|t| t.is(";")) {
+            end = semi.end;
+        }
+        self.blank_to(from, end)
+    }
+
+    fn statement(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        let next = match self.peek() {
+            Some(next) => next,
+            None => return Ok(false),
+        };
+        let after_next = self.lex(next.end, false);
+        let after_next_is = |text: &str| after_next.as_ref().map(|t| t.is(text)).unwrap_or(false);
+        let after_next_ident = after_next.as_ref().map(|t| t.is_ident()).unwrap_or(false);
+        match tok.text.as_str() {
+            "interface" | "declare" if next.is_ident() => {
+                let end = self.statement_end(self.pos, true);
+                self.blank_to(tok.start, end)
+            }
+            "type" if self.is_type_alias(&next) => self.blank_alias(tok.start, tok.end),
+            "enum" if next.is_ident() => Err(self.unsupported(tok, "enum")),
+            "const" if next.is("enum") => Err(self.unsupported(tok, "enum")),
+            "namespace" | "module" if next.is_ident() && after_next_is("{") => {
+                Err(self.unsupported(tok, "namespace"))
+            }
+            "abstract" if next.is("class") => self.blank_to(tok.start, tok.end),
+            "function" | "async" => match self.overload_end(tok) {
+                Some(end) => self.blank_to(tok.start, end),
+                None => Ok(false),
+            },
+            "export" => match next.text.as_str() {
+                "interface" | "declare" if after_next_ident => {
+                    let end = self.statement_end(next.end, true);
+                    self.blank_to(tok.start, end)
+                }
+                "default" if after_next_is("interface") => {
+                    let interface_end = after_next.as_ref().map(|t| t.end).unwrap_or(next.end);
+                    let end = self.statement_end(interface_end, true);
+                    self.blank_to(tok.start, end)
+                }
+                "function" | "async" | "default" if self.overload_end(&next).is_some() => {
+                    let end = self.overload_end(&next).unwrap_or(next.end);
+                    self.blank_to(tok.start, end)
+                }
+                "type" if after_next_is("{") || after_next_is("*") => {
+                    let end = self.statement_end(next.end, false);
+                    self.blank_to(tok.start, end)
+                }
+                "type"
+                    if after_next
+                        .as_ref()
+                        .map(|t| self.is_type_alias(t))
+                        .unwrap_or(false) =>
+                {
+                    self.blank_alias(tok.start, next.end)
+                }
+                "enum" | "namespace" | "const"
+                    if (next.is("const") && after_next_is("enum"))
+                        || (!next.is("const") && after_next_ident) =>
+                {
+                    Err(self.unsupported(&next, next.text.as_str()))
+                }
+                "abstract" if after_next_is("class") => {
+                    // keep the export, blank the abstract
+                    self.blank(next.start, next.end);
+                    self.pos = next.end;
+                    self.module_stmt = true;
+                    Ok(false)
+                }
+                _ => {
+                    self.module_stmt = true;
+                    Ok(false)
+                }
+            },
+            "import" => {
+                if next.is("type")
+                    && (after_next_is("{")
+                        || after_next_is("*")
+                        || (after_next_ident && !after_next_is("from")))
+                {
+                    let end = self.statement_end(next.end, false);
+                    self.blank_to(tok.start, end)
+                } else {
+                    self.module_stmt = true;
+                    Ok(false)
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn class_member(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        let starts_member = tok.is_ident()
+            || tok.kind == TokKind::Literal
+            || matches!(tok.text.as_str(), "[" | "*" | "}");
+        if self.top().member != Member::Start
+            && tok.nl_before
+            && self.prev_ends_expression()
+            && starts_member
+        {
+            // automatic semicolon insertion
+            self.top_mut().member = Member::Start;
+            self.top_mut().member_start = None;
+        }
+        if self.top().member == Member::Start
+            && self.top().member_start.is_none()
+            && starts_member
+            && !tok.is("}")
+        {
+            self.top_mut().member_start = Some(tok.start);
+        }
+
+        let next = self.peek();
+        let next_starts_name = next
+            .as_ref()
+            .map(|n| n.starts_member_name())
+            .unwrap_or(false);
+        let member = self.top().member;
+
+        if member != Member::Initializer && tok.is_ident() && next_starts_name {
+            if MODIFIERS.contains(&tok.text.as_str()) {
+                return self.blank_to(tok.start, tok.end);
+            }
+            if tok.is("declare") || tok.is("abstract") {
+                let end = self.statement_end(tok.end, true);
+                return self.blank_to(tok.start, end);
+            }
+        }
+
+        match member {
+            Member::Start => {
+                if tok.is("[") {
+                    let index_signature = next.as_ref().map(|n| n.is_ident()).unwrap_or(false)
+                        && next
+                            .as_ref()
+                            .and_then(|n| self.lex(n.end, false))
+                            .map(|t| t.is(":"))
+                            .unwrap_or(false);
+                    if index_signature {
+                        let end = self.statement_end(tok.start, false);
+                        return self.blank_to(tok.start, end);
+                    }
+                } else if tok.is_ident() || tok.kind == TokKind::Literal {
+                    self.top_mut().member = Member::Name;
+                }
+                Ok(false)
+            }
+            Member::Name => match tok.text.as_str() {
+                "?" | "!"
+                    if next
+                        .as_ref()
+                        .map(|n| {
+                            n.nl_before || matches!(n.text.as_str(), ":" | ";" | "=" | "(" | "<")
+                        })
+                        .unwrap_or(true) =>
+                {
+                    self.blank_to(tok.start, tok.end)
+                }
+                ":" => {
+                    let end = self.scan_type(tok.end, TypeCtx::Annotation);
+                    self.blank_to(tok.start, end)
+                }
+                "<" => match self.scan_generic(tok) {
+                    Some(end) => self.blank_to(tok.start, end),
+                    None => Ok(false),
+                },
+                "=" => {
+                    self.top_mut().member = Member::Initializer;
+                    Ok(false)
+                }
+                "(" => match self.signature_end(tok) {
+                    // an overload signature, blanked like a declare member
+                    Some(end) => {
+                        let frame = self.top_mut();
+                        let start = frame.member_start.take().unwrap_or(tok.start);
+                        frame.member = Member::Start;
+                        self.blank_to(start, end)
+                    }
+                    None => Ok(false),
+                },
+                _ => Ok(false),
+            },
+            Member::Initializer => Ok(false),
+        }
+    }
+
+    fn ident(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        let kind = self.top().kind;
+        let next = self.peek();
+        match tok.text.as_str() {
+            "let" | "const" | "var" if matches!(kind, FrameKind::Block | FrameKind::Paren) => {
+                self.top_mut().decl = Decl::Binding;
+                return Ok(false);
+            }
+            "class" => {
+                self.class_header = true;
+                return Ok(false);
+            }
+            "case" => {
+                self.top_mut().in_case = true;
+                return Ok(false);
+            }
+            "implements" if self.class_header => {
+                let mut end = tok.end;
+                while let Some(t) = self.lex(end, false) {
+                    if t.is("{") {
+                        break;
+                    }
+                    end = t.end;
+                }
+                return self.blank_to(tok.start, end);
+            }
+            "as" | "satisfies" if kind != FrameKind::Specifiers && self.prev_ends_expression() => {
+                let end = self.scan_type(tok.end, TypeCtx::Cast);
+                return self.blank_to(tok.start, end);
+            }
+            "type" if kind == FrameKind::Specifiers => {
+                if let Some(name) = next.as_ref().filter(|n| n.is_ident() && !n.is("as")) {
+                    let end = match self.lex(name.end, false) {
+                        Some(comma) if comma.is(",") => comma.end,
+                        _ => name.end,
+                    };
+                    return self.blank_to(tok.start, end);
+                }
+            }
+            _ => {}
+        }
+
+        let frame = self.top();
+        if kind == FrameKind::Params
+            && frame.decl == Decl::Binding
+            && tok.is("this")
+            && self.prev.as_ref().map(|p| p.is("(")).unwrap_or(false)
+        {
+            if let Some(colon) = next.as_ref().filter(|n| n.is(":")) {
+                // a this parameter is removed together with its type and a trailing comma
+                let mut end = self.scan_type(colon.end, TypeCtx::Annotation);
+                if let Some(comma) = self.lex(end, false).filter(|t| t.is(",")) {
+                    end = comma.end;
+                }
+                return self.blank_to(tok.start, end);
+            }
+        }
+        if kind == FrameKind::Params
+            && frame.decl == Decl::Binding
+            && MODIFIERS.contains(&tok.text.as_str())
+            && next
+                .as_ref()
+                .map(|n| n.is_ident() || n.is("{") || n.is("["))
+                .unwrap_or(false)
+        {
+            return Err(self.unsupported(tok, "parameter property"));
+        }
+        let frame = self.top_mut();
+        match frame.decl {
+            Decl::Binding => frame.decl = Decl::AfterBinding,
+            Decl::AfterBinding if tok.is("in") || tok.is("of") => frame.decl = Decl::None,
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    fn brace_kind(&self) -> FrameKind {
+        if self.class_header {
+            return FrameKind::ClassBody;
+        }
+        let top = self.top();
+        match &self.prev {
+            None => FrameKind::Block,
+            Some(p) => match p.kind {
+                TokKind::Ident => {
+                    if p.is("import") || p.is("export") || (p.is("type") && self.module_stmt) {
+                        FrameKind::Specifiers
+                    } else if top.decl == Decl::Binding
+                        || (p.is_keyword()
+                            && !matches!(p.text.as_str(), "else" | "try" | "finally" | "do"))
+                    {
+                        FrameKind::Object
+                    } else {
+                        FrameKind::Block
+                    }
+                }
+                TokKind::Literal => FrameKind::Block,
+                TokKind::Punct => match p.text.as_str() {
+                    ")" | "=>" | ";" | "{" | "}" => FrameKind::Block,
+                    ":" if top.kind != FrameKind::Object && top.ternaries == 0 => FrameKind::Block,
+                    "," if self.module_stmt => FrameKind::Specifiers,
+                    _ => FrameKind::Object,
+                },
+            },
+        }
+    }
+
+    fn is_param_list(&self, open: &Tok) -> bool {
+        let top = self.top();
+        if top.kind == FrameKind::ClassBody && top.member == Member::Name {
+            return true;
+        }
+        if top.in_case {
+            return false;
+        }
+        if let Some(p) = &self.prev {
+            if p.is_ident() && matches!(p.text.as_str(), "if" | "for" | "while" | "switch" | "with")
+            {
+                return false;
+            }
+        }
+        match self
+            .matching_paren(open.end)
+            .and_then(|end| self.lex(end, false))
+        {
+            Some(next) if next.is("=>") || next.is("{") => true,
+            Some(next) if next.is(":") => top.ternaries == 0,
+            _ => false,
+        }
+    }
+
+    fn close(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        if self.frames.len() == 1 {
+            return Ok(false);
+        }
+        let frame = self.frames.pop().expect("no frames");
+        let parent = self.top_mut();
+        match frame.kind {
+            FrameKind::Object | FrameKind::Bracket if parent.decl == Decl::Binding => {
+                parent.decl = Decl::AfterBinding
+            }
+            FrameKind::Bracket
+                if parent.kind == FrameKind::ClassBody && parent.member == Member::Start =>
+            {
+                parent.member = Member::Name
+            }
+            FrameKind::Block if parent.kind == FrameKind::ClassBody => {
+                parent.member = Member::Start;
+                parent.member_start = None;
+            }
+            _ => {}
+        }
+        if frame.kind == FrameKind::Params {
+            if let Some(colon) = self.peek().filter(|t| t.is(":")) {
+                // return type
+                self.prev = Some(tok.clone());
+                let end = self.scan_type(colon.end, TypeCtx::Return);
+                self.blank(colon.start, end);
+                self.pos = end;
+            }
+        }
+        Ok(false)
+    }
+
+    fn punct(&mut self, tok: &Tok) -> Result<bool, EsError> {
+        let kind = self.top().kind;
+        let decl = self.top().decl;
+        match tok.text.as_str() {
+            "{" => {
+                let frame_kind = self.brace_kind();
+                if frame_kind == FrameKind::ClassBody {
+                    self.class_header = false;
+                }
+                self.module_stmt = false;
+                self.frames.push(Frame::new(frame_kind));
+            }
+            "(" => {
+                let frame_kind = if self.is_param_list(tok) {
+                    FrameKind::Params
+                } else {
+                    FrameKind::Paren
+                };
+                self.frames.push(Frame::new(frame_kind));
+            }
+            "[" => self.frames.push(Frame::new(FrameKind::Bracket)),
+            ")" | "]" | "}" => return self.close(tok),
+            "?" => {
+                if kind == FrameKind::Params
+                    && decl == Decl::AfterBinding
+                    && (self.peek_is(":") || self.peek_is(",") || self.peek_is(")"))
+                {
+                    // optional parameter
+                    return self.blank_to(tok.start, tok.end);
+                }
+                self.top_mut().ternaries += 1;
+            }
+            "!" => {
+                let adjacent = self
+                    .prev
+                    .as_ref()
+                    .map(|p| p.end == tok.start && p.kind != TokKind::Literal)
+                    .unwrap_or(false);
+                let next_is_operand = self
+                    .peek()
+                    .map(|n| n.kind != TokKind::Punct)
+                    .unwrap_or(false);
+                if adjacent && self.prev_ends_expression() && !next_is_operand {
+                    // non-null assertion or definite assignment
+                    return self.blank_to(tok.start, tok.end);
+                }
+            }
+            ":" => {
+                if matches!(
+                    kind,
+                    FrameKind::Params | FrameKind::Block | FrameKind::Paren
+                ) && decl == Decl::AfterBinding
+                {
+                    let end = self.scan_type(tok.end, TypeCtx::Annotation);
+                    return self.blank_to(tok.start, end);
+                }
+                let frame = self.top_mut();
+                if frame.ternaries > 0 {
+                    frame.ternaries -= 1;
+                } else {
+                    frame.in_case = false;
+                }
+            }
+            "=" if decl == Decl::AfterBinding => {
+                self.top_mut().decl = Decl::Initializer;
+            }
+            "," if kind == FrameKind::Params
+                || decl == Decl::AfterBinding
+                || decl == Decl::Initializer =>
+            {
+                self.top_mut().decl = Decl::Binding;
+            }
+            ";" => {
+                self.module_stmt = false;
+                let frame = self.top_mut();
+                frame.decl = Decl::None;
+                frame.ternaries = 0;
+                frame.in_case = false;
+                frame.member = Member::Start;
+                frame.member_start = None;
+            }
+            "<" => {
+                let adjacent = self
+                    .prev
+                    .as_ref()
+                    .map(|p| {
+                        (p.is_ident() && !p.is_keyword() && p.end == tok.start) || p.is("function")
+                    })
+                    .unwrap_or(false);
+                let starts_expression = !self.prev_ends_expression()
+                    || self.prev.as_ref().map(|p| p.is("async")).unwrap_or(false);
+                // ++ and -- are lexed as two tokens, a < after them is a comparison
+                let after_update = self
+                    .prev
+                    .as_ref()
+                    .map(|p| {
+                        (p.is("+") || p.is("-"))
+                            && p.start > 0
+                            && self.src[p.start - 1] == self.src[p.start]
+                    })
+                    .unwrap_or(false);
+                if starts_expression && !self.class_header {
+                    // type parameters of a generic arrow function, e.g. <T,>(a: T) => a
+                    if let Some(end) = self.scan_generic(tok) {
+                        let is_arrow = self
+                            .lex(end, false)
+                            .filter(|n| n.is("("))
+                            .and_then(|open| self.matching_paren(open.end))
+                            .and_then(|close| self.lex(close, false))
+                            .map(|n| n.is("=>") || n.is(":"))
+                            .unwrap_or(false);
+                        if is_arrow || !after_update {
+                            // a generic arrow function or a type assertion, e.g. <any>a
+                            return self.blank_to(tok.start, end);
+                        }
+                    }
+                    if !after_update {
+                        return Err(self.unsupported(tok, "type assertion"));
+                    }
+                }
+                if adjacent || (self.class_header && self.prev_ends_expression()) {
+                    if let Some(end) = self.scan_generic(tok) {
+                        let followed_by_call = self
+                            .lex(end, false)
+                            .map(|n| n.is("(") || n.text.starts_with('`'))
+                            .unwrap_or(false);
+                        // an instantiation expression, e.g. f<string>; or new A<B>;
+                        let instantiation = self
+                            .lex(end, false)
+                            .map(|n| {
+                                n.nl_before
+                                    || matches!(n.text.as_str(), ";" | ")" | "]" | "," | "}")
+                            })
+                            .unwrap_or(true);
+                        if self.class_header || followed_by_call || instantiation {
+                            return self.blank_to(tok.start, end);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntimebuilder::EsRuntimeBuilder;
+    use crate::esscript::EsScript;
+    use crate::preprocessors::typescript::{strip_types, TypeScriptPreProcessor};
+
+    fn assert_stripped(ts: &str, js: &str) {
+        let res = strip_types(ts).expect("strip failed");
+        assert_eq!(res.trim_end(), js.trim_end());
+        // positions are preserved
+        assert_eq!(res.chars().count(), ts.chars().count());
+    }
+
+    #[test]
+    fn test_strip() {
+        assert_stripped(
+            "function f<T>(a: T, b?: number): Promise<T> {}",
+            "function f   (a   , b         )             {}",
+        );
+        assert_stripped(
+            "let f = function <T>(a: T): T { return a; };",
+            "let f = function    (a   )    { return a; };",
+        );
+        assert_stripped(
+            "const x = y as unknown as string;",
+            "const x = y                     ;",
+        );
+        assert_stripped(
+            "let m = new Map<string, number>();",
+            "let m = new Map                ();",
+        );
+        assert_stripped("let a = b!.c;", "let a = b .c;");
+        assert_stripped(
+            "interface A {\n a: string;\n}\ntype B = A | null;\nlet c = 1 < 2 ? 3 : 4;",
+            "             \n           \n \n                  \nlet c = 1 < 2 ? 3 : 4;",
+        );
+        assert_stripped(
+            "const f = (a: number, {b}: {b: string}): string => a + b;",
+            "const f = (a        , {b}             )         => a + b;",
+        );
+        assert_stripped(
+            "class A<T> extends B implements C, D {\n  private x: number = 1;\n  y?: string;\n  constructor(a: T) { super(); }\n  public get z(): number { return this.x; }\n}",
+            "class A    extends B                 {\n          x         = 1;\n  y         ;\n  constructor(a   ) { super(); }\n         get z()         { return this.x; }\n}",
+        );
+        assert_stripped(
+            "import type { A } from './a.ts';\nimport { type B, c } from './b.ts';\nexport interface C {}",
+            "                                \nimport {         c } from './b.ts';\n                     ",
+        );
+        assert_stripped(
+            "let o = {a: 1, b: c ? d : e};\nswitch (o.a) { case f(1): break; }",
+            "let o = {a: 1, b: c ? d : e};\nswitch (o.a) { case f(1): break; }",
+        );
+        assert_stripped(
+            "let r = /a:b/g.test(s as string);",
+            "let r = /a:b/g.test(s          );",
+        );
+
+        assert_stripped(
+            "function f(this: Window) {}\nfunction g(this: Window, a: number) {}",
+            "function f(            ) {}\nfunction g(              a        ) {}",
+        );
+        assert_stripped(
+            "const f = <T,>(x: T) => x;\nconst g = async <T extends object>(x: T): Promise<T> => x;",
+            "const f =     (x   ) => x;\nconst g = async                   (x   )             => x;",
+        );
+        assert_stripped("let c = a < b ? (d) : e;", "let c = a < b ? (d) : e;");
+
+        // overload signatures
+        assert_stripped(
+            "function f(a: string): void;\nfunction f(a: any) { return a; }",
+            "                            \nfunction f(a     ) { return a; }",
+        );
+        assert_stripped(
+            "export function g<T>(a: T): T\nexport function g(a) { return a; }",
+            "                             \nexport function g(a) { return a; }",
+        );
+        assert_stripped(
+            "export default function h(a: string): void;\nexport default function h(a) {}",
+            "                                           \nexport default function h(a) {}",
+        );
+        assert_stripped(
+            "class A {\n  foo(a: string): void;\n  public foo(a: any) { return a; }\n  static *gen(): Iterator<number>;\n  constructor(a: string);\n  constructor(a) {}\n}",
+            "class A {\n                       \n         foo(a     ) { return a; }\n                                  \n                         \n  constructor(a) {}\n}",
+        );
+        assert_stripped(
+            "export default interface Foo {\n  a: string;\n}\nlet b = 1;",
+            "                              \n            \n \nlet b = 1;",
+        );
+        // instantiation expressions and type assertions
+        assert_stripped(
+            "const g = f<string>;\nconst h = new Foo<Bar>;\nlet c = a<b>c;",
+            "const g = f        ;\nconst h = new Foo     ;\nlet c = a<b>c;",
+        );
+        assert_stripped(
+            "let x = <any>y;\nlet z = (<string[]>w).length;\nlet n = i++ < j;",
+            "let x =      y;\nlet z = (          w).length;\nlet n = i++ < j;",
+        );
+
+        assert!(strip_types("enum A { B }").is_err());
+        assert!(strip_types("class A { constructor(private a: string) {} }").is_err());
+        assert!(strip_types("let x = <any y;").is_err());
+    }
+
+    #[test]
+    fn test_ts_module() {
+        let rt = EsRuntimeBuilder::new()
+            .script_pre_processor(TypeScriptPreProcessor::new())
+            .build();
+        let res = rt.eval_module_sync(EsScript::new(
+            "test_ts_module.ts",
+            "interface Named {\n    name: string;\n}\nclass Person implements Named {\n    constructor(public name: string) {}\n}",
+        ));
+        assert!(res.is_err());
+
+        let res = rt.eval_sync(EsScript::new(
+            "test_ts.ts",
+            "interface Named {\n    name: string;\n}\nclass Person implements Named {\n    readonly name: string;\n    constructor(name: string) {\n        this.name = name;\n    }\n    greet<T extends string>(greeting: T): string {\n        return `${greeting} ${this.name!}`;\n    }\n}\nconst p: Person = new Person('Ts');\n(p.greet<string>('hello') as string).length;",
+        ));
+        let res = match res {
+            Ok(res) => res,
+            Err(e) => panic!("script failed: {}", e),
+        };
+        assert_eq!(res.get_i32(), 8);
+
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_ts_overloads.ts",
+                "function f(a: string): string;\nfunction f(a: number): number;\nfunction f(a: any) {\n    return a;\n}\nclass C {\n    g(a: number): number;\n    g(a: any) {\n        return <number>f(a) * 2;\n    }\n}\nnew C().g(21);",
+            ))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 42);
+
+        // scripts which are not TypeScript are left alone
+        let err = rt
+            .eval_sync(EsScript::new("test_ts.es", "let a: number = 1;"))
+            .expect_err("script should fail");
+        assert!(err.is_script_error());
+    }
+}
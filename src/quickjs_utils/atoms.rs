@@ -75,3 +75,35 @@ pub unsafe fn from_string(context: *mut q::JSContext, string: &str) -> Result<JS
     let atom = q::JS_NewAtomLen(context, s.as_ptr(), len as _);
     Ok(JSAtomRef::new(context, atom))
 }
+
+/// create an atom from a value, e.g. a Symbol
+pub fn from_value_q(q_ctx: &QuickJsContext, value_ref: &JSValueRef) -> Result<JSAtomRef, EsError> {
+    unsafe { from_value(q_ctx.context, value_ref) }
+}
+
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn from_value(
+    context: *mut q::JSContext,
+    value_ref: &JSValueRef,
+) -> Result<JSAtomRef, EsError> {
+    let atom = q::JS_ValueToAtom(context, *value_ref.borrow_value());
+    if atom == q::JS_ATOM_NULL {
+        return Err(QuickJsContext::get_exception(context)
+            .unwrap_or_else(|| EsError::new_str("could not convert value to atom")));
+    }
+    Ok(JSAtomRef::new(context, atom))
+}
+
+/// check if an atom is a symbol (e.g. Symbol.iterator) instead of a string or number
+pub fn is_symbol_q(q_ctx: &QuickJsContext, atom: &q::JSAtom) -> bool {
+    unsafe { is_symbol(q_ctx.context, atom) }
+}
+
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn is_symbol(context: *mut q::JSContext, atom: &q::JSAtom) -> bool {
+    let val = q::JS_AtomToValue(context, *atom);
+    let val_ref = JSValueRef::new(context, val, false, true, "atoms::is_symbol");
+    val_ref.is_symbol()
+}
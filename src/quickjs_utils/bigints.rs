@@ -1,4 +1,4 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::quickjs_utils;
 use crate::quickjs_utils::{functions, primitives};
 use crate::quickjscontext::QuickJsContext;
@@ -61,7 +61,10 @@ pub unsafe fn to_string(
     big_int_ref: &JSValueRef,
 ) -> Result<String, EsError> {
     if !big_int_ref.is_big_int() {
-        return Err(EsError::new_str("big_int_ref was not a big_int"));
+        return Err(EsError::new_kind(
+            EsErrorKind::Conversion,
+            "big_int_ref was not a big_int".to_string(),
+        ));
     }
     functions::call_to_string(context, big_int_ref)
 }
@@ -1,7 +1,9 @@
 //! Utils to compile script to bytecode and run script from bytecode
 
 use crate::eserror::EsError;
+use crate::esruntime_utils::source_maps;
 use crate::esscript::EsScript;
+use crate::quickjs_utils::functions;
 use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::make_cstring;
 use crate::valueref::JSValueRef;
@@ -38,6 +40,8 @@ pub unsafe fn compile(context: *mut q::JSContext, script: EsScript) -> Result<JS
 
     log::debug!("q_js_rt.compile file {}", script.get_path());
 
+    source_maps::register_script(&script);
+
     let value_raw = q::JS_Eval(
         context,
         code_c.as_ptr(),
@@ -94,6 +98,32 @@ pub unsafe fn run_compiled_function(
     }
 }
 
+/// evaluate an internal script of this crate which results in a function and call that function with args
+///
+/// the script is compiled directly so it is not altered by the script pre-processors
+pub(crate) fn eval_internal_q(
+    q_ctx: &QuickJsContext,
+    path: &str,
+    code: &str,
+    args: Vec<JSValueRef>,
+) -> Result<JSValueRef, EsError> {
+    unsafe { eval_internal(q_ctx.context, path, code, args) }
+}
+
+/// evaluate an internal script of this crate, see [eval_internal_q]
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub(crate) unsafe fn eval_internal(
+    context: *mut q::JSContext,
+    path: &str,
+    code: &str,
+    args: Vec<JSValueRef>,
+) -> Result<JSValueRef, EsError> {
+    let func = compile(context, EsScript::new(path, code))?;
+    let factory = run_compiled_function(context, &func)?;
+    functions::call_function(context, &factory, args, None)
+}
+
 /// write a function to bytecode
 /// # Example
 /// ```rust
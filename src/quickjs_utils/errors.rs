@@ -1,9 +1,23 @@
 //! utils for getting and reporting exceptions
 
-use crate::eserror::EsError;
-use crate::quickjs_utils::{objects, primitives};
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esruntime_utils::source_maps;
+use crate::quickjs_utils::atoms::JSAtomRef;
+use crate::quickjs_utils::{compile, functions, get_constructor, objects, primitives};
+use crate::quickjscontext::QuickJsContext;
 use crate::valueref::{JSValueRef, TAG_EXCEPTION};
 use libquickjs_sys as q;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// errors of kind PermissionDenied are marked with a non-enumerable property keyed by a private symbol so script can not see, copy or fake the mark
+// QuickJS has no api to create a private symbol, so it is taken from an instance of a class with a private field
+const PRIVATE_SYMBOL_SCRIPT: &str = "(function() { return new (class { #permissionDenied; })(); })";
+
+thread_local! {
+    // the private symbol atoms per context pointer, created when the first error of kind PermissionDenied is thrown in a context
+    static PERMISSION_DENIED_ATOMS: RefCell<HashMap<usize, JSAtomRef>> = RefCell::new(HashMap::new());
+}
 
 /// Get the last exception from the runtime, and if present, convert it to an EsError.
 /// # Safety
@@ -32,14 +46,24 @@ pub unsafe fn get_exception(context: *mut q::JSContext) -> Option<EsError> {
                 .unwrap();
             let stack_string;
             if stack_ref.is_string() {
-                stack_string = primitives::to_string(context, &stack_ref).ok().unwrap();
+                let raw_stack = primitives::to_string(context, &stack_ref).ok().unwrap();
+                stack_string = source_maps::rewrite_stack(raw_stack.as_str());
             } else {
                 stack_string = "".to_string();
             }
 
-            EsError::new(name_string, message_string, stack_string)
+            let err = EsError::new(name_string, message_string, stack_string);
+            if is_permission_denied(context, &exception_ref) {
+                err.with_kind(EsErrorKind::PermissionDenied)
+            } else {
+                err
+            }
         } else {
-            EsError::new_str("no clue what happened")
+            // a primitive was thrown, e.g. throw "oops";
+            match functions::call_to_string(context, &exception_ref) {
+                Ok(message) => EsError::new_kind(EsErrorKind::JsException, message),
+                Err(_) => EsError::new_str("no clue what happened"),
+            }
         };
         Some(err)
     }
@@ -83,6 +107,208 @@ pub unsafe fn new_error(
     Ok(obj_ref)
 }
 
+/// the builtin Error classes which may be used by the name of an EsError
+const BUILTIN_ERROR_CLASSES: &[&str] = &[
+    "Error",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "URIError",
+    "AggregateError",
+    "InternalError",
+];
+
+/// get the name of the builtin Error class which is used to throw an EsError of a certain kind
+pub fn get_error_class_name(kind: EsErrorKind) -> &'static str {
+    match kind {
+        EsErrorKind::Syntax => "SyntaxError",
+        EsErrorKind::Type | EsErrorKind::Conversion => "TypeError",
+        EsErrorKind::Range => "RangeError",
+        EsErrorKind::Reference | EsErrorKind::ModuleNotFound => "ReferenceError",
+        EsErrorKind::JsException | EsErrorKind::PermissionDenied => "Error",
+        _ => "InternalError",
+    }
+}
+
+/// Create a new Error object based on an EsError
+/// the Error class is determined by
+/// * the name of the EsError, if a class with that name was registered with [QuickJsContext::register_error_class](../../quickjscontext/struct.QuickJsContext.html#method.register_error_class) or is a builtin Error class (e.g. URIError)
+/// * the kind of the EsError, e.g. EsErrorKind::Type results in a TypeError
+///
+/// the extra properties of the EsError are set on the new Error object
+/// if the EsError has a stack (e.g. because it originated in script) that stack is preserved
+pub fn new_error_from_q(q_ctx: &QuickJsContext, error: &EsError) -> Result<JSValueRef, EsError> {
+    unsafe { new_error_from(q_ctx.context, error) }
+}
+
+/// Create a new Error object based on an EsError
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn new_error_from(
+    context: *mut q::JSContext,
+    error: &EsError,
+) -> Result<JSValueRef, EsError> {
+    let name = error.get_name();
+
+    let mut constructor_opt = None;
+    if !name.is_empty() {
+        constructor_opt =
+            QuickJsContext::with_context(context, |q_ctx| q_ctx.get_error_class(name));
+        // other global functions are never called, script could have defined them
+        if constructor_opt.is_none() && BUILTIN_ERROR_CLASSES.contains(&name) {
+            if let Ok(constructor_ref) = get_constructor(context, name) {
+                if functions::is_constructor(context, &constructor_ref) {
+                    constructor_opt = Some(constructor_ref);
+                }
+            }
+        }
+    }
+    let found_by_name = constructor_opt.is_some();
+    let constructor_ref = match constructor_opt {
+        Some(c) => c,
+        None => get_constructor(context, get_error_class_name(error.get_kind()))?,
+    };
+
+    let message_ref = primitives::from_string(context, error.get_message())?;
+    let err_ref = objects::construct_object(context, &constructor_ref, vec![message_ref])?;
+
+    if !found_by_name && !name.is_empty() {
+        objects::set_property(
+            context,
+            &err_ref,
+            "name",
+            &primitives::from_string(context, name)?,
+        )?;
+    }
+    if !error.get_stack().is_empty() {
+        objects::set_property(
+            context,
+            &err_ref,
+            "stack",
+            &primitives::from_string(context, error.get_stack())?,
+        )?;
+    }
+    for (prop_name, prop_value) in error.get_properties() {
+        objects::set_property(
+            context,
+            &err_ref,
+            prop_name,
+            &primitives::from_string(context, prop_value)?,
+        )?;
+    }
+    if error.get_kind() == EsErrorKind::PermissionDenied {
+        let atom = get_permission_denied_atom(context)?;
+        let res = q::JS_DefinePropertyValue(
+            context,
+            *err_ref.borrow_value(),
+            atom,
+            primitives::from_bool(true).clone_value_incr_rc(),
+            0,
+        );
+        if res < 0 {
+            return Err(EsError::new_str(
+                "could not mark error as permission denied",
+            ));
+        }
+    }
+    Ok(err_ref)
+}
+
+/// get the private symbol which marks errors of kind PermissionDenied, it is created once per context
+unsafe fn get_permission_denied_atom(context: *mut q::JSContext) -> Result<q::JSAtom, EsError> {
+    let existing = PERMISSION_DENIED_ATOMS
+        .with(|rc| rc.borrow().get(&(context as usize)).map(|a| a.get_atom()));
+    if let Some(atom) = existing {
+        return Ok(atom);
+    }
+    let holder = compile::eval_internal(
+        context,
+        "permission_denied_symbol.es",
+        PRIVATE_SYMBOL_SCRIPT,
+        vec![],
+    )?;
+
+    let mut properties: *mut q::JSPropertyEnum = std::ptr::null_mut();
+    let mut count: u32 = 0;
+    let ret = q::JS_GetOwnPropertyNames(
+        context,
+        &mut properties,
+        &mut count,
+        *holder.borrow_value(),
+        q::JS_GPN_PRIVATE_MASK as i32,
+    );
+    if ret != 0 {
+        return Err(EsError::new_str("could not get private symbol"));
+    }
+    // the atom of the first property is kept, the others are freed
+    let mut atom_opt = None;
+    for index in 0..count {
+        let atom = (*properties.offset(index as isize)).atom;
+        if atom_opt.is_none() {
+            atom_opt = Some(JSAtomRef::new(context, atom));
+        } else {
+            q::JS_FreeAtom(context, atom);
+        }
+    }
+    q::js_free(context, properties as *mut std::ffi::c_void);
+    let atom_ref = atom_opt.ok_or_else(|| EsError::new_str("could not get private symbol"))?;
+    let atom = atom_ref.get_atom();
+    PERMISSION_DENIED_ATOMS.with(|rc| {
+        rc.borrow_mut().insert(context as usize, atom_ref);
+    });
+    Ok(atom)
+}
+
+/// true if an error object was created for an EsError of kind PermissionDenied (see [new_error_from])
+///
+/// this does not run script, so it is safe for any exception (e.g. when the runtime is out of memory or interrupted)
+unsafe fn is_permission_denied(context: *mut q::JSContext, error_ref: &JSValueRef) -> bool {
+    if !is_error(context, error_ref) {
+        return false;
+    }
+    let atom_opt = PERMISSION_DENIED_ATOMS
+        .with(|rc| rc.borrow().get(&(context as usize)).map(|a| a.get_atom()));
+    match atom_opt {
+        Some(atom) => {
+            q::JS_GetOwnProperty(
+                context,
+                std::ptr::null_mut(),
+                *error_ref.borrow_value(),
+                atom,
+            ) == 1
+        }
+        None => false,
+    }
+}
+
+/// drop the helpers of a context, this is called before the context is freed
+pub(crate) fn release_context(context: *mut q::JSContext) {
+    let atom_opt = PERMISSION_DENIED_ATOMS.with(|rc| rc.borrow_mut().remove(&(context as usize)));
+    drop(atom_opt);
+}
+
+/// throw an EsError as a JavaScript Error (see [new_error_from]) and get an Exception JSValue to return from native methods
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn throw_es_error(context: *mut q::JSContext, error: &EsError) -> q::JSValue {
+    match new_error_from(context, error) {
+        Ok(err_ref) if is_error(context, &err_ref) => throw(context, err_ref),
+        Ok(obj_ref) => {
+            q::JS_Throw(context, obj_ref.clone_value_incr_rc());
+            q::JSValue {
+                u: q::JSValueUnion { int32: 0 },
+                tag: TAG_EXCEPTION,
+            }
+        }
+        Err(e) => {
+            log::error!("could not create Error object: {}", e);
+            QuickJsContext::report_ex_ctx(context, error.get_message())
+        }
+    }
+}
+
 /// See if a JSValueRef is an Error objecte
 /// # Safety
 /// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
@@ -109,11 +335,12 @@ pub unsafe fn throw(context: *mut q::JSContext, error: JSValueRef) -> q::JSValue
 
 #[cfg(test)]
 pub mod tests {
+    use crate::eserror::{EsError, EsErrorKind};
     use crate::esruntime::tests::init_test_rt;
     use crate::esruntime::EsRuntime;
     use crate::esscript::EsScript;
     use crate::esvalue::EsValueConvertible;
-    use crate::quickjs_utils::functions;
+    use crate::quickjs_utils::{functions, get_global_q, objects, primitives};
     use std::sync::Arc;
     use std::time::Duration;
 
@@ -200,4 +427,75 @@ pub mod tests {
 
         std::thread::sleep(Duration::from_secs(1));
     }
+
+    #[test]
+    fn test_throw_es_error() {
+        let rt = init_test_rt();
+        let res = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let class_ref = q_ctx
+                .eval(EsScript::new(
+                    "test_throw_es_error_class.es",
+                    "(class MyError extends Error {});",
+                ))
+                .expect("script failed");
+            q_ctx.register_error_class("MyError", class_ref);
+
+            let type_err_func = functions::new_function_q(
+                q_ctx,
+                "typeErr",
+                |_q_ctx, _this, _args| {
+                    Err(EsError::new_kind(EsErrorKind::Type, "not a thing".to_string())
+                        .with_property("code", "ERR_NOT_A_THING"))
+                },
+                0,
+            )
+            .expect("could not create func");
+            let my_err_func = functions::new_function_q(
+                q_ctx,
+                "myErr",
+                |_q_ctx, _this, _args| {
+                    Err(EsError::new_str("custom").with_name("MyError"))
+                },
+                0,
+            )
+            .expect("could not create func");
+            let named_err_func = functions::new_function_q(
+                q_ctx,
+                "namedErr",
+                |q_ctx, _this, args| {
+                    let name = primitives::to_string_q(q_ctx, &args[0])?;
+                    Err(EsError::new_str("named").with_name(name.as_str()))
+                },
+                1,
+            )
+            .expect("could not create func");
+            let global = get_global_q(q_ctx);
+            objects::set_property_q(q_ctx, &global, "typeErr", &type_err_func)
+                .expect("could not set prop");
+            objects::set_property_q(q_ctx, &global, "namedErr", &named_err_func)
+                .expect("could not set prop");
+            objects::set_property_q(q_ctx, &global, "myErr", &my_err_func)
+                .expect("could not set prop");
+
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_throw_es_error.es",
+                    "let r = [];\n\
+                     try {typeErr();} catch(e) {r.push(e instanceof TypeError, e.message, e.code, typeof e.stack);}\n\
+                     try {myErr();} catch(e) {r.push(e instanceof Error && e.constructor.name === 'MyError', e.message);}\n\
+                     try {namedErr('URIError');} catch(e) {r.push(e instanceof URIError);}\n\
+                     this.evil = function Evil() { r.push('called'); return {}; };\n\
+                     try {namedErr('evil');} catch(e) {r.push(e instanceof Error, e.name);}\n\
+                     r.join(',');",
+                ))
+                .expect("script failed");
+            primitives::to_string_q(q_ctx, &res)
+                .expect("not a string")
+        });
+        assert_eq!(
+            res,
+            "true,not a thing,ERR_NOT_A_THING,string,true,custom,true,true,evil"
+        );
+    }
 }
@@ -0,0 +1,204 @@
+//! finalization utils, these may be used to learn when a JS object is garbage collected
+//!
+//! a holder (an instance of a hidden Proxy class) is stored in a private field of the object, the holder is finalized together with the object
+//! private fields are not visible from script and may also be added to frozen objects
+//! see [WeakJSValueRef](../../valueref/struct.WeakJSValueRef.html) for a weak reference to an object
+
+use crate::eserror::EsError;
+use crate::quickjs_utils::{compile, functions};
+use crate::quickjscontext::QuickJsContext;
+use crate::reflection;
+use crate::reflection::Proxy;
+use crate::valueref::JSValueRef;
+
+const HOLDER_CLASS_NAME: &str = "quickjs_runtime.FinalizationHolder";
+
+// the constructor of Target returns the object it receives so the private field of Attacher is added to that object
+const HELPER_SCRIPT: &str = r#"(function() {
+    'use strict';
+    class Target {
+        constructor(target) {
+            return target;
+        }
+    }
+    class Attacher extends Target {
+        #holder;
+        constructor(target, holder) {
+            super(target);
+            this.#holder = holder;
+        }
+        static get(target) {
+            try {
+                return target.#holder;
+            } catch (ex) {
+                return undefined;
+            }
+        }
+    }
+    return {
+        attach(target, holder) {
+            new Attacher(target, holder);
+        },
+        get: Attacher.get
+    };
+})"#;
+
+/// the value of a holder, the callbacks are called when the holder is dropped
+struct FinalizationHolder {
+    callbacks: Vec<Box<dyn FnOnce()>>,
+}
+
+impl Drop for FinalizationHolder {
+    fn drop(&mut self) {
+        for callback in self.callbacks.drain(..) {
+            callback();
+        }
+    }
+}
+
+/// register a callback which is called when an object is garbage collected (or when its context is dropped)
+///
+/// the callback is called while the GC runs so it should only release rust resources and not run scripts
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// use quickjs_runtime::quickjs_utils::finalization;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     let obj = q_ctx.eval(EsScript::new("test_finalizer.es", "({handle: 1});")).expect("script failed");
+///     let released = Rc::new(Cell::new(false));
+///     let released2 = released.clone();
+///     finalization::register_finalizer_q(q_ctx, &obj, move || released2.set(true)).expect("register failed");
+///     q_js_rt.gc();
+///     assert!(!released.get());
+///     drop(obj);
+///     q_js_rt.gc();
+///     assert!(released.get());
+/// });
+/// ```
+pub fn register_finalizer_q<F>(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+    callback: F,
+) -> Result<(), EsError>
+where
+    F: FnOnce() + 'static,
+{
+    let (_, holder_id) = get_holder_q(q_ctx, obj_ref)?;
+    let holder = reflection::get_instance_data::<FinalizationHolder>(q_ctx, holder_id)
+        .ok_or_else(|| EsError::new_str("no such finalization holder"))?;
+    holder.borrow_mut().callbacks.push(Box::new(callback));
+    Ok(())
+}
+
+/// get the holder of an object, it is created if the object has none yet
+/// returns the holder and its instance id
+pub(crate) fn get_holder_q(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+) -> Result<(JSValueRef, usize), EsError> {
+    if !obj_ref.is_object() {
+        return Err(EsError::new_str(
+            "finalizers can only be registered for objects",
+        ));
+    }
+    let helper = get_helper_q(q_ctx)?;
+    let holder_ref =
+        functions::invoke_member_function_q(q_ctx, &helper, "get", vec![obj_ref.clone()])?;
+    if let Some((_, holder_id)) = reflection::get_proxy_instance_class_and_id(&holder_ref) {
+        return Ok((holder_ref, holder_id));
+    }
+
+    if reflection::get_proxy(q_ctx, HOLDER_CLASS_NAME).is_none() {
+        Proxy::with_data::<FinalizationHolder>()
+            .namespace(vec!["quickjs_runtime"])
+            .name("FinalizationHolder")
+            .install(q_ctx, false)?;
+    }
+    let (holder_id, holder_ref) = reflection::new_instance_with_data(
+        HOLDER_CLASS_NAME,
+        q_ctx,
+        FinalizationHolder { callbacks: vec![] },
+    )?;
+    functions::invoke_member_function_q(
+        q_ctx,
+        &helper,
+        "attach",
+        vec![obj_ref.clone(), holder_ref.clone()],
+    )?;
+    Ok((holder_ref, holder_id))
+}
+
+/// the helper functions which attach a holder to an object, these are created once per context
+fn get_helper_q(q_ctx: &QuickJsContext) -> Result<JSValueRef, EsError> {
+    if let Some(helper) = &*q_ctx.finalization_helper.borrow() {
+        return Ok(helper.clone());
+    }
+    let helper = compile::eval_internal_q(q_ctx, "finalization.es", HELPER_SCRIPT, vec![])?;
+    q_ctx.finalization_helper.replace(Some(helper.clone()));
+    Ok(helper)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esscript::EsScript;
+    use crate::quickjs_utils::{finalization, primitives};
+    use crate::valueref::WeakJSValueRef;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_finalizer() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let log = Rc::new(RefCell::new(vec![]));
+            // a frozen object in a cycle with two callbacks
+            let obj = q_ctx
+                .eval(EsScript::new(
+                    "test_finalizer.es",
+                    "let o = {}; o.self = o; Object.freeze(o);",
+                ))
+                .expect("script failed");
+            for name in &["first", "second"] {
+                let log = log.clone();
+                finalization::register_finalizer_q(q_ctx, &obj, move || {
+                    log.borrow_mut().push(name.to_string())
+                })
+                .expect("register failed");
+            }
+            assert!(finalization::register_finalizer_q(q_ctx, &primitives::from_i32(1), || {}).is_err());
+
+            // the holder is not visible from script
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_finalizer2.es",
+                    "Reflect.ownKeys(o).join(',') + ':' + JSON.stringify(Object.getOwnPropertySymbols(o));",
+                ))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "self:[]"
+            );
+
+            // a weak reference uses the same holder
+            let weak = WeakJSValueRef::new(&obj)
+                .expect("could not create weak ref");
+            drop(obj);
+            q_js_rt.gc();
+            assert!(log.borrow().is_empty());
+            assert!(weak.is_alive());
+            q_ctx
+                .eval(EsScript::new("test_finalizer3.es", "o = null;"))
+                .expect("script failed");
+            q_js_rt.gc();
+            assert_eq!(log.borrow().join(","), "first,second");
+            assert!(weak.upgrade().is_none());
+        });
+    }
+}
@@ -1,6 +1,6 @@
 //! utils to create and invoke functions
 
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::esscript::EsScript;
 use crate::quickjs_utils::{atoms, errors, objects, parse_args, primitives};
 use crate::quickjscontext::QuickJsContext;
@@ -12,6 +12,7 @@ use log::trace;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::os::raw::{c_char, c_int, c_void};
+use std::rc::Rc;
 
 /// parse a function body and its arg_names into a JSValueRef which is a Function
 /// # Example
@@ -234,7 +235,10 @@ pub unsafe fn call_to_string(
         log::trace!("called JS_ToString got a {}", res_ref.borrow_value().tag);
 
         if !res_ref.is_string() {
-            return Err(EsError::new_str("Could not convert value to string"));
+            return Err(EsError::new_kind(
+                EsErrorKind::Conversion,
+                "Could not convert value to string".to_string(),
+            ));
         }
         crate::quickjs_utils::primitives::to_string(context, &res_ref)
     }
@@ -497,7 +501,7 @@ thread_local! {
         RefCell::new(class_id)
     };
 
-    static CALLBACK_REGISTRY: RefCell<AutoIdMap<Box<Callback>>> = {
+    static CALLBACK_REGISTRY: RefCell<AutoIdMap<Rc<Callback>>> = {
         RefCell::new(AutoIdMap::new_with_max_size(i32::MAX as usize))
     };
 
@@ -563,7 +567,7 @@ where
 
     let callback_id = CALLBACK_REGISTRY.with(|registry_rc| {
         let registry = &mut *registry_rc.borrow_mut();
-        registry.insert(Box::new(func))
+        registry.insert(Rc::new(func))
     });
     log::trace!("new_function callback_id = {}", callback_id);
 
@@ -809,6 +813,88 @@ pub mod tests {
         std::thread::sleep(Duration::from_secs(1));
     }
 
+    #[test]
+    fn test_callback_creates_function() {
+        let rt = init_test_rt();
+
+        let res = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            // a callback which creates and calls another function while it runs
+            let outer_ref = new_function_q(
+                q_ctx,
+                "outer",
+                |q_ctx, _this_ref, _args| {
+                    let inner_ref = new_function_q(
+                        q_ctx,
+                        "inner",
+                        |_q_ctx, _this_ref, _args| Ok(primitives::from_i32(7)),
+                        0,
+                    )?;
+                    let res = call_function_q(q_ctx, &inner_ref, vec![], None)?;
+                    Ok(primitives::from_i32(primitives::to_i32(&res)? * 6))
+                },
+                0,
+            )
+            .expect("could not create function");
+
+            let res =
+                call_function_q(q_ctx, &outer_ref, vec![], None).expect("could not call outer");
+            primitives::to_i32(&res).expect("not an i32")
+        });
+        assert_eq!(res, 42);
+        rt.gc_sync();
+    }
+
+    #[test]
+    fn test_callback_finalizer() {
+        let rt = init_test_rt();
+
+        let ran_during_finalize = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            q_ctx
+                .eval(EsScript::new(
+                    "test_callback_finalizer.es",
+                    "this.__fin_job_ran__ = false; Promise.resolve().then(() => {this.__fin_job_ran__ = true;});",
+                ))
+                .expect("script failed");
+
+            let inner_ref = new_function_q(
+                q_ctx,
+                "inner",
+                |_q_ctx, _this_ref, _args| Ok(primitives::from_i32(1)),
+                0,
+            )
+            .expect("could not create function");
+            // the finalizer of outer drops inner
+            let outer_ref = new_function_q(
+                q_ctx,
+                "outer",
+                move |_q_ctx, _this_ref, _args| Ok(inner_ref.clone()),
+                0,
+            )
+            .expect("could not create function");
+            drop(outer_ref);
+
+            // the pending job is not run while the function is finalized
+            let ran = q_ctx
+                .eval(EsScript::new(
+                    "test_callback_finalizer2.es",
+                    "this.__fin_job_ran__;",
+                ))
+                .expect("script failed");
+            primitives::to_bool(&ran).expect("not a bool")
+        });
+        assert!(!ran_during_finalize);
+
+        let ran = rt
+            .eval_sync(EsScript::new(
+                "test_callback_finalizer3.es",
+                "this.__fin_job_ran__;",
+            ))
+            .expect("script failed");
+        assert!(ran.get_boolean());
+    }
+
     #[test]
     fn test_callback_arg_ref_ct() {
         let rt = init_test_rt();
@@ -867,17 +953,15 @@ unsafe extern "C" fn callback_finalizer(_rt: *mut q::JSRuntime, val: q::JSValue)
         let ids = &mut *rc.borrow_mut();
         ids.remove(&callback_id);
     });
-    CALLBACK_REGISTRY.with(|rc| {
+    let removed_callback = CALLBACK_REGISTRY.with(|rc| {
         let registry = &mut *rc.borrow_mut();
 
         let rid = callback_id as usize;
         trace!("callback_finalizer remove id={}", rid);
-        let _ = registry.remove(&rid);
-    });
-
-    QuickJsRuntime::do_with(|q_js_rt| {
-        q_js_rt.run_pending_jobs_if_any();
+        registry.remove(&rid)
     });
+    // the callback is dropped after the registry is released, dropping it may finalize other callbacks
+    drop(removed_callback);
 }
 
 unsafe extern "C" fn callback_function(
@@ -899,30 +983,25 @@ unsafe extern "C" fn callback_function(
 
     trace!("callback_function id = {}", callback_id);
 
-    CALLBACK_REGISTRY.with(|registry_rc| {
+    // the registry is not borrowed while the callback runs, a callback may create new functions
+    let callback_opt = CALLBACK_REGISTRY.with(|registry_rc| {
         let registry = &*registry_rc.borrow();
-        if let Some(callback) = registry.get(&(callback_id as usize)) {
-            let args_vec = parse_args(ctx, argc, argv);
-
-            let this_ref = JSValueRef::new(ctx, this_val, true, true, "callback_function this_val");
-
-            let callback_res: Result<JSValueRef, EsError> = callback(ctx, this_ref, args_vec);
-
-            match callback_res {
-                Ok(res) => res.clone_value_incr_rc(),
-                Err(e) => {
-                    let message =
-                        format!("\n{} at\nnative_code\n{}", e.get_message(), e.get_stack());
-                    let err = errors::new_error(ctx, e.get_name(), message.as_str(), e.get_stack())
-                        .ok()
-                        .expect("could not create err");
-                    errors::throw(ctx, err)
-                }
-            }
-        } else {
-            panic!("callback not found");
+        registry.get(&(callback_id as usize)).cloned()
+    });
+    if let Some(callback) = callback_opt {
+        let args_vec = parse_args(ctx, argc, argv);
+
+        let this_ref = JSValueRef::new(ctx, this_val, true, true, "callback_function this_val");
+
+        let callback_res: Result<JSValueRef, EsError> = callback(ctx, this_ref, args_vec);
+
+        match callback_res {
+            Ok(res) => res.clone_value_incr_rc(),
+            Err(e) => errors::throw_es_error(ctx, &e),
         }
-    })
+    } else {
+        panic!("callback not found");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,891 @@
+//! Inspect utils, these render any value as a human readable string in the style of Node.js' [util.inspect](https://nodejs.org/api/util.html#util_util_inspect_object_options)
+//!
+//! This is used by the console feature for %o / %O, console.dir(), console.table() cells and non-string arguments and may be used from rust to debug a JSValueRef
+//!
+//! * objects nested deeper than the depth of the [InspectOptions] are rendered as `[Object]`, `[Array]` or `[ClassName]`
+//! * circular references are rendered as `[Circular *1]`, the referenced object is prefixed with `<ref *1>`
+//! * functions, classes, Map, Set, Date, RegExp, Error, TypedArray, ArrayBuffer and instances of [Proxy](../../reflection/struct.Proxy.html) classes have their own rendering
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+//! use quickjs_runtime::esscript::EsScript;
+//! use quickjs_runtime::quickjs_utils::inspect::{inspect_q, InspectOptions};
+//!
+//! let rt = EsRuntimeBuilder::new().build();
+//! let res = rt.add_to_event_queue_sync(|q_js_rt| {
+//!     let q_ctx = q_js_rt.get_main_context();
+//!     let obj_ref = q_ctx
+//!         .eval(EsScript::new("inspect.es", "let o = {a: [1, 2], m: new Map([['k', true]])}; o.me = o; o;"))
+//!         .expect("script failed");
+//!     inspect_q(q_ctx, &obj_ref, &InspectOptions::new()).expect("inspect failed")
+//! });
+//! assert_eq!(res, "<ref *1> { a: [ 1, 2 ], m: Map(1) { 'k' => true }, me: [Circular *1] }");
+//! ```
+
+use crate::droppable_value::DroppableValue;
+use crate::eserror::EsError;
+use crate::esruntime_utils::source_maps;
+use crate::quickjs_utils::{arrays, dates, errors, functions, maps, objects, primitives, sets};
+use crate::quickjscontext::QuickJsContext;
+use crate::reflection;
+use crate::valueref::JSValueRef;
+use libquickjs_sys as q;
+use std::collections::HashMap;
+use std::os::raw::c_void;
+
+const TYPED_ARRAYS: [&str; 11] = [
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+];
+
+/// the max number of bytes of an ArrayBuffer to render
+const MAX_BUFFER_BYTES: usize = 50;
+
+/// the max number of nested levels which are rendered regardless of the depth option, this prevents a stack overflow for deeply nested values
+const MAX_DEPTH: usize = 100;
+
+/// options for [inspect_q]
+/// # Example
+/// ```rust
+/// use quickjs_runtime::quickjs_utils::inspect::InspectOptions;
+/// let options = InspectOptions::new().depth(None).colors(true);
+/// ```
+#[derive(Clone)]
+pub struct InspectOptions {
+    depth: Option<usize>,
+    colors: bool,
+    max_array_length: usize,
+    break_length: usize,
+}
+
+impl InspectOptions {
+    pub fn new() -> Self {
+        Self {
+            depth: Some(2),
+            colors: false,
+            max_array_length: 100,
+            break_length: 80,
+        }
+    }
+    /// the number of nested levels to render, None renders all levels (but at most 100), defaults to Some(2)
+    pub fn depth(mut self, depth: Option<usize>) -> Self {
+        self.depth = depth;
+        self
+    }
+    /// style the output with ANSI color codes, defaults to false
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = colors;
+        self
+    }
+    /// the max number of items to render for Arrays, TypedArrays, Maps and Sets, defaults to 100
+    pub fn max_array_length(mut self, max_array_length: usize) -> Self {
+        self.max_array_length = max_array_length;
+        self
+    }
+    /// the line length at which an object is split over multiple lines, defaults to 80
+    pub fn break_length(mut self, break_length: usize) -> Self {
+        self.break_length = break_length;
+        self
+    }
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// render a value as a human readable string
+pub fn inspect_q(
+    q_ctx: &QuickJsContext,
+    value_ref: &JSValueRef,
+    options: &InspectOptions,
+) -> Result<String, EsError> {
+    unsafe { inspect(q_ctx.context, value_ref, options) }
+}
+
+/// render a value as a human readable string
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn inspect(
+    context: *mut q::JSContext,
+    value_ref: &JSValueRef,
+    options: &InspectOptions,
+) -> Result<String, EsError> {
+    let mut inspector = Inspector {
+        context,
+        options,
+        seen: vec![],
+        circular: HashMap::new(),
+    };
+    inspector.format_value(value_ref, 0, 0)
+}
+
+enum Style {
+    Special,
+    Number,
+    Boolean,
+    Undefined,
+    Null,
+    String,
+    Symbol,
+    Date,
+    RegExp,
+}
+
+struct Inspector<'a> {
+    context: *mut q::JSContext,
+    options: &'a InspectOptions,
+    /// the objects currently being rendered, used to detect circular references
+    seen: Vec<*mut c_void>,
+    /// the ref index of objects which are referenced circularly
+    circular: HashMap<*mut c_void, usize>,
+}
+
+impl Inspector<'_> {
+    fn stylize<S: Into<String>>(&self, text: S, style: Style) -> String {
+        let text = text.into();
+        if !self.options.colors {
+            return text;
+        }
+        let (open, close) = match style {
+            Style::Special => (36, 39),
+            Style::Number | Style::Boolean => (33, 39),
+            Style::Undefined => (90, 39),
+            Style::Null => (1, 22),
+            Style::String | Style::Symbol => (32, 39),
+            Style::Date => (35, 39),
+            Style::RegExp => (31, 39),
+        };
+        format!("\x1b[{}m{}\x1b[{}m", open, text, close)
+    }
+
+    unsafe fn format_value(
+        &mut self,
+        value_ref: &JSValueRef,
+        indent: usize,
+        level: usize,
+    ) -> Result<String, EsError> {
+        if value_ref.is_object() {
+            self.format_object(value_ref, indent, level)
+        } else {
+            self.format_primitive(value_ref)
+        }
+    }
+
+    unsafe fn format_primitive(&self, value_ref: &JSValueRef) -> Result<String, EsError> {
+        let ctx = self.context;
+        Ok(if value_ref.is_string() {
+            self.stylize(
+                quote(primitives::to_string(ctx, value_ref)?.as_str()),
+                Style::String,
+            )
+        } else if value_ref.is_undefined() {
+            self.stylize("undefined", Style::Undefined)
+        } else if value_ref.is_null() {
+            self.stylize("null", Style::Null)
+        } else if value_ref.is_bool() {
+            self.stylize(primitives::to_bool(value_ref)?.to_string(), Style::Boolean)
+        } else if value_ref.is_i32() {
+            self.stylize(primitives::to_i32(value_ref)?.to_string(), Style::Number)
+        } else if value_ref.is_f64() {
+            self.stylize(number_to_string(ctx, value_ref)?, Style::Number)
+        } else if value_ref.is_big_int() {
+            let big_int = functions::call_to_string(ctx, value_ref)?;
+            self.stylize(format!("{}n", big_int), Style::Number)
+        } else if value_ref.is_symbol() {
+            self.stylize(symbol_to_string(ctx, value_ref)?, Style::Symbol)
+        } else {
+            functions::call_to_string(ctx, value_ref)?
+        })
+    }
+
+    unsafe fn format_object(
+        &mut self,
+        obj_ref: &JSValueRef,
+        indent: usize,
+        level: usize,
+    ) -> Result<String, EsError> {
+        let ptr = obj_ref.borrow_value().u.ptr;
+        if self.seen.contains(&ptr) {
+            let next_index = self.circular.len() + 1;
+            let index = *self.circular.entry(ptr).or_insert(next_index);
+            return Ok(self.stylize(format!("[Circular *{}]", index), Style::Special));
+        }
+
+        let too_deep = level > self.options.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+
+        self.seen.push(ptr);
+        let res = self.format_raw_object(obj_ref, indent, level, too_deep);
+        self.seen.pop();
+        let res = res?;
+
+        Ok(match self.circular.get(&ptr) {
+            Some(index) => format!(
+                "{} {}",
+                self.stylize(format!("<ref *{}>", index), Style::Special),
+                res
+            ),
+            None => res,
+        })
+    }
+
+    unsafe fn format_raw_object(
+        &mut self,
+        obj_ref: &JSValueRef,
+        indent: usize,
+        level: usize,
+        too_deep: bool,
+    ) -> Result<String, EsError> {
+        let ctx = self.context;
+
+        if let Some((class_name, id)) = reflection::get_proxy_instance_class_and_id(obj_ref) {
+            return Ok(format!(
+                "{} {{ {}: {} }}",
+                class_name,
+                self.stylize("[[ProxyInstanceId]]", Style::Special),
+                self.stylize(id.to_string(), Style::Number)
+            ));
+        }
+        if functions::is_function(ctx, obj_ref) {
+            let base = self.function_base(obj_ref)?;
+            return self.with_own_properties(obj_ref, base, indent, level, too_deep);
+        }
+        if errors::is_error(ctx, obj_ref) {
+            let base = self.error_base(obj_ref, indent)?;
+            return self.with_own_properties(obj_ref, base, indent, level, too_deep);
+        }
+        if arrays::is_array(ctx, obj_ref) {
+            if too_deep {
+                return Ok(self.stylize("[Array]", Style::Special));
+            }
+            let length = arrays::get_length(ctx, obj_ref)? as usize;
+            let mut entries = vec![];
+            for index in 0..length.min(self.options.max_array_length) {
+                let element_ref = arrays::get_element(ctx, obj_ref, index as u32)?;
+                entries.push(self.format_value(&element_ref, indent + 2, level + 1)?);
+            }
+            self.push_more_items(&mut entries, length);
+            return Ok(self.reduce_to_single_string("", "[", "]", entries, indent));
+        }
+        if dates::is_date(ctx, obj_ref)? {
+            let time = dates::get_time(ctx, obj_ref)?;
+            let text = if time.is_nan() {
+                "Invalid Date".to_string()
+            } else {
+                let iso_ref =
+                    functions::invoke_member_function(ctx, obj_ref, "toISOString", vec![])?;
+                primitives::to_string(ctx, &iso_ref)?
+            };
+            let base = self.stylize(text, Style::Date);
+            return self.with_own_properties(obj_ref, base, indent, level, too_deep);
+        }
+        if objects::is_instance_of_by_name(ctx, obj_ref, "RegExp")? {
+            let base = self.stylize(functions::call_to_string(ctx, obj_ref)?, Style::RegExp);
+            return self.with_own_properties(obj_ref, base, indent, level, too_deep);
+        }
+        if maps::is_map(ctx, obj_ref)? {
+            if too_deep {
+                return Ok(self.stylize("[Map]", Style::Special));
+            }
+            let size = maps::size(ctx, obj_ref)? as usize;
+            let pairs = maps::entries(ctx, obj_ref, |key, value| Ok((key, value)))?;
+            let mut entries = vec![];
+            for (key, value) in pairs.iter().take(self.options.max_array_length) {
+                let key = self.format_value(key, indent + 2, level + 1)?;
+                let value = self.format_value(value, indent + 2, level + 1)?;
+                entries.push(format!("{} => {}", key, value));
+            }
+            self.push_more_items(&mut entries, size);
+            let prefix = format!("Map({}) ", size);
+            return Ok(self.reduce_to_single_string(prefix.as_str(), "{", "}", entries, indent));
+        }
+        if sets::is_set(ctx, obj_ref)? {
+            if too_deep {
+                return Ok(self.stylize("[Set]", Style::Special));
+            }
+            let size = sets::size(ctx, obj_ref)? as usize;
+            let values = sets::values(ctx, obj_ref, Ok)?;
+            let mut entries = vec![];
+            for value in values.iter().take(self.options.max_array_length) {
+                entries.push(self.format_value(value, indent + 2, level + 1)?);
+            }
+            self.push_more_items(&mut entries, size);
+            let prefix = format!("Set({}) ", size);
+            return Ok(self.reduce_to_single_string(prefix.as_str(), "{", "}", entries, indent));
+        }
+
+        let class_name = self.get_class_name(obj_ref)?;
+        match class_name.as_deref() {
+            Some(name @ "WeakMap") | Some(name @ "WeakSet") => {
+                return Ok(format!(
+                    "{} {{ {} }}",
+                    name,
+                    self.stylize("<items unknown>", Style::Special)
+                ));
+            }
+            Some("ArrayBuffer") => {
+                if too_deep {
+                    return Ok(self.stylize("[ArrayBuffer]", Style::Special));
+                }
+                return self.format_array_buffer(obj_ref, indent);
+            }
+            Some(name) if TYPED_ARRAYS.contains(&name) => {
+                if too_deep {
+                    return Ok(self.stylize(format!("[{}]", name), Style::Special));
+                }
+                let length_ref = objects::get_property(ctx, obj_ref, "length")?;
+                let length = primitives::to_i32(&length_ref)? as usize;
+                let mut entries = vec![];
+                for index in 0..length.min(self.options.max_array_length) {
+                    let element_ref = arrays::get_element(ctx, obj_ref, index as u32)?;
+                    entries.push(self.format_primitive(&element_ref)?);
+                }
+                self.push_more_items(&mut entries, length);
+                let prefix = format!("{}({}) ", name, length);
+                return Ok(self.reduce_to_single_string(
+                    prefix.as_str(),
+                    "[",
+                    "]",
+                    entries,
+                    indent,
+                ));
+            }
+            _ => {}
+        }
+
+        if too_deep {
+            let name = class_name.as_deref().unwrap_or("Object");
+            return Ok(self.stylize(format!("[{}]", name), Style::Special));
+        }
+        let prefix = match class_name.as_deref() {
+            Some("Object") => "".to_string(),
+            Some(name) => format!("{} ", name),
+            None => "[Object: null prototype] ".to_string(),
+        };
+        let entries = self.own_properties(obj_ref, indent, level)?;
+        Ok(self.reduce_to_single_string(prefix.as_str(), "{", "}", entries, indent))
+    }
+
+    /// render a value which has a base representation (like a function or a date) and may have additional properties
+    unsafe fn with_own_properties(
+        &mut self,
+        obj_ref: &JSValueRef,
+        base: String,
+        indent: usize,
+        level: usize,
+        too_deep: bool,
+    ) -> Result<String, EsError> {
+        if too_deep {
+            return Ok(base);
+        }
+        let entries = self.own_properties(obj_ref, indent, level)?;
+        if entries.is_empty() {
+            Ok(base)
+        } else {
+            let prefix = format!("{} ", base);
+            Ok(self.reduce_to_single_string(prefix.as_str(), "{", "}", entries, indent))
+        }
+    }
+
+    /// render all own enumerable properties of an object as key: value entries, getters are not invoked
+    unsafe fn own_properties(
+        &mut self,
+        obj_ref: &JSValueRef,
+        indent: usize,
+        level: usize,
+    ) -> Result<Vec<String>, EsError> {
+        let context = self.context;
+        let mut properties: *mut q::JSPropertyEnum = std::ptr::null_mut();
+        let mut count: u32 = 0;
+
+        let flags = (q::JS_GPN_STRING_MASK | q::JS_GPN_SYMBOL_MASK | q::JS_GPN_ENUM_ONLY) as i32;
+        let ret = q::JS_GetOwnPropertyNames(
+            context,
+            &mut properties,
+            &mut count,
+            *obj_ref.borrow_value(),
+            flags,
+        );
+        if ret != 0 {
+            return Err(EsError::new_str("Could not get object properties"));
+        }
+
+        let properties = DroppableValue::new(properties, |&mut properties| {
+            for index in 0..count {
+                let prop = properties.offset(index as isize);
+
+                q::JS_FreeAtom(context, (*prop).atom);
+            }
+
+            q::js_free(context, properties as *mut std::ffi::c_void);
+        });
+
+        let mut entries = vec![];
+        for index in 0..count {
+            let atom = (*(*properties).offset(index as isize)).atom;
+
+            let mut desc = q::JSPropertyDescriptor {
+                flags: 0,
+                value: crate::quickjs_utils::new_null(),
+                getter: crate::quickjs_utils::new_null(),
+                setter: crate::quickjs_utils::new_null(),
+            };
+            let res = q::JS_GetOwnProperty(context, &mut desc, *obj_ref.borrow_value(), atom);
+            if res < 0 {
+                return Err(QuickJsContext::get_exception(context)
+                    .unwrap_or_else(|| EsError::new_str("Could not get object property")));
+            }
+            if res == 0 {
+                // property was removed while iterating (e.g. by a Proxy)
+                continue;
+            }
+            let value_ref = JSValueRef::new(context, desc.value, false, true, "inspect value");
+            let getter_ref = JSValueRef::new(context, desc.getter, false, true, "inspect getter");
+            let setter_ref = JSValueRef::new(context, desc.setter, false, true, "inspect setter");
+
+            let key = self.format_key(atom)?;
+            let value = if desc.flags & q::JS_PROP_GETSET as i32 != 0 {
+                let label = match (getter_ref.is_undefined(), setter_ref.is_undefined()) {
+                    (false, false) => "[Getter/Setter]",
+                    (false, true) => "[Getter]",
+                    _ => "[Setter]",
+                };
+                self.stylize(label, Style::Special)
+            } else {
+                self.format_value(&value_ref, indent + 2, level + 1)?
+            };
+            entries.push(format!("{}: {}", key, value));
+        }
+        Ok(entries)
+    }
+
+    unsafe fn format_key(&self, atom: q::JSAtom) -> Result<String, EsError> {
+        let key_ref = JSValueRef::new(
+            self.context,
+            q::JS_AtomToValue(self.context, atom),
+            false,
+            true,
+            "inspect::format_key key_ref",
+        );
+        if key_ref.is_symbol() {
+            let symbol = symbol_to_string(self.context, &key_ref)?;
+            Ok(format!("[{}]", self.stylize(symbol, Style::Symbol)))
+        } else {
+            let key = primitives::to_string(self.context, &key_ref)?;
+            if is_identifier(key.as_str()) {
+                Ok(key)
+            } else {
+                Ok(self.stylize(quote(key.as_str()), Style::String))
+            }
+        }
+    }
+
+    /// get the name of the constructor of an object, None if the object has no prototype
+    unsafe fn get_class_name(&self, obj_ref: &JSValueRef) -> Result<Option<String>, EsError> {
+        let ctx = self.context;
+        let proto_ref = JSValueRef::new(
+            ctx,
+            q::JS_GetPrototype(ctx, *obj_ref.borrow_value()),
+            false,
+            true,
+            "inspect::get_class_name proto_ref",
+        );
+        if proto_ref.is_exception() {
+            return Err(QuickJsContext::get_exception(ctx)
+                .unwrap_or_else(|| EsError::new_str("Could not get prototype")));
+        }
+        if !proto_ref.is_object() {
+            return Ok(None);
+        }
+        let constructor_ref = objects::get_property(ctx, &proto_ref, "constructor")?;
+        if constructor_ref.is_object() {
+            let name_ref = objects::get_property(ctx, &constructor_ref, "name")?;
+            if name_ref.is_string() {
+                let name = primitives::to_string(ctx, &name_ref)?;
+                if !name.is_empty() {
+                    return Ok(Some(name));
+                }
+            }
+        }
+        Ok(Some("Object".to_string()))
+    }
+
+    unsafe fn function_base(&self, func_ref: &JSValueRef) -> Result<String, EsError> {
+        let ctx = self.context;
+        let name_ref = objects::get_property(ctx, func_ref, "name")?;
+        let name = if name_ref.is_string() {
+            primitives::to_string(ctx, &name_ref)?
+        } else {
+            "".to_string()
+        };
+        let source = functions::call_to_string(ctx, func_ref).unwrap_or_default();
+        let base = if source.starts_with("class") {
+            if name.is_empty() {
+                "[class (anonymous)]".to_string()
+            } else {
+                format!("[class {}]", name)
+            }
+        } else {
+            // Function, AsyncFunction, GeneratorFunction or AsyncGeneratorFunction
+            let type_name = self
+                .get_class_name(func_ref)?
+                .unwrap_or_else(|| "Function".to_string());
+            if name.is_empty() {
+                format!("[{} (anonymous)]", type_name)
+            } else {
+                format!("[{}: {}]", type_name, name)
+            }
+        };
+        Ok(self.stylize(base, Style::Special))
+    }
+
+    unsafe fn error_base(&self, error_ref: &JSValueRef, indent: usize) -> Result<String, EsError> {
+        let ctx = self.context;
+        let text = functions::call_to_string(ctx, error_ref)?;
+        let stack_ref = objects::get_property(ctx, error_ref, "stack")?;
+        let stack = if stack_ref.is_string() {
+            source_maps::rewrite_stack(primitives::to_string(ctx, &stack_ref)?.as_str())
+        } else {
+            "".to_string()
+        };
+        let stack = stack.trim_end();
+        let base = if stack.is_empty() {
+            format!("[{}]", text)
+        } else {
+            format!("{}\n{}", text, stack)
+        };
+        if indent > 0 {
+            Ok(base.replace('\n', format!("\n{}", " ".repeat(indent)).as_str()))
+        } else {
+            Ok(base)
+        }
+    }
+
+    unsafe fn format_array_buffer(
+        &self,
+        buffer_ref: &JSValueRef,
+        indent: usize,
+    ) -> Result<String, EsError> {
+        let mut size: q::size_t = 0;
+        let data = q::JS_GetArrayBuffer(self.context, &mut size, *buffer_ref.borrow_value());
+        let size = size as usize;
+        let contents = if data.is_null() {
+            // detached buffers have no data
+            let _ = QuickJsContext::get_exception(self.context);
+            "".to_string()
+        } else {
+            let bytes = std::slice::from_raw_parts(data, size.min(MAX_BUFFER_BYTES));
+            let mut contents = bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<String>>()
+                .join(" ");
+            if size > MAX_BUFFER_BYTES {
+                contents.push_str(
+                    format!(
+                        " ... {} more byte{}",
+                        size - MAX_BUFFER_BYTES,
+                        plural(size - MAX_BUFFER_BYTES)
+                    )
+                    .as_str(),
+                );
+            }
+            contents
+        };
+        let entries = vec![
+            format!(
+                "[Uint8Contents]: {}",
+                self.stylize(format!("<{}>", contents), Style::Special)
+            ),
+            format!(
+                "byteLength: {}",
+                self.stylize(size.to_string(), Style::Number)
+            ),
+        ];
+        Ok(self.reduce_to_single_string("ArrayBuffer ", "{", "}", entries, indent))
+    }
+
+    fn push_more_items(&self, entries: &mut Vec<String>, total: usize) {
+        if total > entries.len() {
+            let more = total - entries.len();
+            entries.push(format!("... {} more item{}", more, plural(more)));
+        }
+    }
+
+    /// put all entries on a single line if they fit within the break length, else render them one per line
+    fn reduce_to_single_string(
+        &self,
+        prefix: &str,
+        open: &str,
+        close: &str,
+        entries: Vec<String>,
+        indent: usize,
+    ) -> String {
+        if entries.is_empty() {
+            return format!("{}{}{}", prefix, open, close);
+        }
+        let start = entries.len() + indent + visible_len(prefix) + open.len() + 10;
+        let total_length = entries.iter().fold(start + entries.len(), |len, entry| {
+            len.saturating_add(visible_len(entry))
+        });
+        if total_length <= self.options.break_length
+            && !prefix.contains('\n')
+            && !entries.iter().any(|entry| entry.contains('\n'))
+        {
+            format!("{}{} {} {}", prefix, open, entries.join(", "), close)
+        } else {
+            let indentation = " ".repeat(indent);
+            format!(
+                "{}{}\n{}  {}\n{}{}",
+                prefix,
+                open,
+                indentation,
+                entries.join(format!(",\n{}  ", indentation).as_str()),
+                indentation,
+                close
+            )
+        }
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// quote a string with single quotes, or double quotes or backticks if that avoids escaping
+fn quote(s: &str) -> String {
+    let quote_char = if !s.contains('\'') {
+        '\''
+    } else if !s.contains('"') {
+        '"'
+    } else if !s.contains('`') && !s.contains("${") {
+        '`'
+    } else {
+        '\''
+    };
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push(quote_char);
+    for c in s.chars() {
+        match c {
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            '\\' => res.push_str("\\\\"),
+            c if c == quote_char => {
+                res.push('\\');
+                res.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                res.push_str(format!("\\x{:02X}", c as u32).as_str())
+            }
+            c => res.push(c),
+        }
+    }
+    res.push(quote_char);
+    res
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    match chars.next() {
+        Some(first) if first.is_alphabetic() || first == '_' || first == '$' => {
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        _ => false,
+    }
+}
+
+/// the length of a string without ANSI color codes
+fn visible_len(s: &str) -> usize {
+    let mut len = 0;
+    let mut in_escape = false;
+    for c in s.chars() {
+        if in_escape {
+            in_escape = c != 'm';
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            len += 1;
+        }
+    }
+    len
+}
+
+unsafe fn number_to_string(
+    context: *mut q::JSContext,
+    number_ref: &JSValueRef,
+) -> Result<String, EsError> {
+    let f = primitives::to_f64(number_ref)?;
+    if f == 0.0 && f.is_sign_negative() {
+        return Ok("-0".to_string());
+    }
+    // use JS_ToString so e.g. Infinity and 1e+21 are rendered as in JavaScript
+    let str_ref = JSValueRef::new(
+        context,
+        q::JS_ToString(context, *number_ref.borrow_value()),
+        false,
+        true,
+        "inspect::number_to_string str_ref",
+    );
+    primitives::to_string(context, &str_ref)
+}
+
+unsafe fn symbol_to_string(
+    context: *mut q::JSContext,
+    symbol_ref: &JSValueRef,
+) -> Result<String, EsError> {
+    let str_ref = functions::invoke_member_function(context, symbol_ref, "toString", vec![])?;
+    primitives::to_string(context, &str_ref)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esscript::EsScript;
+    use crate::quickjs_utils::inspect::{inspect_q, InspectOptions};
+    use crate::reflection::Proxy;
+
+    fn inspect_script(code: &'static str, options: InspectOptions) -> String {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(move |q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let value_ref = q_ctx
+                .eval(EsScript::new("test_inspect.es", code))
+                .expect("script failed");
+            inspect_q(q_ctx, &value_ref, &options).expect("inspect failed")
+        })
+    }
+
+    fn inspect_default(code: &'static str) -> String {
+        inspect_script(code, InspectOptions::new())
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(inspect_default("'it\\'s'"), "\"it's\"");
+        assert_eq!(inspect_default("'a\\nb'"), "'a\\nb'");
+        assert_eq!(inspect_default("-0"), "-0");
+        assert_eq!(inspect_default("1.5"), "1.5");
+        assert_eq!(inspect_default("1/0"), "Infinity");
+        assert_eq!(inspect_default("12n"), "12n");
+        assert_eq!(inspect_default("Symbol('s')"), "Symbol(s)");
+        assert_eq!(inspect_default("undefined"), "undefined");
+        assert_eq!(inspect_default("null"), "null");
+    }
+
+    #[test]
+    fn test_objects() {
+        assert_eq!(
+            inspect_default("({a: 1, b: 'x', c: [1, 2, {d: null}], e: undefined})"),
+            "{ a: 1, b: 'x', c: [ 1, 2, { d: null } ], e: undefined }"
+        );
+        assert_eq!(
+            inspect_default("({a: {b: {c: {d: 1}}}, e: [[[[1]]]]})"),
+            "{ a: { b: { c: [Object] } }, e: [ [ [Array] ] ] }"
+        );
+        assert_eq!(
+            inspect_default("({'a-b': 1, [Symbol('s')]: 2, get g() {return 1;}})"),
+            "{ 'a-b': 1, g: [Getter], [Symbol(s)]: 2 }"
+        );
+        assert_eq!(
+            inspect_default("let o = Object.create(null); o.a = 1; o;"),
+            "[Object: null prototype] { a: 1 }"
+        );
+        assert_eq!(
+            inspect_default("class Bar {constructor() {this.x = 1;}}; new Bar();"),
+            "Bar { x: 1 }"
+        );
+        assert_eq!(
+            inspect_default("let a = {n: 'a'}; let b = {n: 'b', a}; a.b = b; [a];"),
+            "[ <ref *1> { n: 'a', b: { n: 'b', a: [Circular *1] } } ]"
+        );
+        assert_eq!(
+            inspect_default(
+                "({aaaaaaaaaa: 'aaaaaaaaaa', bbbbbbbbbb: 'bbbbbbbbbb', cccccccccc: {dddddddddd: 'dddddddddd'}})"
+            ),
+            "{\n  aaaaaaaaaa: 'aaaaaaaaaa',\n  bbbbbbbbbb: 'bbbbbbbbbb',\n  cccccccccc: { dddddddddd: 'dddddddddd' }\n}"
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(
+            inspect_default("[function foo() {}, () => {}, class Bar {}, async function baz() {}]"),
+            "[\n  [Function: foo],\n  [Function (anonymous)],\n  [class Bar],\n  [AsyncFunction: baz]\n]"
+        );
+        assert_eq!(
+            inspect_default("[new Map([['a', 1], ['b', {c: 2}]]), new Set([1, 'two'])]"),
+            "[ Map(2) { 'a' => 1, 'b' => { c: 2 } }, Set(2) { 1, 'two' } ]"
+        );
+        assert_eq!(
+            inspect_default("[new Date(0), new Date(NaN), /a+/g, new WeakMap()]"),
+            "[\n  1970-01-01T00:00:00.000Z,\n  Invalid Date,\n  /a+/g,\n  WeakMap { <items unknown> }\n]"
+        );
+        assert_eq!(
+            inspect_default("[new Uint8Array([1, 2, 3]), new Uint8Array([4, 5]).buffer]"),
+            "[\n  Uint8Array(3) [ 1, 2, 3 ],\n  ArrayBuffer { [Uint8Contents]: <04 05>, byteLength: 2 }\n]"
+        );
+        let err = inspect_default("function thrower() {let e = new TypeError('boom'); e.code = 12; return e;}; thrower();");
+        assert!(err.starts_with("TypeError: boom\n    at thrower (test_inspect.es"));
+        assert!(err.ends_with("{\n  code: 12\n}"));
+    }
+
+    #[test]
+    fn test_options() {
+        assert_eq!(
+            inspect_script("({a: {b: {c: {d: 1}}}})", InspectOptions::new().depth(None)),
+            "{ a: { b: { c: { d: 1 } } } }"
+        );
+        // deeply nested values stop at the max depth instead of overflowing the stack
+        let nested = inspect_script(
+            "let a = []; for (let i = 0; i < 5000; i++) { a = [a]; } a;",
+            InspectOptions::new().depth(None),
+        );
+        assert!(nested.contains("[Array]"));
+        assert_eq!(nested.matches('[').count(), 102);
+        assert_eq!(
+            inspect_script("[1, 2, 3, 4, 5]", InspectOptions::new().max_array_length(2)),
+            "[ 1, 2, ... 3 more items ]"
+        );
+        assert_eq!(
+            inspect_script("[1, 'a', null]", InspectOptions::new().colors(true)),
+            "[ \x1b[33m1\x1b[39m, \x1b[32m'a'\x1b[39m, \x1b[1mnull\x1b[22m ]"
+        );
+    }
+
+    #[test]
+    fn test_proxy_instance() {
+        let rt = init_test_rt();
+        let res = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::new()
+                .name("InspectedClass")
+                .constructor(|_q_ctx, _id, _args| Ok(()))
+                .install(q_ctx, true)
+                .expect("install failed");
+            let value_ref = q_ctx
+                .eval(EsScript::new(
+                    "test_proxy_instance.es",
+                    "[new InspectedClass()];",
+                ))
+                .expect("script failed");
+            inspect_q(q_ctx, &value_ref, &InspectOptions::new()).expect("inspect failed")
+        });
+        assert!(res.starts_with("[ InspectedClass { [[ProxyInstanceId]]: "));
+    }
+}
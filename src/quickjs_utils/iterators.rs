@@ -1,9 +1,13 @@
 //! utils for the iterator protocol
 
 use crate::eserror::EsError;
-use crate::quickjs_utils::{functions, objects, primitives};
+use crate::esvalue::EsValueFacade;
+use crate::quickjs_utils;
+use crate::quickjs_utils::{errors, functions, objects, primitives, promises};
+use crate::quickjscontext::QuickJsContext;
 use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
+use std::cell::RefCell;
 
 /// iterate over an object conforming to the [iterator](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Iteration_protocols#the_iterator_protocol) protocol
 /// # Safety
@@ -27,3 +31,182 @@ pub unsafe fn iterate<C: Fn(JSValueRef) -> Result<R, EsError>, R>(
 
     Ok(res)
 }
+
+/// create an object conforming to the iterator protocol which produces the items of a rust Iterator
+///
+/// the items are produced lazily, one per call of next(), and converted to a JS value, an Err item is thrown as an Error
+///
+/// the object is also iterable itself ([Symbol.iterator]() returns the object) so it can be used in for...of and spread
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::quickjs_utils::{functions, iterators, primitives};
+/// use quickjs_runtime::esscript::EsScript;
+/// use quickjs_runtime::esvalue::EsValueConvertible;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     let iter_ref = iterators::new_iterator_q(q_ctx, (1..4).map(|i| Ok(i.to_es_value_facade()))).ok().unwrap();
+///     let func_ref = q_ctx.eval(EsScript::new("test_iter.es", "(function(it){return [...it].join('-');});")).ok().unwrap();
+///     let res = functions::call_function_q(q_ctx, &func_ref, vec![iter_ref], None).ok().unwrap();
+///     assert_eq!(primitives::to_string_q(q_ctx, &res).ok().unwrap(), "1-2-3");
+/// });
+/// ```
+pub fn new_iterator_q<I>(q_ctx: &QuickJsContext, iter: I) -> Result<JSValueRef, EsError>
+where
+    I: Iterator<Item = Result<EsValueFacade, EsError>> + 'static,
+{
+    let iter = RefCell::new(iter.fuse());
+    let next_ref = functions::new_function_q(
+        q_ctx,
+        "next",
+        move |q_ctx, _this, _args| match next_item(&iter)? {
+            Some(item) => new_iterator_result_q(q_ctx, item?.as_js_value(q_ctx)?, false),
+            None => new_iterator_result_q(q_ctx, quickjs_utils::new_undefined_ref(), true),
+        },
+        0,
+    )?;
+    new_iterable_q(q_ctx, next_ref, "iterator")
+}
+
+/// create an object conforming to the [async iterator](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Iteration_protocols#the_async_iterator_and_async_iterable_protocols) protocol which produces the items of a rust Iterator
+///
+/// next() returns a Promise, if an item is a Promise (e.g. an [EsPromise](crate::esvalue::EsPromise)) the value it resolves to is used, an Err item results in a rejected Promise
+///
+/// the object is also async iterable itself ([Symbol.asyncIterator]() returns the object) so it can be used in for await...of
+pub fn new_async_iterator_q<I>(q_ctx: &QuickJsContext, iter: I) -> Result<JSValueRef, EsError>
+where
+    I: Iterator<Item = Result<EsValueFacade, EsError>> + 'static,
+{
+    let iter = RefCell::new(iter.fuse());
+    let next_ref = functions::new_function_q(
+        q_ctx,
+        "next",
+        move |q_ctx, _this, _args| match next_item(&iter)? {
+            Some(Ok(mut item)) => {
+                let item = item.as_js_value(q_ctx)?;
+                if promises::is_promise_q(q_ctx, &item) {
+                    let then_ref = functions::new_function_q(
+                        q_ctx,
+                        "",
+                        |q_ctx, _this, args| {
+                            let value = args
+                                .into_iter()
+                                .next()
+                                .unwrap_or_else(quickjs_utils::new_undefined_ref);
+                            new_iterator_result_q(q_ctx, value, false)
+                        },
+                        1,
+                    )?;
+                    functions::invoke_member_function_q(q_ctx, &item, "then", vec![then_ref])
+                } else {
+                    let promise_ref = promises::new_promise_q(q_ctx)?;
+                    promise_ref.resolve_q(q_ctx, new_iterator_result_q(q_ctx, item, false)?)?;
+                    Ok(promise_ref.get_promise_obj_ref())
+                }
+            }
+            Some(Err(e)) => {
+                let promise_ref = promises::new_promise_q(q_ctx)?;
+                promise_ref.reject_q(q_ctx, errors::new_error_from_q(q_ctx, &e)?)?;
+                Ok(promise_ref.get_promise_obj_ref())
+            }
+            None => {
+                let promise_ref = promises::new_promise_q(q_ctx)?;
+                let done_ref =
+                    new_iterator_result_q(q_ctx, quickjs_utils::new_undefined_ref(), true)?;
+                promise_ref.resolve_q(q_ctx, done_ref)?;
+                Ok(promise_ref.get_promise_obj_ref())
+            }
+        },
+        0,
+    )?;
+    new_iterable_q(q_ctx, next_ref, "asyncIterator")
+}
+
+/// get the next item of an iterator, the RefCell is only borrowed while producing the item
+fn next_item<I: Iterator>(iter: &RefCell<I>) -> Result<Option<I::Item>, EsError> {
+    match iter.try_borrow_mut() {
+        Ok(mut iter) => Ok(iter.next()),
+        Err(_) => Err(EsError::new_str("iterator is already running")),
+    }
+}
+
+/// create an object with a next function which returns itself for [Symbol[symbol_name]]()
+fn new_iterable_q(
+    q_ctx: &QuickJsContext,
+    next_ref: JSValueRef,
+    symbol_name: &str,
+) -> Result<JSValueRef, EsError> {
+    let iterator_ref = objects::create_object_q(q_ctx)?;
+    let flags = (q::JS_PROP_WRITABLE | q::JS_PROP_CONFIGURABLE) as i32;
+    objects::set_property2_q(q_ctx, &iterator_ref, "next", &next_ref, flags)?;
+    let self_ref = functions::new_function_q(
+        q_ctx,
+        &format!("[Symbol.{}]", symbol_name),
+        |_q_ctx, this, _args| Ok(this),
+        0,
+    )?;
+    let symbol_ref = objects::get_well_known_symbol_q(q_ctx, symbol_name)?;
+    objects::set_symbol_property2_q(q_ctx, &iterator_ref, &symbol_ref, &self_ref, flags)?;
+    Ok(iterator_ref)
+}
+
+/// create an iterator result object, {value: value, done: done}
+fn new_iterator_result_q(
+    q_ctx: &QuickJsContext,
+    value: JSValueRef,
+    done: bool,
+) -> Result<JSValueRef, EsError> {
+    let result_ref = objects::create_object_q(q_ctx)?;
+    objects::set_property_q(q_ctx, &result_ref, "value", &value)?;
+    objects::set_property_q(q_ctx, &result_ref, "done", &primitives::from_bool(done))?;
+    Ok(result_ref)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::eserror::EsError;
+    use crate::esruntime::tests::init_test_rt;
+    use crate::esscript::EsScript;
+    use crate::esvalue::{EsPromise, EsValueConvertible};
+    use crate::quickjs_utils::{functions, get_global_q, iterators, objects};
+
+    #[test]
+    fn test_async_iterator() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            let items = vec![
+                Ok(1.to_es_value_facade()),
+                Ok(EsPromise::new(|| Ok(2.to_es_value_facade())).to_es_value_facade()),
+                Err(EsError::new_str("no more rows")),
+            ];
+            let iter_ref = iterators::new_async_iterator_q(q_ctx, items.into_iter())
+                .expect("could not create iterator");
+            let func_ref = q_ctx
+                .eval(EsScript::new(
+                    "test_async_iterator.es",
+                    "(async function(it){let res = []; try {for await (const i of it) {res.push(i);}} catch(ex) {res.push(ex.message);} return res.join(',');});",
+                ))
+                .expect("script failed");
+            let promise_ref = functions::call_function_q(q_ctx, &func_ref, vec![iter_ref], None)
+                .expect("call failed");
+            objects::set_property_q(
+                q_ctx,
+                &get_global_q(q_ctx),
+                "asyncIteratorResult",
+                &promise_ref,
+            )
+            .expect("set failed");
+        });
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_async_iterator2.es",
+                "asyncIteratorResult;",
+            ))
+            .expect("script failed");
+        let res = res.get_promise_result_sync().expect("promise failed");
+        assert_eq!(res.get_str(), "1,2,no more rows");
+    }
+}
@@ -6,7 +6,9 @@ pub mod bigints;
 pub mod compile;
 pub mod dates;
 pub mod errors;
+pub mod finalization;
 pub mod functions;
+pub mod inspect;
 pub mod iterators;
 pub mod json;
 pub mod maps;
@@ -34,14 +36,15 @@ pub fn gc(q_js_rt: &QuickJsRuntime) {
     log::trace!("GC done");
 }
 
+pub fn new_undefined() -> q::JSValue {
+    q::JSValue {
+        u: q::JSValueUnion { int32: 0 },
+        tag: TAG_UNDEFINED,
+    }
+}
+
 pub fn new_undefined_ref() -> JSValueRef {
-    JSValueRef::new_no_context(
-        q::JSValue {
-            u: q::JSValueUnion { int32: 0 },
-            tag: TAG_UNDEFINED,
-        },
-        "new_undefined_ref",
-    )
+    JSValueRef::new_no_context(new_undefined(), "new_undefined_ref")
 }
 
 pub fn new_null() -> q::JSValue {
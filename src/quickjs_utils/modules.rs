@@ -1,6 +1,7 @@
 //! utils for working with ES6 Modules
 
 use crate::eserror::EsError;
+use crate::esruntime_utils::source_maps;
 use crate::esscript::EsScript;
 use crate::quickjs_utils::atoms;
 use crate::quickjs_utils::atoms::JSAtomRef;
@@ -22,6 +23,8 @@ pub unsafe fn compile_module(
     let code_c = CString::new(code).ok().unwrap();
     let filename_c = CString::new(script.get_path()).ok().unwrap();
 
+    source_maps::register_script(&script);
+
     let value_raw = q::JS_Eval(
         context,
         code_c.as_ptr(),
@@ -171,6 +174,13 @@ unsafe extern "C" fn js_module_normalize(
         let q_ctx = q_js_rt.get_quickjs_context(ctx);
         for loader in &q_js_rt.module_loaders {
             if let Some(normalized_path) = loader.normalize_path(q_ctx, base_str, name_str) {
+                if let Err(e) = q_ctx
+                    .get_permissions()
+                    .check_module(normalized_path.as_str(), loader.is_native())
+                {
+                    q_ctx.report_es_error(&e);
+                    return ptr::null_mut();
+                }
                 let c_absolute_path = CString::new(normalized_path.as_str()).expect("fail");
 
                 return c_absolute_path.into_raw();
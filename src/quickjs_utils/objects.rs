@@ -7,6 +7,7 @@ use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::{make_cstring, QuickJsRuntime};
 use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 /// get a namespace object
@@ -118,6 +119,61 @@ pub unsafe fn create_object(context: *mut q::JSContext) -> Result<JSValueRef, Es
     Ok(obj_ref)
 }
 
+/// get the prototype of an object, like `Object.getPrototypeOf(obj);`
+pub fn get_prototype_q(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+) -> Result<JSValueRef, EsError> {
+    unsafe { get_prototype(q_ctx.context, obj_ref) }
+}
+
+/// get the prototype of an object, like `Object.getPrototypeOf(obj);`
+/// # Safety
+/// when passing a context ptr please be sure that the corresponding QuickJsContext is still active
+pub unsafe fn get_prototype(
+    context: *mut q::JSContext,
+    obj_ref: &JSValueRef,
+) -> Result<JSValueRef, EsError> {
+    let proto = q::JS_GetPrototype(context, *obj_ref.borrow_value());
+    let proto_ref = JSValueRef::new(context, proto, false, true, "objects::get_prototype");
+    if proto_ref.is_exception() {
+        return if let Some(err) = QuickJsContext::get_exception(context) {
+            Err(err)
+        } else {
+            Err(EsError::new_str("could not get prototype"))
+        };
+    }
+    Ok(proto_ref)
+}
+
+/// set the prototype of an object, like `Object.setPrototypeOf(obj, proto);`
+pub fn set_prototype_q(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+    proto_ref: &JSValueRef,
+) -> Result<(), EsError> {
+    unsafe { set_prototype(q_ctx.context, obj_ref, proto_ref) }
+}
+
+/// set the prototype of an object, like `Object.setPrototypeOf(obj, proto);`
+/// # Safety
+/// when passing a context ptr please be sure that the corresponding QuickJsContext is still active
+pub unsafe fn set_prototype(
+    context: *mut q::JSContext,
+    obj_ref: &JSValueRef,
+    proto_ref: &JSValueRef,
+) -> Result<(), EsError> {
+    let res = q::JS_SetPrototype(context, *obj_ref.borrow_value(), *proto_ref.borrow_value());
+    if res < 0 {
+        return if let Some(err) = QuickJsContext::get_exception(context) {
+            Err(err)
+        } else {
+            Err(EsError::new_str("could not set prototype"))
+        };
+    }
+    Ok(())
+}
+
 /// set a property in an object, like `obj[propName] = val;`
 pub fn set_property_q(
     q_ctx: &QuickJsContext,
@@ -228,6 +284,102 @@ pub unsafe fn set_property2(
     Ok(())
 }
 
+/// get a well-known Symbol by name, e.g. "iterator" for Symbol.iterator
+pub fn get_well_known_symbol_q(q_ctx: &QuickJsContext, name: &str) -> Result<JSValueRef, EsError> {
+    unsafe { get_well_known_symbol(q_ctx.context, name) }
+}
+
+/// get a well-known Symbol by name, e.g. "iterator" for Symbol.iterator
+///
+/// this uses the Symbol constructor the context was created with so scripts can not replace the symbols by overwriting globalThis.Symbol
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn get_well_known_symbol(
+    context: *mut q::JSContext,
+    name: &str,
+) -> Result<JSValueRef, EsError> {
+    let symbol_ctor_opt =
+        SYMBOL_CONSTRUCTORS.with(|rc| rc.borrow().get(&(context as usize)).cloned());
+    let symbol_ctor_ref = match symbol_ctor_opt {
+        Some(symbol_ctor_ref) => symbol_ctor_ref,
+        None => {
+            return Err(EsError::new_str(
+                "the Symbol constructor of the context was not initialized",
+            ))
+        }
+    };
+    let symbol_ref = get_property(context, &symbol_ctor_ref, name)?;
+    if symbol_ref.is_symbol() {
+        Ok(symbol_ref)
+    } else {
+        Err(EsError::new_string(format!(
+            "Symbol.{} is not a Symbol",
+            name
+        )))
+    }
+}
+
+thread_local! {
+    /// the intrinsic Symbol constructor per context, its well-known symbols are not writable and not configurable
+    static SYMBOL_CONSTRUCTORS: RefCell<HashMap<usize, JSValueRef>> = RefCell::new(HashMap::new());
+}
+
+/// keep the Symbol constructor of a new context, this is called before any script runs in the context
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub(crate) unsafe fn init_context(context: *mut q::JSContext) -> Result<(), EsError> {
+    let symbol_ctor_ref = get_property(context, &get_global(context), "Symbol")?;
+    SYMBOL_CONSTRUCTORS.with(|rc| {
+        rc.borrow_mut().insert(context as usize, symbol_ctor_ref);
+    });
+    Ok(())
+}
+
+/// drop the Symbol constructor of a context, this is called before the context is freed
+pub(crate) fn release_context(context: *mut q::JSContext) {
+    let symbol_ctor_opt =
+        SYMBOL_CONSTRUCTORS.with(|rc| rc.borrow_mut().remove(&(context as usize)));
+    drop(symbol_ctor_opt);
+}
+
+/// set a property whose key is a Symbol (e.g. Symbol.iterator) with specific flags
+pub fn set_symbol_property2_q(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+    symbol_ref: &JSValueRef,
+    prop_ref: &JSValueRef,
+    flags: i32,
+) -> Result<(), EsError> {
+    unsafe { set_symbol_property2(q_ctx.context, obj_ref, symbol_ref, prop_ref, flags) }
+}
+
+/// set a property whose key is a Symbol (e.g. Symbol.iterator) with specific flags
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn set_symbol_property2(
+    context: *mut q::JSContext,
+    obj_ref: &JSValueRef,
+    symbol_ref: &JSValueRef,
+    prop_ref: &JSValueRef,
+    flags: i32,
+) -> Result<(), EsError> {
+    if !symbol_ref.is_symbol() {
+        return Err(EsError::new_str("key is not a Symbol"));
+    }
+    let atom_ref = atoms::from_value(context, symbol_ref)?;
+    let ret = q::JS_DefinePropertyValue(
+        context,
+        *obj_ref.borrow_value(),
+        atom_ref.get_atom(),
+        prop_ref.clone_value_incr_rc(),
+        flags,
+    );
+    if ret < 0 {
+        return Err(EsError::new_str("Could not add property to object"));
+    }
+    Ok(())
+}
+
 /// define a getter/setter property
 /// # Example
 /// ```dontrun
@@ -318,7 +470,7 @@ pub unsafe fn define_getter_setter(
 
     log::trace!("objects::define_getter_setter 5 {}", res);
 
-    if res != 0 {
+    if res < 0 {
         if let Some(err) = QuickJsContext::get_exception(context) {
             Err(err)
         } else {
@@ -331,6 +483,41 @@ pub unsafe fn define_getter_setter(
     }
 }
 
+/// delete a property from an object, like `delete obj[propName];`
+pub fn delete_property_q(
+    q_ctx: &QuickJsContext,
+    obj_ref: &JSValueRef,
+    prop_name: &str,
+) -> Result<(), EsError> {
+    unsafe { delete_property(q_ctx.context, obj_ref, prop_name) }
+}
+
+/// delete a property from an object, like `delete obj[propName];`
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn delete_property(
+    context: *mut q::JSContext,
+    obj_ref: &JSValueRef,
+    prop_name: &str,
+) -> Result<(), EsError> {
+    let prop_atom = atoms::from_string(context, prop_name)?;
+    let res = q::JS_DeleteProperty(
+        context,
+        *obj_ref.borrow_value(),
+        prop_atom.get_atom(),
+        q::JS_PROP_THROW as i32,
+    );
+    if res < 0 {
+        if let Some(err) = QuickJsContext::get_exception(context) {
+            Err(err)
+        } else {
+            Err(EsError::new_str("could not delete property"))
+        }
+    } else {
+        Ok(())
+    }
+}
+
 /// get a property from an object by name
 pub fn get_property_q(
     q_ctx: &QuickJsContext,
@@ -590,7 +777,8 @@ pub mod tests {
     use crate::esruntime::tests::init_test_rt;
     use crate::esscript::EsScript;
     use crate::quickjs_utils::objects::{
-        create_object_q, get_property_names_q, get_property_q, set_property_q,
+        create_object_q, get_property_names_q, get_property_q, get_well_known_symbol_q,
+        set_property_q, set_symbol_property2_q,
     };
     use crate::quickjs_utils::primitives::{from_i32, to_i32};
     use crate::quickjs_utils::{get_global_q, primitives};
@@ -737,4 +925,35 @@ pub mod tests {
 
         log::info!("< test_set_prop");
     }
+
+    #[test]
+    fn test_well_known_symbol() {
+        let rt = init_test_rt();
+        let ok = rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            q_ctx
+                .eval(EsScript::new(
+                    "test_well_known_symbol.es",
+                    "this.__orig_symbol__ = Symbol; globalThis.Symbol = {iterator: Symbol('fake')};",
+                ))
+                .expect("script failed");
+
+            let obj = create_object_q(q_ctx).expect("could not create object");
+            let symbol_ref = get_well_known_symbol_q(q_ctx, "iterator")
+                .expect("could not get symbol");
+            set_symbol_property2_q(q_ctx, &obj, &symbol_ref, &from_i32(1), 0)
+                .expect("could not set property");
+            set_property_q(q_ctx, &get_global_q(q_ctx), "__sym_obj__", &obj)
+                .expect("could not set property");
+
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_well_known_symbol2.es",
+                    "let ok = __sym_obj__[__orig_symbol__.iterator] === 1; globalThis.Symbol = __orig_symbol__; ok;",
+                ))
+                .expect("script failed");
+            primitives::to_bool(&res).expect("not a bool")
+        });
+        assert!(ok);
+    }
 }
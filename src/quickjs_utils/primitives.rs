@@ -1,4 +1,4 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
 use crate::quickjscontext::QuickJsContext;
 use crate::valueref::JSValueRef;
 use core::ptr;
@@ -12,7 +12,10 @@ pub fn to_bool(value_ref: &JSValueRef) -> Result<bool, EsError> {
         let val: bool = raw > 0;
         Ok(val)
     } else {
-        Err(EsError::new_str("value is not a boolean"))
+        Err(EsError::new_kind(
+            EsErrorKind::Conversion,
+            "value is not a boolean".to_string(),
+        ))
     }
 }
 
@@ -27,7 +30,10 @@ pub fn to_f64(value_ref: &JSValueRef) -> Result<f64, EsError> {
         let val = unsafe { r.u.float64 };
         Ok(val)
     } else {
-        Err(EsError::new_str("value was not a float64"))
+        Err(EsError::new_kind(
+            EsErrorKind::Conversion,
+            "value was not a float64".to_string(),
+        ))
     }
 }
 
@@ -42,7 +48,10 @@ pub fn to_i32(value_ref: &JSValueRef) -> Result<i32, EsError> {
         let val: i32 = unsafe { r.u.int32 };
         Ok(val)
     } else {
-        Err(EsError::new_str("val is not an int"))
+        Err(EsError::new_kind(
+            EsErrorKind::Conversion,
+            "val is not an int".to_string(),
+        ))
     }
 }
 
@@ -69,8 +78,9 @@ pub unsafe fn to_string(
     let ptr: *const c_char = q::JS_ToCStringLen2(context, &mut len, *value_ref.borrow_value(), 0);
 
     if ptr.is_null() {
-        return Err(EsError::new_str(
-            "Could not convert string: got a null pointer",
+        return Err(EsError::new_kind(
+            EsErrorKind::Conversion,
+            "Could not convert string: got a null pointer".to_string(),
         ));
     }
 
@@ -1,13 +1,17 @@
 use crate::eserror::EsError;
+use crate::esruntime_utils::source_maps;
 use crate::esscript::EsScript;
+use crate::features::console;
+use crate::permissions::Permissions;
 use crate::quickjs_utils::{errors, functions, objects};
 use crate::quickjsruntime::{make_cstring, QuickJsRuntime};
+use crate::reflection;
 use crate::reflection::{Proxy, ProxyInstanceInfo};
 use crate::utils::auto_id_map::AutoIdMap;
 use crate::valueref::{JSValueRef, TAG_EXCEPTION};
 use libquickjs_sys as q;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::os::raw::c_void;
 use std::rc::Rc;
@@ -16,6 +20,12 @@ pub struct QuickJsContext {
     object_cache: RefCell<AutoIdMap<JSValueRef>>,
     pub(crate) instance_id_mappings: RefCell<HashMap<usize, Box<ProxyInstanceInfo>>>,
     pub(crate) proxy_registry: RefCell<HashMap<String, Rc<Proxy>>>, // todo is this Rc needed or can we just borrow the Proxy when needed?
+    // the global names of ProxyRegistry classes which are not installed yet (see reflection::ProxyRegistry)
+    pub(crate) lazy_proxy_roots: RefCell<HashSet<String>>,
+    // the helper functions of quickjs_utils::finalization, created when first used
+    pub(crate) finalization_helper: RefCell<Option<JSValueRef>>,
+    error_classes: RefCell<HashMap<String, JSValueRef>>,
+    permissions: RefCell<Rc<Permissions>>,
     pub id: String,
     pub context: *mut q::JSContext,
 }
@@ -26,6 +36,13 @@ thread_local! {
 
 impl QuickJsContext {
     pub(crate) fn free(&self) {
+        // cached objects are released while the context is still registered, the finalizers of Proxy instances need it
+        let cached_objects = self.object_cache.borrow_mut().remove_values(|_v| true);
+        drop(cached_objects);
+        reflection::release_class_refs(self);
+        drop(self.finalization_helper.borrow_mut().take());
+        errors::release_context(self.context);
+        objects::release_context(self.context);
         unsafe { q::JS_FreeContext(self.context) };
     }
     pub(crate) fn new(id: String, q_js_rt: &QuickJsRuntime) -> Self {
@@ -47,14 +64,28 @@ impl QuickJsContext {
             panic!("ContextCreationFailed");
         }
 
+        unsafe { objects::init_context(context) }.expect("could not init context");
+
         Self {
             id,
             context,
             object_cache: RefCell::new(AutoIdMap::new_with_max_size(i32::MAX as usize)),
             instance_id_mappings: RefCell::new(HashMap::new()),
             proxy_registry: RefCell::new(HashMap::new()),
+            lazy_proxy_roots: RefCell::new(HashSet::new()),
+            finalization_helper: RefCell::new(None),
+            error_classes: RefCell::new(HashMap::new()),
+            permissions: RefCell::new(Rc::new(q_js_rt.default_permissions.clone())),
         }
     }
+    /// get the [Permissions](../permissions/struct.Permissions.html) of this context
+    pub fn get_permissions(&self) -> Rc<Permissions> {
+        self.permissions.borrow().clone()
+    }
+    /// set the [Permissions](../permissions/struct.Permissions.html) of this context
+    pub fn set_permissions(&self, permissions: Permissions) {
+        self.permissions.replace(Rc::new(permissions));
+    }
     /// get the id of a QuickJsContext from a JSContext
     /// # Safety
     /// when passing a context ptr please be sure that the corresponding QuickJsContext is still active
@@ -84,11 +115,14 @@ impl QuickJsContext {
         context: *mut q::JSContext,
         script: EsScript,
     ) -> Result<JSValueRef, EsError> {
+        let script = QuickJsRuntime::pre_process(script)?;
         let filename_c = make_cstring(script.get_path())?;
         let code_c = make_cstring(script.get_code())?;
 
         log::debug!("q_js_rt.eval file {}", script.get_path());
 
+        source_maps::register_script(&script);
+
         let value_raw = q::JS_Eval(
             context,
             code_c.as_ptr(),
@@ -132,9 +166,13 @@ impl QuickJsContext {
     ) -> Result<JSValueRef, EsError> {
         log::debug!("q_js_rt.eval_module file {}", script.get_path());
 
+        let script = QuickJsRuntime::pre_process(script)?;
+
         let filename_c = make_cstring(script.get_path())?;
         let code_c = make_cstring(script.get_code())?;
 
+        source_maps::register_script(&script);
+
         let value_raw = q::JS_Eval(
             context,
             code_c.as_ptr(),
@@ -184,6 +222,40 @@ impl QuickJsContext {
         }
     }
 
+    /// throw an EsError as a JavaScript Error and init an Exception JSValue to return
+    /// the Error class is determined by the name and kind of the EsError (see [errors::new_error_from](../quickjs_utils/errors/fn.new_error_from.html))
+    pub fn report_es_error(&self, error: &EsError) -> q::JSValue {
+        unsafe { Self::report_es_error_ctx(self.context, error) }
+    }
+    /// throw an EsError as a JavaScript Error and init an Exception JSValue to return
+    /// # Safety
+    /// when passing a context ptr please be sure that the corresponding QuickJsContext is still active
+    pub unsafe fn report_es_error_ctx(context: *mut q::JSContext, error: &EsError) -> q::JSValue {
+        errors::throw_es_error(context, error)
+    }
+
+    /// register a custom Error class, when an EsError with the same name is thrown from rust an instance of this class is created
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::esscript::EsScript;
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     let class_ref = q_ctx.eval(EsScript::new("my_error.es", "(class MyError extends Error {});")).ok().unwrap();
+    ///     q_ctx.register_error_class("MyError", class_ref);
+    /// });
+    /// ```
+    pub fn register_error_class(&self, name: &str, constructor: JSValueRef) {
+        let error_classes = &mut *self.error_classes.borrow_mut();
+        error_classes.insert(name.to_string(), constructor);
+    }
+
+    pub(crate) fn get_error_class(&self, name: &str) -> Option<JSValueRef> {
+        let error_classes = &*self.error_classes.borrow();
+        error_classes.get(name).cloned()
+    }
+
     /// Get the last exception from the runtime, and if present, convert it to a EsError.
     pub fn get_exception_ctx(&self) -> Option<EsError> {
         unsafe { errors::get_exception(self.context) }
@@ -242,6 +314,7 @@ impl Drop for QuickJsContext {
                 let registry = &mut *rc.borrow_mut();
                 registry.remove(id);
             });
+            console::remove_state(id);
         }
         {
             let cache_map = &mut *self.object_cache.borrow_mut();
@@ -251,6 +324,10 @@ impl Drop for QuickJsContext {
             let proxies = &mut *self.proxy_registry.borrow_mut();
             proxies.clear();
         }
+        {
+            let error_classes = &mut *self.error_classes.borrow_mut();
+            error_classes.clear();
+        }
         {
             let id_mappings = &mut *self.instance_id_mappings.borrow_mut();
             id_mappings.clear();
@@ -2,13 +2,19 @@
 
 use crate::eserror::EsError;
 use crate::esruntime::EsRuntime;
+use crate::esruntime_utils::source_maps;
 use crate::esscript::EsScript;
+use crate::features::console::ConsoleSink;
+use crate::features::lockdown::LockdownOptions;
+use crate::permissions::Permissions;
+use crate::preprocessors::ScriptPreProcessor;
 use crate::quickjs_utils::modules::{
     add_module_export, compile_module, get_module_def, get_module_name, new_module,
     set_module_export,
 };
 use crate::quickjs_utils::{gc, modules, promises};
 use crate::quickjscontext::QuickJsContext;
+use crate::reflection::ProxyRegistry;
 use crate::valueref::JSValueRef;
 use libquickjs_sys as q;
 use std::cell::RefCell;
@@ -16,6 +22,7 @@ use std::collections::HashMap;
 use std::ffi::CString;
 use std::os::raw::c_int;
 use std::panic;
+use std::rc::Rc;
 use std::sync::{Arc, Weak};
 
 /// this is the internal abstract loader which is used to actually load the modules
@@ -39,6 +46,10 @@ pub trait ModuleLoader {
         q_ctx: &QuickJsContext,
         module: *mut q::JSModuleDef,
     ) -> Result<(), EsError>;
+    /// native modules are checked by name against the [Permissions](../permissions/struct.Permissions.html) of a context, script modules by their normalized path
+    fn is_native(&self) -> bool {
+        false
+    }
 }
 
 // these are the external (util) loaders (todo move these to esruntime?)
@@ -46,6 +57,11 @@ pub trait ModuleLoader {
 pub trait ScriptModuleLoader {
     fn normalize_path(&self, ref_path: &str, path: &str) -> Option<String>;
     fn load_module(&self, absolute_path: &str) -> String;
+    /// load the source map for a module which has a `//# sourceMappingURL=` comment which is not a data url
+    /// the url is passed as it is found in the module source
+    fn load_source_map(&self, _absolute_path: &str, _source_mapping_url: &str) -> Option<String> {
+        None
+    }
 }
 
 pub struct ScriptModuleLoaderAdapter {
@@ -74,8 +90,16 @@ impl ModuleLoader for ScriptModuleLoaderAdapter {
         absolute_path: &str,
     ) -> Result<*mut q::JSModuleDef, EsError> {
         let code = self.inner.load_module(absolute_path);
-        let compiled_module =
-            unsafe { compile_module(q_ctx.context, EsScript::new(absolute_path, code.as_str()))? };
+        let mut script = EsScript::new(absolute_path, code.as_str());
+        if let Some(url) = source_maps::find_source_mapping_url(code.as_str()) {
+            if !url.starts_with("data:") {
+                if let Some(map) = self.inner.load_source_map(absolute_path, url) {
+                    script = script.with_source_map(map.as_str());
+                }
+            }
+        }
+        let script = QuickJsRuntime::pre_process(script)?;
+        let compiled_module = unsafe { compile_module(q_ctx.context, script)? };
         Ok(get_module_def(&compiled_module))
     }
 
@@ -149,6 +173,10 @@ impl ModuleLoader for NativeModuleLoaderAdapter {
         }
         Ok(())
     }
+
+    fn is_native(&self) -> bool {
+        true
+    }
 }
 
 unsafe extern "C" fn native_module_init(
@@ -207,6 +235,9 @@ thread_local! {
 
 }
 
+/// the id of the main context which every runtime has
+pub const MAIN_CONTEXT_ID: &str = "__main__";
+
 pub type ContextInitHooks =
     Vec<Box<dyn Fn(&QuickJsRuntime, &QuickJsContext) -> Result<(), EsError>>>;
 
@@ -217,6 +248,11 @@ pub struct QuickJsRuntime {
     id: String,
     context_init_hooks: RefCell<ContextInitHooks>,
     pub(crate) module_loaders: Vec<Box<dyn ModuleLoader>>,
+    pub(crate) script_pre_processors: Vec<Box<dyn ScriptPreProcessor>>,
+    pub(crate) console_sink: Option<Rc<dyn ConsoleSink>>,
+    pub(crate) opt_lockdown: Option<LockdownOptions>,
+    pub(crate) default_permissions: Permissions,
+    pub(crate) proxy_registry: ProxyRegistry,
 }
 
 impl QuickJsRuntime {
@@ -337,20 +373,25 @@ impl QuickJsRuntime {
             id,
             context_init_hooks: RefCell::new(vec![]),
             module_loaders: vec![],
+            script_pre_processors: vec![],
+            console_sink: None,
+            opt_lockdown: None,
+            default_permissions: Permissions::allow_all(),
+            proxy_registry: ProxyRegistry::new(),
         };
 
         modules::set_module_loader(&q_rt);
         promises::init_promise_rejection_tracker(&q_rt);
 
-        let main_ctx = QuickJsContext::new("__main__".to_string(), &q_rt);
-        q_rt.contexts.insert("__main__".to_string(), main_ctx);
+        let main_ctx = QuickJsContext::new(MAIN_CONTEXT_ID.to_string(), &q_rt);
+        q_rt.contexts.insert(MAIN_CONTEXT_ID.to_string(), main_ctx);
 
         q_rt
     }
 
     pub fn get_main_context(&self) -> &QuickJsContext {
         // todo store this somewhere so we don't need a lookup in the map every time
-        self.get_context("__main__")
+        self.get_context(MAIN_CONTEXT_ID)
     }
 
     /// run the garbage collector
@@ -358,6 +399,29 @@ impl QuickJsRuntime {
         gc(self);
     }
 
+    /// get the memory usage statistics of the quickjs runtime
+    pub fn memory_usage(&self) -> q::JSMemoryUsage {
+        unsafe {
+            let mut usage = std::mem::MaybeUninit::<q::JSMemoryUsage>::uninit();
+            q::JS_ComputeMemoryUsage(self.runtime, usage.as_mut_ptr());
+            usage.assume_init()
+        }
+    }
+
+    /// run the ScriptPreProcessors of the runtime of the current thread for a script
+    pub(crate) fn pre_process(mut script: EsScript) -> Result<EsScript, EsError> {
+        QJS_RT.with(|qjs_rc| {
+            if let Ok(opt) = qjs_rc.try_borrow() {
+                if let Some(q_js_rt) = &*opt {
+                    for pre_processor in &q_js_rt.script_pre_processors {
+                        pre_processor.process(&mut script)?;
+                    }
+                }
+            }
+            Ok(script)
+        })
+    }
+
     pub fn do_with<C, R>(task: C) -> R
     where
         C: FnOnce(&QuickJsRuntime) -> R,
@@ -1,26 +1,44 @@
-use crate::eserror::EsError;
+use crate::eserror::{EsError, EsErrorKind};
+use crate::esruntime_utils;
+use crate::esvalue::EsValueFacade;
+use crate::features::lockdown;
 use crate::quickjs_utils;
-use crate::quickjs_utils::functions::new_native_function_q;
-use crate::quickjs_utils::primitives::from_string;
-use crate::quickjs_utils::{atoms, errors, functions, objects, parse_args, primitives};
+use crate::quickjs_utils::functions::{new_native_function_data_q, new_native_function_q};
+use crate::quickjs_utils::{
+    arrays, atoms, errors, functions, iterators, objects, parse_args, primitives,
+};
 use crate::quickjscontext::QuickJsContext;
 use crate::quickjsruntime::QuickJsRuntime;
 use crate::valueref::JSValueRef;
+use futures::executor::block_on;
 use libquickjs_sys as q;
 use log::trace;
 use rand::{thread_rng, Rng};
+use std::any::{Any, TypeId};
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
 use std::os::raw::{c_char, c_void};
 use std::rc::Rc;
 
-mod eventtarget;
+pub(crate) mod eventtarget;
 
 pub type ProxyConstructor =
     dyn Fn(&QuickJsContext, usize, Vec<JSValueRef>) -> Result<(), EsError> + 'static;
+pub type ProxyNewTargetConstructor =
+    dyn Fn(&QuickJsContext, usize, &JSValueRef, Vec<JSValueRef>) -> Result<(), EsError> + 'static;
 pub type ProxyFinalizer = dyn Fn(&QuickJsContext, usize) + 'static;
+pub type ProxyGcMark = dyn Fn(&usize, &GcMarker) + 'static;
+// the gc_mark functions are stored as closures which also receive the value of a DataProxy instance
+pub(crate) type ProxyInstanceGcMark = dyn Fn(&usize, Option<&Rc<dyn Any>>, &GcMarker) + 'static;
+pub(crate) type ProxyDataConstructor = dyn Fn(&QuickJsContext, usize, &JSValueRef, Vec<JSValueRef>) -> Result<Rc<dyn Any>, EsError>
+    + 'static;
 pub type ProxyMethod =
     dyn Fn(&QuickJsContext, &usize, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static;
+// the methods of instances are stored as closures which also receive the instance itself (this)
+pub(crate) type ProxyInstanceMethod = dyn Fn(&QuickJsContext, &JSValueRef, &usize, Vec<JSValueRef>) -> Result<JSValueRef, EsError>
+    + 'static;
 pub type ProxyNativeMethod = q::JSCFunction;
 pub type ProxyStaticMethod =
     dyn Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static;
@@ -29,44 +47,39 @@ pub type ProxyStaticGetter = dyn Fn(&QuickJsContext) -> Result<JSValueRef, EsErr
 pub type ProxyStaticSetter = dyn Fn(&QuickJsContext, JSValueRef) -> Result<(), EsError> + 'static;
 pub type ProxyGetter = dyn Fn(&QuickJsContext, &usize) -> Result<JSValueRef, EsError> + 'static;
 pub type ProxySetter = dyn Fn(&QuickJsContext, &usize, JSValueRef) -> Result<(), EsError> + 'static;
+pub type ProxyPropertyGetter =
+    dyn Fn(&QuickJsContext, &usize, &str) -> Result<Option<JSValueRef>, EsError> + 'static;
+pub type ProxyPropertySetter =
+    dyn Fn(&QuickJsContext, &usize, &str, JSValueRef) -> Result<bool, EsError> + 'static;
+pub type ProxyPropertyPredicate =
+    dyn Fn(&QuickJsContext, &usize, &str) -> Result<bool, EsError> + 'static;
+pub type ProxyPropertyKeys =
+    dyn Fn(&QuickJsContext, &usize) -> Result<Vec<String>, EsError> + 'static;
 
 static CNAME: &str = "ProxyInstanceClass\0";
 static SCNAME: &str = "ProxyStaticClass\0";
 
 thread_local! {
 
-    static PROXY_STATIC_EXOTIC: RefCell<q::JSClassExoticMethods> = RefCell::new(q::JSClassExoticMethods {
-        get_own_property: None,
-        get_own_property_names: None,
-        delete_property: None,
-        define_own_property: None,
-        has_property: Some(proxy_static_has_prop),
-        get_property: Some(proxy_static_get_prop),
-        set_property: Some(proxy_static_set_prop),
-    });
-
+    // the members of a Proxy class are defined on the prototype of the class (or on the class itself for static members)
+    // the instances only report the getters/setters and the properties of a ProxyPropertyHandler as their own properties
     static PROXY_INSTANCE_EXOTIC: RefCell<q::JSClassExoticMethods> = RefCell::new(q::JSClassExoticMethods {
-        get_own_property: None,
-        get_own_property_names: None,
-        delete_property: None,
-        define_own_property: None,
-        has_property: Some(proxy_instance_has_prop),
-        get_property: Some(proxy_instance_get_prop),
-        set_property: Some(proxy_instance_set_prop),
+        get_own_property: Some(proxy_instance_get_own_prop),
+        get_own_property_names: Some(proxy_instance_get_own_prop_names),
+        delete_property: Some(proxy_instance_delete_prop),
+        define_own_property: Some(proxy_instance_define_own_prop),
+        has_property: None,
+        get_property: None,
+        set_property: None,
     });
 
-    static PROXY_STATIC_CLASS_DEF: RefCell<q::JSClassDef> = {
-        PROXY_STATIC_EXOTIC.with(|e_rc|{
-            let exotic = &mut *e_rc.borrow_mut();
-            RefCell::new(q::JSClassDef {
-                class_name: SCNAME.as_ptr() as *const c_char,
-                finalizer: None,
-                gc_mark: None,
-                call: None,
-                exotic,
-            })
-        })
-    };
+    static PROXY_STATIC_CLASS_DEF: RefCell<q::JSClassDef> = RefCell::new(q::JSClassDef {
+        class_name: SCNAME.as_ptr() as *const c_char,
+        finalizer: None,
+        gc_mark: None,
+        call: None,
+        exotic: std::ptr::null_mut(),
+    });
 
     static PROXY_INSTANCE_CLASS_DEF: RefCell<q::JSClassDef> = {
         PROXY_INSTANCE_EXOTIC.with(|e_rc|{
@@ -74,7 +87,7 @@ thread_local! {
             RefCell::new(q::JSClassDef {
                 class_name: CNAME.as_ptr() as *const c_char,
                 finalizer: Some(finalizer),
-                gc_mark: None,
+                gc_mark: Some(gc_mark),
                 call: None,
                 exotic,
             })
@@ -216,19 +229,33 @@ fn next_id(q_ctx: &QuickJsContext) -> usize {
 /// rt.gc_sync();
 ///
 /// ```
+/// see [Proxy::with_data](#method.with_data) for a Proxy which stores a struct per instance without a thread_local map
 pub struct Proxy {
     name: Option<String>,
     namespace: Option<Vec<String>>,
-    constructor: Option<Box<ProxyConstructor>>,
+    parent_class_name: Option<String>,
+    constructor: Option<Box<ProxyNewTargetConstructor>>,
+    data_constructor: Option<Box<ProxyDataConstructor>>,
+    // the type of the value of the instances of a DataProxy class
+    data_type: Option<TypeId>,
     finalizer: Option<Box<ProxyFinalizer>>,
-    methods: HashMap<String, Box<ProxyMethod>>,
+    gc_mark: Option<Box<ProxyInstanceGcMark>>,
+    methods: HashMap<String, Box<ProxyInstanceMethod>>,
     native_methods: HashMap<String, ProxyNativeMethod>,
     static_methods: HashMap<String, Box<ProxyStaticMethod>>,
     static_native_methods: HashMap<String, ProxyStaticNativeMethod>,
     static_getters_setters: HashMap<String, (Box<ProxyStaticGetter>, Box<ProxyStaticSetter>)>,
     getters_setters: HashMap<String, (Box<ProxyGetter>, Box<ProxySetter>)>,
+    // methods keyed by the name of a well-known Symbol, e.g. "iterator" for [Symbol.iterator]
+    symbol_methods: HashMap<String, Box<ProxyInstanceMethod>>,
+    static_symbol_methods: HashMap<String, Box<ProxyStaticMethod>>,
+    to_string_tag: Option<String>,
+    property_handler: Option<ProxyPropertyHandler>,
     is_event_target: bool,
     is_static_event_target: bool,
+    // the constructor function and the prototype of instances, these are set when the Proxy is installed
+    constructor_ref: RefCell<Option<JSValueRef>>,
+    prototype_ref: RefCell<Option<JSValueRef>>,
 }
 
 impl Default for crate::reflection::Proxy {
@@ -237,10 +264,292 @@ impl Default for crate::reflection::Proxy {
     }
 }
 
+/// release the constructors and prototypes of the Proxy classes of a context
+///
+/// the functions of a class keep the context alive, so these are released before the context is freed, that way the instances are finalized while the context still exists
+pub(crate) fn release_class_refs(q_ctx: &QuickJsContext) {
+    let refs: Vec<(Option<JSValueRef>, Option<JSValueRef>)> = {
+        let registry = &*q_ctx.proxy_registry.borrow();
+        registry
+            .values()
+            .map(|proxy| {
+                (
+                    proxy.constructor_ref.borrow_mut().take(),
+                    proxy.prototype_ref.borrow_mut().take(),
+                )
+            })
+            .collect()
+    };
+    drop(refs);
+}
+
 /// get a proxy by class_name (namespace.ClassName)
+/// classes of the [ProxyRegistry] of the runtime are installed in the context when they are not installed yet
 pub fn get_proxy(q_ctx: &QuickJsContext, class_name: &str) -> Option<Rc<Proxy>> {
-    let registry = &*q_ctx.proxy_registry.borrow();
-    registry.get(class_name).cloned()
+    let opt_proxy = {
+        let registry = &*q_ctx.proxy_registry.borrow();
+        registry.get(class_name).cloned()
+    };
+    if opt_proxy.is_some() {
+        return opt_proxy;
+    }
+    match install_registered_proxy_q(q_ctx, class_name) {
+        Ok(opt_proxy) => opt_proxy,
+        Err(e) => {
+            log::error!("could not install registered Proxy {}: {}", class_name, e);
+            None
+        }
+    }
+}
+
+pub type ProxyFactory = dyn Fn() -> Proxy + Send + 'static;
+
+struct RegisteredProxy {
+    class_name: String,
+    namespace: Option<Vec<String>>,
+    name: String,
+    // the name of the global which holds the class, the class name or the first part of its namespace
+    root: String,
+    factory: Box<ProxyFactory>,
+}
+
+impl RegisteredProxy {
+    /// create the Proxy with the registered namespace and name
+    fn create(&self) -> Proxy {
+        let mut proxy = (self.factory)();
+        proxy.namespace = self.namespace.clone();
+        proxy.name = Some(self.name.clone());
+        proxy
+    }
+}
+
+/// the ProxyRegistry declares Proxy classes once for all contexts of an EsRuntime, see [EsRuntimeBuilder::proxy_registry](../esruntimebuilder/struct.EsRuntimeBuilder.html#method.proxy_registry)
+///
+/// the classes are installed in a context when they are first used, either from script or by [get_proxy], this includes contexts which are created later with [EsRuntime::create_context](../esruntime/struct.EsRuntime.html#method.create_context)
+/// in a locked down runtime the classes are installed when the context is created so the lockdown hardens them
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// use quickjs_runtime::reflection::{Proxy, ProxyRegistry};
+/// use quickjs_runtime::quickjs_utils::primitives;
+/// let registry = ProxyRegistry::new().proxy(vec!["com", "hirofa"], "Answer", || {
+///     Proxy::new().static_method("get", |_q_ctx, _args| Ok(primitives::from_i32(42)))
+/// });
+/// let rt = EsRuntimeBuilder::new().proxy_registry(registry).build();
+/// rt.create_context("other_ctx").expect("could not create context");
+/// let res = rt.eval_sync(EsScript::new("test_registry.es", "com.hirofa.Answer.get();")).expect("script failed");
+/// assert_eq!(res.get_i32(), 42);
+/// let res = rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_context("other_ctx");
+///     let res = q_ctx.eval(EsScript::new("test_registry2.es", "com.hirofa.Answer.get();")).expect("script failed");
+///     primitives::to_i32(&res).ok().unwrap()
+/// });
+/// assert_eq!(res, 42);
+/// ```
+pub struct ProxyRegistry {
+    classes: Vec<RegisteredProxy>,
+}
+
+impl ProxyRegistry {
+    pub fn new() -> Self {
+        Self { classes: vec![] }
+    }
+    /// declare a Proxy class with a namespace (which may be empty) and a name, the factory is called every time the class is installed in a context
+    ///
+    /// the namespace and name are set on the Proxy which is created by the factory, a class with the same namespace and name replaces an earlier declaration
+    pub fn proxy<F>(mut self, namespace: Vec<&str>, name: &str, factory: F) -> Self
+    where
+        F: Fn() -> Proxy + Send + 'static,
+    {
+        let namespace: Vec<String> = namespace.into_iter().map(|ns| ns.to_string()).collect();
+        let root = namespace
+            .first()
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
+        let class_name = if namespace.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", namespace.join("."), name)
+        };
+        self.classes.retain(|rp| rp.class_name != class_name);
+        self.classes.push(RegisteredProxy {
+            class_name,
+            namespace: Some(namespace).filter(|ns| !ns.is_empty()),
+            name: name.to_string(),
+            root,
+            factory: Box::new(factory),
+        });
+        self
+    }
+    /// declare a DataProxy class, see [proxy](#method.proxy)
+    pub fn data_proxy<T: 'static, F>(self, namespace: Vec<&str>, name: &str, factory: F) -> Self
+    where
+        F: Fn() -> DataProxy<T> + Send + 'static,
+    {
+        self.proxy(namespace, name, move || factory().proxy)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+    fn get_registered(&self, class_name: &str) -> Option<&RegisteredProxy> {
+        self.classes.iter().find(|rp| rp.class_name == class_name)
+    }
+    fn get_roots(&self) -> Vec<String> {
+        let mut roots: Vec<String> = vec![];
+        for rp in &self.classes {
+            if !roots.contains(&rp.root) {
+                roots.push(rp.root.clone());
+            }
+        }
+        roots
+    }
+    fn get_class_names(&self, root: &str) -> Vec<String> {
+        self.classes
+            .iter()
+            .filter(|rp| rp.root == root)
+            .map(|rp| rp.class_name.clone())
+            .collect()
+    }
+}
+
+impl Default for ProxyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// add a context init hook which declares the classes of the ProxyRegistry of the runtime in every context
+pub(crate) fn init(q_js_rt: &QuickJsRuntime) -> Result<(), EsError> {
+    if q_js_rt.proxy_registry.is_empty() {
+        return Ok(());
+    }
+    q_js_rt.add_context_init_hook(declare_registered_proxies_q)
+}
+
+/// define a lazy getter/setter on the global object for every root of the ProxyRegistry, the first access installs the classes of that root
+fn declare_registered_proxies_q(
+    q_js_rt: &QuickJsRuntime,
+    q_ctx: &QuickJsContext,
+) -> Result<(), EsError> {
+    let global = quickjs_utils::get_global_q(q_ctx);
+    for root in q_js_rt.proxy_registry.get_roots() {
+        // a locked down global can not be altered later, and a root which already exists (e.g. a shared namespace) can not be replaced by a getter
+        if q_js_rt.opt_lockdown.is_some()
+            || !objects::get_property_q(q_ctx, &global, root.as_str())?.is_undefined()
+        {
+            install_root_classes_q(q_ctx, root.as_str())?;
+            continue;
+        }
+
+        let getter_root = root.clone();
+        let getter = functions::new_function_q(
+            q_ctx,
+            root.as_str(),
+            move |q_ctx, _this, _args| {
+                install_registered_root_q(q_ctx, getter_root.as_str())?;
+                let global = quickjs_utils::get_global_q(q_ctx);
+                objects::get_property_q(q_ctx, &global, getter_root.as_str())
+            },
+            0,
+        )?;
+        let setter_root = root.clone();
+        let setter = functions::new_function_q(
+            q_ctx,
+            root.as_str(),
+            move |q_ctx, _this, args| {
+                install_registered_root_q(q_ctx, setter_root.as_str())?;
+                let global = quickjs_utils::get_global_q(q_ctx);
+                let value = args
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(quickjs_utils::new_undefined_ref);
+                objects::set_property_q(q_ctx, &global, setter_root.as_str(), &value)?;
+                Ok(quickjs_utils::new_undefined_ref())
+            },
+            1,
+        )?;
+        objects::define_getter_setter_q(q_ctx, &global, root.as_str(), &getter, &setter)?;
+        q_ctx.lazy_proxy_roots.borrow_mut().insert(root);
+    }
+    Ok(())
+}
+
+/// install the classes of a root of the ProxyRegistry in a context if the root is still lazy, the lazy getter/setter of the root is removed first
+fn install_registered_root_q(q_ctx: &QuickJsContext, root: &str) -> Result<(), EsError> {
+    let was_lazy = q_ctx.lazy_proxy_roots.borrow_mut().remove(root);
+    if was_lazy {
+        let global = quickjs_utils::get_global_q(q_ctx);
+        objects::delete_property_q(q_ctx, &global, root)?;
+        install_root_classes_q(q_ctx, root)?;
+    }
+    Ok(())
+}
+
+fn install_root_classes_q(q_ctx: &QuickJsContext, root: &str) -> Result<(), EsError> {
+    let class_names =
+        QuickJsRuntime::do_with(|q_js_rt| q_js_rt.proxy_registry.get_class_names(root));
+    for class_name in class_names {
+        if get_proxy(q_ctx, class_name.as_str()).is_none() {
+            return Err(EsError::new_string(format!(
+                "could not install Proxy {}",
+                class_name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// install a class of the ProxyRegistry in a context, returns None if the class is not registered
+fn install_registered_proxy_q(
+    q_ctx: &QuickJsContext,
+    class_name: &str,
+) -> Result<Option<Rc<Proxy>>, EsError> {
+    // the factory is only called when the class is not installed as part of a lazy root
+    let opt_registered = QuickJsRuntime::do_with(|q_js_rt| {
+        q_js_rt.proxy_registry.get_registered(class_name).map(|rp| {
+            if q_ctx.lazy_proxy_roots.borrow().contains(&rp.root) {
+                (rp.root.clone(), None)
+            } else {
+                (rp.root.clone(), Some(rp.create()))
+            }
+        })
+    });
+    if let Some((root, opt_proxy)) = opt_registered {
+        if let Some(proxy) = opt_proxy {
+            proxy.install(q_ctx, true)?;
+        } else {
+            // this installs all classes of the root, including this class
+            install_registered_root_q(q_ctx, root.as_str())?;
+        }
+        let registry = &*q_ctx.proxy_registry.borrow();
+        Ok(registry.get(class_name).cloned())
+    } else {
+        Ok(None)
+    }
+}
+
+/// create a Promise which is resolved with the result of a Future, the Future is run in a helper thread
+fn new_future_promise<F>(
+    q_ctx: &QuickJsContext,
+    future: F,
+    keep_alive: Vec<JSValueRef>,
+) -> Result<JSValueRef, EsError>
+where
+    F: Future<Output = Result<EsValueFacade, EsError>> + Send + 'static,
+{
+    let es_rt = QuickJsRuntime::do_with(|q_js_rt| q_js_rt.get_rt_ref())
+        .ok_or_else(EsError::runtime_dropped)?;
+    esruntime_utils::promises::new_resolving_promise2(
+        q_ctx,
+        move || Ok(block_on(future)),
+        |q_ctx, result| {
+            let mut value = result?;
+            value.as_js_value(q_ctx)
+        },
+        &es_rt,
+        keep_alive,
+    )
 }
 
 impl Proxy {
@@ -249,16 +558,36 @@ impl Proxy {
         Proxy {
             name: None,
             namespace: None,
+            parent_class_name: None,
+            data_type: None,
             constructor: None,
+            data_constructor: None,
             finalizer: None,
+            gc_mark: None,
             methods: Default::default(),
             native_methods: Default::default(),
             static_methods: Default::default(),
             static_native_methods: Default::default(),
             static_getters_setters: Default::default(),
             getters_setters: Default::default(),
+            symbol_methods: Default::default(),
+            static_symbol_methods: Default::default(),
+            to_string_tag: None,
+            property_handler: None,
             is_event_target: false,
             is_static_event_target: false,
+            constructor_ref: RefCell::new(None),
+            prototype_ref: RefCell::new(None),
+        }
+    }
+
+    /// create a Proxy class whose instances each own a value of type T, see [DataProxy]
+    pub fn with_data<T: 'static>() -> DataProxy<T> {
+        let mut proxy = Proxy::new();
+        proxy.data_type = Some(TypeId::of::<T>());
+        DataProxy {
+            proxy,
+            _data_type: PhantomData,
         }
     }
 
@@ -307,10 +636,62 @@ impl Proxy {
     pub fn constructor<C>(mut self, constructor: C) -> Self
     where
         C: Fn(&QuickJsContext, usize, Vec<JSValueRef>) -> Result<(), EsError> + 'static,
+    {
+        self.constructor = Some(Box::new(move |q_ctx, instance_id, _new_target, args| {
+            constructor(q_ctx, instance_id, args)
+        }));
+        self
+    }
+    /// add a constructor for the Proxy class which also receives new.target
+    /// new.target is the Proxy class itself unless the class was extended in script (class Sub extends ProxyClass {...}), in that case it is the subclass
+    pub fn constructor_with_new_target<C>(mut self, constructor: C) -> Self
+    where
+        C: Fn(&QuickJsContext, usize, &JSValueRef, Vec<JSValueRef>) -> Result<(), EsError>
+            + 'static,
     {
         self.constructor = Some(Box::new(constructor));
         self
     }
+    /// extend another Proxy class, the parent class has to be installed in the same context before this class is installed
+    ///
+    /// instances of this class inherit the methods and getters/setters of the parent class (and its parents), the class inherits the static members of the parent class
+    /// and instances of this class are also instanceof the parent class
+    ///
+    /// if this class has no constructor the constructor of the parent class is used, the finalizers of this class and its parent classes are all called
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::quickjs_utils::primitives;
+    /// use quickjs_runtime::esscript::EsScript;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     Proxy::new()
+    ///         .name("Animal")
+    ///         .constructor(|_q_ctx, _id, _args| Ok(()))
+    ///         .method("speak", |_q_ctx, _id, _args| primitives::from_string_q(_q_ctx, "..."))
+    ///         .method("legs", |_q_ctx, _id, _args| Ok(primitives::from_i32(4)))
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    ///     Proxy::new()
+    ///         .name("Dog")
+    ///         .extends("Animal")
+    ///         .method("speak", |_q_ctx, _id, _args| primitives::from_string_q(_q_ctx, "woof"))
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    /// });
+    /// let res = rt.eval_sync(EsScript::new(
+    ///     "test_extends.es",
+    ///     "let d = new Dog(); `${d.speak()} ${d.legs()} ${d instanceof Dog} ${d instanceof Animal}`",
+    /// )).expect("script failed");
+    /// assert_eq!(res.get_str(), "woof 4 true true");
+    /// ```
+    pub fn extends(mut self, parent_class_name: &str) -> Self {
+        self.parent_class_name = Some(parent_class_name.to_string());
+        self
+    }
     /// add a finalizer for the Proxy class
     /// this will be called when an instance of the Proxy class is dropped or garbage collected
     pub fn finalizer<C>(mut self, finalizer: C) -> Self
@@ -320,12 +701,67 @@ impl Proxy {
         self.finalizer = Some(Box::new(finalizer));
         self
     }
+    /// declare the JS values which are held by an instance in rust (e.g. stored callbacks) so the GC can collect cycles between those values and the instance
+    ///
+    /// every JSValueRef which is held by the instance should be marked exactly once, and it should be dropped by the finalizer
+    /// the gc_mark function is called while the GC runs so it may not run scripts or create values
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::valueref::JSValueRef;
+    /// use std::cell::RefCell;
+    /// use std::collections::HashMap;
+    /// thread_local! {
+    ///     static CALLBACKS: RefCell<HashMap<usize, JSValueRef>> = RefCell::new(HashMap::new());
+    /// }
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     Proxy::new()
+    ///         .name("Button")
+    ///         .constructor(|_q_ctx, _id, _args| Ok(()))
+    ///         .method("onClick", |_q_ctx, id, args| {
+    ///             CALLBACKS.with(|rc| rc.borrow_mut().insert(*id, args[0].clone()));
+    ///             Ok(quickjs_runtime::quickjs_utils::new_null_ref())
+    ///         })
+    ///         .gc_mark(|id, marker| {
+    ///             CALLBACKS.with(|rc| {
+    ///                 if let Some(callback) = rc.borrow().get(id) {
+    ///                     marker.mark(callback);
+    ///                 }
+    ///             });
+    ///         })
+    ///         .finalizer(|_q_ctx, id| {
+    ///             let callback = CALLBACKS.with(|rc| rc.borrow_mut().remove(&id));
+    ///             drop(callback);
+    ///         })
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    /// });
+    /// // the callback refers to the button, without gc_mark this cycle would never be collected
+    /// rt.eval_sync(quickjs_runtime::esscript::EsScript::new("test_gc_mark.es", "(function() { let b = new Button(); b.onClick(() => b); })();")).expect("script failed");
+    /// rt.gc_sync();
+    /// assert!(CALLBACKS.with(|rc| rc.borrow().is_empty()));
+    /// ```
+    pub fn gc_mark<M>(mut self, gc_mark: M) -> Self
+    where
+        M: Fn(&usize, &GcMarker) + 'static,
+    {
+        self.gc_mark = Some(Box::new(move |instance_id, _data, marker| {
+            gc_mark(instance_id, marker)
+        }));
+        self
+    }
     /// add a method to the Proxy class, this method will be available as a member of instances of the Proxy class
     pub fn method<M>(mut self, name: &str, method: M) -> Self
     where
         M: Fn(&QuickJsContext, &usize, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
     {
-        self.methods.insert(name.to_string(), Box::new(method));
+        self.methods.insert(
+            name.to_string(),
+            Box::new(move |q_ctx, _this_ref, instance_id, args| method(q_ctx, instance_id, args)),
+        );
         self
     }
     /// add a method to the Proxy class, this method will be available as a member of instances of the Proxy class
@@ -347,6 +783,69 @@ impl Proxy {
         self.static_native_methods.insert(name.to_string(), method);
         self
     }
+    /// add a method to the Proxy class which returns a Promise
+    ///
+    /// the method is called in the EventQueue and returns a Future, that Future is run in a helper thread so it may also do blocking work (e.g. `async move { blocking_query() }`),
+    /// the Promise is resolved with the value the Future produces or rejected with its error
+    ///
+    /// the instance is kept alive until the Promise is resolved or rejected
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::quickjs_utils::primitives;
+    /// use quickjs_runtime::esvalue::EsValueConvertible;
+    /// use quickjs_runtime::esscript::EsScript;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     Proxy::new()
+    ///         .name("Connection")
+    ///         .constructor(|_q_ctx, _id, _args| Ok(()))
+    ///         .async_method("query", |q_ctx, _id, args| {
+    ///             // the arguments are converted in the EventQueue, the Future only gets values which are Send
+    ///             let sql = primitives::to_string_q(q_ctx, &args[0])?;
+    ///             Ok(async move {
+    ///                 std::thread::sleep(std::time::Duration::from_millis(10));
+    ///                 Ok(format!("rows for {}", sql).to_es_value_facade())
+    ///             })
+    ///         })
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    /// });
+    /// let res = rt.eval_sync(EsScript::new(
+    ///     "test_async_method.es",
+    ///     "new Connection().query('select 1');",
+    /// )).expect("script failed");
+    /// let res = res.get_promise_result_sync().expect("promise was rejected");
+    /// assert_eq!(res.get_str(), "rows for select 1");
+    /// ```
+    pub fn async_method<M, F>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, &usize, Vec<JSValueRef>) -> Result<F, EsError> + 'static,
+        F: Future<Output = Result<EsValueFacade, EsError>> + Send + 'static,
+    {
+        self.methods.insert(
+            name.to_string(),
+            Box::new(move |q_ctx, this_ref, instance_id, args| {
+                let future = method(q_ctx, instance_id, args)?;
+                new_future_promise(q_ctx, future, vec![this_ref.clone()])
+            }),
+        );
+        self
+    }
+    /// add a static method to the Proxy class which returns a Promise, see [Proxy::async_method](#method.async_method)
+    pub fn async_static_method<M, F>(self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<F, EsError> + 'static,
+        F: Future<Output = Result<EsValueFacade, EsError>> + Send + 'static,
+    {
+        self.static_method(name, move |q_ctx, args| {
+            let future = method(q_ctx, args)?;
+            new_future_promise(q_ctx, future, vec![])
+        })
+    }
 
     /// add a static getter and setter to the Proxy class
     pub fn static_getter_setter<G, S>(mut self, name: &str, getter: G, setter: S) -> Self
@@ -368,6 +867,116 @@ impl Proxy {
             .insert(name.to_string(), (Box::new(getter), Box::new(setter)));
         self
     }
+    /// add a method to the Proxy class which is keyed by a well-known Symbol, e.g. "toPrimitive" for [Symbol.toPrimitive] or "iterator" for [Symbol.iterator]
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::quickjs_utils::primitives;
+    /// use quickjs_runtime::esscript::EsScript;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     Proxy::new()
+    ///         .name("Money")
+    ///         .constructor(|_q_ctx, _id, _args| Ok(()))
+    ///         .symbol_method("toPrimitive", |q_ctx, _id, args| {
+    ///             // the hint is "number", "string" or "default"
+    ///             if primitives::to_string_q(q_ctx, &args[0])?.eq("number") {
+    ///                 Ok(primitives::from_i32(42))
+    ///             } else {
+    ///                 primitives::from_string_q(q_ctx, "EUR 42")
+    ///             }
+    ///         })
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    /// });
+    /// let res = rt.eval_sync(EsScript::new(
+    ///     "test_symbol_method.es",
+    ///     "let m = new Money(); `${m} ${m * 2}`",
+    /// )).expect("script failed");
+    /// assert_eq!(res.get_str(), "EUR 42 84");
+    /// ```
+    pub fn symbol_method<M>(mut self, symbol_name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, &usize, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
+    {
+        self.symbol_methods.insert(
+            symbol_name.to_string(),
+            Box::new(move |q_ctx, _this_ref, instance_id, args| method(q_ctx, instance_id, args)),
+        );
+        self
+    }
+    /// add a static method to the Proxy class which is keyed by a well-known Symbol, e.g. "hasInstance" for [Symbol.hasInstance]
+    pub fn static_symbol_method<M>(mut self, symbol_name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
+    {
+        self.static_symbol_methods
+            .insert(symbol_name.to_string(), Box::new(method));
+        self
+    }
+    /// set the [Symbol.toStringTag] of instances of the Proxy class, this is used by Object.prototype.toString()
+    pub fn to_string_tag(mut self, tag: &str) -> Self {
+        self.to_string_tag = Some(tag.to_string());
+        self
+    }
+    /// make instances of the Proxy class iterable, the factory creates a new rust Iterator every time an instance is iterated
+    ///
+    /// the items are produced lazily, an Err item is thrown as an Error, see [iterators::new_iterator_q]
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::esvalue::EsValueConvertible;
+    /// use quickjs_runtime::esscript::EsScript;
+    ///
+    /// let rt = EsRuntimeBuilder::new().build();
+    /// rt.add_to_event_queue_sync(|q_js_rt| {
+    ///     let q_ctx = q_js_rt.get_main_context();
+    ///     Proxy::new()
+    ///         .name("ResultSet")
+    ///         .constructor(|_q_ctx, _id, _args| Ok(()))
+    ///         .iterator(|_q_ctx, _id| {
+    ///             let rows = vec!["a", "b", "c"];
+    ///             Ok(rows.into_iter().map(|row| Ok(row.to_string().to_es_value_facade())))
+    ///         })
+    ///         .install(q_ctx, true)
+    ///         .expect("install failed");
+    /// });
+    /// let res = rt.eval_sync(EsScript::new(
+    ///     "test_iterator.es",
+    ///     "let rows = []; for (const row of new ResultSet()) {rows.push(row);} rows.join(',')",
+    /// )).expect("script failed");
+    /// assert_eq!(res.get_str(), "a,b,c");
+    /// ```
+    pub fn iterator<F, I>(self, factory: F) -> Self
+    where
+        F: Fn(&QuickJsContext, &usize) -> Result<I, EsError> + 'static,
+        I: Iterator<Item = Result<EsValueFacade, EsError>> + 'static,
+    {
+        self.symbol_method("iterator", move |q_ctx, id, _args| {
+            iterators::new_iterator_q(q_ctx, factory(q_ctx, id)?)
+        })
+    }
+    /// make instances of the Proxy class async iterable (for await...of), the factory creates a new rust Iterator every time an instance is iterated
+    ///
+    /// an item may be a Promise (e.g. an [EsPromise](crate::esvalue::EsPromise)), see [iterators::new_async_iterator_q]
+    pub fn async_iterator<F, I>(self, factory: F) -> Self
+    where
+        F: Fn(&QuickJsContext, &usize) -> Result<I, EsError> + 'static,
+        I: Iterator<Item = Result<EsValueFacade, EsError>> + 'static,
+    {
+        self.symbol_method("asyncIterator", move |q_ctx, id, _args| {
+            iterators::new_async_iterator_q(q_ctx, factory(q_ctx, id)?)
+        })
+    }
+    /// add a catch-all handler for the properties of instances of this Proxy class, see [ProxyPropertyHandler]
+    pub fn property_handler(mut self, property_handler: ProxyPropertyHandler) -> Self {
+        self.property_handler = Some(property_handler);
+        self
+    }
     /// indicate the Proxy class should implement the EventTarget interface, this will result in the addEventListener, removeEventListener and dispatchEvent methods to be available on instances of the Proxy class
     pub fn event_target(mut self) {
         self.is_event_target = true
@@ -386,8 +995,9 @@ impl Proxy {
             return Err(EsError::new_str("Proxy needs a name"));
         }
 
-        let class_ref = self.install_class_prop(q_ctx, add_variable_to_global)?;
-        eventtarget::impl_event_target(self).install_move_to_registry(q_ctx);
+        let mut proxy = eventtarget::impl_event_target(self);
+        let class_ref = proxy.install_class_prop(q_ctx, add_variable_to_global)?;
+        proxy.install_move_to_registry(q_ctx);
 
         Ok(class_ref)
     }
@@ -399,7 +1009,7 @@ impl Proxy {
         reg_map.insert(proxy.get_class_name(), Rc::new(proxy));
     }
     fn install_class_prop(
-        &self,
+        &mut self,
         q_ctx: &QuickJsContext,
         add_variable_to_global: bool,
     ) -> Result<JSValueRef, EsError> {
@@ -407,17 +1017,35 @@ impl Proxy {
 
         log::trace!("reflection::Proxy::install_class_prop / 1");
 
+        let (parent_constructor_ref, parent_prototype_ref) =
+            if let Some(parent_class_name) = &self.parent_class_name {
+                let parent = get_proxy(q_ctx, parent_class_name).ok_or_else(|| {
+                    EsError::new_string(format!(
+                        "parent class {} of {} is not installed",
+                        parent_class_name,
+                        self.get_class_name()
+                    ))
+                })?;
+                let parent_constructor_ref = parent.constructor_ref.borrow().clone();
+                let parent_prototype_ref = parent.prototype_ref.borrow().clone();
+                (parent_constructor_ref, parent_prototype_ref)
+            } else {
+                (None, None)
+            };
+
         let static_class_id = PROXY_STATIC_CLASS_ID.with(|rc| *rc.borrow());
 
         log::trace!("reflection::Proxy::install_class_prop / 2");
 
-        let constructor_ref = new_native_function_q(
+        // the class name is passed as function data so the constructor knows its class when it is called with a new.target of a subclass
+        let constructor_ref = new_native_function_data_q(
             q_ctx,
-            self.name.as_ref().unwrap().as_str(),
             Some(constructor),
+            self.name.as_ref().unwrap().as_str(),
             1,
-            true,
+            primitives::from_string_q(q_ctx, &self.get_class_name())?,
         )?;
+        unsafe { q::JS_SetConstructorBit(q_ctx.context, *constructor_ref.borrow_value(), 1) };
 
         log::trace!("reflection::Proxy::install_class_prop / 3");
 
@@ -451,22 +1079,35 @@ impl Proxy {
 
         log::trace!("reflection::Proxy::install_class_prop / 6");
 
+        // the static members of a parent class are inherited by making the parent class the prototype of the static object
+        // the exotic get_property of the static object looks up properties it does not know in its prototype
+        let static_proto_ref = if let Some(parent_constructor_ref) = parent_constructor_ref {
+            parent_constructor_ref
+        } else {
+            // Function.prototype
+            objects::get_prototype_q(q_ctx, &constructor_ref)?
+        };
+
+        objects::set_prototype_q(q_ctx, &class_val_ref, &static_proto_ref)?;
+        objects::set_prototype_q(q_ctx, &constructor_ref, &class_val_ref)?;
+
+        assert_eq!(2, class_val_ref.get_ref_count());
+
+        // the prototype of instances, if this class extends another class its prototype is the prototype of the parent class
+        let prototype_ref = objects::create_object_q(q_ctx)?;
+        if let Some(parent_prototype_ref) = parent_prototype_ref {
+            objects::set_prototype_q(q_ctx, &prototype_ref, &parent_prototype_ref)?;
+        }
         unsafe {
-            let res = q::JS_SetPrototype(
+            q::JS_SetConstructor(
                 q_ctx.context,
                 *constructor_ref.borrow_value(),
-                *class_val_ref.borrow_value(),
-            );
-            if res < 0 {
-                return if let Some(err) = QuickJsContext::get_exception(q_ctx.context) {
-                    Err(err)
-                } else {
-                    Err(EsError::new_str("could not set class proto"))
-                };
-            }
-        }
-
-        assert_eq!(2, class_val_ref.get_ref_count());
+                *prototype_ref.borrow_value(),
+            )
+        };
+        self.install_members(q_ctx, &prototype_ref, &class_val_ref)?;
+        self.constructor_ref = RefCell::new(Some(constructor_ref.clone()));
+        self.prototype_ref = RefCell::new(Some(prototype_ref));
 
         log::trace!("reflection::Proxy::install_class_prop / 7");
 
@@ -503,29 +1144,560 @@ impl Proxy {
         }
         log::trace!("reflection::Proxy::install_class_prop / 10");
 
+        lockdown::harden_if_locked_down_q(q_ctx, &constructor_ref)?;
+
         log::trace!("install_class_prop done");
 
         Ok(constructor_ref)
     }
+    /// define the methods and getters/setters on the prototype of the instances and the static members on the class
+    fn install_members(
+        &self,
+        q_ctx: &QuickJsContext,
+        prototype_ref: &JSValueRef,
+        static_ref: &JSValueRef,
+    ) -> Result<(), EsError> {
+        let class_name = self.get_class_name();
+        let method_flags = (q::JS_PROP_WRITABLE | q::JS_PROP_CONFIGURABLE) as i32;
+
+        for name in self.methods.keys() {
+            let func_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_instance_method),
+                name,
+                1,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            objects::set_property2_q(q_ctx, prototype_ref, name, &func_ref, method_flags)?;
+        }
+        for (name, native_method) in &self.native_methods {
+            let func_ref = new_native_function_q(q_ctx, name, *native_method, 1, false)?;
+            objects::set_property2_q(q_ctx, prototype_ref, name, &func_ref, method_flags)?;
+        }
+        for name in self.getters_setters.keys() {
+            let getter_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_instance_getter),
+                name,
+                0,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            let setter_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_instance_setter),
+                name,
+                1,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            objects::define_getter_setter_q(q_ctx, prototype_ref, name, &getter_ref, &setter_ref)?;
+        }
+        for symbol_name in self.symbol_methods.keys() {
+            let func_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_instance_symbol_method),
+                &format!("[Symbol.{}]", symbol_name),
+                1,
+                new_member_data(q_ctx, &class_name, symbol_name)?,
+            )?;
+            let symbol_ref = objects::get_well_known_symbol_q(q_ctx, symbol_name)?;
+            objects::set_symbol_property2_q(
+                q_ctx,
+                prototype_ref,
+                &symbol_ref,
+                &func_ref,
+                method_flags,
+            )?;
+        }
+        if let Some(tag) = &self.to_string_tag {
+            let symbol_ref = objects::get_well_known_symbol_q(q_ctx, "toStringTag")?;
+            objects::set_symbol_property2_q(
+                q_ctx,
+                prototype_ref,
+                &symbol_ref,
+                &primitives::from_string_q(q_ctx, tag)?,
+                q::JS_PROP_CONFIGURABLE as i32,
+            )?;
+        }
+
+        for name in self.static_methods.keys() {
+            let func_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_static_method),
+                name,
+                1,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            objects::set_property2_q(q_ctx, static_ref, name, &func_ref, method_flags)?;
+        }
+        for (name, native_static_method) in &self.static_native_methods {
+            let func_ref = new_native_function_q(q_ctx, name, *native_static_method, 1, false)?;
+            objects::set_property2_q(q_ctx, static_ref, name, &func_ref, method_flags)?;
+        }
+        for name in self.static_getters_setters.keys() {
+            let getter_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_static_getter),
+                name,
+                0,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            let setter_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_static_setter),
+                name,
+                1,
+                new_member_data(q_ctx, &class_name, name)?,
+            )?;
+            objects::define_getter_setter_q(q_ctx, static_ref, name, &getter_ref, &setter_ref)?;
+        }
+        for symbol_name in self.static_symbol_methods.keys() {
+            let func_ref = new_native_function_data_q(
+                q_ctx,
+                Some(proxy_static_symbol_method),
+                &format!("[Symbol.{}]", symbol_name),
+                1,
+                new_member_data(q_ctx, &class_name, symbol_name)?,
+            )?;
+            let symbol_ref = objects::get_well_known_symbol_q(q_ctx, symbol_name)?;
+            objects::set_symbol_property2_q(
+                q_ctx,
+                static_ref,
+                &symbol_ref,
+                &func_ref,
+                method_flags,
+            )?;
+        }
+        Ok(())
+    }
 }
 
-pub fn new_instance2(
-    proxy: &Proxy,
-    q_ctx: &QuickJsContext,
-) -> Result<(usize, JSValueRef), EsError> {
-    let instance_id = next_id(q_ctx);
-    Ok((instance_id, new_instance3(proxy, instance_id, q_ctx)?))
+/// a catch-all handler for the properties of instances of a Proxy class, this enables a rust object to behave like a map whose keys are only known at runtime
+///
+/// the properties of the handler are own properties of an instance, so they show up in Object.keys(), for...in, JSON.stringify() and spread
+///
+/// * get returns the value of a property or None if the handler does not have such a property
+/// * has indicates if the handler has a property, if omitted get is used to determine this
+/// * set returns true if the property was set, if it returns false the property is defined on the instance itself, if omitted the properties are read only
+/// * delete returns true if the property was deleted, if omitted the properties can not be deleted
+/// * keys returns the names of the properties of an instance
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::reflection::{get_instance_data, Proxy, ProxyPropertyHandler};
+/// use quickjs_runtime::quickjs_utils::primitives;
+/// use quickjs_runtime::esscript::EsScript;
+/// use std::collections::BTreeMap;
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     Proxy::with_data::<BTreeMap<String, String>>()
+///         .name("Config")
+///         .constructor(|_q_ctx, _args| Ok(BTreeMap::new()))
+///         .configure(|proxy| {
+///             proxy.property_handler(
+///                 ProxyPropertyHandler::new()
+///                     .get(|q_ctx, id, name| {
+///                         let map = get_instance_data::<BTreeMap<String, String>>(q_ctx, *id).unwrap();
+///                         let map = map.borrow();
+///                         match map.get(name) {
+///                             Some(value) => Ok(Some(primitives::from_string_q(q_ctx, value)?)),
+///                             None => Ok(None),
+///                         }
+///                     })
+///                     .set(|q_ctx, id, name, value| {
+///                         let map = get_instance_data::<BTreeMap<String, String>>(q_ctx, *id).unwrap();
+///                         map.borrow_mut().insert(name.to_string(), primitives::to_string_q(q_ctx, &value)?);
+///                         Ok(true)
+///                     })
+///                     .delete(|q_ctx, id, name| {
+///                         let map = get_instance_data::<BTreeMap<String, String>>(q_ctx, *id).unwrap();
+///                         map.borrow_mut().remove(name);
+///                         Ok(true)
+///                     })
+///                     .keys(|q_ctx, id| {
+///                         let map = get_instance_data::<BTreeMap<String, String>>(q_ctx, *id).unwrap();
+///                         let keys = map.borrow().keys().cloned().collect();
+///                         Ok(keys)
+///                     }),
+///             )
+///         })
+///         .install(q_ctx, true)
+///         .expect("install failed");
+/// });
+/// let res = rt.eval_sync(EsScript::new(
+///     "test_property_handler.es",
+///     "let c = new Config(); c.host = 'localhost'; c.port = '8080'; c.tmp = '1'; delete c.tmp;\
+///      `${Object.keys(c)} ${JSON.stringify(c)} ${'host' in c}`",
+/// )).expect("script failed");
+/// assert_eq!(res.get_str(), "host,port {\"host\":\"localhost\",\"port\":\"8080\"} true");
+/// ```
+#[derive(Default)]
+pub struct ProxyPropertyHandler {
+    get: Option<Box<ProxyPropertyGetter>>,
+    set: Option<Box<ProxyPropertySetter>>,
+    has: Option<Box<ProxyPropertyPredicate>>,
+    delete: Option<Box<ProxyPropertyPredicate>>,
+    keys: Option<Box<ProxyPropertyKeys>>,
 }
 
-pub(crate) fn new_instance3(
-    proxy: &Proxy,
-    instance_id: usize,
-    q_ctx: &QuickJsContext,
-) -> Result<JSValueRef, EsError> {
+impl ProxyPropertyHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// get the value of a property, return None if there is no such property
+    pub fn get<G>(mut self, get: G) -> Self
+    where
+        G: Fn(&QuickJsContext, &usize, &str) -> Result<Option<JSValueRef>, EsError> + 'static,
+    {
+        self.get = Some(Box::new(get));
+        self
+    }
+    /// set the value of a property, return false to define the property on the instance itself
+    pub fn set<S>(mut self, set: S) -> Self
+    where
+        S: Fn(&QuickJsContext, &usize, &str, JSValueRef) -> Result<bool, EsError> + 'static,
+    {
+        self.set = Some(Box::new(set));
+        self
+    }
+    /// check if a property exists
+    pub fn has<H>(mut self, has: H) -> Self
+    where
+        H: Fn(&QuickJsContext, &usize, &str) -> Result<bool, EsError> + 'static,
+    {
+        self.has = Some(Box::new(has));
+        self
+    }
+    /// delete a property, return false if the property could not be deleted
+    pub fn delete<D>(mut self, delete: D) -> Self
+    where
+        D: Fn(&QuickJsContext, &usize, &str) -> Result<bool, EsError> + 'static,
+    {
+        self.delete = Some(Box::new(delete));
+        self
+    }
+    /// get the names of the properties
+    pub fn keys<K>(mut self, keys: K) -> Self
+    where
+        K: Fn(&QuickJsContext, &usize) -> Result<Vec<String>, EsError> + 'static,
+    {
+        self.keys = Some(Box::new(keys));
+        self
+    }
+    fn has_property(
+        &self,
+        q_ctx: &QuickJsContext,
+        instance_id: &usize,
+        name: &str,
+    ) -> Result<bool, EsError> {
+        if let Some(has) = &self.has {
+            has(q_ctx, instance_id, name)
+        } else if let Some(get) = &self.get {
+            Ok(get(q_ctx, instance_id, name)?.is_some())
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// a Proxy class whose instances each own a value of type T
+///
+/// the value is created by the constructor, passed to the methods and getters/setters and dropped when the instance is finalized,
+/// so there is no need to keep the values in a thread_local map
+///
+/// the value is borrowed while a method, getter or setter runs, if such a method calls a method of the same instance
+/// (e.g. through a callback) that call fails with an Error instead of panicking
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::reflection::Proxy;
+/// use quickjs_runtime::quickjs_utils::primitives;
+/// use quickjs_runtime::esscript::EsScript;
+///
+/// struct Counter {
+///     count: i32,
+/// }
+///
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     Proxy::with_data::<Counter>()
+///         .namespace(vec!["com", "hirofa"])
+///         .name("Counter")
+///         .constructor(|q_ctx, args| {
+///             let count = if args.is_empty() { 0 } else { primitives::to_i32(&args[0])? };
+///             Ok(Counter { count })
+///         })
+///         .method_mut("increment", |_q_ctx, counter, _args| {
+///             counter.count += 1;
+///             Ok(primitives::from_i32(counter.count))
+///         })
+///         .getter_setter(
+///             "count",
+///             |_q_ctx, counter| Ok(primitives::from_i32(counter.count)),
+///             |_q_ctx, counter, value| {
+///                 counter.count = primitives::to_i32(&value)?;
+///                 Ok(())
+///             },
+///         )
+///         .install(q_ctx, true)
+///         .expect("install failed");
+/// });
+/// let res = rt.eval_sync(EsScript::new("test_data_proxy.es", "let c = new com.hirofa.Counter(40); c.increment(); c.increment();")).expect("script failed");
+/// assert_eq!(res.get_i32(), 42);
+/// ```
+pub struct DataProxy<T: 'static> {
+    proxy: Proxy,
+    _data_type: PhantomData<T>,
+}
+
+impl<T: 'static> DataProxy<T> {
+    /// set the name of the proxy class
+    pub fn name(mut self, name: &str) -> Self {
+        self.proxy = self.proxy.name(name);
+        self
+    }
+    /// set the namespace of the proxy class
+    pub fn namespace(mut self, namespace: Vec<&str>) -> Self {
+        self.proxy = self.proxy.namespace(namespace);
+        self
+    }
+    /// add a constructor which creates the value for a new instance
+    /// if omitted the Proxy class will not be constructable from script, instances may still be created with [new_instance_with_data]
+    pub fn constructor<C>(mut self, constructor: C) -> Self
+    where
+        C: Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<T, EsError> + 'static,
+    {
+        self.proxy.data_constructor =
+            Some(Box::new(move |q_ctx, _instance_id, _new_target, args| {
+                let data = constructor(q_ctx, args)?;
+                Ok(Rc::new(RefCell::new(data)) as Rc<dyn Any>)
+            }));
+        self
+    }
+    /// add a constructor which also receives new.target, see [Proxy::constructor_with_new_target](struct.Proxy.html#method.constructor_with_new_target)
+    pub fn constructor_with_new_target<C>(mut self, constructor: C) -> Self
+    where
+        C: Fn(&QuickJsContext, &JSValueRef, Vec<JSValueRef>) -> Result<T, EsError> + 'static,
+    {
+        self.proxy.data_constructor =
+            Some(Box::new(move |q_ctx, _instance_id, new_target, args| {
+                let data = constructor(q_ctx, new_target, args)?;
+                Ok(Rc::new(RefCell::new(data)) as Rc<dyn Any>)
+            }));
+        self
+    }
+    /// extend another Proxy class, see [Proxy::extends](struct.Proxy.html#method.extends)
+    ///
+    /// the methods of the parent class are called with the value of the instance, so they only work if the parent class is not a DataProxy with a different type of value
+    pub fn extends(mut self, parent_class_name: &str) -> Self {
+        self.proxy = self.proxy.extends(parent_class_name);
+        self
+    }
+    /// declare the JS values which are held by the value of an instance, see [Proxy::gc_mark](struct.Proxy.html#method.gc_mark)
+    ///
+    /// the JSValueRefs are dropped together with the value when the instance is finalized
+    /// N.B. the values are not marked while the value of the instance is borrowed mutably (e.g. when the GC runs during a method_mut)
+    pub fn gc_mark<M>(mut self, gc_mark: M) -> Self
+    where
+        M: Fn(&T, &GcMarker) + 'static,
+    {
+        self.proxy.gc_mark = Some(Box::new(move |_instance_id, data, marker| {
+            if let Some(data_cell) = data.and_then(|data| data.downcast_ref::<RefCell<T>>()) {
+                if let Ok(data) = data_cell.try_borrow() {
+                    gc_mark(&*data, marker);
+                }
+            }
+        }));
+        self
+    }
+    /// add a method which reads the value of an instance
+    pub fn method<M>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, &T, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
+    {
+        self.proxy = self.proxy.method(name, move |q_ctx, instance_id, args| {
+            with_instance_data(q_ctx, *instance_id, |data| method(q_ctx, data, args))
+        });
+        self
+    }
+    /// add a method which may alter the value of an instance
+    pub fn method_mut<M>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, &mut T, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
+    {
+        self.proxy = self.proxy.method(name, move |q_ctx, instance_id, args| {
+            with_instance_data_mut(q_ctx, *instance_id, |data| method(q_ctx, data, args))
+        });
+        self
+    }
+    /// add a method which returns a Promise, see [Proxy::async_method](struct.Proxy.html#method.async_method)
+    ///
+    /// the value of an instance can be used to create the Future but the Future itself has to be Send, so it should copy what it needs (e.g. an Arc to a connection pool)
+    pub fn async_method<M, F>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, &T, Vec<JSValueRef>) -> Result<F, EsError> + 'static,
+        F: Future<Output = Result<EsValueFacade, EsError>> + Send + 'static,
+    {
+        self.proxy = self
+            .proxy
+            .async_method(name, move |q_ctx, instance_id, args| {
+                with_instance_data(q_ctx, *instance_id, |data| method(q_ctx, data, args))
+            });
+        self
+    }
+    /// add a getter and setter for the value of an instance
+    pub fn getter_setter<G, S>(mut self, name: &str, getter: G, setter: S) -> Self
+    where
+        G: Fn(&QuickJsContext, &T) -> Result<JSValueRef, EsError> + 'static,
+        S: Fn(&QuickJsContext, &mut T, JSValueRef) -> Result<(), EsError> + 'static,
+    {
+        self.proxy = self.proxy.getter_setter(
+            name,
+            move |q_ctx, instance_id| {
+                with_instance_data(q_ctx, *instance_id, |data| getter(q_ctx, data))
+            },
+            move |q_ctx, instance_id, value| {
+                with_instance_data_mut(q_ctx, *instance_id, |data| setter(q_ctx, data, value))
+            },
+        );
+        self
+    }
+    /// add a static method to the Proxy class
+    pub fn static_method<M>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<JSValueRef, EsError> + 'static,
+    {
+        self.proxy = self.proxy.static_method(name, method);
+        self
+    }
+    /// add a static method to the Proxy class which returns a Promise, see [Proxy::async_method](struct.Proxy.html#method.async_method)
+    pub fn async_static_method<M, F>(mut self, name: &str, method: M) -> Self
+    where
+        M: Fn(&QuickJsContext, Vec<JSValueRef>) -> Result<F, EsError> + 'static,
+        F: Future<Output = Result<EsValueFacade, EsError>> + Send + 'static,
+    {
+        self.proxy = self.proxy.async_static_method(name, method);
+        self
+    }
+    /// add a static getter and setter to the Proxy class
+    pub fn static_getter_setter<G, S>(mut self, name: &str, getter: G, setter: S) -> Self
+    where
+        G: Fn(&QuickJsContext) -> Result<JSValueRef, EsError> + 'static,
+        S: Fn(&QuickJsContext, JSValueRef) -> Result<(), EsError> + 'static,
+    {
+        self.proxy = self.proxy.static_getter_setter(name, getter, setter);
+        self
+    }
+    /// configure the underlying untyped Proxy, e.g. to add native methods or a finalizer
+    pub fn configure<F>(mut self, configure: F) -> Self
+    where
+        F: FnOnce(Proxy) -> Proxy,
+    {
+        self.proxy = configure(self.proxy);
+        self
+    }
+    /// install the Proxy class in a QuickJsContext, see [Proxy::install](struct.Proxy.html#method.install)
+    pub fn install(
+        self,
+        q_ctx: &QuickJsContext,
+        add_variable_to_global: bool,
+    ) -> Result<JSValueRef, EsError> {
+        self.proxy.install(q_ctx, add_variable_to_global)
+    }
+}
+
+/// get the value of an instance of a [DataProxy] class, returns None if the instance does not exist or its value is not a T
+pub fn get_instance_data<T: 'static>(
+    q_ctx: &QuickJsContext,
+    instance_id: usize,
+) -> Option<Rc<RefCell<T>>> {
+    let mappings = &*q_ctx.instance_id_mappings.borrow();
+    let data = mappings.get(&instance_id)?.data.as_ref()?.clone();
+    data.downcast::<RefCell<T>>().ok()
+}
+
+fn with_instance_data<T: 'static, R, F>(
+    q_ctx: &QuickJsContext,
+    instance_id: usize,
+    consumer: F,
+) -> Result<R, EsError>
+where
+    F: FnOnce(&T) -> Result<R, EsError>,
+{
+    let data = get_instance_data::<T>(q_ctx, instance_id)
+        .ok_or_else(|| EsError::new_str("proxy instance has no data"))?;
+    let data_ref = data
+        .try_borrow()
+        .map_err(|_| EsError::new_str("proxy instance data is being altered"))?;
+    consumer(&*data_ref)
+}
+
+fn with_instance_data_mut<T: 'static, R, F>(
+    q_ctx: &QuickJsContext,
+    instance_id: usize,
+    consumer: F,
+) -> Result<R, EsError>
+where
+    F: FnOnce(&mut T) -> Result<R, EsError>,
+{
+    let data = get_instance_data::<T>(q_ctx, instance_id)
+        .ok_or_else(|| EsError::new_str("proxy instance has no data"))?;
+    let mut data_ref = data
+        .try_borrow_mut()
+        .map_err(|_| EsError::new_str("proxy instance data is already in use"))?;
+    consumer(&mut *data_ref)
+}
+
+pub fn new_instance2(
+    proxy: &Proxy,
+    q_ctx: &QuickJsContext,
+) -> Result<(usize, JSValueRef), EsError> {
+    let instance_id = next_id(q_ctx);
+    Ok((instance_id, new_instance3(proxy, instance_id, q_ctx, None)?))
+}
+
+/// create a new instance of a [DataProxy] class from rust with an existing value
+///
+/// fails with [EsErrorKind::Type] when the class is not a DataProxy with values of type T
+pub fn new_instance_with_data<T: 'static>(
+    class_name: &str,
+    q_ctx: &QuickJsContext,
+    data: T,
+) -> Result<(usize, JSValueRef), EsError> {
+    let proxy = get_proxy(q_ctx, class_name).ok_or_else(|| EsError::new_str("no such proxy"))?;
+    if proxy.data_type != Some(TypeId::of::<T>()) {
+        return Err(EsError::new_kind(
+            EsErrorKind::Type,
+            format!(
+                "{} is not a DataProxy with values of type {}",
+                class_name,
+                std::any::type_name::<T>()
+            ),
+        ));
+    }
+    let instance_id = next_id(q_ctx);
+    let data = Rc::new(RefCell::new(data)) as Rc<dyn Any>;
+    let instance_ref = new_instance3(&proxy, instance_id, q_ctx, Some(data))?;
+    Ok((instance_id, instance_ref))
+}
+
+pub(crate) fn new_instance3(
+    proxy: &Proxy,
+    instance_id: usize,
+    q_ctx: &QuickJsContext,
+    data: Option<Rc<dyn Any>>,
+) -> Result<JSValueRef, EsError> {
     let ctx = q_ctx.context;
     let class_id = PROXY_INSTANCE_CLASS_ID.with(|rc| *rc.borrow());
 
-    let class_val: q::JSValue = unsafe { q::JS_NewObjectClass(ctx, class_id as i32) };
+    // instances have the prototype of their class so instanceof works, the constructor replaces it when a subclass was constructed in script
+    let class_val: q::JSValue = if let Some(prototype_ref) = &*proxy.prototype_ref.borrow() {
+        unsafe { q::JS_NewObjectProtoClass(ctx, *prototype_ref.borrow_value(), class_id) }
+    } else {
+        unsafe { q::JS_NewObjectClass(ctx, class_id as i32) }
+    };
 
     let class_name = proxy.get_class_name();
 
@@ -558,6 +1730,8 @@ pub(crate) fn new_instance3(
         id: instance_id,
         class_name: proxy.get_class_name(),
         context_id: q_ctx.id.clone(),
+        obj_ptr: unsafe { class_val.u.ptr },
+        data,
     });
 
     let ibp: &mut ProxyInstanceInfo = &mut *bx;
@@ -592,61 +1766,88 @@ unsafe extern "C" fn constructor(
     this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
 ) -> q::JSValue {
     log::trace!("constructor called, this_tag={}", this_val.tag);
 
-    // this is the function we created earlier (the constructor)
-    // so classname = this.name;
-    let this_ref = JSValueRef::new(
+    // this is new.target, the function we created earlier (the constructor) or a subclass which was created in script
+    let new_target_ref = JSValueRef::new(
         context,
         this_val,
         false,
         false,
         "reflection::constructor this_val",
     );
+    // the class name was passed as function data when the constructor was created
+    let class_name_ref = JSValueRef::new(
+        context,
+        *func_data,
+        false,
+        false,
+        "reflection::constructor func_data",
+    );
     QuickJsRuntime::do_with(|q_js_rt| {
-        let name_ref = objects::get_property(context, &this_ref, "name")
-            .ok()
-            .expect("name get failed");
-        let class_name = functions::call_to_string(context, &name_ref)
-            .ok()
-            .expect("name.toString failed");
+        let class_name =
+            primitives::to_string(context, &class_name_ref).expect("class_name.toString failed");
 
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let registry = &*q_ctx.proxy_registry.borrow();
-        if let Some(proxy) = registry.get(&class_name) {
-            if let Some(constructor) = &proxy.constructor {
-                // construct
-
-                let args_vec = parse_args(context, argc, argv);
-                let instance_id = next_id(q_ctx);
-                let constructor_res = constructor(q_ctx, instance_id, args_vec);
-
-                match constructor_res {
-                    Ok(()) => {
-                        let instance_ref_res = new_instance3(proxy, instance_id, q_ctx);
-
-                        match instance_ref_res {
-                            Ok(instance_ref) => instance_ref.clone_value_incr_rc(),
-                            Err(e) => q_ctx.report_ex(
-                                format!(
-                                    "could not create proxy instance for {} due to {}",
-                                    class_name, e
-                                )
-                                .as_str(),
-                            ),
+        let proxy = match get_proxy(q_ctx, &class_name) {
+            Some(proxy) => proxy,
+            None => return q_ctx.report_ex("no such proxy"),
+        };
+        // a class which extends another class and has no constructor of its own uses the constructor of its parent
+        let constructing_proxy = match find_proxy(q_ctx, &class_name, |p| {
+            p.constructor.is_some() || p.data_constructor.is_some()
+        }) {
+            Some(constructing_proxy) => constructing_proxy,
+            None => return q_ctx.report_ex("not a constructor"),
+        };
+
+        // construct
+
+        let args_vec = parse_args(context, argc, argv);
+        let instance_id = next_id(q_ctx);
+        let constructor_res = if let Some(data_constructor) = &constructing_proxy.data_constructor {
+            data_constructor(q_ctx, instance_id, &new_target_ref, args_vec).map(Some)
+        } else {
+            let constructor = constructing_proxy.constructor.as_ref().unwrap();
+            constructor(q_ctx, instance_id, &new_target_ref, args_vec).map(|_| None)
+        };
+
+        match constructor_res {
+            Ok(data) => {
+                let instance_ref_res =
+                    new_instance3(&proxy, instance_id, q_ctx, data).and_then(|instance_ref| {
+                        // a subclass which was created in script has a prototype of its own
+                        let proto_ref =
+                            objects::get_property_q(q_ctx, &new_target_ref, "prototype")?;
+                        if proto_ref.is_object() {
+                            objects::set_prototype_q(q_ctx, &instance_ref, &proto_ref)?;
                         }
-                    }
-                    Err(es_err) => q_ctx.report_ex(
-                        format!("constructor for {} failed with {}", class_name, es_err).as_str(),
+                        Ok(instance_ref)
+                    });
+
+                match instance_ref_res {
+                    Ok(instance_ref) => instance_ref.clone_value_incr_rc(),
+                    Err(e) => q_ctx.report_ex(
+                        format!(
+                            "could not create proxy instance for {} due to {}",
+                            class_name, e
+                        )
+                        .as_str(),
                     ),
                 }
-            } else {
-                q_ctx.report_ex("not a constructor")
             }
-        } else {
-            q_ctx.report_ex("no such proxy")
+            Err(es_err) => {
+                let msg = format!(
+                    "constructor for {} failed with {}",
+                    class_name,
+                    es_err.get_message()
+                );
+                q_ctx.report_es_error(&es_err.with_message(msg))
+            }
         }
     })
 }
@@ -655,6 +1856,10 @@ pub(crate) struct ProxyInstanceInfo {
     id: usize,
     class_name: String, // todo use unsafe to make these &str?
     context_id: String, // todo use unsafe to make these &str?
+    // the object of the instance, this is not a reference, it is only used to verify weak references
+    obj_ptr: *mut c_void,
+    // the value of an instance of a DataProxy class, this is dropped when the instance is finalized
+    data: Option<Rc<dyn Any>>,
 }
 
 fn get_proxy_instance_info(val: &q::JSValue) -> &ProxyInstanceInfo {
@@ -664,6 +1869,158 @@ fn get_proxy_instance_info(val: &q::JSValue) -> &ProxyInstanceInfo {
     info
 }
 
+fn opt_proxy_instance_info(val: &q::JSValue) -> Option<&ProxyInstanceInfo> {
+    let class_id = PROXY_INSTANCE_CLASS_ID.with(|rc| *rc.borrow());
+    let info_ptr: *mut c_void = unsafe { q::JS_GetOpaque(*val, class_id) };
+    if info_ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*(info_ptr as *mut ProxyInstanceInfo) })
+    }
+}
+
+/// get the class name and instance id of an instance of a Proxy class, returns None if the value is not a Proxy instance
+pub fn get_proxy_instance_class_and_id(obj_ref: &JSValueRef) -> Option<(String, usize)> {
+    if !obj_ref.is_object() {
+        return None;
+    }
+    let class_id = PROXY_INSTANCE_CLASS_ID.with(|rc| *rc.borrow());
+    let info_ptr: *mut c_void = unsafe { q::JS_GetOpaque(*obj_ref.borrow_value(), class_id) };
+    if info_ptr.is_null() {
+        None
+    } else {
+        let info: &ProxyInstanceInfo = unsafe { &*(info_ptr as *mut ProxyInstanceInfo) };
+        Some((info.class_name.clone(), info.id))
+    }
+}
+
+/// get the context id and instance id of an instance of a Proxy class, used to create a [WeakJSValueRef](../valueref/struct.WeakJSValueRef.html)
+pub(crate) fn get_proxy_instance_context_and_id(obj_ref: &JSValueRef) -> Option<(String, usize)> {
+    if !obj_ref.is_object() {
+        return None;
+    }
+    opt_proxy_instance_info(obj_ref.borrow_value()).map(|info| (info.context_id.clone(), info.id))
+}
+
+/// check if an instance is still alive
+/// the object is compared as well so an id which was reused by a newer instance is not mistaken for the original instance
+pub(crate) fn is_proxy_instance_alive(
+    q_ctx: &QuickJsContext,
+    instance_id: usize,
+    obj_ptr: *mut c_void,
+) -> bool {
+    let mappings = &*q_ctx.instance_id_mappings.borrow();
+    mappings
+        .get(&instance_id)
+        .is_some_and(|info| info.obj_ptr == obj_ptr)
+}
+
+/// get the chain of Proxy classes of a class, starting with the class itself followed by its parent classes
+fn get_proxy_chain(q_ctx: &QuickJsContext, class_name: &str) -> Vec<Rc<Proxy>> {
+    let mut chain = vec![];
+    let mut opt_class_name = Some(class_name.to_string());
+    while let Some(class_name) = opt_class_name {
+        if let Some(proxy) = get_proxy(q_ctx, class_name.as_str()) {
+            opt_class_name = proxy.parent_class_name.clone();
+            chain.push(proxy);
+        } else {
+            opt_class_name = None;
+        }
+    }
+    chain
+}
+
+/// get the chain of Proxy classes of a class like [get_proxy_chain] but without installing classes of the ProxyRegistry, this is used while the GC runs
+fn get_installed_proxy_chain(q_ctx: &QuickJsContext, class_name: &str) -> Vec<Rc<Proxy>> {
+    let mut chain = vec![];
+    if let Ok(registry) = q_ctx.proxy_registry.try_borrow() {
+        let mut opt_proxy = registry.get(class_name);
+        while let Some(proxy) = opt_proxy {
+            chain.push(proxy.clone());
+            opt_proxy = proxy
+                .parent_class_name
+                .as_ref()
+                .and_then(|parent_class_name| registry.get(parent_class_name));
+        }
+    }
+    chain
+}
+
+/// find the first Proxy in the chain of a class (see [get_proxy_chain]) which matches a predicate
+fn find_proxy<P>(q_ctx: &QuickJsContext, class_name: &str, predicate: P) -> Option<Rc<Proxy>>
+where
+    P: Fn(&Proxy) -> bool,
+{
+    get_proxy_chain(q_ctx, class_name)
+        .into_iter()
+        .find(|proxy| predicate(proxy))
+}
+
+/// the function data of the functions which are defined for the members of a Proxy class, [class_name, member_name]
+fn new_member_data(
+    q_ctx: &QuickJsContext,
+    class_name: &str,
+    name: &str,
+) -> Result<JSValueRef, EsError> {
+    let data_ref = arrays::create_array_q(q_ctx)?;
+    arrays::set_element_q(
+        q_ctx,
+        &data_ref,
+        0,
+        primitives::from_string_q(q_ctx, class_name)?,
+    )?;
+    arrays::set_element_q(q_ctx, &data_ref, 1, primitives::from_string_q(q_ctx, name)?)?;
+    Ok(data_ref)
+}
+
+unsafe fn get_member_data(
+    context: *mut q::JSContext,
+    func_data: *mut q::JSValue,
+) -> (String, String) {
+    let data_ref = JSValueRef::new(
+        context,
+        *func_data,
+        false,
+        false,
+        "reflection::get_member_data func_data",
+    );
+    let class_name_ref =
+        arrays::get_element(context, &data_ref, 0).expect("could not get class_name");
+    let name_ref = arrays::get_element(context, &data_ref, 1).expect("could not get member name");
+    let class_name =
+        primitives::to_string(context, &class_name_ref).expect("could not to_string class_name");
+    let name = primitives::to_string(context, &name_ref).expect("could not to_string member name");
+    (class_name, name)
+}
+
+/// get the info of this_val for a member of a Proxy class, returns an Err if this_val is not an instance of a Proxy class
+fn get_this_info<'a>(
+    q_ctx: &QuickJsContext,
+    this_val: &'a q::JSValue,
+    class_name: &str,
+    name: &str,
+) -> Result<&'a ProxyInstanceInfo, EsError> {
+    let not_an_instance = || {
+        EsError::new_kind(
+            EsErrorKind::Type,
+            format!(
+                "{}.{} was called on an object which is not an instance of {}",
+                class_name, name, class_name
+            ),
+        )
+    };
+    let info = opt_proxy_instance_info(this_val).ok_or_else(not_an_instance)?;
+    // the member may also be called on instances of classes which extend the class of the member
+    if info.class_name != class_name
+        && !get_proxy_chain(q_ctx, &info.class_name)
+            .iter()
+            .any(|proxy| proxy.get_class_name() == class_name)
+    {
+        return Err(not_an_instance());
+    }
+    Ok(info)
+}
+
 #[allow(dead_code)]
 unsafe extern "C" fn finalizer(_rt: *mut q::JSRuntime, val: q::JSValue) {
     //todo
@@ -675,214 +2032,293 @@ unsafe extern "C" fn finalizer(_rt: *mut q::JSRuntime, val: q::JSValue) {
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_context(&info.context_id);
         log::trace!("finalizer called, got q_ctx");
-        let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry.get(&info.class_name).unwrap();
 
-        if let Some(finalizer) = &proxy.finalizer {
-            log::trace!("calling Proxy's finalizer");
-            finalizer(q_ctx, info.id);
-            log::trace!("after calling Proxy's finalizer");
+        // the finalizers of the class and its parent classes are all called
+        for proxy in get_proxy_chain(q_ctx, &info.class_name) {
+            if let Some(finalizer) = &proxy.finalizer {
+                log::trace!("calling Proxy's finalizer");
+                finalizer(q_ctx, info.id);
+                log::trace!("after calling Proxy's finalizer");
+            }
         }
 
-        {
+        let removed_info = {
             log::trace!("reflection::finalizer: remove from INSTANCE_ID_MAPPINGS");
             let id_map = &mut *q_ctx.instance_id_mappings.borrow_mut();
-            let _ = id_map.remove(&info.id).expect("no such id to finalize");
+            let removed_info = id_map.remove(&info.id).expect("no such id to finalize");
             log::trace!("reflection::finalizer: remove from INSTANCE_ID_MAPPINGS -> done");
-        }
+            removed_info
+        };
+        // the data of the instance is dropped after the mappings are released, dropping it may finalize other instances
+        drop(removed_info);
         log::trace!("reflection::finalizer: 2");
 
         log::trace!("reflection::finalizer: 3, exit");
     });
 }
 
-#[allow(dead_code)]
-unsafe extern "C" fn proxy_static_get_prop(
+/// marks the values which are held by an instance, see [Proxy::gc_mark](struct.Proxy.html#method.gc_mark)
+pub struct GcMarker {
+    runtime: *mut q::JSRuntime,
+    mark_func: q::JS_MarkFunc,
+}
+
+impl GcMarker {
+    /// mark a value which is held by an instance, values which do not hold a reference count are ignored
+    pub fn mark(&self, value: &JSValueRef) {
+        if value.ref_ct_decr_on_drop {
+            unsafe { q::JS_MarkValue(self.runtime, *value.borrow_value(), self.mark_func) };
+        }
+    }
+}
+
+unsafe extern "C" fn gc_mark(rt: *mut q::JSRuntime, val: q::JSValue, mark_func: q::JS_MarkFunc) {
+    let info = if let Some(info) = opt_proxy_instance_info(&val) {
+        info
+    } else {
+        return;
+    };
+    let marker = GcMarker {
+        runtime: rt,
+        mark_func,
+    };
+    QuickJsRuntime::do_with(|q_js_rt| {
+        if let Some(q_ctx) = q_js_rt.opt_context(&info.context_id) {
+            // the gc_mark functions of the class and its parent classes are all called
+            for proxy in get_installed_proxy_chain(q_ctx, &info.class_name) {
+                if let Some(gc_mark) = &proxy.gc_mark {
+                    gc_mark(&info.id, info.data.as_ref(), &marker);
+                }
+            }
+        }
+    });
+}
+
+unsafe extern "C" fn proxy_instance_method(
     context: *mut q::JSContext,
-    obj: q::JSValue,
-    atom: q::JSAtom,
-    receiver: q::JSValue,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
 ) -> q::JSValue {
-    // static proxy class, not an instance
-    trace!("proxy_static_get_prop");
+    trace!("proxy_instance_method");
+    let (class_name, func_name) = get_member_data(context, func_data);
+    trace!("proxy_instance_method: {}.{}", class_name, func_name);
 
-    let _obj_ref = JSValueRef::new(
+    call_proxy_instance_method(
         context,
-        obj,
-        false,
-        false,
-        "reflection::proxy_static_get_prop obj",
+        this_val,
+        argc,
+        argv,
+        &class_name,
+        &func_name,
+        |proxy| proxy.methods.get(func_name.as_str()),
+    )
+}
+
+unsafe extern "C" fn proxy_instance_symbol_method(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
+) -> q::JSValue {
+    trace!("proxy_instance_symbol_method");
+    let (class_name, symbol_name) = get_member_data(context, func_data);
+    trace!(
+        "proxy_instance_symbol_method: {}.{}",
+        class_name,
+        symbol_name
     );
-    let receiver_ref = JSValueRef::new(
+
+    call_proxy_instance_method(
         context,
-        receiver,
-        false,
-        false,
-        "reflection::proxy_static_get_prop receiver",
-    );
+        this_val,
+        argc,
+        argv,
+        &class_name,
+        &format!("[Symbol.{}]", symbol_name),
+        |proxy| proxy.symbol_methods.get(symbol_name.as_str()),
+    )
+}
 
+/// call a method of a Proxy class for this_val, the method is looked up in the Proxy of the class
+unsafe fn call_proxy_instance_method<L>(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    class_name: &str,
+    func_name: &str,
+    lookup: L,
+) -> q::JSValue
+where
+    L: for<'a> Fn(&'a Proxy) -> Option<&'a Box<ProxyInstanceMethod>>,
+{
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let proxy_name_ref = objects::get_property(context, &receiver_ref, "name")
-            .ok()
-            .unwrap();
-        let proxy_name = primitives::to_string(context, &proxy_name_ref)
-            .ok()
-            .unwrap();
-        trace!("proxy_static_get_prop: {}", proxy_name);
-
-        let prop_name = atoms::to_string2(context, &atom)
-            .ok()
-            .expect("could not get name");
-        trace!("proxy_static_get_prop: prop: {}", prop_name);
-
-        let registry = &*q_ctx.proxy_registry.borrow();
-        if let Some(proxy) = registry.get(proxy_name.as_str()) {
-            if proxy.static_methods.contains_key(&prop_name) {
-                trace!("found method for {}", prop_name);
-
-                let function_data_ref = from_string(context, prop_name.as_str())
-                    .ok()
-                    .expect("could not create function_data_ref");
-
-                let func_ref = functions::new_native_function_data(
-                    context,
-                    Some(proxy_static_method),
-                    prop_name.as_str(),
-                    1,
-                    function_data_ref,
-                )
-                .ok()
-                .expect("could not create func");
+        let proxy_instance_info = match get_this_info(q_ctx, &this_val, class_name, func_name) {
+            Ok(info) => info,
+            Err(e) => return errors::throw_es_error(context, &e),
+        };
 
-                objects::set_property(context, &receiver_ref, prop_name.as_str(), &func_ref)
-                    .ok()
-                    .expect("set_property 9656738 failed");
+        let args_vec = parse_args(context, argc, argv);
+        let this_ref = JSValueRef::new(
+            context,
+            this_val,
+            true,
+            true,
+            "reflection::call_proxy_instance_method this_val",
+        );
 
-                func_ref.clone_value_incr_rc()
-            } else if let Some(native_static_method) = proxy.static_native_methods.get(&prop_name) {
-                trace!("found static native method for {}", prop_name);
+        let proxy = get_proxy(q_ctx, class_name).unwrap();
+        if let Some(method) = lookup(&proxy) {
+            let m_res: Result<JSValueRef, EsError> =
+                method(q_ctx, &this_ref, &proxy_instance_info.id, args_vec);
 
-                let func_ref = functions::new_native_function(
-                    context,
-                    &prop_name,
-                    *native_static_method,
-                    1,
-                    false,
-                )
-                .ok()
-                .expect("could not create func");
-
-                objects::set_property(context, &receiver_ref, prop_name.as_str(), &func_ref)
-                    .ok()
-                    .expect("set_property 36099 failed");
-
-                func_ref.clone_value_incr_rc()
-            } else if let Some(getter_setter) = proxy.static_getters_setters.get(&prop_name) {
-                // call the getter
-                let getter = &getter_setter.0;
-                let res: Result<JSValueRef, EsError> = getter(q_ctx);
-                match res {
-                    Ok(g_val) => g_val.clone_value_incr_rc(),
-                    Err(e) => {
-                        let es = format!("proxy_static_get_prop failed: {}", e);
-                        q_ctx.report_ex(es.as_str())
-                    }
+            match m_res {
+                Ok(m_res_ref) => m_res_ref.clone_value_incr_rc(),
+                Err(e) => {
+                    let msg = format!("proxy_instance_method failed: {}", e.get_message());
+                    errors::throw_es_error(context, &e.with_message(msg))
                 }
-            } else {
-                quickjs_utils::new_null()
             }
         } else {
-            q_ctx.report_ex("proxy class not found")
+            // return null if nothing was returned
+            quickjs_utils::new_null()
         }
     })
 }
 
-#[allow(dead_code)]
-unsafe extern "C" fn proxy_instance_get_prop(
+unsafe extern "C" fn proxy_instance_getter(
     context: *mut q::JSContext,
-    obj: q::JSValue,
-    atom: q::JSAtom,
-    receiver: q::JSValue,
+    this_val: q::JSValue,
+    _argc: ::std::os::raw::c_int,
+    _argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
 ) -> q::JSValue {
-    trace!("proxy_instance_get_prop");
-
-    let _obj_ref = JSValueRef::new(
-        context,
-        obj,
-        false,
-        false,
-        "reflection::proxy_instance_get_prop obj",
-    );
-    let receiver_ref = JSValueRef::new(
-        context,
-        receiver,
-        false,
-        false,
-        "reflection::proxy_instance_get_prop receiver",
-    );
+    trace!("proxy_instance_getter");
+    let (class_name, prop_name) = get_member_data(context, func_data);
 
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let prop_name = atoms::to_string2(context, &atom)
-            .ok()
-            .expect("could not get name");
-        trace!("proxy_instance_get_prop: {}", prop_name);
+        let info = match get_this_info(q_ctx, &this_val, &class_name, &prop_name) {
+            Ok(info) => info,
+            Err(e) => return errors::throw_es_error(context, &e),
+        };
 
-        let info = get_proxy_instance_info(&obj);
+        let proxy = get_proxy(q_ctx, class_name.as_str()).unwrap();
+        let getter = &proxy.getters_setters.get(&prop_name).unwrap().0;
+        match getter(q_ctx, &info.id) {
+            Ok(g_val) => g_val.clone_value_incr_rc(),
+            Err(e) => {
+                let msg = format!("proxy_instance_get_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg))
+            }
+        }
+    })
+}
 
-        trace!("obj_ref.classname = {}", info.class_name);
+unsafe extern "C" fn proxy_instance_setter(
+    context: *mut q::JSContext,
+    this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
+) -> q::JSValue {
+    trace!("proxy_instance_setter");
+    let (class_name, prop_name) = get_member_data(context, func_data);
 
-        // see if we have a matching method
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry.get(&info.class_name).unwrap();
-        if proxy.methods.contains_key(&prop_name) {
-            trace!("found method for {}", prop_name);
+        let info = match get_this_info(q_ctx, &this_val, &class_name, &prop_name) {
+            Ok(info) => info,
+            Err(e) => return errors::throw_es_error(context, &e),
+        };
 
-            let function_data_ref = from_string(context, prop_name.as_str())
-                .ok()
-                .expect("could not create function_data_ref");
+        let value_ref = parse_args(context, argc, argv)
+            .into_iter()
+            .next()
+            .unwrap_or_else(quickjs_utils::new_undefined_ref);
 
-            let func_ref = functions::new_native_function_data(
-                context,
-                Some(proxy_instance_method),
-                prop_name.as_str(),
-                1,
-                function_data_ref,
-            )
-            .ok()
-            .expect("could not create func");
+        let proxy = get_proxy(q_ctx, class_name.as_str()).unwrap();
+        let setter = &proxy.getters_setters.get(&prop_name).unwrap().1;
+        match setter(q_ctx, &info.id, value_ref) {
+            Ok(()) => quickjs_utils::new_undefined(),
+            Err(e) => {
+                let msg = format!("proxy_instance_set_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg))
+            }
+        }
+    })
+}
 
-            objects::set_property(context, &receiver_ref, prop_name.as_str(), &func_ref)
-                .ok()
-                .expect("set_property 96385 failed"); // todo report ex
+#[allow(dead_code)]
+unsafe extern "C" fn proxy_static_method(
+    context: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
+) -> q::JSValue {
+    trace!("proxy_static_method");
+    let (proxy_name, func_name) = get_member_data(context, func_data);
+    trace!("proxy_static_method: {}.{}", proxy_name, func_name);
 
-            func_ref.clone_value_incr_rc()
-        } else if let Some(native_method) = proxy.native_methods.get(&prop_name) {
-            trace!("found native method for {}", prop_name);
+    call_proxy_static_method(context, argc, argv, &proxy_name, |proxy| {
+        proxy.static_methods.get(func_name.as_str())
+    })
+}
 
-            let func_ref =
-                functions::new_native_function(context, &prop_name, *native_method, 1, false)
-                    .ok()
-                    .expect("could not create func"); // tyodo report ex
+unsafe extern "C" fn proxy_static_symbol_method(
+    context: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
+) -> q::JSValue {
+    trace!("proxy_static_symbol_method");
+    let (proxy_name, symbol_name) = get_member_data(context, func_data);
+    trace!("proxy_static_symbol_method: {}.{}", proxy_name, symbol_name);
 
-            objects::set_property(context, &receiver_ref, prop_name.as_str(), &func_ref)
-                .ok()
-                .expect("set_property 49671 failed"); // todo report ex
+    call_proxy_static_method(context, argc, argv, &proxy_name, |proxy| {
+        proxy.static_symbol_methods.get(symbol_name.as_str())
+    })
+}
 
-            func_ref.clone_value_incr_rc()
-        } else if let Some(getter_setter) = proxy.getters_setters.get(&prop_name) {
-            // call the getter
-            let getter = &getter_setter.0;
-            let res: Result<JSValueRef, EsError> = getter(q_ctx, &info.id);
-            match res {
-                Ok(g_val) => g_val.clone_value_incr_rc(),
+/// call a static method of a Proxy class, the method is looked up in the Proxy of the class
+unsafe fn call_proxy_static_method<L>(
+    context: *mut q::JSContext,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+    proxy_name: &str,
+    lookup: L,
+) -> q::JSValue
+where
+    L: for<'a> Fn(&'a Proxy) -> Option<&'a Box<ProxyStaticMethod>>,
+{
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
+
+        let args_vec = parse_args(context, argc, argv);
+
+        let proxy = get_proxy(q_ctx, proxy_name).unwrap();
+        if let Some(method) = lookup(&proxy) {
+            let m_res: Result<JSValueRef, EsError> = method(q_ctx, args_vec);
+            match m_res {
+                Ok(m_res_ref) => m_res_ref.clone_value_incr_rc(),
                 Err(e) => {
-                    let err = format!("proxy_instance_get_prop failed: {}", e);
-                    q_ctx.report_ex(err.as_str())
+                    let msg = format!("proxy_static_method failed: {}", e.get_message());
+                    q_ctx.report_es_error(&e.with_message(msg))
                 }
             }
         } else {
@@ -890,180 +2326,469 @@ unsafe extern "C" fn proxy_instance_get_prop(
             quickjs_utils::new_null()
         }
     })
-
-    // get constructor name
-    // get proxy
-    // get method or getter or setter
-    // return native func (cache those?)
-}
-#[allow(dead_code)]
-unsafe extern "C" fn proxy_instance_has_prop(
-    _context: *mut q::JSContext,
-    _obj: q::JSValue,
-    _atom: q::JSAtom,
-) -> ::std::os::raw::c_int {
-    trace!("proxy_instance_has_prop");
-    0
 }
-#[allow(dead_code)]
-unsafe extern "C" fn proxy_static_has_prop(
-    _context: *mut q::JSContext,
-    _obj: q::JSValue,
-    _atom: q::JSAtom,
-) -> ::std::os::raw::c_int {
-    trace!("proxy_static_has_prop");
-    0
+
+unsafe extern "C" fn proxy_static_getter(
+    context: *mut q::JSContext,
+    _this_val: q::JSValue,
+    _argc: ::std::os::raw::c_int,
+    _argv: *mut q::JSValue,
+    _magic: ::std::os::raw::c_int,
+    func_data: *mut q::JSValue,
+) -> q::JSValue {
+    trace!("proxy_static_getter");
+    let (proxy_name, prop_name) = get_member_data(context, func_data);
+
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
+
+        let proxy = get_proxy(q_ctx, proxy_name.as_str()).unwrap();
+        let getter = &proxy.static_getters_setters.get(&prop_name).unwrap().0;
+        match getter(q_ctx) {
+            Ok(g_val) => g_val.clone_value_incr_rc(),
+            Err(e) => {
+                let msg = format!("proxy_static_get_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg))
+            }
+        }
+    })
 }
 
-unsafe extern "C" fn proxy_instance_method(
+unsafe extern "C" fn proxy_static_setter(
     context: *mut q::JSContext,
-    this_val: q::JSValue,
+    _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
     _magic: ::std::os::raw::c_int,
     func_data: *mut q::JSValue,
 ) -> q::JSValue {
-    trace!("proxy_instance_method");
+    trace!("proxy_static_setter");
+    let (proxy_name, prop_name) = get_member_data(context, func_data);
+
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let proxy_instance_info: &ProxyInstanceInfo = get_proxy_instance_info(&this_val);
+        let value_ref = parse_args(context, argc, argv)
+            .into_iter()
+            .next()
+            .unwrap_or_else(quickjs_utils::new_undefined_ref);
 
-        let args_vec = parse_args(context, argc, argv);
+        let proxy = get_proxy(q_ctx, proxy_name.as_str()).unwrap();
+        let setter = &proxy.static_getters_setters.get(&prop_name).unwrap().1;
+        match setter(q_ctx, value_ref) {
+            Ok(()) => quickjs_utils::new_undefined(),
+            Err(e) => {
+                let msg = format!("proxy_static_set_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg))
+            }
+        }
+    })
+}
+
+/// the names of the getters/setters of a chain of Proxy classes
+fn get_getter_setter_names(chain: &[Rc<Proxy>]) -> Vec<String> {
+    let mut names = vec![];
+    for proxy in chain {
+        let mut proxy_names: Vec<&String> = proxy.getters_setters.keys().collect();
+        proxy_names.sort();
+        for name in proxy_names {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// find the getter and setter of a property in the prototype chain of an object, returns None if the first property found is not an accessor
+unsafe fn find_accessor(
+    context: *mut q::JSContext,
+    obj: q::JSValue,
+    atom: q::JSAtom,
+) -> Result<Option<(JSValueRef, JSValueRef)>, EsError> {
+    let obj_ref = JSValueRef::new(context, obj, false, false, "reflection::find_accessor obj");
+    let mut proto_ref = objects::get_prototype(context, &obj_ref)?;
+    while proto_ref.is_object() {
+        let mut desc = q::JSPropertyDescriptor {
+            flags: 0,
+            value: quickjs_utils::new_undefined(),
+            getter: quickjs_utils::new_undefined(),
+            setter: quickjs_utils::new_undefined(),
+        };
+        let res = q::JS_GetOwnProperty(context, &mut desc, *proto_ref.borrow_value(), atom);
+        if res < 0 {
+            return Err(QuickJsContext::get_exception(context)
+                .unwrap_or_else(|| EsError::new_str("could not get own property")));
+        }
+        if res > 0 {
+            let _value_ref = JSValueRef::new(
+                context,
+                desc.value,
+                false,
+                true,
+                "reflection::find_accessor value",
+            );
+            let getter_ref = JSValueRef::new(
+                context,
+                desc.getter,
+                false,
+                true,
+                "reflection::find_accessor getter",
+            );
+            let setter_ref = JSValueRef::new(
+                context,
+                desc.setter,
+                false,
+                true,
+                "reflection::find_accessor setter",
+            );
+            return Ok(if desc.flags & q::JS_PROP_GETSET as i32 != 0 {
+                Some((getter_ref, setter_ref))
+            } else {
+                None
+            });
+        }
+        proto_ref = objects::get_prototype(context, &proto_ref)?;
+    }
+    Ok(None)
+}
+
+unsafe extern "C" fn proxy_instance_get_own_prop(
+    context: *mut q::JSContext,
+    desc: *mut q::JSPropertyDescriptor,
+    obj: q::JSValue,
+    atom: q::JSAtom,
+) -> ::std::os::raw::c_int {
+    trace!("proxy_instance_get_own_prop");
+
+    if atoms::is_symbol(context, &atom) {
+        return 0;
+    }
+    let info = match opt_proxy_instance_info(&obj) {
+        Some(info) => info,
+        None => return 0,
+    };
+    let prop_name = atoms::to_string2(context, &atom).expect("could not get name");
+
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
+        let chain = get_proxy_chain(q_ctx, &info.class_name);
+
+        let res: Result<Option<q::JSPropertyDescriptor>, EsError> = if chain
+            .iter()
+            .any(|proxy| proxy.getters_setters.contains_key(&prop_name))
+        {
+            // the getters/setters are reported as own properties, the accessor is the one found in the prototype chain so a subclass which was created in script may override it
+            find_accessor(context, obj, atom).map(|opt| {
+                opt.map(|(getter_ref, setter_ref)| q::JSPropertyDescriptor {
+                    flags: (q::JS_PROP_GETSET | q::JS_PROP_ENUMERABLE) as i32,
+                    value: quickjs_utils::new_undefined(),
+                    getter: getter_ref.clone_value_incr_rc(),
+                    setter: setter_ref.clone_value_incr_rc(),
+                })
+            })
+        } else if let Some(handler) = chain.iter().find_map(|p| p.property_handler.as_ref()) {
+            handler
+                .has_property(q_ctx, &info.id, &prop_name)
+                .and_then(|has| {
+                    if !has {
+                        return Ok(None);
+                    }
+                    let value_ref = if let Some(get) = &handler.get {
+                        get(q_ctx, &info.id, &prop_name)?
+                    } else {
+                        None
+                    };
+                    let mut flags = q::JS_PROP_ENUMERABLE | q::JS_PROP_CONFIGURABLE;
+                    if handler.set.is_some() {
+                        flags |= q::JS_PROP_WRITABLE;
+                    }
+                    Ok(Some(q::JSPropertyDescriptor {
+                        flags: flags as i32,
+                        value: value_ref
+                            .map(|v| v.clone_value_incr_rc())
+                            .unwrap_or_else(quickjs_utils::new_undefined),
+                        getter: quickjs_utils::new_undefined(),
+                        setter: quickjs_utils::new_undefined(),
+                    }))
+                })
+        } else {
+            Ok(None)
+        };
+
+        match res {
+            Ok(Some(prop_desc)) => {
+                if desc.is_null() {
+                    // only the existence of the property was checked
+                    let _desc_ref = (
+                        JSValueRef::new(context, prop_desc.value, false, true, "desc.value"),
+                        JSValueRef::new(context, prop_desc.getter, false, true, "desc.getter"),
+                        JSValueRef::new(context, prop_desc.setter, false, true, "desc.setter"),
+                    );
+                } else {
+                    *desc = prop_desc;
+                }
+                1
+            }
+            Ok(None) => 0,
+            Err(e) => {
+                let msg = format!("proxy_instance_get_own_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg));
+                -1
+            }
+        }
+    })
+}
 
-        let func_name_ref = JSValueRef::new(
-            context,
-            *func_data,
-            false,
-            false,
-            "reflection::proxy_instance_method func_data",
-        );
-        let func_name = primitives::to_string(context, &func_name_ref)
-            .ok()
-            .expect("could not to_string func_name_ref");
+unsafe extern "C" fn proxy_instance_get_own_prop_names(
+    context: *mut q::JSContext,
+    ptab: *mut *mut q::JSPropertyEnum,
+    plen: *mut u32,
+    obj: q::JSValue,
+) -> ::std::os::raw::c_int {
+    trace!("proxy_instance_get_own_prop_names");
 
-        trace!("proxy_instance_method: {}", func_name);
+    let info = match opt_proxy_instance_info(&obj) {
+        Some(info) => info,
+        None => return 0,
+    };
 
-        let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry
-            .get(proxy_instance_info.class_name.as_str())
-            .unwrap();
-        if let Some(method) = proxy.methods.get(func_name.as_str()) {
-            // todo report ex
-            let m_res: Result<JSValueRef, EsError> =
-                method(q_ctx, &proxy_instance_info.id, args_vec);
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
+        let chain = get_proxy_chain(q_ctx, &info.class_name);
 
-            match m_res {
-                Ok(m_res_ref) => m_res_ref.clone_value_incr_rc(),
+        let mut names = get_getter_setter_names(&chain);
+        if let Some(keys) = chain
+            .iter()
+            .find_map(|p| p.property_handler.as_ref())
+            .and_then(|handler| handler.keys.as_ref())
+        {
+            match keys(q_ctx, &info.id) {
+                Ok(keys) => {
+                    for key in keys {
+                        if !names.contains(&key) {
+                            names.push(key);
+                        }
+                    }
+                }
                 Err(e) => {
-                    let msg = format!("proxy_instance_method failed: {}", e.get_message());
-                    let err = errors::new_error(context, e.get_name(), msg.as_str(), e.get_stack())
-                        .ok()
-                        .expect("create error failed");
-                    errors::throw(context, err)
+                    let msg = format!(
+                        "proxy_instance_get_own_prop_names failed: {}",
+                        e.get_message()
+                    );
+                    q_ctx.report_es_error(&e.with_message(msg));
+                    return -1;
                 }
             }
-        } else {
-            // return null if nothing was returned
-            quickjs_utils::new_null()
         }
+
+        // the table is freed by quickjs so it needs to be allocated by quickjs
+        let size = std::mem::size_of::<q::JSPropertyEnum>() * std::cmp::max(names.len(), 1);
+        let tab = q::js_malloc(context, size as _) as *mut q::JSPropertyEnum;
+        if tab.is_null() {
+            return -1;
+        }
+        for (idx, name) in names.iter().enumerate() {
+            let atom_ref = atoms::from_string(context, name).expect("could not create atom");
+            // the atom is owned by the table
+            atom_ref.increment_ref_ct();
+            *tab.add(idx) = q::JSPropertyEnum {
+                is_enumerable: 0,
+                atom: atom_ref.get_atom(),
+            };
+        }
+        *ptab = tab;
+        *plen = names.len() as u32;
+        0
     })
 }
 
-#[allow(dead_code)]
-unsafe extern "C" fn proxy_static_method(
+unsafe extern "C" fn proxy_instance_delete_prop(
     context: *mut q::JSContext,
-    this_val: q::JSValue,
-    argc: ::std::os::raw::c_int,
-    argv: *mut q::JSValue,
-    _magic: ::std::os::raw::c_int,
-    func_data: *mut q::JSValue,
-) -> q::JSValue {
-    trace!("proxy_static_method");
+    obj: q::JSValue,
+    atom: q::JSAtom,
+) -> ::std::os::raw::c_int {
+    trace!("proxy_instance_delete_prop");
+
+    if atoms::is_symbol(context, &atom) {
+        return 1;
+    }
+    let info = match opt_proxy_instance_info(&obj) {
+        Some(info) => info,
+        None => return 1,
+    };
+    let prop_name = atoms::to_string2(context, &atom).expect("could not get name");
+
     QuickJsRuntime::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
-        let this_ref = JSValueRef::new(
-            context,
-            this_val,
-            false,
-            false,
-            "reflection::proxy_static_method this_val",
-        );
+        let chain = get_proxy_chain(q_ctx, &info.class_name);
 
-        let proxy_name_ref = objects::get_property(context, &this_ref, "name")
-            .ok()
-            .unwrap();
-        let proxy_name = primitives::to_string(context, &proxy_name_ref)
-            .ok()
-            .expect("could not to_string classname");
+        if chain
+            .iter()
+            .any(|proxy| proxy.getters_setters.contains_key(&prop_name))
+        {
+            // getters/setters can not be deleted
+            return 0;
+        }
+        let res = if let Some(handler) = chain.iter().find_map(|p| p.property_handler.as_ref()) {
+            handler
+                .has_property(q_ctx, &info.id, &prop_name)
+                .and_then(|has| match &handler.delete {
+                    Some(delete) if has => delete(q_ctx, &info.id, &prop_name),
+                    _ => Ok(!has),
+                })
+        } else {
+            Ok(true)
+        };
+        match res {
+            Ok(deleted) => deleted as i32,
+            Err(e) => {
+                let msg = format!("proxy_instance_delete_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg));
+                -1
+            }
+        }
+    })
+}
 
-        let args_vec = parse_args(context, argc, argv);
+unsafe extern "C" fn proxy_instance_define_own_prop(
+    context: *mut q::JSContext,
+    this_obj: q::JSValue,
+    atom: q::JSAtom,
+    val: q::JSValue,
+    getter: q::JSValue,
+    setter: q::JSValue,
+    flags: ::std::os::raw::c_int,
+) -> ::std::os::raw::c_int {
+    trace!("proxy_instance_define_own_prop");
 
-        let func_name_ref = JSValueRef::new(
+    // the properties which are not handled here are defined on the instance itself
+    let define_default = || {
+        q::JS_DefineProperty(
             context,
-            *func_data,
-            false,
-            false,
-            "reflection::proxy_static_method func_data",
-        );
-        let func_name = primitives::to_string(context, &func_name_ref)
-            .ok()
-            .expect("could not to_string func_name_ref");
+            this_obj,
+            atom,
+            val,
+            getter,
+            setter,
+            flags | q::JS_PROP_NO_EXOTIC as i32,
+        )
+    };
 
-        trace!("proxy_static_method: {}", func_name);
+    if atoms::is_symbol(context, &atom) {
+        return define_default();
+    }
+    let info = match opt_proxy_instance_info(&this_obj) {
+        Some(info) => info,
+        None => return define_default(),
+    };
+    let prop_name = atoms::to_string2(context, &atom).expect("could not get name");
+
+    let has_value = flags & q::JS_PROP_HAS_VALUE as i32 != 0;
+    let has_getter_setter = flags & (q::JS_PROP_HAS_GET | q::JS_PROP_HAS_SET) as i32 != 0;
+    // when defining fails an Error is only thrown if the flags say so, otherwise false is returned
+    let fail = |msg: String| {
+        if flags & q::JS_PROP_THROW as i32 != 0 {
+            Err(EsError::new_kind(EsErrorKind::Type, msg))
+        } else {
+            Ok(Some(false))
+        }
+    };
 
-        let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry.get(proxy_name.as_str()).unwrap();
-        if let Some(method) = proxy.static_methods.get(func_name.as_str()) {
-            let m_res: Result<JSValueRef, EsError> = method(q_ctx, args_vec);
-            match m_res {
-                Ok(m_res_ref) => m_res_ref.clone_value_incr_rc(),
-                Err(e) => {
-                    let err = format!("proxy_static_method failed: {}", e);
-                    q_ctx.report_ex(err.as_str())
+    QuickJsRuntime::do_with(|q_js_rt| {
+        let q_ctx = q_js_rt.get_quickjs_context(context);
+        let chain = get_proxy_chain(q_ctx, &info.class_name);
+
+        let res: Result<Option<bool>, EsError> = if let Some(proxy) = chain
+            .iter()
+            .find(|proxy| proxy.getters_setters.contains_key(&prop_name))
+        {
+            if has_getter_setter {
+                fail(format!(
+                    "property {} of {} can not be redefined",
+                    prop_name, info.class_name
+                ))
+            } else if has_value {
+                // defining a value calls the setter
+                let setter = &proxy.getters_setters.get(&prop_name).unwrap().1;
+                let value_ref = JSValueRef::new(
+                    context,
+                    val,
+                    true,
+                    true,
+                    "reflection::proxy_instance_define_own_prop val",
+                );
+                setter(q_ctx, &info.id, value_ref).map(|_| Some(true))
+            } else {
+                // only the attributes are changed, these are fixed for getters/setters
+                Ok(Some(true))
+            }
+        } else if let Some(handler) = chain.iter().find_map(|p| p.property_handler.as_ref()) {
+            match &handler.set {
+                Some(set) if has_value && !has_getter_setter => {
+                    let value_ref = JSValueRef::new(
+                        context,
+                        val,
+                        true,
+                        true,
+                        "reflection::proxy_instance_define_own_prop val",
+                    );
+                    set(q_ctx, &info.id, &prop_name, value_ref).map(|handled| {
+                        if handled {
+                            Some(true)
+                        } else {
+                            None
+                        }
+                    })
                 }
+                Some(_) => Ok(None),
+                None => handler
+                    .has_property(q_ctx, &info.id, &prop_name)
+                    .and_then(|has| {
+                        if has {
+                            fail(format!(
+                                "property {} of {} is read only",
+                                prop_name, info.class_name
+                            ))
+                        } else {
+                            Ok(None)
+                        }
+                    }),
             }
         } else {
-            // return null if nothing was returned
-            quickjs_utils::new_null()
+            Ok(None)
+        };
+
+        match res {
+            Ok(Some(defined)) => defined as i32,
+            Ok(None) => define_default(),
+            Err(e) => {
+                let msg = format!("proxy_instance_define_own_prop failed: {}", e.get_message());
+                q_ctx.report_es_error(&e.with_message(msg));
+                -1
+            }
         }
     })
 }
 
-unsafe extern "C" fn proxy_static_set_prop(
-    _context: *mut q::JSContext,
-    _obj: q::JSValue,
-    _atom: q::JSAtom,
-    _value: q::JSValue,
-    _receiver: q::JSValue,
-    _flags: ::std::os::raw::c_int,
-) -> ::std::os::raw::c_int {
-    trace!("proxy_static_set_prop");
-    0
-}
-
-unsafe extern "C" fn proxy_instance_set_prop(
-    _context: *mut q::JSContext,
-    _obj: q::JSValue,
-    _atom: q::JSAtom,
-    _value: q::JSValue,
-    _receiver: q::JSValue,
-    _flags: ::std::os::raw::c_int,
-) -> ::std::os::raw::c_int {
-    trace!("proxy_instance_set_prop");
-    0
-}
-
 #[cfg(test)]
 pub mod tests {
-    use crate::eserror::EsError;
+    use crate::eserror::{EsError, EsErrorKind};
     use crate::esruntime::tests::init_test_rt;
+    use crate::esruntimebuilder::EsRuntimeBuilder;
     use crate::esscript::EsScript;
-    use crate::quickjs_utils::{functions, primitives};
-    use crate::reflection::Proxy;
+    use crate::esvalue::{EsPromise, EsValueConvertible};
+    use crate::quickjs_utils::{functions, objects, primitives};
+    use crate::reflection::{
+        get_instance_data, get_proxy, new_instance_with_data, Proxy, ProxyPropertyHandler,
+        ProxyRegistry,
+    };
+    use crate::valueref::{JSValueRef, WeakJSValueRef};
     use log::trace;
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
     use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::time::Duration;
 
     thread_local! {
@@ -1221,4 +2946,510 @@ pub mod tests {
 
         log::info!("< test_proxy");
     }
+
+    struct TestData {
+        name: String,
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Drop for TestData {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    thread_local! {
+        static TEST_DATA_DROPS: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+    }
+
+    #[test]
+    pub fn test_data_proxy() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<TestData>()
+                .name("TestDataClass")
+                .constructor(|q_ctx, args| {
+                    Ok(TestData {
+                        name: primitives::to_string_q(q_ctx, &args[0])?,
+                        drops: TEST_DATA_DROPS.with(|drops| drops.clone()),
+                    })
+                })
+                .method("getName", |q_ctx, data, _args| {
+                    primitives::from_string_q(q_ctx, data.name.as_str())
+                })
+                .method_mut("callBack", |q_ctx, data, args| {
+                    data.name.push('!');
+                    functions::call_function_q(q_ctx, &args[0], vec![], None)
+                })
+                .getter_setter(
+                    "name",
+                    |q_ctx, data| primitives::from_string_q(q_ctx, data.name.as_str()),
+                    |q_ctx, data, value| {
+                        data.name = primitives::to_string_q(q_ctx, &value)?;
+                        Ok(())
+                    },
+                )
+                .install(q_ctx, true)
+                .expect("install failed");
+
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_data_proxy.es",
+                    "let tdc = new TestDataClass('a'); let r = [tdc.getName(), tdc.name];\
+                     try { tdc.callBack(() => tdc.getName()); } catch(ex) { r.push('reentry failed'); }\
+                     r.push(tdc.getName()); tdc = null; r.join(',');",
+                ))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "a,a,reentry failed,a!"
+            );
+
+            let (id, instance_ref) = new_instance_with_data(
+                "TestDataClass",
+                q_ctx,
+                TestData {
+                    name: "from rust".to_string(),
+                    drops: TEST_DATA_DROPS.with(|drops| drops.clone()),
+                },
+            )
+            .expect("could not create instance");
+            assert_eq!(
+                get_instance_data::<TestData>(q_ctx, id)
+                    .unwrap()
+                    .borrow()
+                    .name
+                    .as_str(),
+                "from rust"
+            );
+            assert!(get_instance_data::<String>(q_ctx, id).is_none());
+            drop(instance_ref);
+
+            let err = new_instance_with_data("TestDataClass", q_ctx, "wrong".to_string())
+                .expect_err("instance with a value of the wrong type was created");
+            assert_eq!(err.get_kind(), EsErrorKind::Type);
+            q_js_rt.gc();
+            assert_eq!(TEST_DATA_DROPS.with(|drops| drops.get()), 2);
+        });
+    }
+
+    thread_local! {
+        static TEST_FINALIZED: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    }
+
+    #[test]
+    pub fn test_extends() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::new()
+                .name("Shape")
+                .constructor_with_new_target(|q_ctx, _id, new_target, _args| {
+                    let name_ref = objects::get_property_q(q_ctx, new_target, "name")?;
+                    let name = primitives::to_string_q(q_ctx, &name_ref)?;
+                    TEST_FINALIZED.with(|rc| rc.borrow_mut().push(format!("new {}", name)));
+                    Ok(())
+                })
+                .finalizer(|_q_ctx, _id| {
+                    TEST_FINALIZED.with(|rc| rc.borrow_mut().push("Shape".to_string()))
+                })
+                .method("area", |_q_ctx, _id, _args| Ok(primitives::from_i32(0)))
+                .method("describe", |q_ctx, _id, _args| {
+                    primitives::from_string_q(q_ctx, "shape")
+                })
+                .static_method("kind", |q_ctx, _args| {
+                    primitives::from_string_q(q_ctx, "geometry")
+                })
+                .install(q_ctx, true)
+                .expect("install failed");
+            Proxy::new()
+                .name("Square")
+                .extends("Shape")
+                .finalizer(|_q_ctx, _id| {
+                    TEST_FINALIZED.with(|rc| rc.borrow_mut().push("Square".to_string()))
+                })
+                .method("area", |_q_ctx, _id, _args| Ok(primitives::from_i32(4)))
+                .install(q_ctx, true)
+                .expect("install failed");
+            Proxy::new()
+                .name("Unrelated")
+                .constructor(|_q_ctx, _id, _args| Ok(()))
+                .getter_setter(
+                    "size",
+                    |_q_ctx, _id| Ok(primitives::from_i32(1)),
+                    |_q_ctx, _id, _val| Ok(()),
+                )
+                .install(q_ctx, true)
+                .expect("install failed");
+
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_extends.es",
+                    "class Big extends Square {\
+                       constructor() { super(); this.factor = 10; }\
+                       area() { return super.area() * this.factor; }\
+                     }\
+                     let sq = new Square(); let big = new Big();\
+                     let r = [sq.area(), sq.describe(), big.area(), big.describe(), Big.kind(), Square.kind()];\
+                     r.push(big instanceof Big, big instanceof Square, big instanceof Shape, sq instanceof Big);\
+                     try { Square.prototype.area.call({}); } catch(ex) { r.push(ex.name); }\
+                     try { Square.prototype.area.call(new Unrelated()); } catch(ex) { r.push(ex.name); }\
+                     try { Object.getOwnPropertyDescriptor(Unrelated.prototype, 'size').get.call(sq); } catch(ex) { r.push(ex.name); }\
+                     sq = null; big = null; r.join(',');",
+                ))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "4,shape,40,shape,geometry,geometry,true,true,true,false,TypeError,TypeError,TypeError"
+            );
+            q_js_rt.gc();
+            TEST_FINALIZED.with(|rc| {
+                assert_eq!(
+                    rc.borrow().join(","),
+                    "new Square,new Big,Square,Shape,Square,Shape"
+                );
+            });
+
+            let res = Proxy::new()
+                .name("Orphan")
+                .extends("NotInstalled")
+                .install(q_ctx, true);
+            assert!(res.is_err());
+        });
+    }
+
+    #[test]
+    pub fn test_own_properties() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<HashMap<String, i32>>()
+                .name("Record")
+                .constructor(|_q_ctx, _args| {
+                    let mut map = HashMap::new();
+                    map.insert("a".to_string(), 1);
+                    Ok(map)
+                })
+                .getter_setter(
+                    "size",
+                    |_q_ctx, map| Ok(primitives::from_i32(map.len() as i32)),
+                    |_q_ctx, _map, _value| Ok(()),
+                )
+                .configure(|proxy| {
+                    proxy.property_handler(
+                        ProxyPropertyHandler::new()
+                            .get(|q_ctx, id, name| {
+                                let map = get_instance_data::<HashMap<String, i32>>(q_ctx, *id)
+                                    .unwrap();
+                                let value = map.borrow().get(name).cloned();
+                                Ok(value.map(primitives::from_i32))
+                            })
+                            .keys(|q_ctx, id| {
+                                let map = get_instance_data::<HashMap<String, i32>>(q_ctx, *id)
+                                    .unwrap();
+                                let keys = map.borrow().keys().cloned().collect();
+                                Ok(keys)
+                            }),
+                    )
+                })
+                .install(q_ctx, true)
+                .expect("install failed");
+
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_own_properties.es",
+                    "'use strict'; let rec = new Record(); rec.extra = true; let r = [];\
+                     for (let k in rec) { r.push(k); }\
+                     r.push(Object.keys(rec).join('|'), JSON.stringify(rec), JSON.stringify({...rec}));\
+                     r.push('a' in rec, 'b' in rec, rec.hasOwnProperty('size'), rec.b);\
+                     try { rec.a = 2; } catch(ex) { r.push(ex.name); }\
+                     try { delete rec.size; } catch(ex) { r.push(ex.name); }\
+                     r.push(delete rec.extra, delete rec.b, rec.a, rec.size);\
+                     r.join(',');",
+                ))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "extra,size,a,extra|size|a,{\"extra\":true,\"size\":1,\"a\":1},{\"extra\":true,\"size\":1,\"a\":1},\
+                 true,false,true,,TypeError,TypeError,true,true,1,1"
+            );
+        });
+    }
+
+    #[test]
+    pub fn test_symbol_members() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<Vec<i32>>()
+                .name("Cursor")
+                .constructor(|_q_ctx, _args| Ok(vec![1, 2, 3]))
+                .configure(|proxy| {
+                    proxy
+                        .to_string_tag("Cursor")
+                        .iterator(|q_ctx, id| {
+                            let rows = get_instance_data::<Vec<i32>>(q_ctx, *id).unwrap();
+                            let rows = rows.borrow().clone();
+                            Ok(rows.into_iter().map(|row| {
+                                if row > 2 {
+                                    Err(EsError::new_str("cursor closed"))
+                                } else {
+                                    Ok(row.to_es_value_facade())
+                                }
+                            }))
+                        })
+                        .async_iterator(|q_ctx, id| {
+                            let rows = get_instance_data::<Vec<i32>>(q_ctx, *id).unwrap();
+                            let rows = rows.borrow().clone();
+                            Ok(rows.into_iter().map(|row| {
+                                Ok(EsPromise::new(move || Ok((row * 10).to_es_value_facade()))
+                                    .to_es_value_facade())
+                            }))
+                        })
+                        .symbol_method("toPrimitive", |q_ctx, id, args| {
+                            let hint = primitives::to_string_q(q_ctx, &args[0])?;
+                            let rows = get_instance_data::<Vec<i32>>(q_ctx, *id).unwrap();
+                            let len = rows.borrow().len();
+                            if hint.eq("number") {
+                                Ok(primitives::from_i32(len as i32))
+                            } else {
+                                primitives::from_string_q(
+                                    q_ctx,
+                                    format!("Cursor({})", len).as_str(),
+                                )
+                            }
+                        })
+                        .static_symbol_method("hasInstance", |_q_ctx, args| {
+                            Ok(primitives::from_bool(args[0].is_object()))
+                        })
+                })
+                .install(q_ctx, true)
+                .expect("install failed");
+        });
+
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_symbol_members.es",
+                "let cur = new Cursor(); let r = [];\
+                 try { for (const row of cur) { r.push(row); } } catch(ex) { r.push(ex.message); }\
+                 r.push(Object.prototype.toString.call(cur), `${cur}`, +cur, ({}) instanceof Cursor, 1 instanceof Cursor);\
+                 (async function() { for await (const row of cur) { r.push(row); } return r.join(','); })();",
+            ))
+            .expect("script failed");
+        let res = res.get_promise_result_sync().expect("promise failed");
+        assert_eq!(
+            res.get_str(),
+            "1,2,cursor closed,[object Cursor],Cursor(3),3,true,false,10,20,30"
+        );
+    }
+
+    #[test]
+    pub fn test_async_method() {
+        static CONNECTION_DROPPED: AtomicBool = AtomicBool::new(false);
+        struct TestConnection {
+            name: String,
+        }
+        impl Drop for TestConnection {
+            fn drop(&mut self) {
+                if self.name.eq("kept") {
+                    CONNECTION_DROPPED.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<TestConnection>()
+                .name("Connection")
+                .constructor(|q_ctx, args| {
+                    Ok(TestConnection {
+                        name: primitives::to_string_q(q_ctx, &args[0])?,
+                    })
+                })
+                .async_method("query", |q_ctx, connection, args| {
+                    let sql = primitives::to_string_q(q_ctx, &args[0])?;
+                    let name = connection.name.clone();
+                    Ok(async move {
+                        if sql.is_empty() {
+                            return Err(EsError::new_kind(
+                                EsErrorKind::Type,
+                                "empty query".to_string(),
+                            ));
+                        }
+                        std::thread::sleep(Duration::from_millis(100));
+                        let dropped = CONNECTION_DROPPED.load(Ordering::SeqCst);
+                        Ok(format!("{} {} {}", name, sql, dropped).to_es_value_facade())
+                    })
+                })
+                .async_static_method("connect", |_q_ctx, _args| {
+                    Ok(async { Ok(true.to_es_value_facade()) })
+                })
+                .install(q_ctx, true)
+                .expect("install failed");
+        });
+
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_async_method.es",
+                "(async function() {\
+                 let r = [await Connection.connect()];\
+                 try { await new Connection('other').query(''); } catch(ex) { r.push(ex.name); }\
+                 return r.join(',');\
+                 })();",
+            ))
+            .expect("script failed");
+        let res = res.get_promise_result_sync().expect("promise failed");
+        assert_eq!(res.get_str(), "true,TypeError");
+
+        // the instance is not referenced by script, but it is kept alive until the promise is resolved
+        let res = rt
+            .eval_sync(EsScript::new(
+                "test_async_method2.es",
+                "new Connection('kept').query('select 1');",
+            ))
+            .expect("script failed");
+        rt.gc_sync();
+        let res = res.get_promise_result_sync().expect("promise failed");
+        assert_eq!(res.get_str(), "kept select 1 false");
+        rt.gc_sync();
+        assert!(CONNECTION_DROPPED.load(Ordering::SeqCst));
+    }
+
+    static REGISTRY_FACTORY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[test]
+    pub fn test_proxy_registry() {
+        let registry = ProxyRegistry::new()
+            .proxy(vec![], "RegisteredBase", || {
+                REGISTRY_FACTORY_CALLS.fetch_add(1, Ordering::SeqCst);
+                Proxy::new().method("describe", |q_ctx, _id, _args| {
+                    primitives::from_string_q(q_ctx, "base")
+                })
+            })
+            .data_proxy(vec!["com", "registry"], "Counter", || {
+                REGISTRY_FACTORY_CALLS.fetch_add(1, Ordering::SeqCst);
+                Proxy::with_data::<i32>()
+                    .extends("RegisteredBase")
+                    .constructor(|_q_ctx, args| primitives::to_i32(&args[0]))
+                    .method_mut("inc", |_q_ctx, count, _args| {
+                        *count += 1;
+                        Ok(primitives::from_i32(*count))
+                    })
+            })
+            .proxy(vec![], "RegisteredUnused", || {
+                REGISTRY_FACTORY_CALLS.fetch_add(1, Ordering::SeqCst);
+                // the registered name replaces the name of the Proxy
+                Proxy::new().name("OtherName")
+            });
+
+        let rt = EsRuntimeBuilder::new().proxy_registry(registry).build();
+        rt.create_context("registry_ctx")
+            .expect("could not create context");
+        // nothing is installed until it is used
+        assert_eq!(REGISTRY_FACTORY_CALLS.load(Ordering::SeqCst), 0);
+
+        let script = "let c = new com.registry.Counter(1); c.inc();\
+                      [c.inc(), c.describe(), c instanceof RegisteredBase].join(',');";
+        let res = rt
+            .eval_sync(EsScript::new("test_proxy_registry.es", script))
+            .expect("script failed");
+        assert_eq!(res.get_str(), "3,base,true");
+        assert_eq!(REGISTRY_FACTORY_CALLS.load(Ordering::SeqCst), 2);
+
+        rt.add_to_event_queue_sync(move |q_js_rt| {
+            let q_ctx = q_js_rt.get_context("registry_ctx");
+            let res = q_ctx
+                .eval(EsScript::new("test_proxy_registry2.es", script))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "3,base,true"
+            );
+            assert_eq!(REGISTRY_FACTORY_CALLS.load(Ordering::SeqCst), 4);
+
+            // classes are also installed when they are looked up from rust
+            assert!(get_proxy(q_ctx, "RegisteredUnused").is_some());
+            let res = q_ctx
+                .eval(EsScript::new(
+                    "test_proxy_registry3.es",
+                    "typeof RegisteredUnused;",
+                ))
+                .expect("script failed");
+            assert_eq!(
+                primitives::to_string_q(q_ctx, &res).ok().unwrap(),
+                "function"
+            );
+            assert_eq!(REGISTRY_FACTORY_CALLS.load(Ordering::SeqCst), 5);
+        });
+    }
+
+    thread_local! {
+        static EMITTERS_FINALIZED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    pub fn test_gc_mark() {
+        let rt = init_test_rt();
+        rt.add_to_event_queue_sync(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_context();
+            Proxy::with_data::<Vec<JSValueRef>>()
+                .name("GcEmitter")
+                .constructor(|_q_ctx, _args| Ok(vec![]))
+                .method_mut("on", |_q_ctx, listeners, args| {
+                    listeners.push(args[0].clone());
+                    Ok(primitives::from_i32(listeners.len() as i32))
+                })
+                .method("emit", |q_ctx, listeners, _args| {
+                    for listener in listeners {
+                        functions::call_function_q(q_ctx, listener, vec![], None)?;
+                    }
+                    Ok(primitives::from_i32(listeners.len() as i32))
+                })
+                .gc_mark(|listeners, marker| {
+                    for listener in listeners {
+                        marker.mark(listener);
+                    }
+                })
+                .configure(|proxy| {
+                    proxy.finalizer(|_q_ctx, _id| {
+                        EMITTERS_FINALIZED.with(|c| c.set(c.get() + 1));
+                    })
+                })
+                .install(q_ctx, true)
+                .expect("install failed");
+
+            // the listeners refer to the emitter so the emitter and its listeners form a cycle
+            let emitter = q_ctx
+                .eval(EsScript::new(
+                    "test_gc_mark.es",
+                    "this.emitted = 0; let e2 = (function() {\
+                       let e = new GcEmitter();\
+                       e.on(() => { this.emitted++; return e; });\
+                       e.on(function() { return e.emit; });\
+                       e.emit(); return e;\
+                     })(); e2;",
+                ))
+                .expect("script failed");
+            let weak = WeakJSValueRef::new(&emitter).expect("could not create weak ref");
+            assert!(WeakJSValueRef::new(&primitives::from_i32(1)).is_err());
+
+            q_js_rt.gc();
+            assert!(weak.is_alive());
+            let upgraded = weak.upgrade().expect("emitter was collected");
+            let res = functions::invoke_member_function_q(q_ctx, &upgraded, "emit", vec![])
+                .expect("emit failed");
+            assert_eq!(primitives::to_i32(&res).ok().unwrap(), 2);
+            drop(upgraded);
+            drop(emitter);
+            q_ctx
+                .eval(EsScript::new("test_gc_mark2.es", "e2 = null;"))
+                .expect("script failed");
+
+            q_js_rt.gc();
+            assert_eq!(EMITTERS_FINALIZED.with(|c| c.get()), 1);
+            assert!(!weak.is_alive());
+        });
+    }
 }
@@ -68,6 +68,83 @@ fn get_events_obj_map(
     }
 }
 
+/// add an event listener to an object, args are (eventId: String, listener: Function, options?: Object | boolean)
+pub(crate) fn add_event_listener(
+    q_ctx: &QuickJsContext,
+    this_ref: &JSValueRef,
+    args: &[JSValueRef],
+) -> Result<(), EsError> {
+    if args.len() < 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        Err(EsError::new_str("addEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
+    } else {
+        let event_id = primitives::to_string_q(q_ctx, &args[0])?;
+        let listener_func = args[1].clone();
+
+        // use the passed options arg or create a new obj
+        let options_obj = if args.len() == 3 && args[2].is_object() {
+            args[2].clone()
+        } else {
+            create_object_q(q_ctx)?
+        };
+        // if the third args was a boolean then set that bool as the capture option
+        if args.len() == 3 && args[2].is_bool() {
+            set_property_q(q_ctx, &options_obj, "capture", &args[2])?;
+        }
+
+        // get the Map
+        let events_listeners_map = get_events_obj_map(q_ctx, this_ref, event_id.as_str())?;
+        // add listener and options to the map
+        maps::set_q(q_ctx, &events_listeners_map, listener_func, options_obj)?;
+
+        Ok(())
+    }
+}
+
+/// remove an event listener from an object, args are (eventId: String, listener: Function)
+pub(crate) fn remove_event_listener(
+    q_ctx: &QuickJsContext,
+    this_ref: &JSValueRef,
+    args: &[JSValueRef],
+) -> Result<(), EsError> {
+    if args.len() != 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        Err(EsError::new_str("removeEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
+    } else {
+        let event_id = primitives::to_string_q(q_ctx, &args[0])?;
+        let listener_func = args[1].clone();
+
+        // get the Map
+        let events_listeners_map = get_events_obj_map(q_ctx, this_ref, event_id.as_str())?;
+        // add listener and options to the map
+        maps::delete_q(q_ctx, &events_listeners_map, listener_func)?;
+
+        Ok(())
+    }
+}
+
+/// call all listeners of an object for an event
+pub(crate) fn dispatch_event(
+    q_ctx: &QuickJsContext,
+    this_ref: &JSValueRef,
+    event_id: &str,
+    evt_obj: JSValueRef,
+) -> Result<(), EsError> {
+    // get the Map
+    let events_listeners_map = get_events_obj_map(q_ctx, this_ref, event_id)?;
+    if evt_obj.is_object() {
+        objects::set_property_q(q_ctx, &evt_obj, "target", this_ref)?;
+    }
+    let _results = maps::entries_q(q_ctx, &events_listeners_map, |key, _val| {
+        let _single_res = functions::call_function_q(q_ctx, &key, vec![evt_obj.clone()], None)?;
+        // key is a function
+        // val is an options obj todo: use it
+        // todo check if false was returned or evt_obj.stopImmediatePropagation was called
+        // todo in that case we need a way to stop the current iterate loop
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
 unsafe extern "C" fn ext_add_event_listener(
     ctx: *mut q::JSContext,
     this_val: q::JSValue,
@@ -85,34 +162,11 @@ unsafe extern "C" fn ext_add_event_listener(
 
         let this_ref = JSValueRef::new(ctx, this_val, true, true, "add_event_listener_this");
 
-        if args.len() < 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
-            Err(EsError::new_str("addEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
-        } else {
-            let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let listener_func = args[1].clone();
-
-            // use the passed options arg or create a new obj
-            let options_obj = if args.len() == 3 && args[2].is_object() {
-                args[2].clone()
-            } else {
-                create_object_q(q_ctx)?
-            };
-            // if the third args was a boolean then set that bool as the capture option
-            if args.len() == 3 && args[2].is_bool() {
-                set_property_q(q_ctx, &options_obj, "capture", &args[2])?;
-            }
-
-            // get the Map
-            let events_listeners_map = get_events_obj_map(q_ctx, &this_ref, event_id.as_str())?;
-            // add listener and options to the map
-            maps::set_q(q_ctx, &events_listeners_map, listener_func, options_obj)?;
-
-            Ok(())
-        }
+        add_event_listener(q_ctx, &this_ref, &args)
     });
     match res {
         Ok(_) => quickjs_utils::new_null(),
-        Err(e) => QuickJsContext::report_ex_ctx(ctx, format!("{}", e).as_str()),
+        Err(e) => QuickJsContext::report_es_error_ctx(ctx, &e),
     }
 }
 
@@ -127,23 +181,11 @@ unsafe extern "C" fn ext_remove_event_listener(
 
         let this_ref = JSValueRef::new(ctx, this_val, true, true, "remove_event_listener_this");
 
-        if args.len() != 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
-            Err(EsError::new_str("removeEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
-        } else {
-            let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let listener_func = args[1].clone();
-
-            // get the Map
-            let events_listeners_map = get_events_obj_map(q_ctx, &this_ref, event_id.as_str())?;
-            // add listener and options to the map
-            maps::delete_q(q_ctx, &events_listeners_map, listener_func)?;
-
-            Ok(())
-        }
+        remove_event_listener(q_ctx, &this_ref, &args)
     });
     match res {
         Ok(_) => quickjs_utils::new_null(),
-        Err(e) => QuickJsContext::report_ex_ctx(ctx, format!("{}", e).as_str()),
+        Err(e) => QuickJsContext::report_es_error_ctx(ctx, &e),
     }
 }
 
@@ -164,27 +206,11 @@ unsafe extern "C" fn ext_dispatch_event(
             ))
         } else {
             let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let evt_obj = args[1].clone();
-
-            // get the Map
-            let events_listeners_map = get_events_obj_map(q_ctx, &this_ref, event_id.as_str())?;
-            if evt_obj.is_object() {
-                objects::set_property(ctx, &evt_obj, "target", &this_ref)?;
-            }
-            let _results = maps::entries_q(q_ctx, &events_listeners_map, |key, _val| {
-                let _single_res = functions::call_function(ctx, &key, vec![evt_obj.clone()], None)?;
-                // key is a function
-                // val is an options obj todo: use it
-                // todo check if false was returned or evt_obj.stopImmediatePropagation was called
-                // todo in that case we need a way to stop the current iterate loop
-                Ok(())
-            })?;
-
-            Ok(())
+            dispatch_event(q_ctx, &this_ref, event_id.as_str(), args[1].clone())
         }
     });
     match res {
         Ok(_) => quickjs_utils::new_null(),
-        Err(e) => QuickJsContext::report_ex_ctx(ctx, format!("{}", e).as_str()),
+        Err(e) => QuickJsContext::report_es_error_ctx(ctx, &e),
     }
 }
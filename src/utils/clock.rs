@@ -0,0 +1,109 @@
+//! the Clock provides the time for timers, Date.now() and performance.now() of a runtime
+//!
+//! a runtime uses the real system clock unless a virtual clock is set with [EsRuntimeBuilder::virtual_clock](../../esruntimebuilder/struct.EsRuntimeBuilder.html#method.virtual_clock),
+//! a virtual clock only moves when it is advanced by [EsRuntime::advance_time](../../esruntime/struct.EsRuntime.html#method.advance_time)
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+pub struct Clock {
+    start_instant: Instant,
+    start_time: SystemTime,
+    /// the time elapsed on a virtual clock, None for a real clock
+    virtual_elapsed: Option<Mutex<Duration>>,
+}
+
+impl Clock {
+    /// create a clock which follows the system clock
+    pub fn new_real() -> Self {
+        Self {
+            start_instant: Instant::now(),
+            start_time: SystemTime::now(),
+            virtual_elapsed: None,
+        }
+    }
+
+    /// create a virtual clock which starts at start_time and only moves when it is advanced
+    pub fn new_virtual(start_time: SystemTime) -> Self {
+        Self {
+            start_instant: Instant::now(),
+            start_time,
+            virtual_elapsed: Some(Mutex::new(Duration::from_secs(0))),
+        }
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_elapsed.is_some()
+    }
+
+    /// get the current Instant, for a virtual clock this is the Instant at which the clock was created plus the virtual elapsed time
+    pub fn now(&self) -> Instant {
+        match &self.virtual_elapsed {
+            Some(elapsed) => self.start_instant + *elapsed.lock().unwrap(),
+            None => Instant::now(),
+        }
+    }
+
+    /// get the time elapsed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        self.now().duration_since(self.start_instant)
+    }
+
+    /// get the current system time
+    pub fn system_time(&self) -> SystemTime {
+        if self.is_virtual() {
+            self.start_time + self.elapsed()
+        } else {
+            SystemTime::now()
+        }
+    }
+
+    /// move a virtual clock forward to an Instant, a clock never moves backwards
+    /// # Panics
+    /// panics if this is not a virtual clock
+    pub fn advance_to(&self, instant: Instant) {
+        let elapsed = self.virtual_elapsed.as_ref().expect("not a virtual clock");
+        let elapsed = &mut *elapsed.lock().unwrap();
+        let new_elapsed = instant.saturating_duration_since(self.start_instant);
+        if new_elapsed > *elapsed {
+            *elapsed = new_elapsed;
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new_real()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::utils::clock::Clock;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_virtual_clock() {
+        let start_time = UNIX_EPOCH + Duration::from_secs(1000);
+        let clock = Clock::new_virtual(start_time);
+        let start = clock.now();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.system_time(), start_time);
+
+        clock.advance_to(start + Duration::from_millis(1500));
+        assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+        assert_eq!(
+            clock.system_time(),
+            start_time + Duration::from_millis(1500)
+        );
+
+        // never move backwards
+        clock.advance_to(start);
+        assert_eq!(clock.elapsed(), Duration::from_millis(1500));
+
+        let real = Clock::new_real();
+        assert!(!real.is_virtual());
+        assert!(real.system_time() > SystemTime::now() - Duration::from_secs(1));
+    }
+}
@@ -1,4 +1,5 @@
 pub mod auto_id_map;
+pub mod clock;
 pub mod debug_mutex;
 pub mod single_threaded_event_queue;
 pub mod task_manager;
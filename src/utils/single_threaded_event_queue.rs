@@ -1,4 +1,6 @@
+use crate::eserror::{EsError, EsErrorKind};
 use crate::utils::auto_id_map::AutoIdMap;
+use crate::utils::clock::Clock;
 use crate::utils::debug_mutex::DebugMutex;
 use futures::task::Waker;
 use log::trace;
@@ -7,6 +9,8 @@ use std::future::Future;
 use std::mem::replace;
 use std::ops::Add;
 use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, SendError, Sender};
 use std::sync::{Arc, Condvar, Mutex};
 use std::task::{Context, Poll};
@@ -17,9 +21,10 @@ use uuid::Uuid;
 
 type LocalJob = dyn FnOnce() + 'static;
 type Job = dyn Fn() + 'static;
+type LoopListener = dyn Fn(&SingleThreadedEventQueue) -> Option<Duration> + 'static;
 
 struct ScheduledJob {
-    job: Box<Job>,
+    job: Rc<Job>,
     interval: Option<Duration>,
     next_run: Instant,
 }
@@ -28,6 +33,9 @@ thread_local!(
     static LOCAL_JOBS: RefCell<Vec<Box<LocalJob>>> = RefCell::new(vec![]);
     static SCHEDULED_LOCAL_JOBS: RefCell<AutoIdMap<ScheduledJob>> =
         RefCell::new(AutoIdMap::new_with_max_size(i32::max_value() as usize));
+    static LOOP_LISTENER: RefCell<Option<Rc<LoopListener>>> = const { RefCell::new(None) };
+    // the max time the worker may wait before calling the loop listener again
+    static LOOP_LISTENER_WAIT: RefCell<Option<Duration>> = const { RefCell::new(None) };
 );
 
 ///
@@ -43,6 +51,8 @@ pub struct SingleThreadedEventQueue {
     worker_thread_name: String,
     shutdown_switch: Mutex<Sender<bool>>,
     join_handle: Mutex<Option<JoinHandle<()>>>,
+    clock: Arc<Clock>,
+    is_shut_down: AtomicBool,
 }
 
 pub struct TaskFutureResolver<R> {
@@ -102,6 +112,9 @@ impl<R> Future for TaskFuture<R> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         log::trace!("TaskFuture::poll");
+        // lock the waker before checking for a result so a resolver can not send its result
+        // (and find no waker to wake) between our check and us storing the waker
+        let waker_opt = &mut *self.resolver.waker.lock().unwrap();
         match self.result.try_recv() {
             Ok(res) => {
                 log::trace!("TaskFuture::poll -> Ready");
@@ -109,8 +122,6 @@ impl<R> Future for TaskFuture<R> {
             }
             Err(_) => {
                 log::trace!("TaskFuture::poll -> Pending");
-                let mtx = &self.resolver.waker;
-                let waker_opt = &mut *mtx.lock().unwrap();
                 let _ = waker_opt.replace(cx.waker().clone());
                 Poll::Pending
             }
@@ -120,6 +131,11 @@ impl<R> Future for TaskFuture<R> {
 
 impl SingleThreadedEventQueue {
     pub fn new() -> Arc<Self> {
+        Self::new_with_clock(Clock::new_real())
+    }
+
+    /// create a new event queue which uses a specific clock (e.g. a virtual clock) to run scheduled tasks
+    pub fn new_with_clock(clock: Clock) -> Arc<Self> {
         let uuid = format!("eseq_wt_{}", Uuid::new_v4());
 
         let (shutdown_switch, shutdown_receiver) = channel();
@@ -130,6 +146,8 @@ impl SingleThreadedEventQueue {
             worker_thread_name: uuid.clone(),
             shutdown_switch: Mutex::new(shutdown_switch),
             join_handle: Mutex::new(None),
+            clock: Arc::new(clock),
+            is_shut_down: AtomicBool::new(false),
         };
         let ret = Arc::new(task_manager);
         let arc = ret.clone();
@@ -151,8 +169,12 @@ impl SingleThreadedEventQueue {
         ret
     }
 
+    /// stop the worker thread, tasks which did not run yet are dropped
+    /// tasks which are added after shutdown are dropped, calling shutdown more than once has no effect
     pub fn shutdown(&self) {
-        // todo use a channel
+        if self.is_shut_down.swap(true, Ordering::SeqCst) {
+            return;
+        }
         self.shutdown_switch
             .lock()
             .unwrap()
@@ -163,7 +185,14 @@ impl SingleThreadedEventQueue {
         jh.join().expect("join failed");
     }
 
-    fn shutdown_cleanup(&self) {
+    /// true if shutdown was called
+    pub fn is_shut_down(&self) -> bool {
+        self.is_shut_down.load(Ordering::SeqCst)
+    }
+
+    /// remove all tasks which were added from the worker thread and all scheduled tasks without running them
+    pub fn clear_local_tasks(&self) {
+        self.assert_is_worker_thread();
         LOCAL_JOBS.with(|rc| {
             let lj = &mut *rc.borrow_mut();
             lj.clear();
@@ -174,12 +203,35 @@ impl SingleThreadedEventQueue {
         })
     }
 
+    fn shutdown_cleanup(&self) {
+        // drop tasks from other threads, this also makes exe_task fail instead of wait forever
+        self.jobs.lock("shutdown_cleanup").unwrap().clear();
+        self.clear_local_tasks();
+        LOOP_LISTENER.with(|rc| rc.borrow_mut().take());
+    }
+
+    /// set a listener which is called in the worker thread after every iteration of the worker loop
+    ///
+    /// the listener may return the max duration the worker may wait for new work before the listener is called again
+    pub fn set_loop_listener_from_worker<L>(&self, listener: L)
+    where
+        L: Fn(&SingleThreadedEventQueue) -> Option<Duration> + 'static,
+    {
+        self.assert_is_worker_thread();
+        LOOP_LISTENER.with(|rc| rc.borrow_mut().replace(Rc::new(listener)));
+    }
+
     /// add a task which will run asynchronously
+    /// the number of queued tasks is not limited here, the max_queued_tasks limit of an EsRuntime is checked by [EsRuntime::add_task](../../esruntime/struct.EsRuntime.html#method.add_task)
     pub fn add_task<T: FnOnce() + Send + 'static>(&self, task: T) {
-        // todo block on max size
         trace!("EsEventQueue::add_task");
         {
             let mut lck = self.jobs.lock("add_task").unwrap();
+            // this is checked while holding the lock so the task can not be added after shutdown_cleanup cleared the jobs
+            if self.is_shut_down() {
+                log::debug!("EsEventQueue::add_task: queue was shut down, dropping task");
+                return;
+            }
             let jobs = &mut *lck;
             jobs.push(Box::new(task));
         }
@@ -207,13 +259,34 @@ impl SingleThreadedEventQueue {
     }
 
     /// execute a task synchronously in the worker thread
+    /// # Panics
+    /// panics if the queue was shut down, use [try_exe_task](#method.try_exe_task) if that may be the case
     pub fn exe_task<R: Send + 'static, T: FnOnce() -> R + Send + 'static>(&self, task: T) -> R {
+        match self.try_exe_task(task) {
+            Ok(ret) => ret,
+            Err(e) => {
+                panic!("task failed: {}", e);
+            }
+        }
+    }
+
+    /// execute a task synchronously in the worker thread
+    ///
+    /// returns an EsError of kind ShutDown if the queue was shut down before the task could run
+    pub fn try_exe_task<R: Send + 'static, T: FnOnce() -> R + Send + 'static>(
+        &self,
+        task: T,
+    ) -> Result<R, EsError> {
         trace!("EsEventQueue::exe_task");
 
         if self.is_worker_thread() {
             // don;t block from worker threads
             trace!("EsEventQueue::exe_task, is worker, just run");
-            return task();
+            return Ok(task());
+        }
+
+        if self.is_shut_down() {
+            return Err(queue_shut_down());
         }
 
         // create a channel, put sender in job, wait for receiver here
@@ -230,14 +303,10 @@ impl SingleThreadedEventQueue {
         };
         self.add_task(job);
         trace!("EsEventQueue::exe_task / receive");
+        // the job (and thus the sender) is dropped without running when the queue is shut down
         let res = receiver.recv();
         trace!("EsEventQueue::exe_task / received");
-        match res {
-            Ok(ret) => ret,
-            Err(e) => {
-                panic!("task failed: {}", e);
-            }
-        }
+        res.map_err(|_| queue_shut_down())
     }
 
     /// method for adding tasks from worker, these do not need to impl Send
@@ -271,9 +340,9 @@ impl SingleThreadedEventQueue {
         self.assert_is_worker_thread();
 
         let task = ScheduledJob {
-            job: Box::new(task),
+            job: Rc::new(task),
             interval,
-            next_run: Instant::now().add(delay),
+            next_run: self.clock.now().add(delay),
         };
 
         // return the id
@@ -294,11 +363,60 @@ impl SingleThreadedEventQueue {
         });
     }
 
+    /// get the clock which is used to run scheduled tasks
+    pub fn get_clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// get the Instant at which the next scheduled task should run
+    pub fn get_next_scheduled_run(&self) -> Option<Instant> {
+        self.assert_is_worker_thread();
+        SCHEDULED_LOCAL_JOBS.with(|rc| {
+            let jobs = &*rc.borrow();
+            jobs.map.values().map(|job| job.next_run).min()
+        })
+    }
+
+    /// run all tasks which are ready to run once, these are the tasks added from other threads, tasks added from the worker thread and the scheduled tasks which are due
+    /// returns true if any task was run
+    pub fn run_ready_tasks(&self) -> bool {
+        self.assert_is_worker_thread();
+
+        let jobs = std::mem::take(&mut *self.jobs.lock("run_ready_tasks").unwrap());
+        let mut ran = !jobs.is_empty();
+        for job in jobs {
+            job();
+        }
+        ran |= run_local_jobs();
+        let (sched_ran, _wait_dur) = run_sched_jobs(self.clock.now());
+        ran || sched_ran
+    }
+
     pub fn todo_count(&self) -> usize {
         let jobs_lck = self.jobs.lock("todo_count").unwrap();
         jobs_lck.len()
     }
 
+    /// get the number of tasks which were added from the worker thread and did not run yet
+    pub fn local_todo_count(&self) -> usize {
+        self.assert_is_worker_thread();
+        LOCAL_JOBS.with(|rc| rc.borrow().len())
+    }
+
+    /// get the number of scheduled tasks as (tasks which run once, tasks which run at an interval)
+    pub fn scheduled_task_counts(&self) -> (usize, usize) {
+        self.assert_is_worker_thread();
+        SCHEDULED_LOCAL_JOBS.with(|rc| {
+            let jobs = &*rc.borrow();
+            let intervals = jobs
+                .map
+                .values()
+                .filter(|job| job.interval.is_some())
+                .count();
+            (jobs.len() - intervals, intervals)
+        })
+    }
+
     fn has_local_jobs(&self) -> bool {
         LOCAL_JOBS.with(|rc| {
             let local_jobs = &*rc.borrow();
@@ -306,7 +424,7 @@ impl SingleThreadedEventQueue {
         })
     }
 
-    fn is_worker_thread(&self) -> bool {
+    pub(crate) fn is_worker_thread(&self) -> bool {
         let handle = thread::current();
         if let Some(handle_name) = handle.name() {
             self.worker_thread_name.as_str().eq(handle_name)
@@ -323,7 +441,10 @@ impl SingleThreadedEventQueue {
     }
 
     fn worker_loop(&self) {
-        let wait_dur: Duration = run_sched_jobs();
+        let (_sched_ran, mut wait_dur) = run_sched_jobs(self.clock.now());
+        if let Some(listener_wait) = LOOP_LISTENER_WAIT.with(|rc| rc.borrow_mut().take()) {
+            wait_dur = wait_dur.min(listener_wait);
+        }
 
         let jobs: Vec<Box<dyn FnOnce() + Send + 'static>>;
         {
@@ -346,68 +467,81 @@ impl SingleThreadedEventQueue {
         }
 
         run_local_jobs();
+
+        // the listener is cloned so it may replace itself
+        let listener_opt = LOOP_LISTENER.with(|rc| rc.borrow().clone());
+        if let Some(listener) = listener_opt {
+            let listener_wait = listener(self);
+            LOOP_LISTENER_WAIT.with(|rc| *rc.borrow_mut() = listener_wait);
+        }
     }
 }
 
-fn run_sched_jobs() -> Duration {
-    // NB prevent double borrow mut, so first get removable jobs
-    let now = Instant::now();
-    SCHEDULED_LOCAL_JOBS.with(|rc| {
-        let mut wait_dur = Duration::from_millis(250);
-        {
-            // this block is so we don;t a a mutable borrow while running a job, a job might add another job and then there might already be a mutable borrow. which would be bad
+/// the error for tasks which could not run because the queue was shut down
+fn queue_shut_down() -> EsError {
+    EsError::new_kind(
+        EsErrorKind::ShutDown,
+        "the event queue was shut down".to_string(),
+    )
+}
 
-            let removable_jobs;
-            {
-                let jobs = &mut *rc.borrow_mut();
-                removable_jobs =
-                    jobs.remove_values(|job| job.next_run.lt(&now) && job.interval.is_none());
-            }
+/// run the scheduled jobs which are due at now, returns if any job was run and the duration until the next job is due (max 250 ms)
+fn run_sched_jobs(now: Instant) -> (bool, Duration) {
+    // first get the ids of the due jobs in the order they were due, no borrow is held while running a job because a job might add or remove jobs
+    let due_ids: Vec<usize> = SCHEDULED_LOCAL_JOBS.with(|rc| {
+        let jobs = &*rc.borrow();
+        let mut due: Vec<(Instant, usize)> = jobs
+            .map
+            .iter()
+            .filter(|(_id, job)| job.next_run.le(&now))
+            .map(|(id, job)| (job.next_run, *id))
+            .collect();
+        due.sort();
+        due.into_iter().map(|(_next_run, id)| id).collect()
+    });
 
-            // run those
-            for job in &removable_jobs {
-                let j = &job.job;
-                j();
-            }
+    let ran = !due_ids.is_empty();
 
-            trace!("SingleThreadedEventQueue.run_sched_jobs done");
-
-            // update re-scheds
-            // haha this effing sucks, i need descent iter/map/collect code in AutoIdMap
-            // also figure out a way to dynamically get wait delay for empty condition
-            let re_sched_ids: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new(vec![]));
-            {
-                let jobs = &*rc.borrow();
-                jobs.foreach(|k, v| {
-                    if v.next_run.lt(&now) && v.interval.is_some() {
-                        re_sched_ids.lock().unwrap().push(*k);
-                        let j = &v.job;
-                        j();
-                    }
-                });
+    for id in due_ids {
+        // a previous job may have removed this job (e.g. by calling clearTimeout)
+        let job_opt: Option<Rc<Job>> = SCHEDULED_LOCAL_JOBS.with(|rc| {
+            let jobs = &mut *rc.borrow_mut();
+            if !jobs.contains_key(&id) {
+                return None;
+            }
+            let job = jobs.get_mut(&id).unwrap();
+            let func = job.job.clone();
+            if let Some(interval) = job.interval {
+                job.next_run = now.add(interval);
+            } else {
+                let _ = jobs.remove(&id);
             }
+            Some(func)
+        });
+        if let Some(job) = job_opt {
+            job();
+        }
+    }
 
-            // re sched jobs
-            {
-                let jobs = &mut *rc.borrow_mut();
-                for k in &*re_sched_ids.lock().unwrap() {
-                    let job = jobs.get_mut(k).unwrap();
-                    job.next_run = now.add(job.interval.unwrap());
-                }
+    trace!("SingleThreadedEventQueue.run_sched_jobs done");
 
-                for job in jobs.map.values() {
-                    let wait_opt = job.next_run.duration_since(now);
-                    if wait_opt.lt(&wait_dur) {
-                        wait_dur = wait_opt;
-                    }
-                }
+    let wait_dur = SCHEDULED_LOCAL_JOBS.with(|rc| {
+        let jobs = &*rc.borrow();
+        let mut wait_dur = Duration::from_millis(250);
+        for job in jobs.map.values() {
+            let wait_opt = job.next_run.saturating_duration_since(now);
+            if wait_opt.lt(&wait_dur) {
+                wait_dur = wait_opt;
             }
         }
         wait_dur
-    })
+    });
+
+    (ran, wait_dur)
 }
 
-fn run_local_jobs() {
+/// run the jobs which were added from the worker thread, returns true if any job was run
+fn run_local_jobs() -> bool {
     LOCAL_JOBS.with(|rc| {
         let mut local_todos = vec![];
         {
@@ -417,10 +551,12 @@ fn run_local_jobs() {
                 local_todos.push(local_job);
             }
         }
+        let ran = !local_todos.is_empty();
         for local_todo in local_todos {
             local_todo();
         }
-    });
+        ran
+    })
 }
 
 impl Drop for SingleThreadedEventQueue {
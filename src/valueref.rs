@@ -1,10 +1,16 @@
+use crate::eserror::EsError;
+use crate::quickjs_utils::finalization;
+use crate::quickjscontext::QuickJsContext;
+use crate::quickjsruntime::QuickJsRuntime;
+use crate::reflection;
 use libquickjs_sys as q;
+use std::os::raw::c_void;
 use std::ptr::null_mut;
 
 pub struct JSValueRef {
     pub(crate) context: *mut q::JSContext,
     value: q::JSValue,
-    ref_ct_decr_on_drop: bool,
+    pub(crate) ref_ct_decr_on_drop: bool,
     label: String,
 }
 
@@ -190,6 +196,11 @@ impl JSValueRef {
         self.borrow_value().tag == TAG_FLOAT64
     }
 
+    /// return true if the wrapped value represents a JS Symbol value
+    pub fn is_symbol(&self) -> bool {
+        self.borrow_value().tag == TAG_SYMBOL
+    }
+
     pub fn is_big_int(&self) -> bool {
         // unsafe { q::JS_IsBigInt(ctx, self.borrow_value()) }
         self.borrow_value().tag == TAG_BIG_INT
@@ -211,7 +222,103 @@ impl JSValueRef {
     }
 }
 
+/// a weak reference to an object, it does not keep the object alive but it may be upgraded to a JSValueRef while the object is alive
+///
+/// instances of Proxy classes are tracked by their own finalizer, other objects get a holder (see [finalization](../quickjs_utils/finalization/index.html)) which is finalized together with the object
+/// a Proxy class may use this to refer to other objects (or itself) without creating a cycle
+/// # Example
+/// ```rust
+/// use quickjs_runtime::esruntimebuilder::EsRuntimeBuilder;
+/// use quickjs_runtime::esscript::EsScript;
+/// use quickjs_runtime::reflection::Proxy;
+/// use quickjs_runtime::valueref::WeakJSValueRef;
+/// let rt = EsRuntimeBuilder::new().build();
+/// rt.add_to_event_queue_sync(|q_js_rt| {
+///     let q_ctx = q_js_rt.get_main_context();
+///     Proxy::new()
+///         .name("Node")
+///         .constructor(|_q_ctx, _id, _args| Ok(()))
+///         .install(q_ctx, true)
+///         .expect("install failed");
+///     let node = q_ctx.eval(EsScript::new("test_weak.es", "new Node();")).expect("script failed");
+///     let weak = WeakJSValueRef::new(&node).expect("could not create weak ref");
+///     assert!(weak.upgrade().is_some());
+///     drop(node);
+///     q_js_rt.gc();
+///     assert!(weak.upgrade().is_none());
+///
+///     let obj = q_ctx.eval(EsScript::new("test_weak2.es", "({a: 1});")).expect("script failed");
+///     let weak = WeakJSValueRef::new(&obj).expect("could not create weak ref");
+///     assert!(weak.upgrade().is_some());
+///     // the object is freed as soon as its last reference is dropped
+///     drop(obj);
+///     assert!(weak.upgrade().is_none());
+/// });
+/// ```
+pub struct WeakJSValueRef {
+    context_id: String,
+    // the Proxy instance which is finalized together with the object, this is either the object itself or its holder
+    instance_id: usize,
+    instance_ptr: *mut c_void,
+    value: q::JSValue,
+}
+
+impl WeakJSValueRef {
+    /// create a weak reference, fails if the value is not an object
+    pub fn new(value_ref: &JSValueRef) -> Result<Self, EsError> {
+        if !value_ref.is_object() || value_ref.context.is_null() {
+            return Err(EsError::new_str("only objects can be referenced weakly"));
+        }
+        let (context_id, instance_id, instance_ptr) = if let Some((context_id, instance_id)) =
+            reflection::get_proxy_instance_context_and_id(value_ref)
+        {
+            (context_id, instance_id, unsafe { value_ref.value.u.ptr })
+        } else {
+            let (holder_ref, holder_id) = unsafe {
+                QuickJsContext::with_context(value_ref.context, |q_ctx| {
+                    finalization::get_holder_q(q_ctx, value_ref)
+                })
+            }?;
+            let (context_id, _) = reflection::get_proxy_instance_context_and_id(&holder_ref)
+                .ok_or_else(|| EsError::new_str("holder is not a Proxy instance"))?;
+            (context_id, holder_id, unsafe { holder_ref.value.u.ptr })
+        };
+        Ok(Self {
+            context_id,
+            instance_id,
+            instance_ptr,
+            value: value_ref.value,
+        })
+    }
+    /// get a JSValueRef to the object if it is still alive, this should be called from the worker thread of the runtime (and not from a finalizer)
+    pub fn upgrade(&self) -> Option<JSValueRef> {
+        QuickJsRuntime::do_with(|q_js_rt| {
+            let q_ctx = q_js_rt.opt_context(&self.context_id)?;
+            if reflection::is_proxy_instance_alive(q_ctx, self.instance_id, self.instance_ptr) {
+                Some(JSValueRef::new(
+                    q_ctx.context,
+                    self.value,
+                    true,
+                    true,
+                    "WeakJSValueRef::upgrade",
+                ))
+            } else {
+                None
+            }
+        })
+    }
+    /// check if the object is still alive
+    pub fn is_alive(&self) -> bool {
+        QuickJsRuntime::do_with(|q_js_rt| {
+            q_js_rt.opt_context(&self.context_id).is_some_and(|q_ctx| {
+                reflection::is_proxy_instance_alive(q_ctx, self.instance_id, self.instance_ptr)
+            })
+        })
+    }
+}
+
 pub(crate) const TAG_BIG_INT: i64 = -10;
+pub(crate) const TAG_SYMBOL: i64 = -8;
 pub(crate) const TAG_STRING: i64 = -7;
 pub(crate) const TAG_MODULE: i64 = -3;
 pub(crate) const TAG_FUNCTION_BYTECODE: i64 = -2;